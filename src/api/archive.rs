@@ -0,0 +1,35 @@
+use super::auth;
+use actix_web::{
+    dev::Payload,
+    error::{ErrorBadRequest, ErrorForbidden},
+    web::Query,
+    Error, FromRequest, HttpRequest,
+};
+use futures::future::{ready, Ready};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct ArchiveQuery {
+    #[serde(default)]
+    include_deleted: bool,
+}
+
+/// Whether archived records should be returned, from `?include_deleted=true`.
+/// Only administrators may ask for archived records.
+pub struct IncludeDeleted(pub bool);
+
+impl FromRequest for IncludeDeleted {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let query = match Query::<ArchiveQuery>::from_query(req.query_string()) {
+            Ok(query) => query.into_inner(),
+            Err(err) => return ready(Err(ErrorBadRequest(err.to_string()))),
+        };
+        if query.include_deleted && !auth::is_admin(req) {
+            return ready(Err(ErrorForbidden("include_deleted requires admin access")));
+        }
+        ready(Ok(IncludeDeleted(query.include_deleted)))
+    }
+}
@@ -0,0 +1,557 @@
+use super::{auth::Admin, errors::internal_server_error, validation::field_error};
+use crate::{
+    config::StorageSettings,
+    models::{
+        attachment::{Attachment, AttachmentRef, OwnerKind},
+        audit_entry::AuditContext,
+        validators,
+    },
+    repository::mongodb_repo::MongoRepo,
+    storage::BlobStore,
+};
+use actix_multipart::{Multipart, MultipartError};
+use actix_web::{
+    http::header::{self, ContentDisposition, DispositionParam, DispositionType, EntityTag},
+    web::{self, Data, Path},
+    HttpResponse,
+};
+use futures::TryStreamExt;
+use mongodb::bson::{oid::ObjectId, DateTime};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Most files accepted in one upload.
+const MAX_FILES: usize = 10;
+
+/// Types that share the zip container, told apart by what was declared.
+const ZIP_BASED: [&str; 2] = [
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+];
+
+/// The type the content shows itself to be from its leading bytes, never
+/// trusting the declared type beyond choosing among zip-based formats.
+/// Markup such as SVG or HTML is only ever text, so it cannot run scripts
+/// when opened.
+pub fn sniff_content_type(declared: &str, bytes: &[u8]) -> String {
+    let signatures: [(&[u8], &str); 6] = [
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"PK\x03\x04", "application/zip"),
+    ];
+    let sniffed = signatures
+        .iter()
+        .find(|(signature, _)| bytes.starts_with(signature))
+        .map(|(_, content_type)| *content_type);
+    let content_type = match sniffed {
+        Some("application/zip") => ZIP_BASED
+            .into_iter()
+            .find(|zip_based| declared.eq_ignore_ascii_case(zip_based))
+            .unwrap_or("application/zip"),
+        Some(content_type) => content_type,
+        None if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") => "image/webp",
+        None if !bytes.contains(&0) && std::str::from_utf8(bytes).is_ok() => "text/plain",
+        None => "application/octet-stream",
+    };
+    content_type.to_string()
+}
+
+pub struct UploadedFile {
+    pub name: String,
+    pub content_type: String,
+    pub bytes: Vec<u8>,
+}
+
+/// The parts of a multipart upload: text fields by name and `file` parts.
+pub struct Upload {
+    pub fields: BTreeMap<String, String>,
+    pub files: Vec<UploadedFile>,
+}
+
+/// Reads the text fields named in `fields` and any `file` parts, refusing
+/// files of a type or size the storage settings do not allow before they
+/// are read in full. Files are then typed by their content, which must be
+/// allowed too.
+pub async fn read_upload(
+    mut payload: Multipart,
+    settings: &StorageSettings,
+    fields: &[&str],
+) -> Result<Upload, HttpResponse> {
+    let bad_request = |err: MultipartError| {
+        HttpResponse::BadRequest().body(format!("invalid multipart body: {}", err))
+    };
+    let mut upload = Upload {
+        fields: BTreeMap::new(),
+        files: Vec::new(),
+    };
+    while let Some(mut field) = payload.try_next().await.map_err(bad_request)? {
+        let name = field.name().unwrap_or_default().to_string();
+        let filename = field
+            .content_disposition()
+            .and_then(|disposition| disposition.get_filename())
+            .map(str::to_string);
+        let is_file = name == "file" && filename.is_some();
+        if !is_file && !fields.contains(&name.as_str()) {
+            return Err(field_error(&name, "is not an expected field"));
+        }
+        let content_type = field
+            .content_type()
+            .map(|mime| mime.essence_str().to_string())
+            .unwrap_or_else(|| "application/octet-stream".to_string());
+        if is_file && !settings.allows(&content_type) {
+            let message = format!("type {} is not accepted", content_type);
+            return Err(field_error("file", message));
+        }
+        if is_file && upload.files.len() == MAX_FILES {
+            let message = format!("at most {} files may be uploaded at once", MAX_FILES);
+            return Err(field_error("file", message));
+        }
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.try_next().await.map_err(bad_request)? {
+            if bytes.len() + chunk.len() > settings.max_file_bytes {
+                let message = format!("must be at most {} bytes", settings.max_file_bytes);
+                return Err(field_error(&name, message));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        match filename {
+            Some(filename) if is_file => {
+                let content_type = sniff_content_type(&content_type, &bytes);
+                if !settings.allows(&content_type) {
+                    let message = format!("content of type {} is not accepted", content_type);
+                    return Err(field_error("file", message));
+                }
+                upload.files.push(UploadedFile {
+                    name: filename,
+                    content_type,
+                    bytes,
+                })
+            }
+            _ => {
+                let Ok(value) = String::from_utf8(bytes) else {
+                    return Err(field_error(&name, "must be UTF-8 text"));
+                };
+                upload.fields.insert(name, value);
+            }
+        }
+    }
+    Ok(upload)
+}
+
+/// Puts each file in the blob store and records it as an attachment of
+/// the owner. Listing the files on the owner is left to the caller.
+pub async fn store_files(
+    db: &MongoRepo,
+    store: &dyn BlobStore,
+    owner_kind: OwnerKind,
+    owner_id: ObjectId,
+    files: Vec<UploadedFile>,
+    actor: &AuditContext,
+) -> Result<Vec<Attachment>, HttpResponse> {
+    let mut attachments = Vec::with_capacity(files.len());
+    for file in files {
+        let id = ObjectId::new();
+        let attachment = Attachment {
+            id: Some(id),
+            owner_kind,
+            owner_id,
+            name: file.name,
+            content_type: file.content_type,
+            size: file.bytes.len() as i64,
+            sha256: format!("{:x}", Sha256::digest(&file.bytes)),
+            uploaded_by: actor.actor.clone(),
+            uploaded_at: DateTime::now(),
+        };
+        // The bytes go first so no attachment is recorded without them.
+        if let Err(err) = store.put(&id.to_hex(), file.bytes).await {
+            return Err(internal_server_error(err));
+        }
+        if let Err(err) = db.create_attachment(attachment.clone(), actor).await {
+            return Err(internal_server_error(err));
+        }
+        attachments.push(attachment);
+    }
+    Ok(attachments)
+}
+
+/// Sends the attachment's bytes, after checking them against the checksum
+/// taken on upload, as a download typed by its content so that browsers
+/// neither render nor sniff it.
+pub async fn download(store: &dyn BlobStore, attachment: Attachment) -> HttpResponse {
+    let Some(id) = attachment.id else {
+        return HttpResponse::NotFound().body("No attachment found with specified ID");
+    };
+    let bytes = match store.get(&id.to_hex()).await {
+        Ok(Some(bytes)) => bytes,
+        Ok(None) => {
+            return internal_server_error(format!("content of attachment {} is missing", id))
+        }
+        Err(err) => return internal_server_error(err),
+    };
+    if format!("{:x}", Sha256::digest(&bytes)) != attachment.sha256 {
+        return internal_server_error(format!("content of attachment {} is corrupt", id));
+    }
+    HttpResponse::Ok()
+        .content_type(sniff_content_type(&attachment.content_type, &bytes))
+        .insert_header((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(attachment.name)],
+        })
+        .insert_header(header::ETag(EntityTag::new_strong(attachment.sha256)))
+        .insert_header((header::CACHE_CONTROL, "private, max-age=300"))
+        .body(bytes)
+}
+
+/// Stores the uploaded files and lists them on the owner.
+async fn attach(
+    db: &MongoRepo,
+    store: &dyn BlobStore,
+    storage: &StorageSettings,
+    owner_kind: OwnerKind,
+    owner_id: ObjectId,
+    payload: Multipart,
+    actor: &AuditContext,
+) -> HttpResponse {
+    let upload = match read_upload(payload, storage, &[]).await {
+        Ok(upload) => upload,
+        Err(response) => return response,
+    };
+    if upload.files.is_empty() {
+        return field_error("file", "at least one file is required");
+    }
+    let attachments = match store_files(db, store, owner_kind, owner_id, upload.files, actor).await
+    {
+        Ok(attachments) => attachments,
+        Err(response) => return response,
+    };
+    for reference in attachments.iter().filter_map(Attachment::reference) {
+        if let Err(err) = db
+            .add_attachment_ref(owner_kind, owner_id, &reference, actor)
+            .await
+        {
+            return internal_server_error(err);
+        }
+    }
+    HttpResponse::Ok().json(attachments)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/students/{id}/attachments",
+    tag = "attachments",
+    request_body(content = String, content_type = "multipart/form-data", description = "One or more `file` parts of an accepted type and size"),
+    params(("id" = String, Path, description = "Student id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Files attached to the student", body = Vec<Attachment>),
+        (status = 400, description = "Invalid id or malformed multipart body", body = String),
+        (status = 404, description = "Student not found", body = String),
+        (status = 422, description = "A file of a type or size not accepted, or no file", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database or storage error", body = String),
+    )
+)]
+pub async fn attach_to_student(
+    db: Data<MongoRepo>,
+    store: Data<dyn BlobStore>,
+    storage: Data<StorageSettings>,
+    path: Path<String>,
+    payload: Multipart,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let Ok(owner_id) = ObjectId::parse_str(&id) else {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    match db.get_student(&id, false).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("No student found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    }
+    let store = store.get_ref();
+    attach(
+        &db,
+        store,
+        &storage,
+        OwnerKind::Student,
+        owner_id,
+        payload,
+        &actor,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/teachers/{id}/attachments",
+    tag = "attachments",
+    request_body(content = String, content_type = "multipart/form-data", description = "One or more `file` parts of an accepted type and size"),
+    params(("id" = String, Path, description = "Teacher id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Files attached to the teacher", body = Vec<Attachment>),
+        (status = 400, description = "Invalid id or malformed multipart body", body = String),
+        (status = 404, description = "Teacher not found", body = String),
+        (status = 422, description = "A file of a type or size not accepted, or no file", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database or storage error", body = String),
+    )
+)]
+pub async fn attach_to_teacher(
+    db: Data<MongoRepo>,
+    store: Data<dyn BlobStore>,
+    storage: Data<StorageSettings>,
+    path: Path<String>,
+    payload: Multipart,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let Ok(owner_id) = ObjectId::parse_str(&id) else {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    match db.get_teacher(&id, false).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("No teacher found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    }
+    let store = store.get_ref();
+    attach(
+        &db,
+        store,
+        &storage,
+        OwnerKind::Teacher,
+        owner_id,
+        payload,
+        &actor,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/homework/{id}/attachments",
+    tag = "attachments",
+    request_body(content = String, content_type = "multipart/form-data", description = "One or more `file` parts of an accepted type and size"),
+    params(("id" = String, Path, description = "Assignment id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Files attached to the assignment", body = Vec<Attachment>),
+        (status = 400, description = "Invalid id or malformed multipart body", body = String),
+        (status = 404, description = "Assignment not found", body = String),
+        (status = 422, description = "A file of a type or size not accepted, or no file", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database or storage error", body = String),
+    )
+)]
+pub async fn attach_to_assignment(
+    db: Data<MongoRepo>,
+    store: Data<dyn BlobStore>,
+    storage: Data<StorageSettings>,
+    path: Path<String>,
+    payload: Multipart,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let Ok(owner_id) = ObjectId::parse_str(&id) else {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    match db.get_homework_assignment(&id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("No assignment found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    }
+    let store = store.get_ref();
+    attach(
+        &db,
+        store,
+        &storage,
+        OwnerKind::Assignment,
+        owner_id,
+        payload,
+        &actor,
+    )
+    .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/attachments/{id}",
+    tag = "attachments",
+    params(("id" = String, Path, description = "Attachment id")),
+    responses(
+        (status = 200, description = "What the file is, who it belongs to and its checksum", body = Attachment),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "Attachment not found", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn get_attachment(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    path: Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.get_attachment(&id).await {
+        Ok(Some(attachment)) => HttpResponse::Ok().json(attachment),
+        Ok(None) => HttpResponse::NotFound().body("No attachment found with specified ID"),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/attachments/{id}/content",
+    tag = "attachments",
+    params(("id" = String, Path, description = "Attachment id")),
+    responses(
+        (status = 200, description = "The file as it was uploaded, as a download; the ETag is its SHA-256", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "Attachment not found", body = String),
+        (status = 500, description = "Database or storage error, or content that fails its checksum", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn get_attachment_content(
+    db: Data<MongoRepo>,
+    store: Data<dyn BlobStore>,
+    _admin: Admin,
+    path: Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.get_attachment(&id).await {
+        Ok(Some(attachment)) => download(store.get_ref(), attachment).await,
+        Ok(None) => HttpResponse::NotFound().body("No attachment found with specified ID"),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/attachments/{id}",
+    tag = "attachments",
+    params(("id" = String, Path, description = "Attachment id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Attachment removed from its owner and deleted", body = String),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "Attachment not found", body = String),
+        (status = 409, description = "Files handed in with a submission or sent in a message cannot be deleted", body = String),
+        (status = 500, description = "Database or storage error", body = String),
+    )
+)]
+pub async fn delete_attachment(
+    db: Data<MongoRepo>,
+    store: Data<dyn BlobStore>,
+    path: Path<String>,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let Ok(attachment_id) = ObjectId::parse_str(&id) else {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    let attachment = match db.get_attachment(&id).await {
+        Ok(Some(attachment)) => attachment,
+        Ok(None) => return HttpResponse::NotFound().json("Attachment with specified ID not found"),
+        Err(err) => return internal_server_error(err),
+    };
+    match attachment.owner_kind {
+        OwnerKind::Submission => {
+            return HttpResponse::Conflict()
+                .body("Files handed in with a submission cannot be deleted")
+        }
+        // Kept so admins can still see what was sent.
+        OwnerKind::Message => {
+            return HttpResponse::Conflict().body("Files sent in a message cannot be deleted")
+        }
+        OwnerKind::Student | OwnerKind::Teacher | OwnerKind::Assignment => {}
+    }
+    if let Err(err) = db
+        .remove_attachment_ref(
+            attachment.owner_kind,
+            attachment.owner_id,
+            attachment_id,
+            &actor,
+        )
+        .await
+    {
+        return internal_server_error(err);
+    }
+    match db.delete_attachment(&id, &actor).await {
+        Ok(res) if res.deleted_count == 1 => {}
+        Ok(_) => return HttpResponse::NotFound().json("Attachment with specified ID not found"),
+        Err(err) => return internal_server_error(err),
+    }
+    // Only unreachable bytes are left behind if this fails.
+    if let Err(err) = store.delete(&id).await {
+        tracing::warn!(error = %err, attachment = %id, "error deleting attachment content");
+    }
+    HttpResponse::Ok().json("Attachment successfully deleted")
+}
+
+/// References of `attachments`, for owners that list them.
+pub fn references(attachments: &[Attachment]) -> Vec<AttachmentRef> {
+    attachments
+        .iter()
+        .filter_map(Attachment::reference)
+        .collect()
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/students/{id}/attachments").route(web::post().to(attach_to_student)),
+    )
+    .service(web::resource("/teachers/{id}/attachments").route(web::post().to(attach_to_teacher)))
+    .service(
+        web::resource("/homework/{id}/attachments").route(web::post().to(attach_to_assignment)),
+    )
+    .service(
+        web::resource("/attachments/{id}")
+            .route(web::get().to(get_attachment))
+            .route(web::delete().to(delete_attachment)),
+    )
+    .service(
+        web::resource("/attachments/{id}/content").route(web::get().to(get_attachment_content)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sniffing_trusts_content_over_the_declared_type() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        assert_eq!(sniff_content_type("text/html", png), "image/png");
+        let svg = b"<svg xmlns=\"http://www.w3.org/2000/svg\"><script>alert(1)</script></svg>";
+        assert_eq!(sniff_content_type("image/svg+xml", svg), "text/plain");
+        assert_eq!(
+            sniff_content_type("image/png", b"\0\x01binary"),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn sniffing_uses_the_declared_type_only_among_zip_formats() {
+        let docx = ZIP_BASED[0];
+        assert_eq!(sniff_content_type(docx, b"PK\x03\x04rest"), docx);
+        assert_eq!(
+            sniff_content_type("image/png", b"PK\x03\x04rest"),
+            "application/zip"
+        );
+        assert_eq!(sniff_content_type(docx, b"%PDF-1.7"), "application/pdf");
+    }
+
+    #[test]
+    fn sniffing_recognises_webp() {
+        assert_eq!(
+            sniff_content_type("", b"RIFF\0\0\0\0WEBPVP8 "),
+            "image/webp"
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use super::{auth::Admin, errors::internal_server_error};
+use crate::repository::mongodb_repo::MongoRepo;
+use actix_web::{
+    web::{self, Data, Query},
+    HttpResponse,
+};
+use mongodb::bson::{doc, oid::ObjectId, DateTime, Document};
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+#[derive(Deserialize, IntoParams)]
+pub struct AuditQuery {
+    /// Entity type, e.g. `student`
+    entity: Option<String>,
+    /// Id of the changed record
+    id: Option<String>,
+    /// `admin` or `anonymous`
+    actor: Option<String>,
+    /// Name an `X-Actor` header gave, unverified
+    claimed_actor: Option<String>,
+    /// RFC 3339 lower bound on the timestamp
+    from: Option<String>,
+    /// RFC 3339 upper bound on the timestamp
+    to: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/audit",
+    tag = "audit",
+    params(AuditQuery),
+    responses(
+        (status = 200, description = "Matching audit entries, newest first", body = [AuditEntry]),
+        (status = 400, description = "Invalid id or date", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn get_audit_log(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    query: Query<AuditQuery>,
+) -> HttpResponse {
+    let query = query.into_inner();
+    let mut filter = Document::new();
+    if let Some(entity) = query.entity {
+        filter.insert("entity", entity);
+    }
+    if let Some(id) = query.id {
+        match ObjectId::parse_str(&id) {
+            Ok(obj_id) => filter.insert("entity_id", obj_id),
+            Err(_) => return HttpResponse::BadRequest().body("invalid ID"),
+        };
+    }
+    if let Some(actor) = query.actor {
+        filter.insert("actor", actor);
+    }
+    if let Some(claimed_actor) = query.claimed_actor {
+        filter.insert("claimed_actor", claimed_actor);
+    }
+    let mut timestamp = Document::new();
+    for (operator, bound) in [("$gte", query.from), ("$lte", query.to)] {
+        if let Some(bound) = bound {
+            match DateTime::parse_rfc3339_str(&bound) {
+                Ok(date) => timestamp.insert(operator, date),
+                Err(_) => return HttpResponse::BadRequest().body("invalid RFC 3339 date"),
+            };
+        }
+    }
+    if !timestamp.is_empty() {
+        filter.insert("timestamp", timestamp);
+    }
+
+    let entries = db.get_audit_entries(filter).await;
+    match entries {
+        Ok(entry) => HttpResponse::Ok().json(entry),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("/audit", web::get().to(get_audit_log));
+}
@@ -0,0 +1,61 @@
+use crate::models::audit_entry::AuditContext;
+use actix_web::{
+    dev::Payload, error::ErrorForbidden, http::header::AUTHORIZATION, web::Data, Error,
+    FromRequest, HttpRequest,
+};
+use futures::future::{ready, Ready};
+
+/// Bearer token granting administrative access, loaded from `ADMIN_TOKEN`.
+pub struct AdminToken(pub String);
+
+pub fn is_admin(req: &HttpRequest) -> bool {
+    let token = match req.app_data::<Data<AdminToken>>() {
+        Some(token) if !token.0.is_empty() => token,
+        _ => return false,
+    };
+    req.headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|bearer| bearer == token.0)
+}
+
+/// Guards admin-only handlers, rejecting everyone else with 403.
+pub struct Admin;
+
+impl FromRequest for Admin {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(if is_admin(req) {
+            Ok(Admin)
+        } else {
+            Err(ErrorForbidden("admin access required"))
+        })
+    }
+}
+
+/// The caller is `admin` when holding the admin token and `anonymous`
+/// otherwise. An `X-Actor` header is unauthenticated, so it is only kept as
+/// who the caller claims to be.
+impl FromRequest for AuditContext {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let actor = if is_admin(req) { "admin" } else { "anonymous" };
+        let claimed_actor = req
+            .headers()
+            .get("X-Actor")
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(String::from);
+        ready(Ok(AuditContext {
+            actor: actor.to_string(),
+            claimed_actor,
+            client_ip: req.connection_info().realip_remote_addr().map(String::from),
+        }))
+    }
+}
@@ -0,0 +1,985 @@
+use super::{
+    auth::Admin,
+    errors::internal_server_error,
+    validation::{self, field_error},
+};
+use crate::{
+    config::SchoolSettings,
+    models::{
+        audit_entry::AuditContext,
+        discount::{Discount, DiscountKind},
+        fee_item::FeeItem,
+        invoice::{document_number, Invoice, InvoiceLine, InvoiceStatus, RECEIPT_PREFIX},
+        payment::{Payment, PaymentMethod},
+        student::StudentSummary,
+        validators,
+    },
+    repository::mongodb_repo::MongoRepo,
+};
+use actix_web::{
+    web::{self, Data, Json, Path, Query},
+    HttpResponse,
+};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+/// Responds 422 unless `term` is a term of the school year.
+fn check_term(school: &SchoolSettings, term: i32) -> Option<HttpResponse> {
+    (term > school.terms_per_year)
+        .then(|| field_error("term", format!("must be at most {}", school.terms_per_year)))
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct FeeItemRequest {
+    grade_id: i64,
+    year: i32,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    term: i32,
+    #[validate(length(min = 1, message = "must not be empty"))]
+    name: String,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    amount_cents: i64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/fees",
+    tag = "billing",
+    request_body = FeeItemRequest,
+    params(("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Fee item created; returns the inserted id", body = Object),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 422, description = "Validation errors per field", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn create_fee_item(
+    db: Data<MongoRepo>,
+    school: Data<SchoolSettings>,
+    request: Json<FeeItemRequest>,
+    _admin: Admin,
+    actor: AuditContext,
+) -> HttpResponse {
+    if let Err(errors) = request.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
+    if let Some(response) = check_term(&school, request.term) {
+        return response;
+    }
+    let data = FeeItem {
+        id: None,
+        grade_id: request.grade_id,
+        year: request.year,
+        term: request.term,
+        name: request.name.to_string(),
+        amount_cents: request.amount_cents,
+    };
+    let fee_detail = db.create_fee_item(data, &actor).await;
+    match fee_detail {
+        Ok(fee) => HttpResponse::Ok().json(fee),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct FeeQuery {
+    /// Only fees for this grade
+    grade_id: Option<i64>,
+    /// Only fees in this school year
+    year: Option<i32>,
+    /// Only fees in this term
+    term: Option<i32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/fees",
+    tag = "billing",
+    params(FeeQuery),
+    responses(
+        (status = 200, description = "Fee items, latest term first", body = Vec<FeeItem>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_fee_items(db: Data<MongoRepo>, query: Query<FeeQuery>) -> HttpResponse {
+    match db
+        .get_fee_items(query.grade_id, query.year, query.term)
+        .await
+    {
+        Ok(fees) => HttpResponse::Ok().json(fees),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/fees/{id}",
+    tag = "billing",
+    params(("id" = String, Path, description = "Fee item id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Fee item deleted; invoices already issued keep it", body = String),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "Fee item not found", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn delete_fee_item(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    _admin: Admin,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.delete_fee_item(&id, &actor).await {
+        Ok(res) => {
+            if res.deleted_count == 1 {
+                HttpResponse::Ok().json("Fee item successfully deleted")
+            } else {
+                HttpResponse::NotFound().json("Fee item with specified ID not found")
+            }
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct DiscountRequest {
+    #[validate(length(min = 1, message = "must not be empty"))]
+    name: String,
+    kind: DiscountKind,
+    /// A percentage for `percent` discounts, cents for `fixed` ones
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    value: i64,
+    year: i32,
+    /// Every term of the year when absent
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    term: Option<i32>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/students/{id}/discounts",
+    tag = "billing",
+    request_body = DiscountRequest,
+    params(("id" = String, Path, description = "Student id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Discount granted; applies to invoices generated from now on", body = Object),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "Student not found", body = String),
+        (status = 422, description = "Validation errors per field", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn create_discount(
+    db: Data<MongoRepo>,
+    school: Data<SchoolSettings>,
+    path: Path<String>,
+    request: Json<DiscountRequest>,
+    _admin: Admin,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    if let Err(errors) = request.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
+    if request.kind == DiscountKind::Percent && request.value > 100 {
+        return field_error("value", "must be at most 100 for a percent discount");
+    }
+    if let Some(response) = request.term.and_then(|term| check_term(&school, term)) {
+        return response;
+    }
+    let student = match db.get_student(&id, false).await {
+        Ok(Some(student)) => student,
+        Ok(None) => return HttpResponse::NotFound().body("No student found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    let data = Discount {
+        id: None,
+        student: StudentSummary::from(&student),
+        name: request.name.to_string(),
+        kind: request.kind,
+        value: request.value,
+        year: request.year,
+        term: request.term,
+    };
+    let discount_detail = db.create_discount(data, &actor).await;
+    match discount_detail {
+        Ok(discount) => HttpResponse::Ok().json(discount),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/students/{id}/discounts",
+    tag = "billing",
+    params(("id" = String, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "Discounts granted to the student, latest first", body = Vec<Discount>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_student_discounts(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.get_discounts_for_student(&id).await {
+        Ok(discounts) => HttpResponse::Ok().json(discounts),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/discounts/{id}",
+    tag = "billing",
+    params(("id" = String, Path, description = "Discount id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Discount withdrawn; invoices already issued keep it", body = String),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "Discount not found", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn delete_discount(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    _admin: Admin,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.delete_discount(&id, &actor).await {
+        Ok(res) => {
+            if res.deleted_count == 1 {
+                HttpResponse::Ok().json("Discount successfully deleted")
+            } else {
+                HttpResponse::NotFound().json("Discount with specified ID not found")
+            }
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct GenerateInvoicesRequest {
+    year: i32,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    term: i32,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    due_at: DateTime,
+}
+
+/// What an invoice run did.
+#[derive(Serialize, ToSchema)]
+pub struct InvoiceRun {
+    /// Ids of the invoices issued
+    created: Vec<String>,
+    /// Students skipped because they already have an invoice for the term
+    already_invoiced: usize,
+    /// Students skipped because their grade has no fees for the term
+    without_fees: usize,
+}
+
+/// The invoice lines for one student: the grade's fees, then what their
+/// discounts take off those fees.
+fn invoice_lines(fees: &[&FeeItem], discounts: &[&Discount]) -> Vec<InvoiceLine> {
+    let mut lines: Vec<InvoiceLine> = fees
+        .iter()
+        .map(|fee| InvoiceLine {
+            description: fee.name.clone(),
+            amount_cents: fee.amount_cents,
+        })
+        .collect();
+    let mut remaining: i64 = fees.iter().map(|fee| fee.amount_cents).sum();
+    let subtotal = remaining;
+    for discount in discounts {
+        // Percentages are of the fees, so discounts do not compound.
+        let off = discount.amount_off(subtotal).min(remaining);
+        if off > 0 {
+            remaining -= off;
+            lines.push(InvoiceLine {
+                description: discount.name.clone(),
+                amount_cents: -off,
+            });
+        }
+    }
+    lines
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/invoices/generate",
+    tag = "billing",
+    request_body = GenerateInvoicesRequest,
+    params(("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Invoices issued to every student enrolled that year whose grade has fees for the term; safe to repeat", body = InvoiceRun),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 422, description = "Validation errors per field", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn generate_invoices(
+    db: Data<MongoRepo>,
+    school: Data<SchoolSettings>,
+    request: Json<GenerateInvoicesRequest>,
+    _admin: Admin,
+    actor: AuditContext,
+) -> HttpResponse {
+    if let Err(errors) = request.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
+    if let Some(response) = check_term(&school, request.term) {
+        return response;
+    }
+    let (year, term) = (request.year, request.term);
+    let fees = match db.get_fee_items(None, Some(year), Some(term)).await {
+        Ok(fees) => fees,
+        Err(err) => return internal_server_error(err),
+    };
+    let mut fees_by_grade: HashMap<i64, Vec<&FeeItem>> = HashMap::new();
+    for fee in &fees {
+        fees_by_grade.entry(fee.grade_id).or_default().push(fee);
+    }
+    let discounts = match db.get_discounts_for_term(year, term).await {
+        Ok(discounts) => discounts,
+        Err(err) => return internal_server_error(err),
+    };
+    let mut discounts_by_student: HashMap<ObjectId, Vec<&Discount>> = HashMap::new();
+    for discount in &discounts {
+        if let Some(student_id) = discount.student.id {
+            discounts_by_student
+                .entry(student_id)
+                .or_default()
+                .push(discount);
+        }
+    }
+    let mut invoiced: HashSet<ObjectId> = match db.get_invoiced_students(year, term).await {
+        Ok(students) => students.into_iter().collect(),
+        Err(err) => return internal_server_error(err),
+    };
+    let classrooms = match db.get_classrooms_for_year(year).await {
+        Ok(classrooms) => classrooms,
+        Err(err) => return internal_server_error(err),
+    };
+    let grades: HashMap<ObjectId, i64> = classrooms
+        .iter()
+        .filter_map(|classroom| Some((classroom.id?, classroom.grade_id)))
+        .collect();
+    let classroom_ids: Vec<ObjectId> = grades.keys().copied().collect();
+    let enrollments = match db.get_enrollments_for_classrooms(&classroom_ids).await {
+        Ok(enrollments) => enrollments,
+        Err(err) => return internal_server_error(err),
+    };
+    // Bill students as they are now, not as they were when enrolled, and
+    // not at all once archived.
+    let student_ids: Vec<ObjectId> = enrollments
+        .iter()
+        .filter_map(|enrollment| enrollment.student.id)
+        .collect();
+    let mut students: HashMap<ObjectId, _> = match db.get_students_by_ids(&student_ids).await {
+        Ok(students) => students
+            .into_iter()
+            .filter_map(|student| Some((student.id?, student)))
+            .collect(),
+        Err(err) => return internal_server_error(err),
+    };
+
+    let mut run = InvoiceRun {
+        created: Vec::new(),
+        already_invoiced: 0,
+        without_fees: 0,
+    };
+    for enrollment in &enrollments {
+        let (Some(student_id), Some(classroom_id)) =
+            (enrollment.student.id, enrollment.classroom.id)
+        else {
+            continue;
+        };
+        if invoiced.contains(&student_id) {
+            run.already_invoiced += 1;
+            continue;
+        }
+        let Some(fees) = grades
+            .get(&classroom_id)
+            .and_then(|grade_id| fees_by_grade.get(grade_id))
+        else {
+            run.without_fees += 1;
+            continue;
+        };
+        let Some(student) = students.remove(&student_id) else {
+            continue;
+        };
+        let discounts = discounts_by_student
+            .get(&student_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let lines = invoice_lines(fees, discounts);
+        let total_cents = lines.iter().map(|line| line.amount_cents).sum();
+        let data = Invoice {
+            id: None,
+            number: None,
+            student: StudentSummary::from(&student),
+            year,
+            term,
+            lines,
+            total_cents,
+            paid_cents: 0,
+            status: if total_cents > 0 {
+                InvoiceStatus::Open
+            } else {
+                InvoiceStatus::Paid
+            },
+            issued_at: DateTime::now(),
+            due_at: request.due_at,
+        };
+        let id = match db.create_invoice(data, &actor).await {
+            Ok(Some(inserted)) => inserted.inserted_id.as_object_id(),
+            // Invoiced by a concurrent run since the snapshot above.
+            Ok(None) => {
+                run.already_invoiced += 1;
+                invoiced.insert(student_id);
+                continue;
+            }
+            Err(err) => return internal_server_error(err),
+        };
+        invoiced.insert(student_id);
+        if let Some(id) = id {
+            if let Err(err) = db.number_invoice(id, year).await {
+                return internal_server_error(err);
+            }
+            run.created.push(id.to_hex());
+        }
+    }
+    HttpResponse::Ok().json(run)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/invoices/{id}",
+    tag = "billing",
+    params(("id" = String, Path, description = "Invoice id")),
+    responses(
+        (status = 200, description = "The invoice", body = Invoice),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "Invoice not found", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn get_invoice(db: Data<MongoRepo>, _admin: Admin, path: Path<String>) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.get_invoice(&id).await {
+        Ok(Some(invoice)) => HttpResponse::Ok().json(invoice),
+        Ok(None) => HttpResponse::NotFound().body("No invoice found with specified ID"),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/students/{id}/invoices",
+    tag = "billing",
+    params(("id" = String, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "The student's invoices, oldest first", body = Vec<Invoice>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn get_student_invoices(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    path: Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    student_invoices(&db, &id).await
+}
+
+pub async fn student_invoices(db: &MongoRepo, id: &String) -> HttpResponse {
+    match db.get_invoices_for_student(id).await {
+        Ok(invoices) => HttpResponse::Ok().json(invoices),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/invoices/{id}/void",
+    tag = "billing",
+    params(("id" = String, Path, description = "Invoice id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Invoice voided; the student can be invoiced for the term again", body = Invoice),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "Invoice not found", body = String),
+        (status = 409, description = "Only open invoices without payments can be voided", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn void_invoice(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    _admin: Admin,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    let voided = match db.void_invoice(&id, &actor).await {
+        Ok(voided) => voided,
+        Err(err) => return internal_server_error(err),
+    };
+    match db.get_invoice(&id).await {
+        Ok(Some(invoice)) if voided => HttpResponse::Ok().json(invoice),
+        Ok(Some(_)) => {
+            HttpResponse::Conflict().body("Only open invoices without payments can be voided")
+        }
+        Ok(None) => HttpResponse::NotFound().body("No invoice found with specified ID"),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct PaymentRequest {
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    amount_cents: i64,
+    method: PaymentMethod,
+    #[serde(default)]
+    reference: String,
+    /// When the money came in; now when absent
+    #[schema(value_type = Option<Object>, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    received_at: Option<DateTime>,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/invoices/{id}/payments",
+    tag = "billing",
+    request_body = PaymentRequest,
+    params(("id" = String, Path, description = "Invoice id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Payment recorded; returns the inserted id", body = Object),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "Invoice not found", body = String),
+        (status = 409, description = "The invoice is not open, or changed while the payment was recorded", body = String),
+        (status = 422, description = "Validation errors per field, including paying more than is owed", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn record_payment(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    request: Json<PaymentRequest>,
+    _admin: Admin,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let Ok(invoice_id) = ObjectId::parse_str(&id) else {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    if let Err(errors) = request.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
+    let invoice = match db.get_invoice(&id).await {
+        Ok(Some(invoice)) => invoice,
+        Ok(None) => return HttpResponse::NotFound().body("No invoice found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    if invoice.status != InvoiceStatus::Open {
+        return HttpResponse::Conflict()
+            .body("Payments can only be recorded against open invoices");
+    }
+    let outstanding = invoice.outstanding_cents();
+    if request.amount_cents > outstanding {
+        return field_error("amount_cents", format!("must be at most {}", outstanding));
+    }
+    let Some(student_id) = invoice.student.id else {
+        return internal_server_error(format!("invoice {} has no student id", id));
+    };
+    let data = Payment {
+        id: None,
+        invoice_id,
+        student_id,
+        amount_cents: request.amount_cents,
+        method: request.method,
+        reference: request.reference.to_string(),
+        received_at: request.received_at.unwrap_or_else(DateTime::now),
+        recorded_by: actor.actor.clone(),
+        receipt_number: None,
+    };
+    let payment = match db.record_payment(&invoice, data, &actor).await {
+        Ok(Some(payment)) => payment,
+        Ok(None) => {
+            return HttpResponse::Conflict()
+                .body("The invoice changed while the payment was recorded; try again")
+        }
+        Err(err) => return internal_server_error(err),
+    };
+    if let Some(payment_id) = payment.inserted_id.as_object_id() {
+        if let Err(err) = db.number_receipt(payment_id, invoice.year).await {
+            return internal_server_error(err);
+        }
+    }
+    HttpResponse::Ok().json(payment)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/invoices/{id}/payments",
+    tag = "billing",
+    params(("id" = String, Path, description = "Invoice id")),
+    responses(
+        (status = 200, description = "Payments against the invoice, oldest first", body = Vec<Payment>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn get_invoice_payments(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    path: Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let Ok(invoice_id) = ObjectId::parse_str(&id) else {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    invoice_payments(&db, invoice_id).await
+}
+
+pub async fn invoice_payments(db: &MongoRepo, invoice_id: ObjectId) -> HttpResponse {
+    match db.get_payments_for_invoices(&[invoice_id]).await {
+        Ok(payments) => HttpResponse::Ok().json(payments),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+/// What a student has been billed and has paid, void invoices aside.
+#[derive(Serialize, ToSchema)]
+pub struct StudentBalance {
+    student_id: String,
+    name: String,
+    invoiced_cents: i64,
+    paid_cents: i64,
+    /// Still owed
+    balance_cents: i64,
+}
+
+/// Totals per student over `invoices`, in order of first invoice.
+fn student_balances(invoices: &[Invoice]) -> Vec<StudentBalance> {
+    let mut balances: Vec<StudentBalance> = Vec::new();
+    for invoice in invoices {
+        if invoice.status == InvoiceStatus::Void {
+            continue;
+        }
+        let student_id = invoice.student.id.map(|id| id.to_hex()).unwrap_or_default();
+        let index = match balances
+            .iter()
+            .position(|balance| balance.student_id == student_id)
+        {
+            Some(index) => index,
+            None => {
+                balances.push(StudentBalance {
+                    student_id,
+                    name: format!("{} {}", invoice.student.fname, invoice.student.lname),
+                    invoiced_cents: 0,
+                    paid_cents: 0,
+                    balance_cents: 0,
+                });
+                balances.len() - 1
+            }
+        };
+        let balance = &mut balances[index];
+        balance.invoiced_cents += invoice.total_cents;
+        balance.paid_cents += invoice.paid_cents;
+        balance.balance_cents += invoice.outstanding_cents();
+    }
+    balances
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Balance {
+    currency: String,
+    #[serde(flatten)]
+    student: StudentBalance,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/students/{id}/balance",
+    tag = "billing",
+    params(("id" = String, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "What the student has been billed, has paid and still owes", body = Balance),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "Student not found", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn get_student_balance(
+    db: Data<MongoRepo>,
+    school: Data<SchoolSettings>,
+    _admin: Admin,
+    path: Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    student_balance(&db, &school, id).await
+}
+
+pub async fn student_balance(db: &MongoRepo, school: &SchoolSettings, id: String) -> HttpResponse {
+    let student = match db.get_student(&id, true).await {
+        Ok(Some(student)) => student,
+        Ok(None) => return HttpResponse::NotFound().body("No student found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    let invoices = match db.get_invoices_for_student(&id).await {
+        Ok(invoices) => invoices,
+        Err(err) => return internal_server_error(err),
+    };
+    let student = student_balances(&invoices)
+        .pop()
+        .unwrap_or_else(|| StudentBalance {
+            student_id: id,
+            name: format!("{} {}", student.fname, student.lname),
+            invoiced_cents: 0,
+            paid_cents: 0,
+            balance_cents: 0,
+        });
+    HttpResponse::Ok().json(Balance {
+        currency: school.currency.clone(),
+        student,
+    })
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    Invoice,
+    Payment,
+}
+
+/// One line of a statement; invoices are debits and payments credits.
+#[derive(Serialize, ToSchema)]
+pub struct StatementEntry {
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    date: DateTime,
+    kind: EntryKind,
+    description: String,
+    student_id: String,
+    invoice_id: String,
+    /// Invoice or receipt number
+    #[serde(skip_serializing_if = "Option::is_none")]
+    number: Option<String>,
+    /// Path to the printable invoice or receipt, relative to a billing
+    /// link issued for the parent, e.g. `invoices/{id}.pdf`
+    document: String,
+    debit_cents: i64,
+    credit_cents: i64,
+    /// Owed after this entry
+    balance_cents: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Statement {
+    parent_id: String,
+    currency: String,
+    /// Oldest first
+    entries: Vec<StatementEntry>,
+    students: Vec<StudentBalance>,
+    invoiced_cents: i64,
+    paid_cents: i64,
+    balance_cents: i64,
+}
+
+pub fn method_label(method: PaymentMethod) -> &'static str {
+    match method {
+        PaymentMethod::Cash => "cash",
+        PaymentMethod::Card => "card",
+        PaymentMethod::BankTransfer => "bank transfer",
+        PaymentMethod::Cheque => "cheque",
+        PaymentMethod::Other => "other",
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/parents/{id}/statement",
+    tag = "billing",
+    params(("id" = String, Path, description = "Parent id")),
+    responses(
+        (status = 200, description = "Invoices and payments for all the parent's children with a running balance", body = Statement),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "Parent not found", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn get_parent_statement(
+    db: Data<MongoRepo>,
+    school: Data<SchoolSettings>,
+    _admin: Admin,
+    path: Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.get_parent(&id, true).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("No parent found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    }
+    parent_statement(&db, &school, id).await
+}
+
+/// Invoices and payments for the children the parent is now guardian of.
+pub async fn parent_statement(db: &MongoRepo, school: &SchoolSettings, id: String) -> HttpResponse {
+    let invoices: Vec<Invoice> = match db.get_invoices_for_parent(&id).await {
+        Ok(invoices) => invoices
+            .into_iter()
+            .filter(|invoice| invoice.status != InvoiceStatus::Void)
+            .collect(),
+        Err(err) => return internal_server_error(err),
+    };
+    let invoice_ids: Vec<ObjectId> = invoices.iter().filter_map(|invoice| invoice.id).collect();
+    let payments = match db.get_payments_for_invoices(&invoice_ids).await {
+        Ok(payments) => payments,
+        Err(err) => return internal_server_error(err),
+    };
+
+    let years: HashMap<ObjectId, i32> = invoices
+        .iter()
+        .filter_map(|invoice| Some((invoice.id?, invoice.year)))
+        .collect();
+
+    let mut entries: Vec<StatementEntry> = invoices
+        .iter()
+        .map(|invoice| {
+            let invoice_id = invoice.id.map(|id| id.to_hex()).unwrap_or_default();
+            StatementEntry {
+                date: invoice.issued_at,
+                kind: EntryKind::Invoice,
+                description: format!(
+                    "{} {}, {} term {}",
+                    invoice.student.fname, invoice.student.lname, invoice.year, invoice.term
+                ),
+                student_id: invoice.student.id.map(|id| id.to_hex()).unwrap_or_default(),
+                number: invoice.display_number(),
+                document: format!("invoices/{}.pdf", invoice_id),
+                invoice_id,
+                debit_cents: invoice.total_cents,
+                credit_cents: 0,
+                balance_cents: 0,
+            }
+        })
+        .chain(payments.iter().map(|payment| {
+            let mut description = format!("Payment by {}", method_label(payment.method));
+            if !payment.reference.is_empty() {
+                description.push_str(&format!(", ref. {}", payment.reference));
+            }
+            let year = years.get(&payment.invoice_id).copied().unwrap_or_default();
+            let payment_id = payment.id.map(|id| id.to_hex()).unwrap_or_default();
+            StatementEntry {
+                date: payment.received_at,
+                kind: EntryKind::Payment,
+                description,
+                student_id: payment.student_id.to_hex(),
+                invoice_id: payment.invoice_id.to_hex(),
+                number: payment
+                    .receipt_number
+                    .map(|number| document_number(RECEIPT_PREFIX, year, number)),
+                document: format!("receipts/{}.pdf", payment_id),
+                debit_cents: 0,
+                credit_cents: payment.amount_cents,
+                balance_cents: 0,
+            }
+        }))
+        .collect();
+    // Stable, so an invoice stays ahead of a payment made the same instant.
+    entries.sort_by_key(|entry| entry.date);
+    let mut balance = 0;
+    for entry in &mut entries {
+        balance += entry.debit_cents - entry.credit_cents;
+        entry.balance_cents = balance;
+    }
+    let students = student_balances(&invoices);
+    HttpResponse::Ok().json(Statement {
+        parent_id: id,
+        currency: school.currency.clone(),
+        invoiced_cents: students.iter().map(|student| student.invoiced_cents).sum(),
+        paid_cents: students.iter().map(|student| student.paid_cents).sum(),
+        balance_cents: students.iter().map(|student| student.balance_cents).sum(),
+        entries,
+        students,
+    })
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/fees")
+            .route(web::get().to(get_fee_items))
+            .route(web::post().to(create_fee_item)),
+    )
+    .service(web::resource("/fees/{id}").route(web::delete().to(delete_fee_item)))
+    .service(
+        web::resource("/students/{id}/discounts")
+            .route(web::get().to(get_student_discounts))
+            .route(web::post().to(create_discount)),
+    )
+    .service(web::resource("/discounts/{id}").route(web::delete().to(delete_discount)))
+    .service(web::resource("/invoices/generate").route(web::post().to(generate_invoices)))
+    .service(web::resource("/invoices/{id}").route(web::get().to(get_invoice)))
+    .service(web::resource("/invoices/{id}/void").route(web::post().to(void_invoice)))
+    .service(
+        web::resource("/invoices/{id}/payments")
+            .route(web::get().to(get_invoice_payments))
+            .route(web::post().to(record_payment)),
+    )
+    .service(web::resource("/students/{id}/invoices").route(web::get().to(get_student_invoices)))
+    .service(web::resource("/students/{id}/balance").route(web::get().to(get_student_balance)))
+    .service(web::resource("/parents/{id}/statement").route(web::get().to(get_parent_statement)));
+}
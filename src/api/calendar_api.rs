@@ -0,0 +1,428 @@
+use super::{auth::Admin, errors::internal_server_error, validation};
+use crate::repository::Error;
+use crate::{
+    config::SchoolSettings,
+    ical::{Calendar, Event, When},
+    models::{
+        audit_entry::AuditContext,
+        calendar_feed::{CalendarFeed, FeedKind},
+        exam::Exam,
+        period::Weekday,
+        school_event::SchoolEvent,
+        timetable_entry::TimetableEntry,
+        validators,
+    },
+    repository::mongodb_repo::MongoRepo,
+};
+use actix_web::{
+    http::header,
+    web::{self, Data, Json, Path},
+    HttpResponse,
+};
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use uuid::Uuid;
+use validator::Validate;
+
+/// The UTC calendar date of `instant`.
+fn date_of(instant: DateTime) -> Option<NaiveDate> {
+    Utc.timestamp_millis_opt(instant.timestamp_millis())
+        .single()
+        .map(|utc| utc.naive_utc().date())
+}
+
+/// The first `day` on or after `date`.
+fn next_weekday(date: NaiveDate, day: Weekday) -> NaiveDate {
+    let target = day as i64;
+    let current = date.weekday().num_days_from_monday() as i64;
+    date + Duration::days((target - current).rem_euclid(7))
+}
+
+/// A weekly repeating event for the entry's period. It starts in the week
+/// the entry was scheduled, which the id records, so the event and its UID
+/// stay the same on every fetch.
+fn timetable_event(entry: &TimetableEntry, school: &SchoolSettings) -> Option<Event> {
+    let id = entry.id?;
+    let first = next_weekday(date_of(id.timestamp())?, entry.period.day);
+    let start = NaiveTime::parse_from_str(&entry.period.start_time, "%H:%M").ok()?;
+    let end = NaiveTime::parse_from_str(&entry.period.end_time, "%H:%M").ok()?;
+    Some(Event {
+        uid: format!("timetable-{}@{}", id.to_hex(), school.code),
+        summary: format!(
+            "{} ({} {})",
+            entry.course.name, entry.classroom.year, entry.classroom.section
+        ),
+        description: format!("Teacher: {} {}", entry.teacher.fname, entry.teacher.lname),
+        location: entry.room.clone(),
+        start: When::Floating(first.and_time(start)),
+        end: When::Floating(first.and_time(end)),
+        recurrence: Some("FREQ=WEEKLY".to_string()),
+    })
+}
+
+/// Exams have a start date only, so each is shown as an all-day event.
+fn exam_event(exam: &Exam, school: &SchoolSettings) -> Option<Event> {
+    let day = date_of(exam.start_date)?;
+    Some(Event {
+        uid: format!("exam-{}@{}", exam.id?.to_hex(), school.code),
+        summary: format!("{} ({})", exam.name, exam.exam_type.name),
+        description: exam.exam_type.desc.clone(),
+        location: String::new(),
+        start: When::Date(day),
+        end: When::Date(day + Duration::days(1)),
+        recurrence: None,
+    })
+}
+
+fn school_event(event: &SchoolEvent, school: &SchoolSettings) -> Option<Event> {
+    let (start, end) = if event.all_day {
+        let first = date_of(event.starts_at)?;
+        let last = date_of(event.ends_at)?.max(first);
+        // DTEND is exclusive for dates.
+        (When::Date(first), When::Date(last + Duration::days(1)))
+    } else {
+        (When::Instant(event.starts_at), When::Instant(event.ends_at))
+    };
+    Some(Event {
+        uid: format!("event-{}@{}", event.id?.to_hex(), school.code),
+        summary: event.title.clone(),
+        description: event.description.clone(),
+        location: event.location.clone(),
+        start,
+        end,
+        recurrence: None,
+    })
+}
+
+/// The subject's display name and timetable, or `None` if it is gone.
+async fn subject_timetable(
+    db: &MongoRepo,
+    feed: &CalendarFeed,
+) -> Result<Option<(String, Vec<TimetableEntry>)>, Error> {
+    let id = feed.subject_id.to_hex();
+    match feed.kind {
+        FeedKind::Student => {
+            let student = match db.get_student(&id, false).await? {
+                Some(student) => student,
+                None => return Ok(None),
+            };
+            let classroom_ids: Vec<ObjectId> = db
+                .get_enrollments_for_students(&[feed.subject_id])
+                .await?
+                .into_iter()
+                .filter_map(|enrollment| enrollment.classroom.id)
+                .collect();
+            let entries = db.get_timetable_for_classrooms(&classroom_ids).await?;
+            Ok(Some((
+                format!("{} {}", student.fname, student.lname),
+                entries,
+            )))
+        }
+        FeedKind::Teacher => {
+            let teacher = match db.get_teacher(&id, false).await? {
+                Some(teacher) => teacher,
+                None => return Ok(None),
+            };
+            let entries = db.get_timetable_for_teacher(&id).await?;
+            Ok(Some((
+                format!("{} {}", teacher.fname, teacher.lname),
+                entries,
+            )))
+        }
+        FeedKind::Classroom => {
+            let classroom = match db.get_classroom(&id).await? {
+                Some(classroom) => classroom,
+                None => return Ok(None),
+            };
+            let entries = db.get_timetable_for_classroom(&id).await?;
+            Ok(Some((
+                format!("{} {}", classroom.year, classroom.section),
+                entries,
+            )))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/calendar/{token}.ics",
+    tag = "calendar",
+    params(("token" = String, Path, description = "Feed token")),
+    responses(
+        (status = 200, description = "Timetable, exams and school events as iCalendar", content_type = "text/calendar", body = String),
+        (status = 404, description = "No feed with this token, or its subject was removed", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_calendar(
+    db: Data<MongoRepo>,
+    school: Data<SchoolSettings>,
+    path: Path<String>,
+) -> HttpResponse {
+    let token = path.into_inner();
+    let feed = match db.get_calendar_feed_by_token(&token).await {
+        Ok(Some(feed)) => feed,
+        Ok(None) => return HttpResponse::NotFound().body("No calendar feed found"),
+        Err(err) => return internal_server_error(err),
+    };
+    let (name, entries) = match subject_timetable(&db, &feed).await {
+        Ok(Some(subject)) => subject,
+        Ok(None) => return HttpResponse::NotFound().body("No calendar feed found"),
+        Err(err) => return internal_server_error(err),
+    };
+    let exams = match db.get_all_exams().await {
+        Ok(exams) => exams,
+        Err(err) => return internal_server_error(err),
+    };
+    let events = match db.get_all_school_events().await {
+        Ok(events) => events,
+        Err(err) => return internal_server_error(err),
+    };
+    let mut calendar = Calendar::new(
+        format!("{}: {}", school.name, name),
+        format!("-//school-manager//{}//EN", school.code),
+    );
+    entries
+        .iter()
+        .filter_map(|entry| timetable_event(entry, &school))
+        .chain(exams.iter().filter_map(|exam| exam_event(exam, &school)))
+        .chain(
+            events
+                .iter()
+                .filter_map(|event| school_event(event, &school)),
+        )
+        .for_each(|event| calendar.push(event));
+    HttpResponse::Ok()
+        .content_type("text/calendar; charset=utf-8")
+        .insert_header((header::CACHE_CONTROL, "private, max-age=300"))
+        .body(calendar.render(DateTime::now()))
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct FeedRequest {
+    kind: FeedKind,
+    /// Id of the student, teacher or classroom the feed is for
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    subject_id: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct IssuedFeed {
+    #[schema(value_type = Object)]
+    id: ObjectId,
+    kind: FeedKind,
+    #[schema(value_type = Object)]
+    subject_id: ObjectId,
+    /// Path to subscribe to, relative to this server
+    url: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/calendar/feeds",
+    tag = "calendar",
+    request_body = FeedRequest,
+    params(("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Feed issued", body = IssuedFeed),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "No student, teacher or classroom found with specified ID", body = String),
+        (status = 422, description = "Validation errors per field", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn create_feed(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    request: Json<FeedRequest>,
+    actor: AuditContext,
+) -> HttpResponse {
+    if let Err(errors) = request.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
+    let (exists, subject) = match request.kind {
+        FeedKind::Student => (
+            db.get_student(&request.subject_id, false)
+                .await
+                .map(|student| student.is_some()),
+            "student",
+        ),
+        FeedKind::Teacher => (
+            db.get_teacher(&request.subject_id, false)
+                .await
+                .map(|teacher| teacher.is_some()),
+            "teacher",
+        ),
+        FeedKind::Classroom => (
+            db.get_classroom(&request.subject_id)
+                .await
+                .map(|classroom| classroom.is_some()),
+            "classroom",
+        ),
+    };
+    match exists {
+        Ok(true) => {}
+        Ok(false) => {
+            return HttpResponse::NotFound().body(format!("No {} found with specified ID", subject))
+        }
+        Err(err) => return internal_server_error(err),
+    }
+    let data = CalendarFeed {
+        id: None,
+        kind: request.kind,
+        subject_id: ObjectId::parse_str(&request.subject_id).unwrap(),
+        token: format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()),
+        created_at: DateTime::now(),
+    };
+    let url = format!("/api/v1/calendar/{}.ics", data.token);
+    let (kind, subject_id) = (data.kind, data.subject_id);
+    match db.create_calendar_feed(data, &actor).await {
+        Ok(inserted) => match inserted.inserted_id.as_object_id() {
+            Some(id) => HttpResponse::Ok().json(IssuedFeed {
+                id,
+                kind,
+                subject_id,
+                url,
+            }),
+            None => internal_server_error("calendar feed stored without an id"),
+        },
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/calendar/feeds/{id}",
+    tag = "calendar",
+    params(("id" = String, Path, description = "Feed id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Feed revoked; its URL stops working", body = String),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "No calendar feed found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn delete_feed(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    path: Path<String>,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.delete_calendar_feed(&id, &actor).await {
+        Ok(res) => {
+            if res.deleted_count == 1 {
+                HttpResponse::Ok().json("Calendar feed successfully revoked")
+            } else {
+                HttpResponse::NotFound().json("Calendar feed with specified ID not found")
+            }
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/events",
+    tag = "calendar",
+    request_body = SchoolEvent,
+    params(("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Event created; returns the inserted id", body = Object),
+        (status = 422, description = "Validation errors per field", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn create_school_event(
+    db: Data<MongoRepo>,
+    new_event: Json<SchoolEvent>,
+    actor: AuditContext,
+) -> HttpResponse {
+    if let Err(errors) = new_event.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
+    let data = SchoolEvent {
+        id: None,
+        title: new_event.title.to_string(),
+        description: new_event.description.to_string(),
+        location: new_event.location.to_string(),
+        starts_at: new_event.starts_at,
+        ends_at: new_event.ends_at,
+        all_day: new_event.all_day,
+    };
+    let event_detail = db.create_school_event(data, &actor).await;
+    match event_detail {
+        Ok(event) => HttpResponse::Ok().json(event),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/events",
+    tag = "calendar",
+    responses(
+        (status = 200, description = "School events, soonest first", body = Vec<SchoolEvent>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_all_school_events(db: Data<MongoRepo>) -> HttpResponse {
+    let events = db.get_all_school_events().await;
+    match events {
+        Ok(events) => HttpResponse::Ok().json(events),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/events/{id}",
+    tag = "calendar",
+    params(("id" = String, Path, description = "Event id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Event removed; feeds drop it on their next refresh", body = String),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No event found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn delete_school_event(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.delete_school_event(&id, &actor).await {
+        Ok(res) => {
+            if res.deleted_count == 1 {
+                HttpResponse::Ok().json("Event successfully deleted")
+            } else {
+                HttpResponse::NotFound().json("Event with specified ID not found")
+            }
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/calendar/feeds").route(web::post().to(create_feed)))
+        .service(web::resource("/calendar/feeds/{id}").route(web::delete().to(delete_feed)))
+        .service(web::resource("/calendar/{token}.ics").route(web::get().to(get_calendar)))
+        .service(
+            web::resource("/events")
+                .route(web::get().to(get_all_school_events))
+                .route(web::post().to(create_school_event)),
+        )
+        .service(web::resource("/events/{id}").route(web::delete().to(delete_school_event)));
+}
@@ -0,0 +1,703 @@
+use super::{
+    auth::Admin,
+    billing_api::{self, method_label},
+    errors::internal_server_error,
+};
+use crate::{
+    config::SchoolSettings,
+    format::date,
+    models::{
+        audit_entry::AuditContext,
+        billing_link::BillingLink,
+        invoice::{document_number, format_cents, Invoice, InvoiceStatus, RECEIPT_PREFIX},
+        parent::Parent,
+        payment::Payment,
+        validators,
+    },
+    pdf::{Document, Weight},
+    repository::mongodb_repo::MongoRepo,
+};
+use actix_web::{
+    http::header::{self, ContentDisposition, DispositionParam, DispositionType},
+    web::{self, Data, Path},
+    HttpResponse,
+};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+fn pdf_response(filename: String, document: Document) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("application/pdf")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Inline,
+            parameters: vec![DispositionParam::Filename(filename)],
+        })
+        .insert_header((header::CACHE_CONTROL, "private, no-cache"))
+        .body(document.render())
+}
+
+/// The guardian a billing link was issued to, unless since archived.
+async fn link_parent(db: &MongoRepo, token: &str) -> Result<Option<Parent>, HttpResponse> {
+    let parent_id = match db.get_billing_link_by_token(token).await {
+        Ok(Some(link)) => link.parent_id,
+        Ok(None) => return Ok(None),
+        Err(err) => return Err(internal_server_error(err)),
+    };
+    db.get_parent(&parent_id.to_hex(), false)
+        .await
+        .map_err(internal_server_error)
+}
+
+/// Whether `parent` is the student's guardian now. The guardian recorded
+/// on an invoice is only who it was when the invoice was made.
+async fn is_guardian(
+    db: &MongoRepo,
+    parent: &Parent,
+    student_id: Option<ObjectId>,
+) -> Result<bool, HttpResponse> {
+    let Some(student_id) = student_id else {
+        return Ok(false);
+    };
+    match db.get_student(&student_id.to_hex(), true).await {
+        Ok(Some(student)) => Ok(parent.id.is_some() && student.parent.id == parent.id),
+        Ok(None) => Ok(false),
+        Err(err) => Err(internal_server_error(err)),
+    }
+}
+
+/// Looks up an invoice for the current guardian of the student it bills.
+/// Someone else's invoice is reported as missing rather than forbidden, so
+/// ids cannot be probed through another parent's link.
+async fn guardian_invoice(
+    db: &MongoRepo,
+    parent: &Parent,
+    invoice_id: &String,
+) -> Result<Option<Invoice>, HttpResponse> {
+    let invoice = match db.get_invoice(invoice_id).await {
+        Ok(Some(invoice)) => invoice,
+        Ok(None) => return Ok(None),
+        Err(err) => return Err(internal_server_error(err)),
+    };
+    match is_guardian(db, parent, invoice.student.id).await? {
+        true => Ok(Some(invoice)),
+        false => Ok(None),
+    }
+}
+
+fn invoice_document(
+    school: &SchoolSettings,
+    parent: &Parent,
+    invoice: &Invoice,
+    number: &str,
+    payments: &[Payment],
+) -> Document {
+    let student = &invoice.student;
+    let mut document = Document::new(format!("Invoice {}", number));
+    document.heading(&school.name);
+    document.text(&format!("Invoice {}", number), Weight::Bold);
+    document.space();
+    document.text(
+        &format!("Issued: {}", date(invoice.issued_at)),
+        Weight::Regular,
+    );
+    document.text(&format!("Due: {}", date(invoice.due_at)), Weight::Regular);
+    document.text(
+        &format!("For: {} term {}", invoice.year, invoice.term),
+        Weight::Regular,
+    );
+    document.text(
+        &format!("Bill to: {} {}", parent.fname, parent.lname),
+        Weight::Regular,
+    );
+    document.text(
+        &format!("Student: {} {}", student.fname, student.lname),
+        Weight::Regular,
+    );
+    document.space();
+    document.row(
+        "Description",
+        &format!("Amount ({})", school.currency),
+        Weight::Bold,
+    );
+    document.rule();
+    for line in &invoice.lines {
+        document.row(
+            &line.description,
+            &format_cents(line.amount_cents),
+            Weight::Regular,
+        );
+    }
+    document.rule();
+    document.row("Total", &format_cents(invoice.total_cents), Weight::Bold);
+    for payment in payments {
+        let label = match payment.receipt_number {
+            Some(receipt) => format!(
+                "Paid {} (receipt {})",
+                date(payment.received_at),
+                document_number(RECEIPT_PREFIX, invoice.year, receipt)
+            ),
+            None => format!("Paid {}", date(payment.received_at)),
+        };
+        document.row(
+            &label,
+            &format_cents(-payment.amount_cents),
+            Weight::Regular,
+        );
+    }
+    document.rule();
+    document.row(
+        "Balance due",
+        &format_cents(invoice.outstanding_cents()),
+        Weight::Bold,
+    );
+    if invoice.status == InvoiceStatus::Void {
+        document.space();
+        document.text(
+            "This invoice has been voided and nothing is owed on it.",
+            Weight::Bold,
+        );
+    }
+    document
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/billing/{token}/invoices/{id}.pdf",
+    tag = "billing",
+    params(
+        ("token" = String, Path, description = "Billing link token of the guardian linked to the invoiced student"),
+        ("id" = String, Path, description = "Invoice id"),
+    ),
+    responses(
+        (status = 200, description = "The invoice, with the payments made against it, as a printable PDF", content_type = "application/pdf", body = Vec<u8>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No billing link with this token, or no invoice with this id for the parent's children", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_invoice_pdf(
+    db: Data<MongoRepo>,
+    school: Data<SchoolSettings>,
+    path: Path<(String, String)>,
+) -> HttpResponse {
+    let (token, id) = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    let parent = match link_parent(&db, &token).await {
+        Ok(Some(parent)) => parent,
+        Ok(None) => return HttpResponse::NotFound().body("No billing link found"),
+        Err(response) => return response,
+    };
+    let mut invoice = match guardian_invoice(&db, &parent, &id).await {
+        Ok(Some(invoice)) => invoice,
+        Ok(None) => return HttpResponse::NotFound().body("No invoice found with specified ID"),
+        Err(response) => return response,
+    };
+    let Some(invoice_id) = invoice.id else {
+        return internal_server_error(format!("invoice {} has no id", id));
+    };
+    // Invoices issued before numbering began get their number on first print.
+    if invoice.number.is_none() {
+        match db.number_invoice(invoice_id, invoice.year).await {
+            Ok(number) => invoice.number = Some(number),
+            Err(err) => return internal_server_error(err),
+        }
+    }
+    let payments = match db.get_payments_for_invoices(&[invoice_id]).await {
+        Ok(payments) => payments,
+        Err(err) => return internal_server_error(err),
+    };
+    let number = invoice.display_number().unwrap_or_default();
+    let document = invoice_document(&school, &parent, &invoice, &number, &payments);
+    pdf_response(format!("{}.pdf", number), document)
+}
+
+fn receipt_document(
+    school: &SchoolSettings,
+    parent: &Parent,
+    invoice: &Invoice,
+    payment: &Payment,
+    number: &str,
+    balance_after: i64,
+) -> Document {
+    let student = &invoice.student;
+    let mut document = Document::new(format!("Receipt {}", number));
+    document.heading(&school.name);
+    document.text(&format!("Receipt {}", number), Weight::Bold);
+    document.space();
+    document.text(
+        &format!("Received: {}", date(payment.received_at)),
+        Weight::Regular,
+    );
+    document.text(
+        &format!("From: {} {}", parent.fname, parent.lname),
+        Weight::Regular,
+    );
+    document.text(
+        &format!("Student: {} {}", student.fname, student.lname),
+        Weight::Regular,
+    );
+    let mut method = format!("Method: {}", method_label(payment.method));
+    if !payment.reference.is_empty() {
+        method.push_str(&format!(", ref. {}", payment.reference));
+    }
+    document.text(&method, Weight::Regular);
+    document.space();
+    document.row(
+        "Description",
+        &format!("Amount ({})", school.currency),
+        Weight::Bold,
+    );
+    document.rule();
+    document.row(
+        &format!(
+            "Payment towards invoice {}, {} term {}",
+            invoice.display_number().unwrap_or_default(),
+            invoice.year,
+            invoice.term
+        ),
+        &format_cents(payment.amount_cents),
+        Weight::Regular,
+    );
+    document.rule();
+    document.row(
+        "Amount received",
+        &format_cents(payment.amount_cents),
+        Weight::Bold,
+    );
+    document.row(
+        "Still owed on the invoice after this payment",
+        &format_cents(balance_after),
+        Weight::Regular,
+    );
+    document
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/billing/{token}/receipts/{id}.pdf",
+    tag = "billing",
+    params(
+        ("token" = String, Path, description = "Billing link token of the guardian linked to the student paid for"),
+        ("id" = String, Path, description = "Payment id"),
+    ),
+    responses(
+        (status = 200, description = "A receipt for the payment as a printable PDF", content_type = "application/pdf", body = Vec<u8>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No billing link with this token, or no payment with this id for the parent's children", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_receipt_pdf(
+    db: Data<MongoRepo>,
+    school: Data<SchoolSettings>,
+    path: Path<(String, String)>,
+) -> HttpResponse {
+    let (token, id) = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    let parent = match link_parent(&db, &token).await {
+        Ok(Some(parent)) => parent,
+        Ok(None) => return HttpResponse::NotFound().body("No billing link found"),
+        Err(response) => return response,
+    };
+    let mut payment = match db.get_payment(&id).await {
+        Ok(Some(payment)) => payment,
+        Ok(None) => return HttpResponse::NotFound().body("No payment found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    let invoice_id = payment.invoice_id.to_hex();
+    let mut invoice = match guardian_invoice(&db, &parent, &invoice_id).await {
+        Ok(Some(invoice)) => invoice,
+        Ok(None) => return HttpResponse::NotFound().body("No payment found with specified ID"),
+        Err(response) => return response,
+    };
+    if invoice.number.is_none() {
+        match db.number_invoice(payment.invoice_id, invoice.year).await {
+            Ok(number) => invoice.number = Some(number),
+            Err(err) => return internal_server_error(err),
+        }
+    }
+    let Some(payment_id) = payment.id else {
+        return internal_server_error(format!("payment {} has no id", id));
+    };
+    if payment.receipt_number.is_none() {
+        match db.number_receipt(payment_id, invoice.year).await {
+            Ok(number) => payment.receipt_number = Some(number),
+            Err(err) => return internal_server_error(err),
+        }
+    }
+    let payments = match db.get_payments_for_invoices(&[payment.invoice_id]).await {
+        Ok(payments) => payments,
+        Err(err) => return internal_server_error(err),
+    };
+    // Payments come oldest first, so this one and those before it.
+    let paid_by_then: i64 = match payments.iter().position(|other| other.id == payment.id) {
+        Some(index) => payments[..=index]
+            .iter()
+            .map(|other| other.amount_cents)
+            .sum(),
+        None => payment.amount_cents,
+    };
+    let number = document_number(
+        RECEIPT_PREFIX,
+        invoice.year,
+        payment.receipt_number.unwrap_or_default(),
+    );
+    let balance_after = invoice.total_cents - paid_by_then;
+    let document = receipt_document(&school, &parent, &invoice, &payment, &number, balance_after);
+    pdf_response(format!("{}.pdf", number), document)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/billing/{token}/statement",
+    tag = "billing",
+    params(("token" = String, Path, description = "Billing link token")),
+    responses(
+        (status = 200, description = "Invoices and payments for all the guardian's children with a running balance", body = Statement),
+        (status = 404, description = "No billing link with this token", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_guardian_statement(
+    db: Data<MongoRepo>,
+    school: Data<SchoolSettings>,
+    path: Path<String>,
+) -> HttpResponse {
+    let token = path.into_inner();
+    let parent_id = match link_parent(&db, &token).await {
+        Ok(Some(parent)) => parent.id.map(|id| id.to_hex()).unwrap_or_default(),
+        Ok(None) => return HttpResponse::NotFound().body("No billing link found"),
+        Err(response) => return response,
+    };
+    billing_api::parent_statement(&db, &school, parent_id).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/billing/{token}/invoices/{id}",
+    tag = "billing",
+    params(
+        ("token" = String, Path, description = "Billing link token of the guardian of the invoiced student"),
+        ("id" = String, Path, description = "Invoice id"),
+    ),
+    responses(
+        (status = 200, description = "The invoice", body = Invoice),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No billing link with this token, or no invoice with this id for the parent's children", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_guardian_invoice(
+    db: Data<MongoRepo>,
+    path: Path<(String, String)>,
+) -> HttpResponse {
+    let (token, id) = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    let parent = match link_parent(&db, &token).await {
+        Ok(Some(parent)) => parent,
+        Ok(None) => return HttpResponse::NotFound().body("No billing link found"),
+        Err(response) => return response,
+    };
+    match guardian_invoice(&db, &parent, &id).await {
+        Ok(Some(invoice)) => HttpResponse::Ok().json(invoice),
+        Ok(None) => HttpResponse::NotFound().body("No invoice found with specified ID"),
+        Err(response) => response,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/billing/{token}/invoices/{id}/payments",
+    tag = "billing",
+    params(
+        ("token" = String, Path, description = "Billing link token of the guardian of the invoiced student"),
+        ("id" = String, Path, description = "Invoice id"),
+    ),
+    responses(
+        (status = 200, description = "Payments against the invoice, oldest first", body = Vec<Payment>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No billing link with this token, or no invoice with this id for the parent's children", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_guardian_invoice_payments(
+    db: Data<MongoRepo>,
+    path: Path<(String, String)>,
+) -> HttpResponse {
+    let (token, id) = path.into_inner();
+    let Ok(invoice_id) = ObjectId::parse_str(&id) else {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    let parent = match link_parent(&db, &token).await {
+        Ok(Some(parent)) => parent,
+        Ok(None) => return HttpResponse::NotFound().body("No billing link found"),
+        Err(response) => return response,
+    };
+    match guardian_invoice(&db, &parent, &id).await {
+        Ok(Some(_)) => billing_api::invoice_payments(&db, invoice_id).await,
+        Ok(None) => HttpResponse::NotFound().body("No invoice found with specified ID"),
+        Err(response) => response,
+    }
+}
+
+/// Checks that a billing link's guardian is the student's guardian now,
+/// answering 404 for any other student.
+async fn check_guardian_of(
+    db: &MongoRepo,
+    token: &str,
+    student_id: &str,
+) -> Result<(), HttpResponse> {
+    let Ok(student_id) = ObjectId::parse_str(student_id) else {
+        return Err(HttpResponse::BadRequest().body("invalid ID"));
+    };
+    let parent = match link_parent(db, token).await? {
+        Some(parent) => parent,
+        None => return Err(HttpResponse::NotFound().body("No billing link found")),
+    };
+    match is_guardian(db, &parent, Some(student_id)).await? {
+        true => Ok(()),
+        false => Err(HttpResponse::NotFound().body("No student found with specified ID")),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/billing/{token}/students/{id}/invoices",
+    tag = "billing",
+    params(
+        ("token" = String, Path, description = "Billing link token of the student's guardian"),
+        ("id" = String, Path, description = "Student id"),
+    ),
+    responses(
+        (status = 200, description = "The student's invoices, oldest first", body = Vec<Invoice>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No billing link with this token, or no such student among the parent's children", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_guardian_student_invoices(
+    db: Data<MongoRepo>,
+    path: Path<(String, String)>,
+) -> HttpResponse {
+    let (token, id) = path.into_inner();
+    match check_guardian_of(&db, &token, &id).await {
+        Ok(()) => billing_api::student_invoices(&db, &id).await,
+        Err(response) => response,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/billing/{token}/students/{id}/balance",
+    tag = "billing",
+    params(
+        ("token" = String, Path, description = "Billing link token of the student's guardian"),
+        ("id" = String, Path, description = "Student id"),
+    ),
+    responses(
+        (status = 200, description = "What the student has been billed, has paid and still owes", body = Balance),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No billing link with this token, or no such student among the parent's children", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_guardian_student_balance(
+    db: Data<MongoRepo>,
+    school: Data<SchoolSettings>,
+    path: Path<(String, String)>,
+) -> HttpResponse {
+    let (token, id) = path.into_inner();
+    match check_guardian_of(&db, &token, &id).await {
+        Ok(()) => billing_api::student_balance(&db, &school, id).await,
+        Err(response) => response,
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct IssuedBillingLink {
+    #[schema(value_type = Object)]
+    id: ObjectId,
+    #[schema(value_type = Object)]
+    parent_id: ObjectId,
+    /// Path the guardian's documents are under, relative to this server;
+    /// statement entries give each document's path relative to it
+    url: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/parents/{id}/billing-links",
+    tag = "billing",
+    params(("id" = String, Path, description = "Parent id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Link issued; anyone holding it can see the billing of the parent's children and print their invoices and receipts", body = IssuedBillingLink),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "Parent not found", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn create_billing_link(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    path: Path<String>,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let Ok(parent_id) = ObjectId::parse_str(&id) else {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    match db.get_parent(&id, false).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("No parent found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    }
+    let data = BillingLink {
+        id: None,
+        parent_id,
+        token: format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()),
+        created_at: DateTime::now(),
+    };
+    let url = format!("/api/v1/billing/{}", data.token);
+    match db.create_billing_link(data, &actor).await {
+        Ok(inserted) => match inserted.inserted_id.as_object_id() {
+            Some(id) => HttpResponse::Ok().json(IssuedBillingLink { id, parent_id, url }),
+            None => internal_server_error("billing link stored without an id"),
+        },
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/billing-links/{id}",
+    tag = "billing",
+    params(("id" = String, Path, description = "Billing link id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Link revoked; its documents can no longer be fetched through it", body = String),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "No billing link found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn delete_billing_link(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    path: Path<String>,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.delete_billing_link(&id, &actor).await {
+        Ok(res) => {
+            if res.deleted_count == 1 {
+                HttpResponse::Ok().json("Billing link successfully revoked")
+            } else {
+                HttpResponse::NotFound().json("Billing link with specified ID not found")
+            }
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/billing/{token}/invoices/{id}.pdf").route(web::get().to(get_invoice_pdf)),
+    )
+    .service(
+        web::resource("/billing/{token}/receipts/{id}.pdf").route(web::get().to(get_receipt_pdf)),
+    )
+    .service(
+        web::resource("/billing/{token}/statement").route(web::get().to(get_guardian_statement)),
+    )
+    .service(
+        web::resource("/billing/{token}/invoices/{id}").route(web::get().to(get_guardian_invoice)),
+    )
+    .service(
+        web::resource("/billing/{token}/invoices/{id}/payments")
+            .route(web::get().to(get_guardian_invoice_payments)),
+    )
+    .service(
+        web::resource("/billing/{token}/students/{id}/invoices")
+            .route(web::get().to(get_guardian_student_invoices)),
+    )
+    .service(
+        web::resource("/billing/{token}/students/{id}/balance")
+            .route(web::get().to(get_guardian_student_balance)),
+    )
+    .service(
+        web::resource("/parents/{id}/billing-links").route(web::post().to(create_billing_link)),
+    )
+    .service(web::resource("/billing-links/{id}").route(web::delete().to(delete_billing_link)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{self, person};
+    use actix_web::{
+        http::StatusCode,
+        test::{call_and_read_body_json, call_service, init_service, TestRequest},
+        App,
+    };
+    use serde_json::Value;
+
+    #[actix_web::test]
+    #[ignore = "needs MongoDB at SM_TEST_MONGODB_URI"]
+    async fn a_revoked_billing_link_stops_working_and_leaves_others_alone() {
+        let db = testing::mongodb_repo().await;
+        let app = init_service(App::new().configure(testing::app(db.clone()))).await;
+        let request = TestRequest::post()
+            .uri("/api/v1/parents")
+            .set_json(person("guardian@home.example"))
+            .to_request();
+        let parent_id = testing::inserted_id(&call_and_read_body_json(&app, request).await);
+        let issue = || {
+            TestRequest::post()
+                .uri(&format!("/api/v1/parents/{}/billing-links", parent_id))
+                .insert_header(testing::admin())
+                .to_request()
+        };
+        let revoked: Value = call_and_read_body_json(&app, issue()).await;
+        let kept: Value = call_and_read_body_json(&app, issue()).await;
+        let statement = |link: &Value| {
+            let url = link["url"].as_str().unwrap();
+            TestRequest::get()
+                .uri(&format!("{}/statement", url))
+                .to_request()
+        };
+        let response = call_service(&app, statement(&revoked)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let revoke = || {
+            let id = revoked["id"]["$oid"].as_str().unwrap();
+            TestRequest::delete().uri(&format!("/api/v1/billing-links/{}", id))
+        };
+        let response = call_service(&app, revoke().to_request()).await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let request = revoke().insert_header(testing::admin()).to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = call_service(&app, statement(&revoked)).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = call_service(&app, statement(&kept)).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let request = revoke().insert_header(testing::admin()).to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        testing::drop_database(&db).await;
+    }
+}
@@ -0,0 +1,9 @@
+use actix_web::HttpResponse;
+use std::fmt::Display;
+
+/// Logs a repository failure, tagged with the current request's id by the
+/// enclosing span, and responds 500.
+pub fn internal_server_error(err: impl Display) -> HttpResponse {
+    tracing::error!(error = %err, "repository error");
+    HttpResponse::InternalServerError().body(err.to_string())
+}
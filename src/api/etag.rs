@@ -0,0 +1,43 @@
+use actix_web::{
+    dev::Payload,
+    error::ErrorPreconditionFailed,
+    http::header::{ETag, EntityTag, Header, IfMatch},
+    Error, FromRequest, HttpRequest, HttpResponse,
+};
+use futures::future::{ready, Ready};
+
+pub fn entity_tag(version: i64) -> ETag {
+    ETag(EntityTag::new_strong(version.to_string()))
+}
+
+pub fn precondition_failed() -> HttpResponse {
+    HttpResponse::PreconditionFailed().body("Record was modified by another request")
+}
+
+/// The version a client expects to modify, read from its `If-Match` header.
+/// A missing header or `*` means the write is unconditional.
+pub struct ExpectedVersion(pub Option<i64>);
+
+impl FromRequest for ExpectedVersion {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let tags = match IfMatch::parse(req) {
+            Ok(IfMatch::Items(tags)) => tags,
+            Ok(IfMatch::Any) => return ready(Ok(ExpectedVersion(None))),
+            Err(_) if !req.headers().contains_key(IfMatch::name()) => {
+                return ready(Ok(ExpectedVersion(None)))
+            }
+            Err(_) => return ready(Err(ErrorPreconditionFailed("invalid If-Match header"))),
+        };
+        let version = tags
+            .iter()
+            .filter(|tag| !tag.weak)
+            .find_map(|tag| tag.tag().parse::<i64>().ok());
+        ready(match version {
+            Some(version) => Ok(ExpectedVersion(Some(version))),
+            None => Err(ErrorPreconditionFailed("If-Match does not name a version")),
+        })
+    }
+}
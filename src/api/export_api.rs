@@ -0,0 +1,399 @@
+//! Spreadsheet exports. CSV is streamed to the client row by row as the
+//! repository cursor yields documents. XLSX is a zip archive that can only be
+//! sent once complete, so rows go from the cursor into a constant-memory
+//! worksheet (spooled to a temp file) and the finished file is sent in one go.
+
+use super::{archive::IncludeDeleted, auth::Admin, errors::internal_server_error};
+use crate::{
+    models::{
+        attendance::Attendance, classroom_student::ClassroomStudent, exam_result::ExamResult,
+        student::Student, validators,
+    },
+    repository::mongodb_repo::MongoRepo,
+};
+use actix_web::{
+    http::header::{ContentDisposition, DispositionParam, DispositionType},
+    web::{self, Bytes, Data, Path, Query},
+    HttpResponse,
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use mongodb::{
+    bson::{oid::ObjectId, DateTime},
+    Cursor,
+};
+use rust_xlsxwriter::{Format, Workbook};
+use serde::{de::DeserializeOwned, Deserialize};
+use std::borrow::Cow;
+use utoipa::IntoParams;
+
+const CSV: &str = "text/csv; charset=utf-8";
+const XLSX: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+/// Default span of the attendance report when no `from` is given.
+const ATTENDANCE_DEFAULT_DAYS: i64 = 30;
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+/// A record that exports as one spreadsheet row.
+trait Tabular: DeserializeOwned + Unpin + Send + Sync + 'static {
+    const HEADERS: &'static [&'static str];
+
+    fn cells(&self) -> Vec<String>;
+}
+
+fn to_id(id: &Option<ObjectId>) -> String {
+    id.map(|id| id.to_hex()).unwrap_or_default()
+}
+
+fn to_day(date: &DateTime) -> String {
+    date.try_to_rfc3339_string()
+        .map(|date| date[..10].to_string())
+        .unwrap_or_default()
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
+
+impl Tabular for Student {
+    const HEADERS: &'static [&'static str] = &[
+        "ID",
+        "Email",
+        "First name",
+        "Last name",
+        "Date of birth",
+        "Phone",
+        "Mobile",
+        "Guardian",
+        "Guardian email",
+        "Date of join",
+        "Active",
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            to_id(&self.id),
+            self.email.clone(),
+            self.fname.clone(),
+            self.lname.clone(),
+            to_day(&self.dob),
+            self.phone.clone(),
+            self.mobile.clone(),
+            format!("{} {}", self.parent.fname, self.parent.lname),
+            self.parent.email.clone(),
+            to_day(&self.date_of_join),
+            yes_no(self.status),
+        ]
+    }
+}
+
+impl Tabular for ClassroomStudent {
+    const HEADERS: &'static [&'static str] = &[
+        "Student ID",
+        "First name",
+        "Last name",
+        "Email",
+        "Date of birth",
+        "Guardian",
+        "Guardian email",
+        "Guardian mobile",
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        let student = &self.student;
+        vec![
+            to_id(&student.id),
+            student.fname.clone(),
+            student.lname.clone(),
+            student.email.clone(),
+            to_day(&student.dob),
+            format!("{} {}", student.parent.fname, student.parent.lname),
+            student.parent.email.clone(),
+            student.parent.mobile.clone(),
+        ]
+    }
+}
+
+impl Tabular for Attendance {
+    const HEADERS: &'static [&'static str] = &[
+        "Date",
+        "Student ID",
+        "First name",
+        "Last name",
+        "Present",
+        "Remark",
+    ];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            to_day(&self.date),
+            to_id(&self.student.id),
+            self.student.fname.clone(),
+            self.student.lname.clone(),
+            yes_no(self.status),
+            self.remark.clone(),
+        ]
+    }
+}
+
+impl Tabular for ExamResult {
+    const HEADERS: &'static [&'static str] =
+        &["Student ID", "First name", "Last name", "Course", "Marks"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            to_id(&self.student.id),
+            self.student.fname.clone(),
+            self.student.lname.clone(),
+            self.course.name.clone(),
+            self.marks.clone(),
+        ]
+    }
+}
+
+fn attachment(filename: &str) -> ContentDisposition {
+    ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters: vec![DispositionParam::Filename(filename.to_string())],
+    }
+}
+
+/// Characters that make a spreadsheet read a cell as a formula.
+const FORMULA_TRIGGERS: [u8; 6] = [b'=', b'+', b'-', b'@', b'\t', b'\r'];
+
+/// The cell as text a spreadsheet will not evaluate: one that would start a
+/// formula gets a leading `'`.
+fn inert(cell: &[u8]) -> Cow<'_, [u8]> {
+    match cell.first() {
+        Some(first) if FORMULA_TRIGGERS.contains(first) => Cow::Owned([b"'", cell].concat()),
+        _ => Cow::Borrowed(cell),
+    }
+}
+
+fn csv_line<I, T>(cells: I) -> Bytes
+where
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let cells: Vec<T> = cells.into_iter().collect();
+    writer
+        .write_record(cells.iter().map(|cell| inert(cell.as_ref())))
+        .expect("Writing CSV to memory cannot fail");
+    Bytes::from(
+        writer
+            .into_inner()
+            .expect("Writing CSV to memory cannot fail"),
+    )
+}
+
+/// Streams the header and then one line per document. A database error
+/// mid-stream can no longer change the status, so it is logged and the
+/// response is cut short.
+fn csv_response<T: Tabular>(cursor: Cursor<T>, filename: &str) -> HttpResponse {
+    let header = stream::once(async { Ok(csv_line(T::HEADERS)) });
+    let rows = cursor
+        .map_ok(|record| csv_line(record.cells()))
+        .map_err(|err| {
+            tracing::error!(error = %err, "export aborted");
+            err
+        });
+    HttpResponse::Ok()
+        .content_type(CSV)
+        .insert_header(attachment(filename))
+        .streaming(header.chain(rows))
+}
+
+async fn xlsx_response<T: Tabular>(
+    mut cursor: Cursor<T>,
+    sheet: &str,
+    filename: &str,
+) -> HttpResponse {
+    let mut workbook = Workbook::new();
+    let worksheet = workbook.add_worksheet_with_constant_memory();
+    if let Err(err) = worksheet.set_name(sheet) {
+        return internal_server_error(err);
+    }
+    let bold = Format::new().set_bold();
+    if let Err(err) = worksheet.write_row_with_format(0, 0, T::HEADERS.to_vec(), &bold) {
+        return internal_server_error(err);
+    }
+    worksheet.set_freeze_panes(1, 0).ok();
+    let mut row = 1;
+    loop {
+        let record = match cursor.try_next().await {
+            Ok(Some(record)) => record,
+            Ok(None) => break,
+            Err(err) => return internal_server_error(err),
+        };
+        if let Err(err) = worksheet.write_row(row, 0, record.cells()) {
+            return internal_server_error(err);
+        }
+        row += 1;
+    }
+    match workbook.save_to_buffer() {
+        Ok(buffer) => HttpResponse::Ok()
+            .content_type(XLSX)
+            .insert_header(attachment(filename))
+            .body(buffer),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/export/students.csv",
+    tag = "export",
+    params(("include_deleted" = Option<bool>, Query, description = "Also export archived students")),
+    responses(
+        (status = 200, description = "Students by last name", content_type = "text/csv", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn export_students(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    IncludeDeleted(include_deleted): IncludeDeleted,
+) -> HttpResponse {
+    match db.stream_students(include_deleted).await {
+        Ok(cursor) => csv_response(cursor, "students.csv"),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/export/classrooms/{id}/roster.xlsx",
+    tag = "export",
+    params(("id" = String, Path, description = "Classroom id")),
+    responses(
+        (status = 200, description = "Students enrolled in the classroom", content_type = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", body = Vec<u8>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "No classroom found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn export_roster(db: Data<MongoRepo>, _admin: Admin, path: Path<String>) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    let classroom = match db.get_classroom(&id).await {
+        Ok(Some(classroom)) => classroom,
+        Ok(None) => return HttpResponse::NotFound().body("No classroom found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    let sheet = format!("{} {}", classroom.year, classroom.section);
+    match db.stream_roster(&id).await {
+        Ok(cursor) => xlsx_response(cursor, sheet.trim(), "roster.xlsx").await,
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct AttendanceReportQuery {
+    /// RFC 3339 start of the report, inclusive; defaults to 30 days before `to`
+    from: Option<String>,
+    /// RFC 3339 end of the report, exclusive; defaults to now
+    to: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/export/reports/attendance.xlsx",
+    tag = "export",
+    params(AttendanceReportQuery),
+    responses(
+        (status = 200, description = "Attendance by day and student", content_type = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet", body = Vec<u8>),
+        (status = 400, description = "Invalid date", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn export_attendance(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    query: Query<AttendanceReportQuery>,
+) -> HttpResponse {
+    let query = query.into_inner();
+    let parse = |bound: Option<String>| bound.map(|bound| DateTime::parse_rfc3339_str(&bound));
+    let to = match parse(query.to) {
+        Some(Ok(to)) => to,
+        None => DateTime::now(),
+        Some(Err(_)) => return HttpResponse::BadRequest().body("invalid RFC 3339 date"),
+    };
+    let from = match parse(query.from) {
+        Some(Ok(from)) => from,
+        None => DateTime::from_millis(to.timestamp_millis() - ATTENDANCE_DEFAULT_DAYS * DAY_MILLIS),
+        Some(Err(_)) => return HttpResponse::BadRequest().body("invalid RFC 3339 date"),
+    };
+    match db.stream_attendance(from, to).await {
+        Ok(cursor) => xlsx_response(cursor, "Attendance", "attendance.xlsx").await,
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/export/exams/{id}/results.csv",
+    tag = "export",
+    params(("id" = String, Path, description = "Exam id")),
+    responses(
+        (status = 200, description = "Results by student and course", content_type = "text/csv", body = String),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "No exam found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn export_exam_results(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    path: Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.get_exam(&id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("No exam found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    }
+    match db.stream_exam_results(&id).await {
+        Ok(cursor) => csv_response(cursor, "results.csv"),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/export")
+            .route("/students.csv", web::get().to(export_students))
+            .route("/classrooms/{id}/roster.xlsx", web::get().to(export_roster))
+            .route("/reports/attendance.xlsx", web::get().to(export_attendance))
+            .route(
+                "/exams/{id}/results.csv",
+                web::get().to(export_exam_results),
+            ),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_cells_that_would_start_a_formula_are_quoted() {
+        let line = csv_line(["=HYPERLINK(\"x\")", "+1", "-2", "@SUM(A1)", "Ada", ""]);
+        assert_eq!(
+            line,
+            Bytes::from_static(b"\"'=HYPERLINK(\"\"x\"\")\",'+1,'-2,'@SUM(A1),Ada,\n")
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use crate::repository::mongodb_repo::MongoRepo;
+use actix_web::{
+    web::{self, Data},
+    HttpResponse,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use utoipa::ToSchema;
+
+#[derive(Serialize, ToSchema)]
+pub struct DependencyStatus {
+    /// `up` or `down`
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    latency_ms: Option<u128>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Readiness {
+    /// `ready` or `unavailable`
+    status: &'static str,
+    checks: BTreeMap<&'static str, DependencyStatus>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct VersionInfo {
+    name: &'static str,
+    version: &'static str,
+    git_sha: &'static str,
+    rustc: &'static str,
+    profile: &'static str,
+}
+
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "health",
+    responses((status = 200, description = "The process is alive")),
+)]
+pub async fn liveness() -> HttpResponse {
+    HttpResponse::Ok().json(serde_json::json!({"status": "ok"}))
+}
+
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses(
+        (status = 200, description = "Every dependency is reachable", body = Readiness),
+        (status = 503, description = "A dependency is unreachable", body = Readiness),
+    ),
+)]
+pub async fn readiness(db: Data<MongoRepo>) -> HttpResponse {
+    let mongodb = match db.ping().await {
+        Ok(latency) => DependencyStatus {
+            status: "up",
+            latency_ms: Some(latency.as_millis()),
+            error: None,
+        },
+        Err(err) => DependencyStatus {
+            status: "down",
+            latency_ms: None,
+            error: Some(err),
+        },
+    };
+    let ready = mongodb.status == "up";
+    let body = Readiness {
+        status: if ready { "ready" } else { "unavailable" },
+        checks: BTreeMap::from([("mongodb", mongodb)]),
+    };
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/version",
+    tag = "health",
+    responses((status = 200, description = "Build information", body = VersionInfo)),
+)]
+pub async fn version() -> HttpResponse {
+    HttpResponse::Ok().json(VersionInfo {
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        git_sha: env!("BUILD_GIT_SHA"),
+        rustc: env!("BUILD_RUSTC_VERSION"),
+        profile: env!("BUILD_PROFILE"),
+    })
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("/healthz", web::get().to(liveness))
+        .route("/readyz", web::get().to(readiness))
+        .route("/version", web::get().to(version));
+}
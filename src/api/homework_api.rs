@@ -0,0 +1,595 @@
+use super::{
+    attachments_api::{self, read_upload, store_files},
+    errors::internal_server_error,
+    staffing_api::require_qualification,
+    validation::{self, field_error},
+};
+use crate::{
+    config::{SchoolSettings, StorageSettings},
+    models::{
+        assignment::Assignment,
+        attachment::OwnerKind,
+        audit_entry::AuditContext,
+        course::Course,
+        submission::{Submission, SubmissionGrade},
+        validators,
+    },
+    repository::mongodb_repo::MongoRepo,
+    storage::BlobStore,
+};
+use actix_multipart::Multipart;
+use actix_web::{
+    web::{self, Data, Json, Path, Query},
+    HttpResponse,
+};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct HomeworkRequest {
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    classroom_id: String,
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    course_id: String,
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    teacher_id: String,
+    #[validate(length(min = 1, message = "must not be empty"))]
+    title: String,
+    #[serde(default)]
+    instructions: String,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    due_at: DateTime,
+    #[validate(custom(function = "validators::positive", message = "must be greater than 0"))]
+    max_points: f64,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/homework",
+    tag = "homework",
+    request_body = HomeworkRequest,
+    params(("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Assignment posted; returns the inserted id", body = Object),
+        (status = 404, description = "Classroom, course or teacher not found", body = String),
+        (status = 422, description = "Validation errors per field, including a teacher not qualified for the course", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn create_homework(
+    db: Data<MongoRepo>,
+    request: Json<HomeworkRequest>,
+    actor: AuditContext,
+) -> HttpResponse {
+    if let Err(errors) = request.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
+    let classroom = match db.get_classroom(&request.classroom_id).await {
+        Ok(Some(classroom)) => classroom,
+        Ok(None) => return HttpResponse::NotFound().body("No classroom found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    let course = match db.get_course(&request.course_id).await {
+        Ok(Some(course)) => course,
+        Ok(None) => return HttpResponse::NotFound().body("No course found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    let teacher = match db.get_teacher(&request.teacher_id, false).await {
+        Ok(Some(teacher)) => teacher,
+        Ok(None) => return HttpResponse::NotFound().body("No teacher found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    if let Err(response) = require_qualification(&db, &request.teacher_id, &request.course_id).await
+    {
+        return response;
+    }
+    let data = Assignment {
+        id: None,
+        classroom,
+        course,
+        teacher,
+        title: request.title.to_string(),
+        instructions: request.instructions.to_string(),
+        due_at: request.due_at,
+        max_points: request.max_points,
+        created_at: DateTime::now(),
+        attachments: Vec::new(),
+    };
+    let assignment_detail = db.create_homework_assignment(data, &actor).await;
+    match assignment_detail {
+        Ok(assignment) => HttpResponse::Ok().json(assignment),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/homework/{id}",
+    tag = "homework",
+    params(("id" = String, Path, description = "Assignment id")),
+    responses(
+        (status = 200, description = "The assignment", body = Assignment),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "Assignment not found", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_homework(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.get_homework_assignment(&id).await {
+        Ok(Some(assignment)) => HttpResponse::Ok().json(assignment),
+        Ok(None) => HttpResponse::NotFound().body("No assignment found with specified ID"),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/classrooms/{id}/homework",
+    tag = "homework",
+    params(("id" = String, Path, description = "Classroom id")),
+    responses(
+        (status = 200, description = "Assignments set for the classroom, soonest due first", body = Vec<Assignment>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_classroom_homework(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+    let id = path.into_inner();
+    let Ok(classroom_id) = ObjectId::parse_str(&id) else {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    match db.get_homework_for_classrooms(&[classroom_id]).await {
+        Ok(assignments) => HttpResponse::Ok().json(assignments),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/homework/{id}/submissions",
+    tag = "homework",
+    request_body(content = String, content_type = "multipart/form-data", description = "A `student_id` field, an optional `text` field and any number of `file` parts of an accepted type and size"),
+    params(("id" = String, Path, description = "Assignment id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Work handed in; returns the inserted id", body = Object),
+        (status = 400, description = "Invalid id or malformed multipart body", body = String),
+        (status = 404, description = "Assignment or student not found", body = String),
+        (status = 409, description = "The student has already handed in this assignment", body = Submission),
+        (status = 422, description = "Validation errors per field, including a student not in the assignment's classroom or a file not accepted", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database or storage error", body = String),
+    )
+)]
+pub async fn create_submission(
+    db: Data<MongoRepo>,
+    store: Data<dyn BlobStore>,
+    storage: Data<StorageSettings>,
+    path: Path<String>,
+    payload: Multipart,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    let mut upload = match read_upload(payload, &storage, &["student_id", "text"]).await {
+        Ok(upload) => upload,
+        Err(response) => return response,
+    };
+    let student_id = upload.fields.remove("student_id").unwrap_or_default();
+    let student_id = student_id.trim();
+    let text = upload.fields.remove("text").unwrap_or_default();
+    if validators::object_id(student_id).is_err() {
+        return field_error("student_id", "must be a valid id");
+    }
+    if text.trim().is_empty() && upload.files.is_empty() {
+        return field_error("text", "must not be empty when no file is attached");
+    }
+    let student_id = student_id.to_string();
+    let assignment = match db.get_homework_assignment(&id).await {
+        Ok(Some(assignment)) => assignment,
+        Ok(None) => return HttpResponse::NotFound().body("No assignment found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    let student = match db.get_student(&student_id, false).await {
+        Ok(Some(student)) => student,
+        Ok(None) => return HttpResponse::NotFound().body("No student found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    let student_oid = ObjectId::parse_str(&student_id).unwrap();
+    match db.get_enrollments_for_students(&[student_oid]).await {
+        Ok(enrollments) => {
+            let enrolled = enrollments
+                .iter()
+                .any(|enrollment| enrollment.classroom.id == assignment.classroom.id);
+            if !enrolled {
+                return field_error(
+                    "student_id",
+                    "is not enrolled in the assignment's classroom",
+                );
+            }
+        }
+        Err(err) => return internal_server_error(err),
+    }
+    match db.get_student_submission(&id, &student_id).await {
+        Ok(Some(existing)) => return HttpResponse::Conflict().json(existing),
+        Ok(None) => {}
+        Err(err) => return internal_server_error(err),
+    }
+    let submission_id = ObjectId::new();
+    let submitted_at = DateTime::now();
+    let stored = store_files(
+        &db,
+        store.get_ref(),
+        OwnerKind::Submission,
+        submission_id,
+        upload.files,
+        &actor,
+    )
+    .await;
+    let files = match stored {
+        Ok(attachments) => attachments_api::references(&attachments),
+        Err(response) => return response,
+    };
+    let data = Submission {
+        id: Some(submission_id),
+        late: submitted_at > assignment.due_at,
+        assignment,
+        student,
+        text,
+        files,
+        submitted_at,
+        grade: None,
+    };
+    let submission_detail = db.create_submission(data, &actor).await;
+    match submission_detail {
+        Ok(submission) => HttpResponse::Ok().json(submission),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/homework/{id}/submissions",
+    tag = "homework",
+    params(("id" = String, Path, description = "Assignment id")),
+    responses(
+        (status = 200, description = "Work handed in for the assignment, earliest first", body = Vec<Submission>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_submissions(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.get_submissions_for_assignment(&id).await {
+        Ok(submissions) => HttpResponse::Ok().json(submissions),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct GradeRequest {
+    #[validate(range(min = 0.0, message = "must not be negative"))]
+    points: f64,
+    #[serde(default)]
+    comment: String,
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/submissions/{id}/grade",
+    tag = "homework",
+    request_body = GradeRequest,
+    params(("id" = String, Path, description = "Submission id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Submission graded; replaces any earlier grade", body = SubmissionGrade),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "Submission not found", body = String),
+        (status = 422, description = "Validation errors per field, including points above the assignment's maximum", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn grade_submission(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    request: Json<GradeRequest>,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    if let Err(errors) = request.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
+    let submission = match db.get_submission(&id).await {
+        Ok(Some(submission)) => submission,
+        Ok(None) => return HttpResponse::NotFound().body("No submission found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    if request.points > submission.assignment.max_points {
+        let message = format!("must be at most {}", submission.assignment.max_points);
+        return field_error("points", message);
+    }
+    let grade = SubmissionGrade {
+        points: request.points,
+        comment: request.comment.to_string(),
+        graded_by: actor.actor.clone(),
+        graded_at: DateTime::now(),
+    };
+    match db.grade_submission(&id, grade.clone(), &actor).await {
+        Ok(update) => {
+            if update.matched_count == 1 {
+                HttpResponse::Ok().json(grade)
+            } else {
+                HttpResponse::NotFound().body("No submission found with specified ID")
+            }
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/submissions/{id}/files/{file_id}",
+    tag = "homework",
+    params(("id" = String, Path, description = "Submission id"), ("file_id" = String, Path, description = "File id, from the submission's `files`")),
+    responses(
+        (status = 200, description = "The file as it was uploaded; the ETag is its SHA-256", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No such file on the submission", body = String),
+        (status = 500, description = "Database or storage error, or content that fails its checksum", body = String),
+    )
+)]
+pub async fn get_submission_file(
+    db: Data<MongoRepo>,
+    store: Data<dyn BlobStore>,
+    path: Path<(String, String)>,
+) -> HttpResponse {
+    let (id, file_id) = path.into_inner();
+    let Ok(submission_id) = ObjectId::parse_str(&id) else {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    if validators::object_id(&file_id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.get_attachment(&file_id).await {
+        Ok(Some(file))
+            if file.owner_kind == OwnerKind::Submission && file.owner_id == submission_id =>
+        {
+            attachments_api::download(store.get_ref(), file).await
+        }
+        Ok(_) => HttpResponse::NotFound().body("No file found with specified ID"),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+/// An assignment as one student sees it.
+#[derive(Serialize, ToSchema)]
+pub struct StudentHomework {
+    assignment: Assignment,
+    /// What the student handed in, if anything.
+    submission: Option<Submission>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/students/{id}/homework",
+    tag = "homework",
+    params(("id" = String, Path, description = "Student id")),
+    responses(
+        (status = 200, description = "Assignments for the student's classrooms with what they handed in, soonest due first", body = Vec<StudentHomework>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_student_homework(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+    let id = path.into_inner();
+    let Ok(student_id) = ObjectId::parse_str(&id) else {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    let classroom_ids: Vec<ObjectId> = match db.get_enrollments_for_students(&[student_id]).await {
+        Ok(enrollments) => enrollments
+            .into_iter()
+            .filter_map(|enrollment| enrollment.classroom.id)
+            .collect(),
+        Err(err) => return internal_server_error(err),
+    };
+    let assignments = match db.get_homework_for_classrooms(&classroom_ids).await {
+        Ok(assignments) => assignments,
+        Err(err) => return internal_server_error(err),
+    };
+    let mut submissions: HashMap<ObjectId, Submission> =
+        match db.get_submissions_for_student(&id).await {
+            Ok(submissions) => submissions
+                .into_iter()
+                .filter_map(|submission| Some((submission.assignment.id?, submission)))
+                .collect(),
+            Err(err) => return internal_server_error(err),
+        };
+    let homework: Vec<StudentHomework> = assignments
+        .into_iter()
+        .map(|assignment| StudentHomework {
+            submission: assignment.id.and_then(|id| submissions.remove(&id)),
+            assignment,
+        })
+        .collect();
+    HttpResponse::Ok().json(homework)
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct TermRange {
+    /// Start of the term, RFC 3339
+    from: String,
+    /// End of the term (exclusive), RFC 3339
+    to: String,
+}
+
+/// A student's standing in one course over a term, as percentages.
+#[derive(Serialize, ToSchema)]
+pub struct CourseGrade {
+    course: Course,
+    /// Mean of the exam marks, each out of 100; absent without exams.
+    exam_percent: Option<f64>,
+    /// Points earned over points available on graded homework; absent
+    /// without graded homework.
+    homework_percent: Option<f64>,
+    /// The two blended by the school's homework weight, or whichever one
+    /// there is.
+    percent: f64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TermGrades {
+    student_id: String,
+    from: String,
+    to: String,
+    homework_weight_percent: u32,
+    courses: Vec<CourseGrade>,
+}
+
+#[derive(Default)]
+struct Tally {
+    marks: Vec<f64>,
+    points: f64,
+    max_points: f64,
+}
+
+fn round2(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/students/{id}/term-grades",
+    tag = "homework",
+    params(("id" = String, Path, description = "Student id"), TermRange),
+    responses(
+        (status = 200, description = "Per-course grades from exams held and homework due within the term", body = TermGrades),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 422, description = "Validation errors per field", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_term_grades(
+    db: Data<MongoRepo>,
+    school: Data<SchoolSettings>,
+    path: Path<String>,
+    query: Query<TermRange>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let Ok(student_id) = ObjectId::parse_str(&id) else {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    let Ok(from) = DateTime::parse_rfc3339_str(&query.from) else {
+        return field_error("from", "must be an RFC 3339 timestamp");
+    };
+    let Ok(to) = DateTime::parse_rfc3339_str(&query.to) else {
+        return field_error("to", "must be an RFC 3339 timestamp");
+    };
+    if to <= from {
+        return field_error("to", "must be after from");
+    }
+    let in_term = |instant: DateTime| from <= instant && instant < to;
+
+    // Keyed by course id; courses without one cannot be told apart.
+    let mut tallies: BTreeMap<ObjectId, (Course, Tally)> = BTreeMap::new();
+    match db.get_exam_results_for_students(&[student_id]).await {
+        Ok(results) => {
+            for result in results {
+                let (Some(course_id), Ok(mark)) =
+                    (result.course.id, result.marks.trim().parse::<f64>())
+                else {
+                    continue;
+                };
+                if in_term(result.exam.start_date) {
+                    let (_, tally) = tallies
+                        .entry(course_id)
+                        .or_insert_with(|| (result.course, Tally::default()));
+                    tally.marks.push(mark);
+                }
+            }
+        }
+        Err(err) => return internal_server_error(err),
+    }
+    match db.get_submissions_for_student(&id).await {
+        Ok(submissions) => {
+            for submission in submissions {
+                let assignment = submission.assignment;
+                let (Some(course_id), Some(grade)) = (assignment.course.id, submission.grade)
+                else {
+                    continue;
+                };
+                if in_term(assignment.due_at) {
+                    let (_, tally) = tallies
+                        .entry(course_id)
+                        .or_insert_with(|| (assignment.course, Tally::default()));
+                    tally.points += grade.points;
+                    tally.max_points += assignment.max_points;
+                }
+            }
+        }
+        Err(err) => return internal_server_error(err),
+    }
+
+    let weight = f64::from(school.homework_weight_percent) / 100.0;
+    let mut courses: Vec<CourseGrade> = tallies
+        .into_values()
+        .map(|(course, tally)| {
+            let exam_percent = (!tally.marks.is_empty())
+                .then(|| tally.marks.iter().sum::<f64>() / tally.marks.len() as f64);
+            let homework_percent =
+                (tally.max_points > 0.0).then(|| tally.points / tally.max_points * 100.0);
+            let percent = match (exam_percent, homework_percent) {
+                (Some(exam), Some(homework)) => exam * (1.0 - weight) + homework * weight,
+                (Some(only), None) | (None, Some(only)) => only,
+                (None, None) => 0.0,
+            };
+            CourseGrade {
+                course,
+                exam_percent: exam_percent.map(round2),
+                homework_percent: homework_percent.map(round2),
+                percent: round2(percent),
+            }
+        })
+        .collect();
+    courses.sort_by(|a, b| a.course.name.cmp(&b.course.name));
+    HttpResponse::Ok().json(TermGrades {
+        student_id: id,
+        from: query.from.clone(),
+        to: query.to.clone(),
+        homework_weight_percent: school.homework_weight_percent,
+        courses,
+    })
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/homework").route(web::post().to(create_homework)))
+        .service(web::resource("/homework/{id}").route(web::get().to(get_homework)))
+        .service(
+            web::resource("/homework/{id}/submissions")
+                .route(web::get().to(get_submissions))
+                .route(web::post().to(create_submission)),
+        )
+        .service(
+            web::resource("/classrooms/{id}/homework").route(web::get().to(get_classroom_homework)),
+        )
+        .service(web::resource("/submissions/{id}/grade").route(web::put().to(grade_submission)))
+        .service(
+            web::resource("/submissions/{id}/files/{file_id}")
+                .route(web::get().to(get_submission_file)),
+        )
+        .service(
+            web::resource("/students/{id}/homework").route(web::get().to(get_student_homework)),
+        )
+        .service(web::resource("/students/{id}/term-grades").route(web::get().to(get_term_grades)));
+}
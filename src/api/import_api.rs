@@ -0,0 +1,543 @@
+use super::{auth::Admin, validation};
+use crate::repository::Error;
+use crate::{
+    models::{audit_entry::AuditContext, parent::Parent, student::Student, teacher::Teacher},
+    repository::mongodb_repo::MongoRepo,
+};
+use actix_web::{
+    web::{self, Bytes, Data, Path, Query},
+    HttpResponse,
+};
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+/// Large enough for a district-wide spreadsheet.
+const MAX_CSV_BYTES: usize = 10 * 1024 * 1024;
+
+const PERSON_FIELDS: [&str; 8] = [
+    "email", "password", "fname", "lname", "dob", "phone", "mobile", "status",
+];
+const STUDENT_FIELDS: [&str; 2] = ["date_of_join", "parent_email"];
+const REQUIRED_FIELDS: [&str; 6] = ["email", "fname", "lname", "dob", "phone", "mobile"];
+
+#[derive(Clone, Copy, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportEntity {
+    Teachers,
+    Parents,
+    Students,
+}
+
+impl ImportEntity {
+    fn accepts(self, field: &str) -> bool {
+        PERSON_FIELDS.contains(&field)
+            || matches!(self, ImportEntity::Students) && STUDENT_FIELDS.contains(&field)
+    }
+
+    fn required(self) -> Vec<&'static str> {
+        let mut required = REQUIRED_FIELDS.to_vec();
+        if let ImportEntity::Students = self {
+            required.extend(STUDENT_FIELDS);
+        }
+        required
+    }
+
+    /// Whether a record of this kind already has `email`.
+    async fn email_taken(self, db: &MongoRepo, email: &str) -> Result<bool, Error> {
+        Ok(match self {
+            ImportEntity::Teachers => db.get_teacher_by_email(email).await?.is_some(),
+            ImportEntity::Parents => db.get_parent_by_email(email).await?.is_some(),
+            ImportEntity::Students => db.get_student_by_email(email).await?.is_some(),
+        })
+    }
+
+    fn singular(self) -> &'static str {
+        match self {
+            ImportEntity::Teachers => "teacher",
+            ImportEntity::Parents => "parent",
+            ImportEntity::Students => "student",
+        }
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ImportQuery {
+    /// Validate and report without writing anything
+    #[serde(default)]
+    dry_run: bool,
+    /// Write nothing unless every row is valid. Valid rows are then still
+    /// written one at a time, not in a transaction, so a database failure
+    /// partway through keeps the rows written before it
+    #[serde(default)]
+    all_or_nothing: bool,
+    /// JSON object mapping CSV column headers to field names, e.g.
+    /// `{"First Name": "fname", "Guardian Email": "parent_email"}`. Columns
+    /// already named after a field need no mapping.
+    mapping: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RowStatus {
+    /// Written to the database
+    Created,
+    /// Would be written; only reported on dry runs
+    Valid,
+    Invalid,
+    /// Valid, but not written because another row was invalid or the
+    /// import stopped at a database error
+    Skipped,
+    /// Valid, but writing it failed; the import stopped here
+    Failed,
+    /// Already present; left unchanged
+    Existing,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct RowReport {
+    /// Line number in the CSV, counting the header as line 1
+    line: u64,
+    status: RowStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    errors: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ImportReport {
+    entity: ImportEntity,
+    dry_run: bool,
+    all_or_nothing: bool,
+    created: usize,
+    invalid: usize,
+    rows: Vec<RowReport>,
+    /// The database error that stopped the import; rows before the failed
+    /// one were written
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+pub(super) enum Record {
+    Teacher(Teacher),
+    Parent(Parent),
+    Student(Box<Student>),
+}
+
+pub(super) type FieldErrors = BTreeMap<String, Vec<String>>;
+
+pub(super) fn add_error(errors: &mut FieldErrors, field: &str, message: &str) {
+    errors
+        .entry(field.to_string())
+        .or_default()
+        .push(message.to_string());
+}
+
+impl RowReport {
+    pub(super) fn new(
+        line: u64,
+        status: RowStatus,
+        id: Option<String>,
+        errors: FieldErrors,
+    ) -> Self {
+        RowReport {
+            line,
+            status,
+            id,
+            errors,
+        }
+    }
+}
+
+/// Inserts a validated record, returning its new id.
+pub(super) async fn create_record(
+    db: &MongoRepo,
+    record: Record,
+    actor: &AuditContext,
+) -> Result<Option<String>, Error> {
+    let inserted = match record {
+        Record::Teacher(teacher) => db.create_teacher(teacher, actor).await?,
+        Record::Parent(parent) => db.create_parent(parent, actor).await?,
+        Record::Student(student) => db.create_student(*student, actor).await?,
+    };
+    Ok(inserted.inserted_id.as_object_id().map(|id| id.to_hex()))
+}
+
+/// Accepts `YYYY-MM-DD` as spreadsheets export it, or a full RFC 3339 timestamp.
+fn parse_date(value: &str) -> Option<DateTime> {
+    DateTime::parse_rfc3339_str(value)
+        .or_else(|_| DateTime::parse_rfc3339_str(format!("{}T00:00:00Z", value)))
+        .ok()
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "" | "true" | "yes" | "y" | "1" | "active" => Some(true),
+        "false" | "no" | "n" | "0" | "inactive" => Some(false),
+        _ => None,
+    }
+}
+
+/// Reads the typed columns shared by every person record, noting parse
+/// failures in `errors`.
+struct PersonRow {
+    email: String,
+    password: String,
+    fname: String,
+    lname: String,
+    dob: DateTime,
+    phone: String,
+    mobile: String,
+    status: bool,
+}
+
+impl PersonRow {
+    fn parse(fields: &HashMap<&str, String>, errors: &mut FieldErrors) -> Self {
+        let text = |field: &str| fields.get(field).cloned().unwrap_or_default();
+        let dob = parse_date(&text("dob")).unwrap_or_else(|| {
+            add_error(errors, "dob", "must be a date (YYYY-MM-DD or RFC 3339)");
+            DateTime::from_millis(0)
+        });
+        let status = parse_bool(&text("status")).unwrap_or_else(|| {
+            add_error(errors, "status", "must be true or false");
+            true
+        });
+        let password = match text("password") {
+            // Imported accounts without a password get an unguessable one and
+            // must reset it before first login.
+            password if password.is_empty() => Uuid::new_v4().to_string(),
+            password => password,
+        };
+        PersonRow {
+            email: text("email"),
+            password,
+            fname: text("fname"),
+            lname: text("lname"),
+            dob,
+            phone: text("phone"),
+            mobile: text("mobile"),
+            status,
+        }
+    }
+}
+
+/// Stands in for a student's unknown guardian so the rest of the row can
+/// still be validated.
+fn unknown_parent() -> Parent {
+    Parent {
+        id: None,
+        email: String::new(),
+        password: String::new(),
+        fname: String::new(),
+        lname: String::new(),
+        dob: DateTime::from_millis(0),
+        phone: String::new(),
+        mobile: String::new(),
+        status: false,
+        last_login_date: DateTime::from_millis(0),
+        last_login_ip: String::new(),
+        version: 0,
+        deleted_at: None,
+    }
+}
+
+/// Builds and validates the record for one CSV row, looking guardians up by
+/// email (cached, as siblings usually share one). A row whose email another
+/// record of the same kind already has is invalid.
+pub(super) async fn build_record(
+    db: &MongoRepo,
+    entity: ImportEntity,
+    fields: &HashMap<&str, String>,
+    guardians: &mut HashMap<String, Option<Parent>>,
+) -> Result<Record, FieldErrors> {
+    let mut errors = FieldErrors::new();
+    let person = PersonRow::parse(fields, &mut errors);
+    let taken = !person.email.is_empty()
+        && entity
+            .email_taken(db, &person.email)
+            .await
+            .unwrap_or_default();
+    if taken {
+        let message = format!("a {} with this email already exists", entity.singular());
+        add_error(&mut errors, "email", &message);
+    }
+    let mut known_parent = true;
+    let record = match entity {
+        ImportEntity::Teachers => Record::Teacher(Teacher {
+            id: None,
+            email: person.email,
+            password: person.password,
+            fname: person.fname,
+            lname: person.lname,
+            dob: person.dob,
+            phone: person.phone,
+            mobile: person.mobile,
+            status: person.status,
+            last_login_date: DateTime::now(),
+            last_login_ip: String::new(),
+            attachments: Vec::new(),
+            version: 0,
+            deleted_at: None,
+        }),
+        ImportEntity::Parents => Record::Parent(Parent {
+            id: None,
+            email: person.email,
+            password: person.password,
+            fname: person.fname,
+            lname: person.lname,
+            dob: person.dob,
+            phone: person.phone,
+            mobile: person.mobile,
+            status: person.status,
+            last_login_date: DateTime::now(),
+            last_login_ip: String::new(),
+            version: 0,
+            deleted_at: None,
+        }),
+        ImportEntity::Students => {
+            let date_of_join = fields
+                .get("date_of_join")
+                .and_then(|value| parse_date(value))
+                .unwrap_or_else(|| {
+                    add_error(
+                        &mut errors,
+                        "date_of_join",
+                        "must be a date (YYYY-MM-DD or RFC 3339)",
+                    );
+                    DateTime::now()
+                });
+            let parent_email = fields.get("parent_email").cloned().unwrap_or_default();
+            if !guardians.contains_key(&parent_email) {
+                let parent = db
+                    .get_parent_by_email(&parent_email)
+                    .await
+                    .unwrap_or_default();
+                guardians.insert(parent_email.clone(), parent);
+            }
+            let parent = match &guardians[&parent_email] {
+                Some(parent) => parent.clone(),
+                None => {
+                    add_error(&mut errors, "parent_email", "no parent has this email");
+                    known_parent = false;
+                    unknown_parent()
+                }
+            };
+            Record::Student(Box::new(Student {
+                id: None,
+                email: person.email,
+                password: person.password,
+                fname: person.fname,
+                lname: person.lname,
+                dob: person.dob,
+                phone: person.phone,
+                mobile: person.mobile,
+                parent,
+                date_of_join,
+                status: person.status,
+                last_login_date: DateTime::now(),
+                last_login_ip: String::new(),
+                attachments: Vec::new(),
+                version: 0,
+                deleted_at: None,
+            }))
+        }
+    };
+    let validated = match &record {
+        Record::Teacher(teacher) => teacher.validate(),
+        Record::Parent(parent) => parent.validate(),
+        Record::Student(student) => student.validate(),
+    };
+    if let Err(validation_errors) = validated {
+        for (field, messages) in validation::field_errors(&validation_errors) {
+            if known_parent || !field.starts_with("parent.") {
+                errors.entry(field).or_default().extend(messages);
+            }
+        }
+    }
+    if errors.is_empty() {
+        Ok(record)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Resolves each CSV column to the field it fills, or `None` when ignored.
+fn resolve_columns(
+    entity: ImportEntity,
+    headers: &csv::StringRecord,
+    mapping: &HashMap<String, String>,
+) -> Result<Vec<Option<&'static str>>, String> {
+    let known = |name: &str| {
+        PERSON_FIELDS
+            .iter()
+            .chain(STUDENT_FIELDS.iter())
+            .copied()
+            .find(|field| *field == name && entity.accepts(field))
+    };
+    for target in mapping.values() {
+        if known(target).is_none() {
+            return Err(format!("mapping targets unknown field `{}`", target));
+        }
+    }
+    let columns: Vec<Option<&'static str>> = headers
+        .iter()
+        .map(|header| match mapping.get(header) {
+            Some(target) => known(target),
+            None => known(&header.trim().to_lowercase()),
+        })
+        .collect();
+    let missing: Vec<&str> = entity
+        .required()
+        .into_iter()
+        .filter(|field| !columns.contains(&Some(field)))
+        .collect();
+    if missing.is_empty() {
+        Ok(columns)
+    } else {
+        Err(format!("missing columns for: {}", missing.join(", ")))
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/import/{entity}",
+    tag = "import",
+    request_body(content = String, content_type = "text/csv", description = "CSV with a header row"),
+    params(
+        ("entity" = ImportEntity, Path, description = "teachers, parents or students"),
+        ImportQuery,
+    ),
+    responses(
+        (status = 200, description = "Per-row import report; a row repeating an email from an earlier row or an existing record is invalid", body = ImportReport),
+        (status = 400, description = "Unreadable CSV, bad mapping or missing columns", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 422, description = "All-or-nothing import rejected; nothing was written", body = ImportReport),
+        (status = 500, description = "Database error; the report shows which rows were written before it", body = ImportReport),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn import_records(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    path: Path<ImportEntity>,
+    query: Query<ImportQuery>,
+    body: Bytes,
+    actor: AuditContext,
+) -> HttpResponse {
+    let entity = path.into_inner();
+    let query = query.into_inner();
+    let mapping: HashMap<String, String> = match &query.mapping {
+        Some(mapping) => match serde_json::from_str(mapping) {
+            Ok(mapping) => mapping,
+            Err(err) => {
+                return HttpResponse::BadRequest().body(format!("invalid mapping: {}", err))
+            }
+        },
+        None => HashMap::new(),
+    };
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(body.as_ref());
+    let headers = match reader.headers() {
+        Ok(headers) => headers.clone(),
+        Err(err) => return HttpResponse::BadRequest().body(format!("unreadable CSV: {}", err)),
+    };
+    let columns = match resolve_columns(entity, &headers, &mapping) {
+        Ok(columns) => columns,
+        Err(message) => return HttpResponse::BadRequest().body(message),
+    };
+
+    let mut guardians = HashMap::new();
+    // Line each email was first seen on, so later rows repeating it fail.
+    let mut first_lines: HashMap<String, u64> = HashMap::new();
+    let mut parsed = Vec::new();
+    for row in reader.records() {
+        let row = match row {
+            Ok(row) => row,
+            Err(err) => {
+                let line = err.position().map_or(0, |position| position.line());
+                let mut errors = FieldErrors::new();
+                add_error(&mut errors, "_row", &err.to_string());
+                parsed.push((line, Err(errors)));
+                continue;
+            }
+        };
+        let line = row.position().map_or(0, |position| position.line());
+        let fields: HashMap<&str, String> = columns
+            .iter()
+            .zip(row.iter())
+            .filter_map(|(field, value)| field.map(|field| (field, value.to_string())))
+            .collect();
+        let mut record = build_record(&db, entity, &fields, &mut guardians).await;
+        let email = fields.get("email").cloned().unwrap_or_default();
+        if !email.is_empty() {
+            match first_lines.get(&email) {
+                Some(first) => {
+                    let mut errors = record.err().unwrap_or_default();
+                    add_error(&mut errors, "email", &format!("same as line {}", first));
+                    record = Err(errors);
+                }
+                None => {
+                    first_lines.insert(email, line);
+                }
+            }
+        }
+        parsed.push((line, record));
+    }
+
+    let invalid = parsed.iter().filter(|(_, record)| record.is_err()).count();
+    let rejected = query.all_or_nothing && invalid > 0;
+    let write = !query.dry_run && !rejected;
+    let mut rows = Vec::with_capacity(parsed.len());
+    let mut created = 0;
+    let mut failure = None;
+    for (line, record) in parsed {
+        let (status, id, errors) = match record {
+            Err(errors) => (RowStatus::Invalid, None, errors),
+            Ok(_) if query.dry_run => (RowStatus::Valid, None, FieldErrors::new()),
+            Ok(_) if !write || failure.is_some() => (RowStatus::Skipped, None, FieldErrors::new()),
+            Ok(record) => match create_record(&db, record, &actor).await {
+                Ok(id) => {
+                    created += 1;
+                    (RowStatus::Created, id, FieldErrors::new())
+                }
+                Err(err) => {
+                    tracing::error!(error = %err, line, "import stopped");
+                    failure = Some(err.to_string());
+                    (RowStatus::Failed, None, FieldErrors::new())
+                }
+            },
+        };
+        rows.push(RowReport::new(line, status, id, errors));
+    }
+
+    let report = ImportReport {
+        entity,
+        dry_run: query.dry_run,
+        all_or_nothing: query.all_or_nothing,
+        created,
+        invalid,
+        rows,
+        error: failure,
+    };
+    if report.error.is_some() {
+        HttpResponse::InternalServerError().json(report)
+    } else if rejected {
+        HttpResponse::UnprocessableEntity().json(report)
+    } else {
+        HttpResponse::Ok().json(report)
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/import/{entity}")
+            .app_data(web::PayloadConfig::new(MAX_CSV_BYTES))
+            .route(web::post().to(import_records)),
+    );
+}
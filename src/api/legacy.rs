@@ -0,0 +1,85 @@
+use super::{audit_api::*, parents_api::*, students_api::*, teachers_api::*};
+use actix_web::{middleware::DefaultHeaders, web};
+
+fn deprecated() -> DefaultHeaders {
+    DefaultHeaders::new().add(("Deprecation", "true"))
+}
+
+/// Pre-versioning routes, kept as deprecated aliases of `/api/v1` until
+/// clients have migrated.
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/teacher")
+            .wrap(deprecated())
+            .route(web::post().to(create_teacher)),
+    )
+    .service(
+        web::resource("/teacher/{id}")
+            .wrap(deprecated())
+            .route(web::get().to(get_teacher))
+            .route(web::put().to(update_teacher))
+            .route(web::patch().to(patch_teacher))
+            .route(web::delete().to(delete_teacher)),
+    )
+    .service(
+        web::resource("/teacher/{id}/restore")
+            .wrap(deprecated())
+            .route(web::post().to(restore_teacher)),
+    )
+    .service(
+        web::resource("/teachers")
+            .wrap(deprecated())
+            .route(web::get().to(get_all_teachers)),
+    )
+    .service(
+        web::resource("/parent")
+            .wrap(deprecated())
+            .route(web::post().to(create_parent)),
+    )
+    .service(
+        web::resource("/parent/{id}")
+            .wrap(deprecated())
+            .route(web::get().to(get_parent))
+            .route(web::put().to(update_parent))
+            .route(web::patch().to(patch_parent))
+            .route(web::delete().to(delete_parent)),
+    )
+    .service(
+        web::resource("/parent/{id}/restore")
+            .wrap(deprecated())
+            .route(web::post().to(restore_parent)),
+    )
+    .service(
+        web::resource("/parents")
+            .wrap(deprecated())
+            .route(web::get().to(get_all_parents)),
+    )
+    .service(
+        web::resource("/student")
+            .wrap(deprecated())
+            .route(web::post().to(create_student)),
+    )
+    .service(
+        web::resource("/student/{id}")
+            .wrap(deprecated())
+            .route(web::get().to(get_student))
+            .route(web::put().to(update_student))
+            .route(web::patch().to(patch_student))
+            .route(web::delete().to(delete_student)),
+    )
+    .service(
+        web::resource("/student/{id}/restore")
+            .wrap(deprecated())
+            .route(web::post().to(restore_student)),
+    )
+    .service(
+        web::resource("/students")
+            .wrap(deprecated())
+            .route(web::get().to(get_all_students)),
+    )
+    .service(
+        web::resource("/audit")
+            .wrap(deprecated())
+            .route(web::get().to(get_audit_log)),
+    );
+}
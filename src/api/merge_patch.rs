@@ -0,0 +1,154 @@
+use mongodb::bson::{to_document, Document};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+/// Fields that can never be changed through a merge patch, at any depth:
+/// `{"parent": {"_id": ...}}` is refused like `{"_id": ...}`.
+pub const IMMUTABLE_FIELDS: [&str; 7] = [
+    "_id",
+    "id",
+    "attachments",
+    "last_login_date",
+    "last_login_ip",
+    "version",
+    "deleted_at",
+];
+
+/// The dotted path of the first immutable field the patch touches.
+fn immutable_path(patch: &Value, prefix: &str) -> Option<String> {
+    let fields = patch.as_object()?;
+    fields.iter().find_map(|(key, value)| {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        if IMMUTABLE_FIELDS.contains(&key.as_str()) {
+            Some(path)
+        } else {
+            immutable_path(value, &path)
+        }
+    })
+}
+
+/// Applies an RFC 7396 JSON Merge Patch to `target` in place.
+pub fn merge(target: &mut Value, patch: &Value) {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        _ => {
+            *target = patch.clone();
+            return;
+        }
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    let target = target.as_object_mut().unwrap();
+    for (key, value) in patch {
+        if value.is_null() {
+            target.remove(key);
+        } else {
+            merge(target.entry(key.as_str()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+/// Merges `patch` into `current` and returns the patched record together with
+/// the `$set` document holding only the top-level fields the patch touched.
+pub fn apply<T>(current: &T, patch: &Value) -> Result<(T, Document), String>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let fields = match patch.as_object() {
+        Some(fields) => fields,
+        None => return Err("merge patch must be a JSON object".to_string()),
+    };
+    if let Some(path) = immutable_path(patch, "") {
+        return Err(format!("field `{}` is immutable", path));
+    }
+
+    let mut merged = serde_json::to_value(current).map_err(|err| err.to_string())?;
+    merge(&mut merged, patch);
+    let patched: T = serde_json::from_value(merged).map_err(|err| err.to_string())?;
+
+    let changes = to_document(&patched)
+        .map_err(|err| err.to_string())?
+        .into_iter()
+        .filter(|(key, _)| fields.contains_key(key))
+        .collect();
+    Ok((patched, changes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Guardian {
+        #[serde(rename = "_id")]
+        id: String,
+        name: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Record {
+        name: String,
+        nickname: Option<String>,
+        parent: Guardian,
+    }
+
+    fn record() -> Record {
+        Record {
+            name: "Ada".to_string(),
+            nickname: Some("A".to_string()),
+            parent: Guardian {
+                id: "p1".to_string(),
+                name: "Byron".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn merge_follows_rfc_7396() {
+        let mut target = json!({"a": "b", "c": {"d": "e", "f": "g"}});
+        merge(&mut target, &json!({"a": "z", "c": {"f": null}}));
+        assert_eq!(target, json!({"a": "z", "c": {"d": "e"}}));
+
+        let mut target = json!({"a": [1, 2]});
+        merge(&mut target, &json!({"a": [3]}));
+        assert_eq!(target, json!({"a": [3]}));
+
+        let mut target = json!("scalar");
+        merge(&mut target, &json!({"a": {"b": null}}));
+        assert_eq!(target, json!({"a": {}}));
+    }
+
+    #[test]
+    fn apply_sets_only_touched_fields() {
+        let (patched, changes) = apply(&record(), &json!({"nickname": null})).unwrap();
+        assert_eq!(patched.nickname, None);
+        assert_eq!(patched.name, "Ada");
+        assert_eq!(changes.keys().collect::<Vec<_>>(), vec!["nickname"]);
+    }
+
+    #[test]
+    fn apply_rejects_immutable_fields_at_any_depth() {
+        assert_eq!(
+            apply(&record(), &json!({"_id": "x"})).unwrap_err(),
+            "field `_id` is immutable"
+        );
+        assert_eq!(
+            apply(&record(), &json!({"parent": {"_id": "p2"}})).unwrap_err(),
+            "field `parent._id` is immutable"
+        );
+        assert!(apply(&record(), &json!({"parent": {"name": "Ada's"}})).is_ok());
+    }
+
+    #[test]
+    fn apply_rejects_non_objects() {
+        assert!(apply(&record(), &json!(["name"])).is_err());
+        assert!(apply(&record(), &json!({"name": 5})).is_err());
+    }
+}
@@ -0,0 +1,959 @@
+use super::{
+    attachments_api::{self, read_upload, store_files},
+    auth::Admin,
+    errors::internal_server_error,
+    validation::{self, field_error},
+};
+use crate::{
+    config::StorageSettings,
+    models::{
+        attachment::OwnerKind,
+        audit_entry::AuditContext,
+        conversation::{Conversation, Message, Participant},
+        messaging_link::MessagingLink,
+        validators,
+    },
+    repository::mongodb_repo::MongoRepo,
+    storage::BlobStore,
+};
+use actix_multipart::Multipart;
+use actix_web::{
+    web::{self, Data, Json, Path, Query},
+    HttpResponse,
+};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use validator::Validate;
+
+fn not_taught() -> HttpResponse {
+    HttpResponse::Forbidden().body("Teachers can only message guardians of students they teach")
+}
+
+/// The participant's full name, or 404 when they do not exist or are
+/// archived.
+async fn participant_name(
+    db: &MongoRepo,
+    side: Participant,
+    id: &String,
+) -> Result<String, HttpResponse> {
+    let name = match side {
+        Participant::Teacher => db
+            .get_teacher(id, false)
+            .await
+            .map(|teacher| teacher.map(|teacher| format!("{} {}", teacher.fname, teacher.lname))),
+        Participant::Parent => db
+            .get_parent(id, false)
+            .await
+            .map(|parent| parent.map(|parent| format!("{} {}", parent.fname, parent.lname))),
+    };
+    match (name, side) {
+        (Ok(Some(name)), _) => Ok(name),
+        (Ok(None), Participant::Teacher) => {
+            Err(HttpResponse::NotFound().body("No teacher found with specified ID"))
+        }
+        (Ok(None), Participant::Parent) => {
+            Err(HttpResponse::NotFound().body("No parent found with specified ID"))
+        }
+        (Err(err), _) => Err(internal_server_error(err)),
+    }
+}
+
+/// Whether `id` takes part in the conversation on `side`; a teacher and a
+/// parent are never taken for one another, whatever their ids.
+fn is_member(conversation: &Conversation, side: Participant, id: &str) -> bool {
+    let member_id = match side {
+        Participant::Teacher => conversation.teacher_id,
+        Participant::Parent => conversation.parent_id,
+    };
+    member_id.to_hex() == id
+}
+
+/// Looks up a conversation the participant is part of. Anyone else's is
+/// reported as missing, so ids cannot be probed through another path.
+async fn participant_conversation(
+    db: &MongoRepo,
+    side: Participant,
+    id: &str,
+    conversation_id: &String,
+) -> Result<Conversation, HttpResponse> {
+    match db.get_conversation(conversation_id).await {
+        Ok(Some(conversation)) if is_member(&conversation, side, id) => Ok(conversation),
+        Ok(_) => Err(HttpResponse::NotFound().body("No conversation found with specified ID")),
+        Err(err) => Err(internal_server_error(err)),
+    }
+}
+
+async fn list_conversations(db: &MongoRepo, side: Participant, id: String) -> HttpResponse {
+    let conversations = match side {
+        Participant::Teacher => db.get_conversations(Some(&id), None, None).await,
+        Participant::Parent => db.get_conversations(None, Some(&id), None).await,
+    };
+    match conversations {
+        Ok(conversations) => HttpResponse::Ok().json(conversations),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+/// What either side gives to start a conversation.
+struct NewConversation<'a> {
+    teacher_id: &'a String,
+    student_id: &'a String,
+    subject: &'a str,
+    body: &'a str,
+}
+
+/// Starts a conversation about the student with its first message, once
+/// both sides exist and the teacher teaches the student.
+async fn start_conversation(
+    db: &MongoRepo,
+    side: Participant,
+    sender_id: &String,
+    new: NewConversation<'_>,
+    actor: &AuditContext,
+) -> HttpResponse {
+    let NewConversation {
+        teacher_id,
+        student_id,
+        subject,
+        body,
+    } = new;
+    let teacher_name = match participant_name(db, Participant::Teacher, teacher_id).await {
+        Ok(name) => name,
+        Err(response) => return response,
+    };
+    let student = match db.get_student(student_id, false).await {
+        Ok(Some(student)) => student,
+        Ok(None) => return HttpResponse::NotFound().body("No student found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    let Some(parent_id) = student.parent.id else {
+        return field_error("student_id", "has no guardian on record");
+    };
+    if side == Participant::Parent && parent_id.to_hex() != *sender_id {
+        return field_error("student_id", "is not a child of this parent");
+    }
+    let parent_name = match participant_name(db, Participant::Parent, &parent_id.to_hex()).await {
+        Ok(name) => name,
+        Err(response) => return response,
+    };
+    let teacher_oid = ObjectId::parse_str(teacher_id).unwrap();
+    let student_oid = ObjectId::parse_str(student_id).unwrap();
+    match db.teaches_student(teacher_oid, student_oid).await {
+        Ok(true) => {}
+        Ok(false) => return not_taught(),
+        Err(err) => return internal_server_error(err),
+    }
+    let now = DateTime::now();
+    let conversation_id = ObjectId::new();
+    let conversation = Conversation {
+        id: Some(conversation_id),
+        teacher_id: teacher_oid,
+        parent_id,
+        student_id: student_oid,
+        teacher_name,
+        parent_name,
+        student_name: format!("{} {}", student.fname, student.lname),
+        subject: subject.trim().to_string(),
+        started_by: side,
+        created_at: now,
+        last_message_at: now,
+        teacher_unread: 0,
+        parent_unread: 0,
+    };
+    if let Err(err) = db.create_conversation(conversation, actor).await {
+        return internal_server_error(err);
+    }
+    let message = Message {
+        id: None,
+        conversation_id,
+        sender: side,
+        sender_id: ObjectId::parse_str(sender_id).unwrap(),
+        body: body.to_string(),
+        attachments: Vec::new(),
+        sent_at: now,
+        read_at: None,
+    };
+    if let Err(err) = db.create_message(message, actor).await {
+        return internal_server_error(err);
+    }
+    match db.get_conversation(&conversation_id.to_hex()).await {
+        Ok(Some(conversation)) => HttpResponse::Ok().json(conversation),
+        Ok(None) => HttpResponse::NotFound().body("No conversation found with specified ID"),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+async fn list_messages(db: &MongoRepo, side: Participant, path: (String, String)) -> HttpResponse {
+    let (id, conversation_id) = path;
+    if validators::object_id(&conversation_id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    if let Err(response) = participant_conversation(db, side, &id, &conversation_id).await {
+        return response;
+    }
+    match db
+        .get_messages(ObjectId::parse_str(&conversation_id).unwrap())
+        .await
+    {
+        Ok(messages) => HttpResponse::Ok().json(messages),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+async fn send_message(
+    db: &MongoRepo,
+    store: &dyn BlobStore,
+    storage: &StorageSettings,
+    side: Participant,
+    path: (String, String),
+    payload: Multipart,
+    actor: &AuditContext,
+) -> HttpResponse {
+    let (id, conversation_id) = path;
+    if validators::object_id(&conversation_id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    let conversation = match participant_conversation(db, side, &id, &conversation_id).await {
+        Ok(conversation) => conversation,
+        Err(response) => return response,
+    };
+    // Either side may reply only while the teacher still teaches the student.
+    match db
+        .teaches_student(conversation.teacher_id, conversation.student_id)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => return not_taught(),
+        Err(err) => return internal_server_error(err),
+    }
+    let mut upload = match read_upload(payload, storage, &["body"]).await {
+        Ok(upload) => upload,
+        Err(response) => return response,
+    };
+    let body = upload.fields.remove("body").unwrap_or_default();
+    if body.trim().is_empty() && upload.files.is_empty() {
+        return field_error("body", "must not be empty when no file is attached");
+    }
+    let message_id = ObjectId::new();
+    let stored = store_files(
+        db,
+        store,
+        OwnerKind::Message,
+        message_id,
+        upload.files,
+        actor,
+    )
+    .await;
+    let attachments = match stored {
+        Ok(attachments) => attachments_api::references(&attachments),
+        Err(response) => return response,
+    };
+    let message = Message {
+        id: Some(message_id),
+        conversation_id: ObjectId::parse_str(&conversation_id).unwrap(),
+        sender: side,
+        sender_id: ObjectId::parse_str(&id).unwrap(),
+        body,
+        attachments,
+        sent_at: DateTime::now(),
+        read_at: None,
+    };
+    match db.create_message(message.clone(), actor).await {
+        Ok(_) => HttpResponse::Ok().json(message),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+/// How many messages a read receipt was given for.
+#[derive(Serialize, ToSchema)]
+pub struct ReadReceipt {
+    marked_read: u64,
+}
+
+async fn mark_read(db: &MongoRepo, side: Participant, path: (String, String)) -> HttpResponse {
+    let (id, conversation_id) = path;
+    if validators::object_id(&conversation_id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    if let Err(response) = participant_conversation(db, side, &id, &conversation_id).await {
+        return response;
+    }
+    match db
+        .mark_conversation_read(ObjectId::parse_str(&conversation_id).unwrap(), side)
+        .await
+    {
+        Ok(marked_read) => HttpResponse::Ok().json(ReadReceipt { marked_read }),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+async fn message_file(
+    db: &MongoRepo,
+    store: &dyn BlobStore,
+    side: Participant,
+    path: (String, String, String),
+) -> HttpResponse {
+    let (id, conversation_id, file_id) = path;
+    if validators::object_id(&conversation_id).is_err() || validators::object_id(&file_id).is_err()
+    {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    let conversation = match participant_conversation(db, side, &id, &conversation_id).await {
+        Ok(conversation) => conversation,
+        Err(response) => return response,
+    };
+    let file = match db.get_attachment(&file_id).await {
+        Ok(Some(file)) if file.owner_kind == OwnerKind::Message => file,
+        Ok(_) => return HttpResponse::NotFound().body("No file found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    match db.get_message(&file.owner_id.to_hex()).await {
+        Ok(Some(message)) if Some(message.conversation_id) == conversation.id => {
+            attachments_api::download(store, file).await
+        }
+        Ok(_) => HttpResponse::NotFound().body("No file found with specified ID"),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+/// The teacher or parent a messaging link was issued to, provided they
+/// still exist and are not archived.
+async fn link_participant(
+    db: &MongoRepo,
+    token: &str,
+) -> Result<(Participant, String), HttpResponse> {
+    let link = match db.get_messaging_link_by_token(token).await {
+        Ok(Some(link)) => link,
+        Ok(None) => return Err(HttpResponse::NotFound().body("No messaging link found")),
+        Err(err) => return Err(internal_server_error(err)),
+    };
+    let id = link.participant_id.to_hex();
+    participant_name(db, link.participant, &id).await?;
+    Ok((link.participant, id))
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct ConversationRequest {
+    /// One of the student's teachers; given by parents only, since a
+    /// teacher always writes as themselves.
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    teacher_id: Option<String>,
+    /// For a teacher, a student they teach, whose guardian is the other
+    /// side; for a parent, one of their children.
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    student_id: String,
+    #[validate(length(min = 1, max = 200, message = "must be 1 to 200 characters"))]
+    subject: String,
+    /// The first message.
+    #[validate(length(min = 1, message = "must not be empty"))]
+    body: String,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/messaging/{token}/conversations",
+    tag = "messaging",
+    params(("token" = String, Path, description = "Messaging link token")),
+    responses(
+        (status = 200, description = "The link holder's conversations, latest message first", body = Vec<Conversation>),
+        (status = 404, description = "No messaging link with this token, or its teacher or parent is gone", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_link_conversations(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+    match link_participant(&db, &path.into_inner()).await {
+        Ok((side, id)) => list_conversations(&db, side, id).await,
+        Err(response) => response,
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/messaging/{token}/conversations",
+    tag = "messaging",
+    request_body = ConversationRequest,
+    params(("token" = String, Path, description = "Messaging link token"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Conversation started with the other side", body = Conversation),
+        (status = 403, description = "The teacher does not teach the student", body = String),
+        (status = 404, description = "No messaging link with this token, or teacher, student or guardian not found", body = String),
+        (status = 422, description = "Validation errors per field, including a student with no guardian or who is not the parent's child", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn start_link_conversation(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    request: Json<ConversationRequest>,
+    actor: AuditContext,
+) -> HttpResponse {
+    let (side, id) = match link_participant(&db, &path.into_inner()).await {
+        Ok(participant) => participant,
+        Err(response) => return response,
+    };
+    if let Err(errors) = request.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
+    let teacher_id = match (side, &request.teacher_id) {
+        (Participant::Teacher, None) => &id,
+        (Participant::Teacher, Some(_)) => {
+            return field_error("teacher_id", "is chosen by parents only")
+        }
+        (Participant::Parent, Some(teacher_id)) => teacher_id,
+        (Participant::Parent, None) => return field_error("teacher_id", "is required"),
+    };
+    start_conversation(
+        &db,
+        side,
+        &id,
+        NewConversation {
+            teacher_id,
+            student_id: &request.student_id,
+            subject: &request.subject,
+            body: &request.body,
+        },
+        &actor,
+    )
+    .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/messaging/{token}/conversations/{conversation_id}/messages",
+    tag = "messaging",
+    params(
+        ("token" = String, Path, description = "Messaging link token"),
+        ("conversation_id" = String, Path, description = "Conversation id"),
+    ),
+    responses(
+        (status = 200, description = "The conversation's messages, oldest first, with when each was read", body = Vec<Message>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No messaging link with this token, or no such conversation for its holder", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_link_messages(db: Data<MongoRepo>, path: Path<(String, String)>) -> HttpResponse {
+    let (token, conversation_id) = path.into_inner();
+    match link_participant(&db, &token).await {
+        Ok((side, id)) => list_messages(&db, side, (id, conversation_id)).await,
+        Err(response) => response,
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/messaging/{token}/conversations/{conversation_id}/messages",
+    tag = "messaging",
+    request_body(content = String, content_type = "multipart/form-data", description = "A `body` field and any number of `file` parts of an accepted type and size"),
+    params(
+        ("token" = String, Path, description = "Messaging link token"),
+        ("conversation_id" = String, Path, description = "Conversation id"),
+        ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor"),
+    ),
+    responses(
+        (status = 200, description = "Message sent to the other side", body = Message),
+        (status = 400, description = "Invalid id or malformed multipart body", body = String),
+        (status = 403, description = "The teacher no longer teaches the student", body = String),
+        (status = 404, description = "No messaging link with this token, or no such conversation for its holder", body = String),
+        (status = 422, description = "No text and no file, or a file not accepted", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database or storage error", body = String),
+    )
+)]
+pub async fn send_link_message(
+    db: Data<MongoRepo>,
+    store: Data<dyn BlobStore>,
+    storage: Data<StorageSettings>,
+    path: Path<(String, String)>,
+    payload: Multipart,
+    actor: AuditContext,
+) -> HttpResponse {
+    let (token, conversation_id) = path.into_inner();
+    let (side, id) = match link_participant(&db, &token).await {
+        Ok(participant) => participant,
+        Err(response) => return response,
+    };
+    send_message(
+        &db,
+        store.get_ref(),
+        &storage,
+        side,
+        (id, conversation_id),
+        payload,
+        &actor,
+    )
+    .await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/messaging/{token}/conversations/{conversation_id}/read",
+    tag = "messaging",
+    params(
+        ("token" = String, Path, description = "Messaging link token"),
+        ("conversation_id" = String, Path, description = "Conversation id"),
+    ),
+    responses(
+        (status = 200, description = "The other side's messages marked read", body = ReadReceipt),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No messaging link with this token, or no such conversation for its holder", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn mark_link_read(db: Data<MongoRepo>, path: Path<(String, String)>) -> HttpResponse {
+    let (token, conversation_id) = path.into_inner();
+    match link_participant(&db, &token).await {
+        Ok((side, id)) => mark_read(&db, side, (id, conversation_id)).await,
+        Err(response) => response,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/messaging/{token}/conversations/{conversation_id}/files/{file_id}",
+    tag = "messaging",
+    params(
+        ("token" = String, Path, description = "Messaging link token"),
+        ("conversation_id" = String, Path, description = "Conversation id"),
+        ("file_id" = String, Path, description = "File id, from a message's `attachments`"),
+    ),
+    responses(
+        (status = 200, description = "The file as it was sent; the ETag is its SHA-256", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No messaging link with this token, or no such file in its holder's conversation", body = String),
+        (status = 500, description = "Database or storage error, or content that fails its checksum", body = String),
+    )
+)]
+pub async fn get_link_file(
+    db: Data<MongoRepo>,
+    store: Data<dyn BlobStore>,
+    path: Path<(String, String, String)>,
+) -> HttpResponse {
+    let (token, conversation_id, file_id) = path.into_inner();
+    match link_participant(&db, &token).await {
+        Ok((side, id)) => {
+            message_file(&db, store.get_ref(), side, (id, conversation_id, file_id)).await
+        }
+        Err(response) => response,
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct IssuedMessagingLink {
+    #[schema(value_type = Object)]
+    id: ObjectId,
+    participant: Participant,
+    #[schema(value_type = Object)]
+    participant_id: ObjectId,
+    /// Path the holder's conversations are under, relative to this server
+    url: String,
+}
+
+/// Issues a messaging link for a teacher or parent who exists.
+async fn issue_messaging_link(
+    db: &MongoRepo,
+    side: Participant,
+    id: String,
+    actor: &AuditContext,
+) -> HttpResponse {
+    let Ok(participant_id) = ObjectId::parse_str(&id) else {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    if let Err(response) = participant_name(db, side, &id).await {
+        return response;
+    }
+    let data = MessagingLink {
+        id: None,
+        participant: side,
+        participant_id,
+        token: format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple()),
+        created_at: DateTime::now(),
+    };
+    let url = format!("/api/v1/messaging/{}/conversations", data.token);
+    match db.create_messaging_link(data, actor).await {
+        Ok(inserted) => match inserted.inserted_id.as_object_id() {
+            Some(id) => HttpResponse::Ok().json(IssuedMessagingLink {
+                id,
+                participant: side,
+                participant_id,
+                url,
+            }),
+            None => internal_server_error("messaging link stored without an id"),
+        },
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/teachers/{id}/messaging-links",
+    tag = "messaging",
+    params(("id" = String, Path, description = "Teacher id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Link issued; anyone holding it can read and send the teacher's messages", body = IssuedMessagingLink),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "Teacher not found", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn create_teacher_messaging_link(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    path: Path<String>,
+    actor: AuditContext,
+) -> HttpResponse {
+    issue_messaging_link(&db, Participant::Teacher, path.into_inner(), &actor).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/parents/{id}/messaging-links",
+    tag = "messaging",
+    params(("id" = String, Path, description = "Parent id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Link issued; anyone holding it can read and send the parent's messages", body = IssuedMessagingLink),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "Parent not found", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn create_parent_messaging_link(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    path: Path<String>,
+    actor: AuditContext,
+) -> HttpResponse {
+    issue_messaging_link(&db, Participant::Parent, path.into_inner(), &actor).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/messaging-links/{id}",
+    tag = "messaging",
+    params(("id" = String, Path, description = "Messaging link id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Link revoked; its holder can no longer message through it", body = String),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "No messaging link found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn delete_messaging_link(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    path: Path<String>,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.delete_messaging_link(&id, &actor).await {
+        Ok(res) => {
+            if res.deleted_count == 1 {
+                HttpResponse::Ok().json("Messaging link successfully revoked")
+            } else {
+                HttpResponse::NotFound().json("Messaging link with specified ID not found")
+            }
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ConversationQuery {
+    /// Only conversations of this teacher
+    teacher_id: Option<String>,
+    /// Only conversations of this parent
+    parent_id: Option<String>,
+    /// Only conversations about this student
+    student_id: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/conversations",
+    tag = "messaging",
+    params(ConversationQuery),
+    responses(
+        (status = 200, description = "All conversations for moderation, latest message first", body = Vec<Conversation>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn get_conversations(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    query: Query<ConversationQuery>,
+) -> HttpResponse {
+    let ids = [&query.teacher_id, &query.parent_id, &query.student_id];
+    if ids.iter().any(|id| {
+        id.as_deref()
+            .is_some_and(|id| validators::object_id(id).is_err())
+    }) {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db
+        .get_conversations(
+            query.teacher_id.as_ref(),
+            query.parent_id.as_ref(),
+            query.student_id.as_ref(),
+        )
+        .await
+    {
+        Ok(conversations) => HttpResponse::Ok().json(conversations),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/conversations/{id}/messages",
+    tag = "messaging",
+    params(("id" = String, Path, description = "Conversation id")),
+    responses(
+        (status = 200, description = "The conversation's messages, oldest first; reading them as an admin leaves them unread", body = Vec<Message>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "Conversation not found", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn get_conversation_messages(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    path: Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let Ok(conversation_id) = ObjectId::parse_str(&id) else {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    match db.get_conversation(&id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            return HttpResponse::NotFound().body("No conversation found with specified ID")
+        }
+        Err(err) => return internal_server_error(err),
+    }
+    match db.get_messages(conversation_id).await {
+        Ok(messages) => HttpResponse::Ok().json(messages),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/messaging/{token}/conversations")
+            .route(web::get().to(get_link_conversations))
+            .route(web::post().to(start_link_conversation)),
+    )
+    .service(
+        web::resource("/messaging/{token}/conversations/{conversation_id}/messages")
+            .route(web::get().to(get_link_messages))
+            .route(web::post().to(send_link_message)),
+    )
+    .service(
+        web::resource("/messaging/{token}/conversations/{conversation_id}/read")
+            .route(web::post().to(mark_link_read)),
+    )
+    .service(
+        web::resource("/messaging/{token}/conversations/{conversation_id}/files/{file_id}")
+            .route(web::get().to(get_link_file)),
+    )
+    .service(
+        web::resource("/teachers/{id}/messaging-links")
+            .route(web::post().to(create_teacher_messaging_link)),
+    )
+    .service(
+        web::resource("/parents/{id}/messaging-links")
+            .route(web::post().to(create_parent_messaging_link)),
+    )
+    .service(web::resource("/messaging-links/{id}").route(web::delete().to(delete_messaging_link)))
+    .service(web::resource("/conversations").route(web::get().to(get_conversations)))
+    .service(
+        web::resource("/conversations/{id}/messages")
+            .route(web::get().to(get_conversation_messages)),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{self, person};
+    use super::*;
+    use actix_web::{
+        http::StatusCode,
+        test::{call_and_read_body_json, call_service, init_service, TestRequest},
+        App,
+    };
+    use serde_json::{json, Value};
+
+    fn conversation(
+        teacher_id: ObjectId,
+        parent_id: ObjectId,
+        student_id: ObjectId,
+    ) -> Conversation {
+        let now = DateTime::now();
+        Conversation {
+            id: Some(ObjectId::new()),
+            teacher_id,
+            parent_id,
+            student_id,
+            teacher_name: "Ada Lovelace".to_string(),
+            parent_name: "Grace Hopper".to_string(),
+            student_name: "Alan Turing".to_string(),
+            subject: "Homework".to_string(),
+            started_by: Participant::Teacher,
+            created_at: now,
+            last_message_at: now,
+            teacher_unread: 0,
+            parent_unread: 0,
+        }
+    }
+
+    #[test]
+    fn only_the_two_sides_are_members() {
+        let (teacher, parent) = (ObjectId::new(), ObjectId::new());
+        let conversation = conversation(teacher, parent, ObjectId::new());
+        assert!(is_member(
+            &conversation,
+            Participant::Teacher,
+            &teacher.to_hex()
+        ));
+        assert!(is_member(
+            &conversation,
+            Participant::Parent,
+            &parent.to_hex()
+        ));
+        assert!(!is_member(
+            &conversation,
+            Participant::Parent,
+            &teacher.to_hex()
+        ));
+        assert!(!is_member(
+            &conversation,
+            Participant::Teacher,
+            &parent.to_hex()
+        ));
+        assert!(!is_member(
+            &conversation,
+            Participant::Parent,
+            &ObjectId::new().to_hex()
+        ));
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs MongoDB at SM_TEST_MONGODB_URI"]
+    async fn links_reach_only_their_holders_conversations() {
+        let db = testing::mongodb_repo().await;
+        let app = init_service(App::new().configure(testing::app(db.clone()))).await;
+        let mut ids = Vec::new();
+        for (kind, email) in [
+            ("teachers", "teacher@school.example"),
+            ("teachers", "other.teacher@school.example"),
+            ("parents", "guardian@home.example"),
+            ("parents", "other.guardian@home.example"),
+        ] {
+            let request = TestRequest::post()
+                .uri(&format!("/api/v1/{}", kind))
+                .set_json(person(email))
+                .to_request();
+            ids.push(testing::inserted_id(
+                &call_and_read_body_json(&app, request).await,
+            ));
+        }
+        let [teacher, other_teacher, guardian, other_guardian] = ids.as_slice() else {
+            unreachable!()
+        };
+        let mut student = person("pupil@school.example");
+        student["parent"] = person("guardian@home.example");
+        student["parent"]["_id"] = json!({ "$oid": guardian });
+        student["date_of_join"] = json!({"$date": {"$numberLong": "1262304000000"}});
+        let request = TestRequest::post()
+            .uri("/api/v1/students")
+            .set_json(&student)
+            .to_request();
+        let student_id = testing::inserted_id(&call_and_read_body_json(&app, request).await);
+        let oid = |id: &str| ObjectId::parse_str(id).unwrap();
+        let started = conversation(oid(teacher), oid(guardian), oid(&student_id));
+        let conversation_id = started.id.unwrap().to_hex();
+        db.create_conversation(started, &AuditContext::system("test"))
+            .await
+            .unwrap();
+
+        let mut links = Vec::new();
+        for (kind, id) in [
+            ("teachers", teacher),
+            ("teachers", other_teacher),
+            ("parents", guardian),
+            ("parents", other_guardian),
+        ] {
+            let request = TestRequest::post()
+                .uri(&format!("/api/v1/{}/{}/messaging-links", kind, id))
+                .insert_header(testing::admin())
+                .to_request();
+            let link: Value = call_and_read_body_json(&app, request).await;
+            links.push(link["url"].as_str().unwrap().to_string());
+        }
+        for (url, member) in links.iter().zip([true, false, true, false]) {
+            let request = TestRequest::get()
+                .uri(&format!("{}/conversations", url))
+                .to_request();
+            let listed: Value = call_and_read_body_json(&app, request).await;
+            assert_eq!(
+                listed.as_array().unwrap().len(),
+                usize::from(member),
+                "{}",
+                url
+            );
+            let expected = if member {
+                StatusCode::OK
+            } else {
+                StatusCode::NOT_FOUND
+            };
+            for action in ["messages", "read"] {
+                let uri = format!("{}/conversations/{}/{}", url, conversation_id, action);
+                let request = match action {
+                    "messages" => TestRequest::get().uri(&uri),
+                    _ => TestRequest::post().uri(&uri),
+                };
+                let response = call_service(&app, request.to_request()).await;
+                assert_eq!(response.status(), expected, "{} {}", action, url);
+            }
+        }
+
+        // Neither side can speak for someone else when starting one.
+        let request = TestRequest::post()
+            .uri(&format!("{}/conversations", links[1]))
+            .set_json(json!({
+                "teacher_id": teacher,
+                "student_id": student_id,
+                "subject": "Homework",
+                "body": "Hello",
+            }))
+            .to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let request = TestRequest::post()
+            .uri(&format!("{}/conversations", links[3]))
+            .set_json(json!({
+                "teacher_id": teacher,
+                "student_id": student_id,
+                "subject": "Homework",
+                "body": "Hello",
+            }))
+            .to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        testing::drop_database(&db).await;
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{
+    metrics::{self, ACTIVE_STUDENTS, ATTENDANCE_RATE_TODAY},
+    repository::mongodb_repo::MongoRepo,
+};
+use actix_web::{
+    web::{self, Data},
+    HttpResponse,
+};
+use mongodb::bson::DateTime;
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+/// Recomputes the domain gauges, which are cheap counts, on every scrape.
+async fn refresh_gauges(db: &MongoRepo) {
+    if let Ok(active) = db.count_active_students().await {
+        ACTIVE_STUDENTS.set(active as i64);
+    }
+    let now = DateTime::now().timestamp_millis();
+    let midnight = now - now.rem_euclid(DAY_MILLIS);
+    let from = DateTime::from_millis(midnight);
+    let to = DateTime::from_millis(midnight + DAY_MILLIS);
+    if let Ok((present, total)) = db.count_attendance(from, to).await {
+        ATTENDANCE_RATE_TODAY.set(if total == 0 {
+            0.0
+        } else {
+            present as f64 / total as f64
+        });
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "health",
+    responses((status = 200, description = "Metrics in the Prometheus text format", body = String)),
+)]
+pub async fn get_metrics(db: Data<MongoRepo>) -> HttpResponse {
+    refresh_gauges(&db).await;
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics::render())
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("/metrics", web::get().to(get_metrics));
+}
@@ -1,3 +1,29 @@
+pub mod archive;
+pub mod attachments_api;
+pub mod audit_api;
+pub mod auth;
+pub mod billing_api;
+pub mod calendar_api;
+pub mod documents_api;
+pub mod errors;
+pub mod etag;
+pub mod export_api;
+pub mod health_api;
+pub mod homework_api;
+pub mod import_api;
+pub mod legacy;
+pub mod merge_patch;
+pub mod messaging_api;
+pub mod metrics_api;
+pub mod notifications_api;
+pub mod oneroster_api;
+pub mod openapi;
 pub mod parents_api;
+pub mod staffing_api;
 pub mod students_api;
 pub mod teachers_api;
+#[cfg(test)]
+pub mod testing;
+pub mod timetable_api;
+pub mod v1;
+pub mod validation;
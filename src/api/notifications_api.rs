@@ -0,0 +1,273 @@
+use super::{auth::Admin, errors::internal_server_error};
+use crate::{
+    models::{
+        audit_entry::AuditContext,
+        notification::{ChannelKind, DeliveryStatus},
+        notification_preferences::NotificationPreferences,
+        validators,
+    },
+    repository::mongodb_repo::MongoRepo,
+};
+use actix_web::{
+    web::{self, Data, Json, Path, Query},
+    HttpResponse,
+};
+use mongodb::bson::oid::ObjectId;
+use serde::Deserialize;
+use utoipa::{IntoParams, ToSchema};
+
+/// Most outbox entries returned at once.
+const MAX_OUTBOX_LIMIT: i64 = 1000;
+
+/// Responds 404 unless the parent exists and is not archived.
+async fn require_parent(db: &MongoRepo, id: &String) -> Option<HttpResponse> {
+    match db.get_parent(id, false).await {
+        Ok(Some(_)) => None,
+        Ok(None) => Some(HttpResponse::NotFound().body("No parent found with specified ID")),
+        Err(err) => Some(internal_server_error(err)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/parents/{id}/notification-preferences",
+    tag = "notifications",
+    params(("id" = String, Path, description = "Parent id")),
+    responses(
+        (status = 200, description = "Channels per kind of notification; the defaults until the parent chooses", body = NotificationPreferences),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "Parent not found", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_preferences(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+    let id = path.into_inner();
+    let Ok(parent_id) = ObjectId::parse_str(&id) else {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    if let Some(response) = require_parent(&db, &id).await {
+        return response;
+    }
+    match db.get_notification_preferences(&id).await {
+        Ok(preferences) => HttpResponse::Ok()
+            .json(preferences.unwrap_or_else(|| NotificationPreferences::defaults(parent_id))),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+/// Channels for each kind of notification; an empty list turns it off.
+#[derive(Deserialize, ToSchema)]
+pub struct PreferencesRequest {
+    absence: Vec<ChannelKind>,
+    exam_result: Vec<ChannelKind>,
+    overdue_fee: Vec<ChannelKind>,
+}
+
+#[utoipa::path(
+    put,
+    path = "/api/v1/parents/{id}/notification-preferences",
+    tag = "notifications",
+    request_body = PreferencesRequest,
+    params(("id" = String, Path, description = "Parent id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Preferences saved; email goes to the parent's `email` and SMS to their `mobile`", body = NotificationPreferences),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "Parent not found", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn set_preferences(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    request: Json<PreferencesRequest>,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    let Ok(parent_id) = ObjectId::parse_str(&id) else {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    if let Some(response) = require_parent(&db, &id).await {
+        return response;
+    }
+    let request = request.into_inner();
+    let dedup = |mut channels: Vec<ChannelKind>| {
+        let mut seen = Vec::new();
+        channels.retain(|channel| {
+            let first = !seen.contains(channel);
+            seen.push(*channel);
+            first
+        });
+        channels
+    };
+    let preferences = NotificationPreferences {
+        parent_id,
+        absence: dedup(request.absence),
+        exam_result: dedup(request.exam_result),
+        overdue_fee: dedup(request.overdue_fee),
+    };
+    match db
+        .set_notification_preferences(preferences.clone(), &actor)
+        .await
+    {
+        Ok(()) => HttpResponse::Ok().json(preferences),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct InboxQuery {
+    /// Only notifications not yet read
+    #[serde(default)]
+    unread: bool,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/parents/{id}/inbox",
+    tag = "notifications",
+    params(("id" = String, Path, description = "Parent id"), InboxQuery),
+    responses(
+        (status = 200, description = "The parent's in-app notifications, newest first", body = Vec<Notification>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "Parent not found", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_inbox(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    query: Query<InboxQuery>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    if let Some(response) = require_parent(&db, &id).await {
+        return response;
+    }
+    match db.get_inbox(&id, query.unread).await {
+        Ok(notifications) => HttpResponse::Ok().json(notifications),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/parents/{id}/inbox/{notification_id}/read",
+    tag = "notifications",
+    params(
+        ("id" = String, Path, description = "Parent id"),
+        ("notification_id" = String, Path, description = "Notification id"),
+    ),
+    responses(
+        (status = 200, description = "Marked read; reading it again keeps the first time", body = String),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No such notification in the parent's inbox", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn mark_read(db: Data<MongoRepo>, path: Path<(String, String)>) -> HttpResponse {
+    let (id, notification_id) = path.into_inner();
+    if validators::object_id(&id).is_err() || validators::object_id(&notification_id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.mark_notification_read(&id, &notification_id).await {
+        Ok(true) => HttpResponse::Ok().json("Notification marked read"),
+        Ok(false) => HttpResponse::NotFound().body("No notification found with specified ID"),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct OutboxQuery {
+    /// Only notifications in this state
+    status: Option<DeliveryStatus>,
+    /// Only notifications to this parent
+    parent_id: Option<String>,
+    /// At most this many, 100 by default
+    limit: Option<i64>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/notifications",
+    tag = "notifications",
+    params(OutboxQuery),
+    responses(
+        (status = 200, description = "The outbox, newest first", body = Vec<Notification>),
+        (status = 400, description = "Invalid id or limit", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn get_outbox(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    query: Query<OutboxQuery>,
+) -> HttpResponse {
+    if query
+        .parent_id
+        .as_deref()
+        .is_some_and(|id| validators::object_id(id).is_err())
+    {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    let limit = query.limit.unwrap_or(100);
+    if !(1..=MAX_OUTBOX_LIMIT).contains(&limit) {
+        return HttpResponse::BadRequest()
+            .body(format!("limit must be between 1 and {}", MAX_OUTBOX_LIMIT));
+    }
+    match db
+        .get_notifications(query.status, query.parent_id.as_ref(), limit)
+        .await
+    {
+        Ok(notifications) => HttpResponse::Ok().json(notifications),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/notifications/{id}/retry",
+    tag = "notifications",
+    params(("id" = String, Path, description = "Notification id")),
+    responses(
+        (status = 200, description = "Queued again with a fresh set of attempts", body = String),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 409, description = "Not a failed notification", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn retry_notification(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    path: Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.retry_notification(&id).await {
+        Ok(true) => HttpResponse::Ok().json("Notification queued again"),
+        Ok(false) => HttpResponse::Conflict().body("Only failed notifications can be retried"),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/parents/{id}/notification-preferences")
+            .route(web::get().to(get_preferences))
+            .route(web::put().to(set_preferences)),
+    )
+    .service(web::resource("/parents/{id}/inbox").route(web::get().to(get_inbox)))
+    .service(
+        web::resource("/parents/{id}/inbox/{notification_id}/read")
+            .route(web::post().to(mark_read)),
+    )
+    .service(web::resource("/notifications").route(web::get().to(get_outbox)))
+    .service(web::resource("/notifications/{id}/retry").route(web::post().to(retry_notification)));
+}
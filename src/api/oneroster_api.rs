@@ -0,0 +1,442 @@
+use super::{
+    auth::Admin,
+    errors::internal_server_error,
+    import_api::{self, FieldErrors, ImportEntity, RowReport, RowStatus},
+};
+use crate::repository::Error;
+use crate::{
+    config::SchoolSettings,
+    models::{
+        audit_entry::AuditContext, classroom::Classroom, classroom_student::ClassroomStudent,
+        validators,
+    },
+    oneroster::{
+        self, AcademicSession, Bundle, BundleRow, Class, Demographics, Enrollment, Record, Roster,
+        User,
+    },
+    repository::mongodb_repo::MongoRepo,
+};
+use actix_web::{
+    http::header::{ContentDisposition, DispositionParam, DispositionType},
+    web::{self, Bytes, Data},
+    HttpResponse,
+};
+use mongodb::{bson::DateTime, results::InsertOneResult};
+use serde::Serialize;
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+/// Bundles carry every record of a school, so allow more than a plain CSV.
+const MAX_BUNDLE_BYTES: usize = 50 * 1024 * 1024;
+
+const ZIP: &str = "application/zip";
+
+#[derive(Serialize, ToSchema)]
+pub struct OneRosterImportReport {
+    created: usize,
+    invalid: usize,
+    /// Rows of `users.csv`, parents first so students can be linked to them
+    users: Vec<RowReport>,
+    /// Rows of `classes.csv`
+    classes: Vec<RowReport>,
+    /// Student rows of `enrollments.csv`; teacher rows are read with the class
+    enrollments: Vec<RowReport>,
+}
+
+#[derive(Clone, Copy)]
+enum Section {
+    Users,
+    Classes,
+    Enrollments,
+}
+
+impl OneRosterImportReport {
+    fn record(&mut self, section: Section, row: &BundleRow, outcome: Outcome) {
+        let (status, id, errors) = match outcome {
+            Outcome::Created(id) => {
+                self.created += 1;
+                (RowStatus::Created, Some(id), FieldErrors::new())
+            }
+            Outcome::Existing(id) => (RowStatus::Existing, Some(id), FieldErrors::new()),
+            Outcome::Invalid(errors) => {
+                self.invalid += 1;
+                (RowStatus::Invalid, None, errors)
+            }
+        };
+        let rows = match section {
+            Section::Users => &mut self.users,
+            Section::Classes => &mut self.classes,
+            Section::Enrollments => &mut self.enrollments,
+        };
+        rows.push(RowReport::new(row.line, status, id, errors));
+    }
+}
+
+/// What one bundle row came to.
+enum Outcome {
+    Created(String),
+    Existing(String),
+    Invalid(FieldErrors),
+}
+
+impl Outcome {
+    fn invalid(field: &str, message: &str) -> Self {
+        let mut errors = FieldErrors::new();
+        import_api::add_error(&mut errors, field, message);
+        Outcome::Invalid(errors)
+    }
+}
+
+fn inserted_id(result: InsertOneResult) -> String {
+    result
+        .inserted_id
+        .as_object_id()
+        .map(|id| id.to_hex())
+        .unwrap_or_default()
+}
+
+/// The local id for a bundle `sourcedId`: one created by this import, or an
+/// id from an earlier export.
+fn resolve<'a>(ids: &'a HashMap<String, String>, sourced_id: &'a str) -> Option<&'a str> {
+    match ids.get(sourced_id) {
+        Some(id) => Some(id),
+        None if validators::object_id(sourced_id).is_ok() => Some(sourced_id),
+        None => None,
+    }
+}
+
+fn entity_for_role(role: &str) -> Option<ImportEntity> {
+    match role {
+        "parent" | "guardian" | "relative" => Some(ImportEntity::Parents),
+        "teacher" => Some(ImportEntity::Teachers),
+        "student" => Some(ImportEntity::Students),
+        _ => None,
+    }
+}
+
+async fn user_exists(db: &MongoRepo, entity: ImportEntity, id: &String) -> Result<bool, Error> {
+    if validators::object_id(id).is_err() {
+        return Ok(false);
+    }
+    Ok(match entity {
+        ImportEntity::Teachers => db.get_teacher(id, true).await?.is_some(),
+        ImportEntity::Parents => db.get_parent(id, true).await?.is_some(),
+        ImportEntity::Students => db.get_student(id, true).await?.is_some(),
+    })
+}
+
+/// Maps a `users.csv` row onto the bulk import fields. OneRoster has no
+/// join date, so students join on the day they are imported.
+async fn user_fields(
+    db: &MongoRepo,
+    row: &BundleRow,
+    birth_dates: &HashMap<&str, &str>,
+    parent_emails: &HashMap<&str, &str>,
+) -> Result<HashMap<&'static str, String>, Error> {
+    let birth_date = birth_dates.get(row.get("sourcedId")).copied().unwrap_or("");
+    let mobile = match row.get("sms") {
+        "" => row.get("phone"),
+        sms => sms,
+    };
+    let mut fields = HashMap::from([
+        ("email", row.get("email").to_string()),
+        ("fname", row.get("givenName").to_string()),
+        ("lname", row.get("familyName").to_string()),
+        ("dob", birth_date.to_string()),
+        ("phone", row.get("phone").to_string()),
+        ("mobile", mobile.to_string()),
+        ("status", row.get("enabledUser").to_string()),
+    ]);
+    if row.get("role") == "student" {
+        let today = DateTime::now().try_to_rfc3339_string().unwrap_or_default();
+        fields.insert("date_of_join", today);
+        let mut parent_email = String::new();
+        for agent in row.list("agentSourcedIds") {
+            if let Some(email) = parent_emails.get(agent) {
+                parent_email = email.to_string();
+            } else if validators::object_id(agent).is_ok() {
+                if let Some(parent) = db.get_parent(&agent.to_string(), false).await? {
+                    parent_email = parent.email;
+                }
+            }
+            if !parent_email.is_empty() {
+                break;
+            }
+        }
+        fields.insert("parent_email", parent_email);
+    }
+    Ok(fields)
+}
+
+/// Builds a classroom from a `classes.csv` row. The teacher is the class's
+/// primary teacher enrollment, or its first one.
+async fn class_record(
+    db: &MongoRepo,
+    bundle: &Bundle,
+    row: &BundleRow,
+    ids: &HashMap<String, String>,
+) -> Result<Result<Classroom, Outcome>, Error> {
+    let sourced_id = row.get("sourcedId");
+    let school_years: HashMap<&str, &str> = bundle
+        .rows(AcademicSession::FILE)
+        .iter()
+        .map(|session| (session.get("sourcedId"), session.get("schoolYear")))
+        .collect();
+    let year = row.list("termSourcedIds").into_iter().find_map(|term| {
+        school_years
+            .get(term)
+            .copied()
+            .or_else(|| term.strip_prefix("year-"))
+            .and_then(|year| year.parse::<i32>().ok())
+    });
+    let year = match year {
+        Some(year) => year,
+        None => {
+            return Ok(Err(Outcome::invalid(
+                "termSourcedIds",
+                "must name a school year",
+            )))
+        }
+    };
+    let grade_id = match row
+        .list("grades")
+        .first()
+        .and_then(|grade| grade.parse::<i64>().ok())
+    {
+        Some(grade_id) => grade_id,
+        None => return Ok(Err(Outcome::invalid("grades", "must be a numeric grade"))),
+    };
+    let mut teachers: Vec<&BundleRow> = bundle
+        .rows(Enrollment::FILE)
+        .iter()
+        .filter(|enrollment| {
+            enrollment.get("classSourcedId") == sourced_id && enrollment.get("role") == "teacher"
+        })
+        .collect();
+    teachers.sort_by_key(|enrollment| enrollment.get("primary") != "true");
+    let teacher_id = teachers
+        .first()
+        .and_then(|enrollment| resolve(ids, enrollment.get("userSourcedId")));
+    let teacher = match teacher_id {
+        Some(id) => db.get_teacher(&id.to_string(), false).await?,
+        None => None,
+    };
+    let teacher = match teacher {
+        Some(teacher) => teacher,
+        None => {
+            return Ok(Err(Outcome::invalid(
+                "teacher",
+                "class has no known teacher",
+            )))
+        }
+    };
+    let section = match row.get("classCode") {
+        "" => row.get("title"),
+        code => code,
+    };
+    Ok(Ok(Classroom {
+        id: None,
+        year,
+        grade_id,
+        section: section.to_string(),
+        status: true,
+        remarks: row.get("title").to_string(),
+        teacher,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/oneroster/export.zip",
+    tag = "oneroster",
+    responses(
+        (status = 200, description = "OneRoster 1.1 bulk CSV bundle", content_type = "application/zip", body = Vec<u8>),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn export_bundle(
+    db: Data<MongoRepo>,
+    school: Data<SchoolSettings>,
+    _admin: Admin,
+) -> HttpResponse {
+    let roster = match Roster::load(&db, &school).await {
+        Ok(roster) => roster,
+        Err(err) => return internal_server_error(err),
+    };
+    match oneroster::write_bundle(&roster, &school) {
+        Ok(bundle) => HttpResponse::Ok()
+            .content_type(ZIP)
+            .insert_header(ContentDisposition {
+                disposition: DispositionType::Attachment,
+                parameters: vec![DispositionParam::Filename("oneroster.zip".to_string())],
+            })
+            .body(bundle),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+/// Imports the users, classes and student enrollments of a OneRoster 1.1
+/// bulk bundle. Records whose `sourcedId` is already a local id are left
+/// alone, so re-importing an export is harmless. Courses, sessions and
+/// results are derived locally and not imported.
+#[utoipa::path(
+    post,
+    path = "/api/v1/oneroster/import",
+    tag = "oneroster",
+    request_body(content = Vec<u8>, content_type = "application/zip", description = "OneRoster 1.1 bulk CSV bundle"),
+    responses(
+        (status = 200, description = "Per-row import report", body = OneRosterImportReport),
+        (status = 400, description = "Unreadable bundle", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn import_bundle(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    body: Bytes,
+    actor: AuditContext,
+) -> HttpResponse {
+    let bundle = match Bundle::read(&body) {
+        Ok(bundle) => bundle,
+        Err(message) => return HttpResponse::BadRequest().body(message),
+    };
+    match import(&db, &bundle, &actor).await {
+        Ok(report) => HttpResponse::Ok().json(report),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+async fn import(
+    db: &MongoRepo,
+    bundle: &Bundle,
+    actor: &AuditContext,
+) -> Result<OneRosterImportReport, Error> {
+    let mut report = OneRosterImportReport {
+        created: 0,
+        invalid: 0,
+        users: Vec::new(),
+        classes: Vec::new(),
+        enrollments: Vec::new(),
+    };
+    // sourcedId -> local id of everything this import created or found.
+    let mut ids: HashMap<String, String> = HashMap::new();
+    let users = bundle.rows(User::FILE);
+    let parent_emails: HashMap<&str, &str> = users
+        .iter()
+        .filter(|row| entity_for_role(row.get("role")) == Some(ImportEntity::Parents))
+        .map(|row| (row.get("sourcedId"), row.get("email")))
+        .collect();
+    let birth_dates: HashMap<&str, &str> = bundle
+        .rows(Demographics::FILE)
+        .iter()
+        .map(|row| (row.get("sourcedId"), row.get("birthDate")))
+        .collect();
+    let mut guardians = HashMap::new();
+
+    for entity in [
+        ImportEntity::Parents,
+        ImportEntity::Teachers,
+        ImportEntity::Students,
+    ] {
+        for row in users {
+            let role = entity_for_role(row.get("role"));
+            if role != Some(entity) {
+                // Roles the school has no record for are reported once.
+                if role.is_none() && entity == ImportEntity::Parents {
+                    let outcome = Outcome::invalid(
+                        "role",
+                        "only students, teachers and parents are imported",
+                    );
+                    report.record(Section::Users, row, outcome);
+                }
+                continue;
+            }
+            let sourced_id = row.get("sourcedId").to_string();
+            let outcome = if user_exists(db, entity, &sourced_id).await? {
+                Outcome::Existing(sourced_id.clone())
+            } else {
+                let fields = user_fields(db, row, &birth_dates, &parent_emails).await?;
+                match import_api::build_record(db, entity, &fields, &mut guardians).await {
+                    Ok(person) => Outcome::Created(
+                        import_api::create_record(db, person, actor)
+                            .await?
+                            .unwrap_or_default(),
+                    ),
+                    Err(errors) => Outcome::Invalid(errors),
+                }
+            };
+            if let Outcome::Created(id) | Outcome::Existing(id) = &outcome {
+                ids.insert(sourced_id, id.clone());
+            }
+            report.record(Section::Users, row, outcome);
+        }
+    }
+
+    for row in bundle.rows(Class::FILE) {
+        let sourced_id = row.get("sourcedId").to_string();
+        let existing = validators::object_id(&sourced_id).is_ok()
+            && db.get_classroom(&sourced_id).await?.is_some();
+        let outcome = if existing {
+            Outcome::Existing(sourced_id.clone())
+        } else {
+            match class_record(db, bundle, row, &ids).await? {
+                Ok(classroom) => {
+                    Outcome::Created(inserted_id(db.create_classroom(classroom, actor).await?))
+                }
+                Err(outcome) => outcome,
+            }
+        };
+        if let Outcome::Created(id) | Outcome::Existing(id) = &outcome {
+            ids.insert(sourced_id, id.clone());
+        }
+        report.record(Section::Classes, row, outcome);
+    }
+
+    for row in bundle.rows(Enrollment::FILE) {
+        if row.get("role") != "student" {
+            continue;
+        }
+        let sourced_id = row.get("sourcedId").to_string();
+        if validators::object_id(&sourced_id).is_ok()
+            && db.get_enrollment(&sourced_id).await?.is_some()
+        {
+            report.record(Section::Enrollments, row, Outcome::Existing(sourced_id));
+            continue;
+        }
+        let classroom = match resolve(&ids, row.get("classSourcedId")) {
+            Some(id) => db.get_classroom(&id.to_string()).await?,
+            None => None,
+        };
+        let student = match resolve(&ids, row.get("userSourcedId")) {
+            Some(id) => db.get_student(&id.to_string(), false).await?,
+            None => None,
+        };
+        let outcome = match (classroom, student) {
+            (None, _) => Outcome::invalid("classSourcedId", "unknown class"),
+            (_, None) => Outcome::invalid("userSourcedId", "unknown student"),
+            (Some(classroom), Some(student)) => {
+                let enrollment = ClassroomStudent {
+                    id: None,
+                    classroom,
+                    student,
+                };
+                Outcome::Created(inserted_id(db.enroll_student(enrollment, actor).await?))
+            }
+        };
+        report.record(Section::Enrollments, row, outcome);
+    }
+    Ok(report)
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.route("/oneroster/export.zip", web::get().to(export_bundle))
+        .service(
+            web::resource("/oneroster/import")
+                .app_data(web::PayloadConfig::new(MAX_BUNDLE_BYTES))
+                .route(web::post().to(import_bundle)),
+        );
+}
@@ -0,0 +1,309 @@
+use super::{
+    attachments_api, audit_api,
+    billing_api::{
+        self, Balance, DiscountRequest, EntryKind, FeeItemRequest, GenerateInvoicesRequest,
+        InvoiceRun, PaymentRequest, Statement, StatementEntry, StudentBalance,
+    },
+    calendar_api::{self, FeedRequest, IssuedFeed},
+    documents_api::{self, IssuedBillingLink},
+    export_api,
+    health_api::{self, DependencyStatus, Readiness, VersionInfo},
+    homework_api::{self, CourseGrade, GradeRequest, HomeworkRequest, StudentHomework, TermGrades},
+    import_api::{self, ImportEntity, ImportReport, RowReport, RowStatus},
+    messaging_api::{self, ConversationRequest, IssuedMessagingLink, ReadReceipt},
+    metrics_api,
+    notifications_api::{self, PreferencesRequest},
+    oneroster_api::{self, OneRosterImportReport},
+    parents_api,
+    staffing_api::{self, AssignmentRequest, CourseLoad, QualificationRequest, Workload},
+    students_api, teachers_api,
+    timetable_api::{
+        self, Clash, CourseRequirement, GenerateTimetableRequest, RoomCapacity,
+        TeacherAvailability, TimetableConflict, TimetableEntryRequest,
+    },
+};
+use crate::models::{
+    assignment::Assignment,
+    attachment::{Attachment, AttachmentRef, OwnerKind},
+    attendance::Attendance,
+    audit_entry::AuditEntry,
+    calendar_feed::FeedKind,
+    classroom::Classroom,
+    classroom_student::ClassroomStudent,
+    conversation::{Conversation, Message, Participant},
+    course::Course,
+    course_assignment::CourseAssignment,
+    discount::{Discount, DiscountKind},
+    exam::Exam,
+    exam_result::ExamResult,
+    exam_type::ExamType,
+    fee_item::FeeItem,
+    grade::Grade,
+    invoice::{Invoice, InvoiceLine, InvoiceStatus},
+    notification::{ChannelKind, DeliveryStatus, Notification, NotificationKind},
+    notification_preferences::NotificationPreferences,
+    parent::Parent,
+    payment::{Payment, PaymentMethod},
+    period::{Period, Weekday},
+    qualification::Qualification,
+    school_event::SchoolEvent,
+    student::{Student, StudentSummary},
+    submission::{Submission, SubmissionGrade},
+    teacher::Teacher,
+    timetable_entry::TimetableEntry,
+    timetable_job::{JobStatus, TimetableJob},
+};
+use actix_web::{get, HttpResponse};
+use utoipa::{
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "School Manager API"),
+    paths(
+        teachers_api::create_teacher,
+        teachers_api::get_teacher,
+        teachers_api::update_teacher,
+        teachers_api::patch_teacher,
+        teachers_api::delete_teacher,
+        teachers_api::restore_teacher,
+        teachers_api::get_all_teachers,
+        parents_api::create_parent,
+        parents_api::get_parent,
+        parents_api::update_parent,
+        parents_api::patch_parent,
+        parents_api::delete_parent,
+        parents_api::restore_parent,
+        parents_api::get_all_parents,
+        students_api::create_student,
+        students_api::get_student,
+        students_api::update_student,
+        students_api::patch_student,
+        students_api::delete_student,
+        students_api::restore_student,
+        students_api::get_all_students,
+        audit_api::get_audit_log,
+        import_api::import_records,
+        export_api::export_students,
+        export_api::export_roster,
+        export_api::export_attendance,
+        export_api::export_exam_results,
+        oneroster_api::export_bundle,
+        oneroster_api::import_bundle,
+        timetable_api::create_period,
+        timetable_api::get_all_periods,
+        timetable_api::create_timetable_entry,
+        timetable_api::delete_timetable_entry,
+        timetable_api::get_classroom_timetable,
+        timetable_api::get_teacher_timetable,
+        timetable_api::generate_timetable,
+        timetable_api::get_timetable_job,
+        calendar_api::get_calendar,
+        calendar_api::create_feed,
+        calendar_api::delete_feed,
+        calendar_api::create_school_event,
+        calendar_api::get_all_school_events,
+        calendar_api::delete_school_event,
+        staffing_api::create_qualification,
+        staffing_api::get_qualifications,
+        staffing_api::delete_qualification,
+        staffing_api::create_assignment,
+        staffing_api::get_classroom_assignments,
+        staffing_api::get_teacher_assignments,
+        staffing_api::delete_assignment,
+        staffing_api::get_workload,
+        homework_api::create_homework,
+        homework_api::get_homework,
+        homework_api::get_classroom_homework,
+        homework_api::create_submission,
+        homework_api::get_submissions,
+        homework_api::grade_submission,
+        homework_api::get_submission_file,
+        homework_api::get_student_homework,
+        homework_api::get_term_grades,
+        attachments_api::attach_to_student,
+        attachments_api::attach_to_teacher,
+        attachments_api::attach_to_assignment,
+        attachments_api::get_attachment,
+        attachments_api::get_attachment_content,
+        attachments_api::delete_attachment,
+        billing_api::create_fee_item,
+        billing_api::get_fee_items,
+        billing_api::delete_fee_item,
+        billing_api::create_discount,
+        billing_api::get_student_discounts,
+        billing_api::delete_discount,
+        billing_api::generate_invoices,
+        billing_api::get_invoice,
+        billing_api::get_student_invoices,
+        billing_api::void_invoice,
+        billing_api::record_payment,
+        billing_api::get_invoice_payments,
+        billing_api::get_student_balance,
+        billing_api::get_parent_statement,
+        documents_api::get_invoice_pdf,
+        documents_api::get_receipt_pdf,
+        documents_api::get_guardian_statement,
+        documents_api::get_guardian_invoice,
+        documents_api::get_guardian_invoice_payments,
+        documents_api::get_guardian_student_invoices,
+        documents_api::get_guardian_student_balance,
+        documents_api::create_billing_link,
+        documents_api::delete_billing_link,
+        notifications_api::get_preferences,
+        notifications_api::set_preferences,
+        notifications_api::get_inbox,
+        notifications_api::mark_read,
+        notifications_api::get_outbox,
+        notifications_api::retry_notification,
+        messaging_api::get_link_conversations,
+        messaging_api::start_link_conversation,
+        messaging_api::get_link_messages,
+        messaging_api::send_link_message,
+        messaging_api::mark_link_read,
+        messaging_api::get_link_file,
+        messaging_api::create_teacher_messaging_link,
+        messaging_api::create_parent_messaging_link,
+        messaging_api::delete_messaging_link,
+        messaging_api::get_conversations,
+        messaging_api::get_conversation_messages,
+        health_api::liveness,
+        health_api::readiness,
+        health_api::version,
+        metrics_api::get_metrics,
+    ),
+    components(schemas(
+        Teacher,
+        Parent,
+        Student,
+        StudentSummary,
+        AuditEntry,
+        Attendance,
+        Classroom,
+        ClassroomStudent,
+        Course,
+        Exam,
+        ExamResult,
+        ExamType,
+        Grade,
+        Readiness,
+        DependencyStatus,
+        VersionInfo,
+        ImportEntity,
+        ImportReport,
+        RowReport,
+        RowStatus,
+        OneRosterImportReport,
+        Period,
+        Weekday,
+        TimetableEntry,
+        TimetableEntryRequest,
+        TimetableConflict,
+        Clash,
+        GenerateTimetableRequest,
+        CourseRequirement,
+        TeacherAvailability,
+        RoomCapacity,
+        TimetableJob,
+        JobStatus,
+        SchoolEvent,
+        FeedKind,
+        FeedRequest,
+        IssuedFeed,
+        IssuedBillingLink,
+        Qualification,
+        QualificationRequest,
+        CourseAssignment,
+        AssignmentRequest,
+        Workload,
+        CourseLoad,
+        HomeworkRequest,
+        Assignment,
+        Submission,
+        SubmissionGrade,
+        GradeRequest,
+        StudentHomework,
+        CourseGrade,
+        TermGrades,
+        Attachment,
+        AttachmentRef,
+        OwnerKind,
+        FeeItem,
+        FeeItemRequest,
+        Discount,
+        DiscountKind,
+        DiscountRequest,
+        Invoice,
+        InvoiceLine,
+        InvoiceStatus,
+        GenerateInvoicesRequest,
+        InvoiceRun,
+        Payment,
+        PaymentMethod,
+        PaymentRequest,
+        StudentBalance,
+        Balance,
+        EntryKind,
+        StatementEntry,
+        Statement,
+        Notification,
+        NotificationKind,
+        ChannelKind,
+        DeliveryStatus,
+        NotificationPreferences,
+        PreferencesRequest,
+        Conversation,
+        Message,
+        Participant,
+        ConversationRequest,
+        IssuedMessagingLink,
+        ReadReceipt
+    )),
+    modifiers(&AdminTokenAddon)
+)]
+pub struct ApiDoc;
+
+struct AdminTokenAddon;
+
+impl Modify for AdminTokenAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "admin_token",
+                SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+            );
+        }
+    }
+}
+
+/// Swagger UI page rendering `/openapi.json`.
+const SWAGGER_UI: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>School Manager API</title>
+  <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js"></script>
+  <script>
+    window.ui = SwaggerUIBundle({ url: "/openapi.json", dom_id: "#swagger-ui" });
+  </script>
+</body>
+</html>
+"##;
+
+#[get("/openapi.json")]
+pub async fn get_openapi() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
+
+#[get("/docs")]
+pub async fn get_docs() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(SWAGGER_UI)
+}
@@ -1,12 +1,40 @@
-use crate::{models::parent::Parent, repository::mongodb_repo::MongoRepo};
+use super::{
+    archive::IncludeDeleted,
+    errors::internal_server_error,
+    etag::{self, ExpectedVersion},
+    merge_patch, validation,
+};
+use crate::{
+    models::{audit_entry::AuditContext, parent::Parent, validators},
+    repository::mongodb_repo::MongoRepo,
+};
 use actix_web::{
-    delete, get, post, put,
-    web::{Data, Json, Path},
+    web::{self, Data, Json, Path},
     HttpResponse,
 };
+use serde_json::Value;
+use validator::Validate;
 
-#[post("/parent")]
-pub async fn create_parent(db: Data<MongoRepo>, new_parent: Json<Parent>) -> HttpResponse {
+#[utoipa::path(
+    post,
+    path = "/api/v1/parents",
+    tag = "parents",
+    request_body = Parent,
+    params(("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Parent created; returns the inserted id", body = Object),
+        (status = 422, description = "Validation errors per field", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn create_parent(
+    db: Data<MongoRepo>,
+    new_parent: Json<Parent>,
+    actor: AuditContext,
+) -> HttpResponse {
+    if let Err(errors) = new_parent.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
     let data = Parent {
         id: None,
         email: new_parent.email.to_string(),
@@ -19,37 +47,76 @@ pub async fn create_parent(db: Data<MongoRepo>, new_parent: Json<Parent>) -> Htt
         status: new_parent.status.to_owned(),
         last_login_date: new_parent.last_login_date.to_owned(),
         last_login_ip: new_parent.last_login_ip.to_string(),
+        version: 0,
+        deleted_at: None,
     };
-    let parent_detail = db.create_parent(data).await;
+    let parent_detail = db.create_parent(data, &actor).await;
     match parent_detail {
         Ok(parent) => HttpResponse::Ok().json(parent),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Err(err) => internal_server_error(err),
     }
 }
 
-#[get("/parent/{id}")]
-pub async fn get_parent(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+#[utoipa::path(
+    get,
+    path = "/api/v1/parents/{id}",
+    tag = "parents",
+    params(("id" = String, Path, description = "Parent id"), ("include_deleted" = Option<bool>, Query, description = "Include archived records (admin only)")),
+    responses(
+        (status = 200, description = "Parent found", body = Parent, headers(("ETag" = String, description = "Current version of the parent"))),
+        (status = 403, description = "include_deleted requires admin access", body = String),
+        (status = 404, description = "No parent found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security((), ("admin_token" = []))
+)]
+pub async fn get_parent(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    IncludeDeleted(include_deleted): IncludeDeleted,
+) -> HttpResponse {
     let id = path.into_inner();
-    if id.is_empty() {
+    if validators::object_id(&id).is_err() {
         return HttpResponse::BadRequest().body("invalid ID");
     }
-    let parent_detail = db.get_parent(&id).await;
+    let parent_detail = db.get_parent(&id, include_deleted).await;
     match parent_detail {
-        Ok(parent) => HttpResponse::Ok().json(parent),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Ok(Some(parent)) => HttpResponse::Ok()
+            .insert_header(etag::entity_tag(parent.version))
+            .json(parent),
+        Ok(None) => HttpResponse::NotFound().body("No parent found with specified ID"),
+        Err(err) => internal_server_error(err),
     }
 }
 
-#[put("/parent/{id}")]
+#[utoipa::path(
+    put,
+    path = "/api/v1/parents/{id}",
+    tag = "parents",
+    request_body(content = Parent, description = "The parent; last_login_* are kept as they are"),
+    params(("id" = String, Path, description = "Parent id"), ("If-Match" = Option<String>, Header, description = "Version from a previous ETag; 412 if stale"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Parent replaced", body = Parent, headers(("ETag" = String, description = "Current version of the parent"))),
+        (status = 404, description = "No parent found with specified ID", body = String),
+        (status = 412, description = "Record was modified by another request", body = String),
+        (status = 422, description = "Validation errors per field", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
 pub async fn update_parent(
     db: Data<MongoRepo>,
     path: Path<String>,
     new_parent: Json<Parent>,
+    ExpectedVersion(expected_version): ExpectedVersion,
+    actor: AuditContext,
 ) -> HttpResponse {
     let id = path.into_inner();
-    if id.is_empty() {
+    if validators::object_id(&id).is_err() {
         return HttpResponse::BadRequest().body("invalid ID");
     };
+    if let Err(errors) = new_parent.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
     let data = Parent {
         id: None,
         email: new_parent.email.to_string(),
@@ -62,48 +129,218 @@ pub async fn update_parent(
         status: new_parent.status.to_owned(),
         last_login_date: new_parent.last_login_date.to_owned(),
         last_login_ip: new_parent.last_login_ip.to_string(),
+        version: 0,
+        deleted_at: None,
     };
-    let update_result = db.update_parent(&id, data).await;
+    let update_result = db.update_parent(&id, data, expected_version, &actor).await;
     match update_result {
         Ok(update) => {
             if update.matched_count == 1 {
-                let updated_parent_info = db.get_parent(&id).await;
-                return match updated_parent_info {
-                    Ok(parent) => HttpResponse::Ok().json(parent),
-                    Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
-                };
+                let updated_parent_info = db.get_parent(&id, false).await;
+                match updated_parent_info {
+                    Ok(Some(parent)) => HttpResponse::Ok()
+                        .insert_header(etag::entity_tag(parent.version))
+                        .json(parent),
+                    Ok(None) => HttpResponse::NotFound().body("No parent found with specified ID"),
+                    Err(err) => internal_server_error(err),
+                }
+            } else if expected_version.is_some()
+                && matches!(db.get_parent(&id, false).await, Ok(Some(_)))
+            {
+                etag::precondition_failed()
+            } else {
+                HttpResponse::NotFound().body("No parent found with specified ID")
+            }
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/parents/{id}",
+    tag = "parents",
+    request_body(
+        content = Object,
+        content_type = "application/merge-patch+json",
+        description = "RFC 7396 merge patch; id, version and last_login_* are immutable, including inside embedded records"
+    ),
+    params(("id" = String, Path, description = "Parent id"), ("If-Match" = Option<String>, Header, description = "Version from a previous ETag; 412 if stale"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Parent patched", body = Parent, headers(("ETag" = String, description = "Current version of the parent"))),
+        (status = 404, description = "No parent found with specified ID", body = String),
+        (status = 412, description = "Record was modified by another request", body = String),
+        (status = 422, description = "Validation errors on the fields the patch sets, or that it newly breaks; a value stored before it had to be valid is left alone", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn patch_parent(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    patch_body: Json<Value>,
+    ExpectedVersion(expected_version): ExpectedVersion,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    let current_parent = match db.get_parent(&id, false).await {
+        Ok(Some(parent)) => parent,
+        Ok(None) => return HttpResponse::NotFound().body("No parent found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    if expected_version.is_some_and(|version| version != current_parent.version) {
+        return etag::precondition_failed();
+    }
+    let (mut patched_parent, fields) = match merge_patch::apply(&current_parent, &patch_body) {
+        Ok(patched) => patched,
+        Err(err) => return HttpResponse::UnprocessableEntity().body(err),
+    };
+    if let Err(errors) = patched_parent.validate() {
+        let current = current_parent.validate().err();
+        let fields = validation::patch_field_errors(&errors, current.as_ref(), &patch_body);
+        if !fields.is_empty() {
+            return HttpResponse::UnprocessableEntity().json(fields);
+        }
+    }
+    if fields.is_empty() {
+        return HttpResponse::Ok()
+            .insert_header(etag::entity_tag(patched_parent.version))
+            .json(patched_parent);
+    }
+    let patch_result = db
+        .patch_parent(&id, fields, current_parent.version, &actor)
+        .await;
+    match patch_result {
+        Ok(update) => {
+            if update.matched_count == 1 {
+                patched_parent.version += 1;
+                HttpResponse::Ok()
+                    .insert_header(etag::entity_tag(patched_parent.version))
+                    .json(patched_parent)
+            } else {
+                etag::precondition_failed()
+            }
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/parents/{id}",
+    tag = "parents",
+    params(("id" = String, Path, description = "Parent id"), ("If-Match" = Option<String>, Header, description = "Version from a previous ETag; 412 if stale"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Parent archived", body = String),
+        (status = 404, description = "No parent found with specified ID", body = String),
+        (status = 412, description = "Record was modified by another request", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn delete_parent(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    ExpectedVersion(expected_version): ExpectedVersion,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    let result = db.delete_parent(&id, expected_version, &actor).await;
+    match result {
+        Ok(res) => {
+            if res.modified_count == 1 {
+                HttpResponse::Ok().json("Parent successfully deleted")
+            } else if expected_version.is_some()
+                && matches!(db.get_parent(&id, false).await, Ok(Some(_)))
+            {
+                etag::precondition_failed()
             } else {
-                return HttpResponse::NotFound().body("No parent found with specified ID");
+                HttpResponse::NotFound().json("Parent with specified ID not found")
             }
         }
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Err(err) => internal_server_error(err),
     }
 }
 
-#[delete("/parent/{id}")]
-pub async fn delete_parent(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+#[utoipa::path(
+    post,
+    path = "/api/v1/parents/{id}/restore",
+    tag = "parents",
+    params(("id" = String, Path, description = "Parent id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Parent restored", body = Parent, headers(("ETag" = String, description = "Current version of the parent"))),
+        (status = 404, description = "No archived parent found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn restore_parent(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    actor: AuditContext,
+) -> HttpResponse {
     let id = path.into_inner();
-    if id.is_empty() {
+    if validators::object_id(&id).is_err() {
         return HttpResponse::BadRequest().body("invalid ID");
     };
-    let result = db.delete_parent(&id).await;
+    let result = db.restore_parent(&id, &actor).await;
     match result {
         Ok(res) => {
-            if res.deleted_count == 1 {
-                return HttpResponse::Ok().json("Parent successfully deleted");
+            if res.modified_count == 1 {
+                let restored_parent_info = db.get_parent(&id, false).await;
+                match restored_parent_info {
+                    Ok(Some(parent)) => HttpResponse::Ok()
+                        .insert_header(etag::entity_tag(parent.version))
+                        .json(parent),
+                    Ok(None) => HttpResponse::NotFound().body("No parent found with specified ID"),
+                    Err(err) => internal_server_error(err),
+                }
             } else {
-                return HttpResponse::NotFound().json("Parent with specified ID not found");
+                HttpResponse::NotFound().body("No archived parent found with specified ID")
             }
         }
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Err(err) => internal_server_error(err),
     }
 }
 
-#[get("/parents")]
-pub async fn get_all_parents(db: Data<MongoRepo>) -> HttpResponse {
-    let parents = db.get_all_parents().await;
+#[utoipa::path(
+    get,
+    path = "/api/v1/parents",
+    tag = "parents",
+    params(("include_deleted" = Option<bool>, Query, description = "Include archived records (admin only)")),
+    responses(
+        (status = 200, description = "All parents", body = [Parent]),
+        (status = 403, description = "include_deleted requires admin access", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security((), ("admin_token" = []))
+)]
+pub async fn get_all_parents(
+    db: Data<MongoRepo>,
+    IncludeDeleted(include_deleted): IncludeDeleted,
+) -> HttpResponse {
+    let parents = db.get_all_parents(include_deleted).await;
     match parents {
         Ok(parent) => HttpResponse::Ok().json(parent),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Err(err) => internal_server_error(err),
     }
 }
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/parents")
+            .route(web::get().to(get_all_parents))
+            .route(web::post().to(create_parent)),
+    )
+    .service(
+        web::resource("/parents/{id}")
+            .route(web::get().to(get_parent))
+            .route(web::put().to(update_parent))
+            .route(web::patch().to(patch_parent))
+            .route(web::delete().to(delete_parent)),
+    )
+    .service(web::resource("/parents/{id}/restore").route(web::post().to(restore_parent)));
+}
@@ -0,0 +1,451 @@
+use super::{errors::internal_server_error, validation};
+use crate::{
+    config::SchoolSettings,
+    models::{
+        audit_entry::AuditContext, course_assignment::CourseAssignment,
+        qualification::Qualification, validators,
+    },
+    repository::mongodb_repo::MongoRepo,
+};
+use actix_web::{
+    web::{self, Data, Json, Path, Query},
+    HttpResponse,
+};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::{IntoParams, ToSchema};
+use validator::Validate;
+
+/// Responds 422 unless the teacher is qualified to teach the course.
+pub async fn require_qualification(
+    db: &MongoRepo,
+    teacher_id: &String,
+    course_id: &String,
+) -> Result<(), HttpResponse> {
+    match db.get_qualification(teacher_id, course_id).await {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => {
+            let errors =
+                BTreeMap::from([("teacher_id", ["is not qualified to teach this course"])]);
+            Err(HttpResponse::UnprocessableEntity().json(errors))
+        }
+        Err(err) => Err(internal_server_error(err)),
+    }
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct QualificationRequest {
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    course_id: String,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/teachers/{id}/qualifications",
+    tag = "staffing",
+    request_body = QualificationRequest,
+    params(("id" = String, Path, description = "Teacher id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Teacher qualified; returns the inserted id", body = Object),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "Teacher or course not found", body = String),
+        (status = 409, description = "Teacher is already qualified for the course", body = Qualification),
+        (status = 422, description = "Validation errors per field", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn create_qualification(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    request: Json<QualificationRequest>,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    if let Err(errors) = request.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
+    let teacher = match db.get_teacher(&id, false).await {
+        Ok(Some(teacher)) => teacher,
+        Ok(None) => return HttpResponse::NotFound().body("No teacher found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    let course = match db.get_course(&request.course_id).await {
+        Ok(Some(course)) => course,
+        Ok(None) => return HttpResponse::NotFound().body("No course found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    match db.get_qualification(&id, &request.course_id).await {
+        Ok(Some(existing)) => return HttpResponse::Conflict().json(existing),
+        Ok(None) => {}
+        Err(err) => return internal_server_error(err),
+    }
+    let data = Qualification {
+        id: None,
+        teacher,
+        course,
+    };
+    let qualification_detail = db.create_qualification(data, &actor).await;
+    match qualification_detail {
+        Ok(qualification) => HttpResponse::Ok().json(qualification),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/teachers/{id}/qualifications",
+    tag = "staffing",
+    params(("id" = String, Path, description = "Teacher id")),
+    responses(
+        (status = 200, description = "Courses the teacher may teach", body = Vec<Qualification>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_qualifications(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.get_qualifications_for_teacher(&id).await {
+        Ok(qualifications) => HttpResponse::Ok().json(qualifications),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/teachers/{id}/qualifications/{course_id}",
+    tag = "staffing",
+    params(("id" = String, Path, description = "Teacher id"), ("course_id" = String, Path, description = "Course id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Qualification withdrawn; existing assignments are kept", body = String),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "Teacher is not qualified for the course", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn delete_qualification(
+    db: Data<MongoRepo>,
+    path: Path<(String, String)>,
+    actor: AuditContext,
+) -> HttpResponse {
+    let (id, course_id) = path.into_inner();
+    if validators::object_id(&id).is_err() || validators::object_id(&course_id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    let qualification_id = match db.get_qualification(&id, &course_id).await {
+        Ok(Some(Qualification { id: Some(id), .. })) => id.to_hex(),
+        Ok(_) => return HttpResponse::NotFound().json("Qualification not found"),
+        Err(err) => return internal_server_error(err),
+    };
+    match db.delete_qualification(&qualification_id, &actor).await {
+        Ok(res) => {
+            if res.deleted_count == 1 {
+                HttpResponse::Ok().json("Qualification successfully deleted")
+            } else {
+                HttpResponse::NotFound().json("Qualification not found")
+            }
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct AssignmentRequest {
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    classroom_id: String,
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    course_id: String,
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    teacher_id: String,
+    year: i32,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    term: i32,
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/assignments",
+    tag = "staffing",
+    request_body = AssignmentRequest,
+    params(("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Course assigned; returns the inserted id", body = Object),
+        (status = 404, description = "Classroom, course or teacher not found", body = String),
+        (status = 409, description = "The course already has a teacher for that classroom and term", body = CourseAssignment),
+        (status = 422, description = "Validation errors per field, including a teacher not qualified for the course", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn create_assignment(
+    db: Data<MongoRepo>,
+    school: Data<SchoolSettings>,
+    request: Json<AssignmentRequest>,
+    actor: AuditContext,
+) -> HttpResponse {
+    if let Err(errors) = request.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
+    if request.term > school.terms_per_year {
+        let message = format!("must be at most {}", school.terms_per_year);
+        return HttpResponse::UnprocessableEntity().json(BTreeMap::from([("term", [message])]));
+    }
+    let classroom = match db.get_classroom(&request.classroom_id).await {
+        Ok(Some(classroom)) => classroom,
+        Ok(None) => return HttpResponse::NotFound().body("No classroom found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    let course = match db.get_course(&request.course_id).await {
+        Ok(Some(course)) => course,
+        Ok(None) => return HttpResponse::NotFound().body("No course found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    let teacher = match db.get_teacher(&request.teacher_id, false).await {
+        Ok(Some(teacher)) => teacher,
+        Ok(None) => return HttpResponse::NotFound().body("No teacher found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    if let Err(response) = require_qualification(&db, &request.teacher_id, &request.course_id).await
+    {
+        return response;
+    }
+    match db
+        .get_course_assignment_for_term(
+            &request.classroom_id,
+            &request.course_id,
+            request.year,
+            request.term,
+        )
+        .await
+    {
+        Ok(Some(existing)) => return HttpResponse::Conflict().json(existing),
+        Ok(None) => {}
+        Err(err) => return internal_server_error(err),
+    }
+    let data = CourseAssignment {
+        id: None,
+        classroom,
+        course,
+        teacher,
+        year: request.year,
+        term: request.term,
+    };
+    let assignment_detail = db.create_course_assignment(data, &actor).await;
+    match assignment_detail {
+        Ok(assignment) => HttpResponse::Ok().json(assignment),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct TermQuery {
+    /// Only assignments in this school year
+    year: Option<i32>,
+    /// Only assignments in this term
+    term: Option<i32>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/classrooms/{id}/assignments",
+    tag = "staffing",
+    params(("id" = String, Path, description = "Classroom id"), TermQuery),
+    responses(
+        (status = 200, description = "Who teaches each course, latest term first", body = Vec<CourseAssignment>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_classroom_assignments(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    query: Query<TermQuery>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db
+        .get_assignments_for_classroom(&id, query.year, query.term)
+        .await
+    {
+        Ok(assignments) => HttpResponse::Ok().json(assignments),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/teachers/{id}/assignments",
+    tag = "staffing",
+    params(("id" = String, Path, description = "Teacher id"), TermQuery),
+    responses(
+        (status = 200, description = "Courses the teacher is assigned, latest term first", body = Vec<CourseAssignment>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_teacher_assignments(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    query: Query<TermQuery>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db
+        .get_assignments_for_teacher(&id, query.year, query.term)
+        .await
+    {
+        Ok(assignments) => HttpResponse::Ok().json(assignments),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/assignments/{id}",
+    tag = "staffing",
+    params(("id" = String, Path, description = "Course assignment id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Assignment removed", body = String),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No assignment found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn delete_assignment(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.delete_course_assignment(&id, &actor).await {
+        Ok(res) => {
+            if res.deleted_count == 1 {
+                HttpResponse::Ok().json("Course assignment successfully deleted")
+            } else {
+                HttpResponse::NotFound().json("Course assignment with specified ID not found")
+            }
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+/// Weekly periods of one course taught to one classroom.
+#[derive(Serialize, ToSchema)]
+pub struct CourseLoad {
+    #[schema(value_type = Option<Object>)]
+    course_id: Option<ObjectId>,
+    course: String,
+    #[schema(value_type = Option<Object>)]
+    classroom_id: Option<ObjectId>,
+    classroom: String,
+    periods: u32,
+    minutes: i64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Workload {
+    #[schema(value_type = Object)]
+    teacher_id: ObjectId,
+    weekly_minutes: i64,
+    weekly_hours: f64,
+    max_weekly_hours: u32,
+    over_limit: bool,
+    courses: Vec<CourseLoad>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/teachers/{id}/workload",
+    tag = "staffing",
+    params(("id" = String, Path, description = "Teacher id")),
+    responses(
+        (status = 200, description = "Weekly teaching time from the timetable against the configured maximum", body = Workload),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No teacher found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_workload(
+    db: Data<MongoRepo>,
+    school: Data<SchoolSettings>,
+    path: Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.get_teacher(&id, false).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("No teacher found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    }
+    let entries = match db.get_timetable_for_teacher(&id).await {
+        Ok(entries) => entries,
+        Err(err) => return internal_server_error(err),
+    };
+    let mut courses: Vec<CourseLoad> = Vec::new();
+    for entry in entries {
+        let minutes = entry.period.minutes();
+        let load = match courses.iter_mut().find(|load| {
+            load.course_id == entry.course.id && load.classroom_id == entry.classroom.id
+        }) {
+            Some(load) => load,
+            None => {
+                courses.push(CourseLoad {
+                    course_id: entry.course.id,
+                    course: entry.course.name.clone(),
+                    classroom_id: entry.classroom.id,
+                    classroom: format!("{} {}", entry.classroom.year, entry.classroom.section),
+                    periods: 0,
+                    minutes: 0,
+                });
+                courses.last_mut().unwrap()
+            }
+        };
+        load.periods += 1;
+        load.minutes += minutes;
+    }
+    let weekly_minutes: i64 = courses.iter().map(|load| load.minutes).sum();
+    HttpResponse::Ok().json(Workload {
+        teacher_id: ObjectId::parse_str(&id).unwrap(),
+        weekly_minutes,
+        weekly_hours: weekly_minutes as f64 / 60.0,
+        max_weekly_hours: school.max_weekly_teaching_hours,
+        over_limit: weekly_minutes > school.max_weekly_teaching_hours as i64 * 60,
+        courses,
+    })
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/teachers/{id}/qualifications")
+            .route(web::get().to(get_qualifications))
+            .route(web::post().to(create_qualification)),
+    )
+    .service(
+        web::resource("/teachers/{id}/qualifications/{course_id}")
+            .route(web::delete().to(delete_qualification)),
+    )
+    .service(
+        web::resource("/teachers/{id}/assignments").route(web::get().to(get_teacher_assignments)),
+    )
+    .service(web::resource("/teachers/{id}/workload").route(web::get().to(get_workload)))
+    .service(web::resource("/assignments").route(web::post().to(create_assignment)))
+    .service(web::resource("/assignments/{id}").route(web::delete().to(delete_assignment)))
+    .service(
+        web::resource("/classrooms/{id}/assignments")
+            .route(web::get().to(get_classroom_assignments)),
+    );
+}
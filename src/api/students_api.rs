@@ -1,12 +1,42 @@
-use crate::{models::student::Student, repository::mongodb_repo::MongoRepo};
+use super::{
+    archive::IncludeDeleted,
+    errors::internal_server_error,
+    etag::{self, ExpectedVersion},
+    merge_patch, validation,
+};
+use crate::{
+    models::{audit_entry::AuditContext, parent::Parent, student::Student, validators},
+    repository::{mongodb_repo::MongoRepo, Error},
+};
 use actix_web::{
-    delete, get, post, put,
-    web::{Data, Json, Path},
+    web::{self, Data, Json, Path},
     HttpResponse,
 };
+use mongodb::bson::{oid::ObjectId, to_bson};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use validator::Validate;
 
-#[post("/student")]
-pub async fn create_student(db: Data<MongoRepo>, new_student: Json<Student>) -> HttpResponse {
+#[utoipa::path(
+    post,
+    path = "/api/v1/students",
+    tag = "students",
+    request_body = Student,
+    params(("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Student created; returns the inserted id", body = Object),
+        (status = 422, description = "Validation errors per field", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn create_student(
+    db: Data<MongoRepo>,
+    new_student: Json<Student>,
+    actor: AuditContext,
+) -> HttpResponse {
+    if let Err(errors) = new_student.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
     let data = Student {
         id: None,
         email: new_student.email.to_string(),
@@ -21,37 +51,85 @@ pub async fn create_student(db: Data<MongoRepo>, new_student: Json<Student>) ->
         date_of_join: new_student.date_of_join.to_owned(),
         last_login_date: new_student.last_login_date.to_owned(),
         last_login_ip: new_student.last_login_ip.to_string(),
+        attachments: Vec::new(),
+        version: 0,
+        deleted_at: None,
     };
-    let new_student = db.create_student(data).await;
+    let new_student = db.create_student(data, &actor).await;
     match new_student {
         Ok(student) => HttpResponse::Ok().json(student),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Err(err) => internal_server_error(err),
     }
 }
 
-#[get("/student/{id}")]
-pub async fn get_student(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+#[utoipa::path(
+    get,
+    path = "/api/v1/students/{id}",
+    tag = "students",
+    params(("id" = String, Path, description = "Student id"), ("include_deleted" = Option<bool>, Query, description = "Include archived records (admin only)")),
+    responses(
+        (status = 200, description = "Student found", body = Student, headers(("ETag" = String, description = "Current version of the student"))),
+        (status = 403, description = "include_deleted requires admin access", body = String),
+        (status = 404, description = "No student found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security((), ("admin_token" = []))
+)]
+pub async fn get_student(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    IncludeDeleted(include_deleted): IncludeDeleted,
+) -> HttpResponse {
     let id = path.into_inner();
-    if id.is_empty() {
+    if validators::object_id(&id).is_err() {
         return HttpResponse::BadRequest().body("invalid ID");
     }
-    let new_student = db.get_student(&id).await;
+    let new_student = db.get_student(&id, include_deleted).await;
     match new_student {
-        Ok(student) => HttpResponse::Ok().json(student),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Ok(Some(student)) => HttpResponse::Ok()
+            .insert_header(etag::entity_tag(student.version))
+            .json(student),
+        Ok(None) => HttpResponse::NotFound().body("No student found with specified ID"),
+        Err(err) => internal_server_error(err),
     }
 }
 
-#[put("/student/{id}")]
+#[utoipa::path(
+    put,
+    path = "/api/v1/students/{id}",
+    tag = "students",
+    request_body(
+        content = Student,
+        description = "The student; `parent` is linked by its `_id` and stored as that parent's current record, and last_login_* are kept as they are"
+    ),
+    params(("id" = String, Path, description = "Student id"), ("If-Match" = Option<String>, Header, description = "Version from a previous ETag; 412 if stale"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Student replaced", body = Student, headers(("ETag" = String, description = "Current version of the student"))),
+        (status = 404, description = "No student found with specified ID", body = String),
+        (status = 412, description = "Record was modified by another request", body = String),
+        (status = 422, description = "Validation errors per field, including a parent that does not exist", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
 pub async fn update_student(
     db: Data<MongoRepo>,
     path: Path<String>,
     new_student: Json<Student>,
+    ExpectedVersion(expected_version): ExpectedVersion,
+    actor: AuditContext,
 ) -> HttpResponse {
     let id = path.into_inner();
-    if id.is_empty() {
+    if validators::object_id(&id).is_err() {
         return HttpResponse::BadRequest().body("invalid ID");
     };
+    if let Err(errors) = new_student.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
+    let parent = match linked_parent(&db, new_student.parent.id).await {
+        Ok(Some(parent)) => parent,
+        Ok(None) => return unknown_parent(),
+        Err(err) => return internal_server_error(err),
+    };
     let data = Student {
         id: None,
         email: new_student.email.to_string(),
@@ -61,53 +139,348 @@ pub async fn update_student(
         dob: new_student.dob.to_owned(),
         phone: new_student.phone.to_string(),
         mobile: new_student.mobile.to_string(),
-        parent: new_student.parent.to_owned(),
+        parent,
         date_of_join: new_student.date_of_join.to_owned(),
         status: new_student.status.to_owned(),
         last_login_date: new_student.last_login_date.to_owned(),
         last_login_ip: new_student.last_login_ip.to_string(),
+        attachments: Vec::new(),
+        version: 0,
+        deleted_at: None,
     };
-    let update_result = db.update_student(&id, data).await;
+    let update_result = db.update_student(&id, data, expected_version, &actor).await;
     match update_result {
         Ok(update) => {
             if update.matched_count == 1 {
-                let updated_student_info = db.get_student(&id).await;
-                return match updated_student_info {
-                    Ok(student) => HttpResponse::Ok().json(student),
-                    Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
-                };
+                let updated_student_info = db.get_student(&id, false).await;
+                match updated_student_info {
+                    Ok(Some(student)) => HttpResponse::Ok()
+                        .insert_header(etag::entity_tag(student.version))
+                        .json(student),
+                    Ok(None) => HttpResponse::NotFound().body("No student found with specified ID"),
+                    Err(err) => internal_server_error(err),
+                }
+            } else if expected_version.is_some()
+                && matches!(db.get_student(&id, false).await, Ok(Some(_)))
+            {
+                etag::precondition_failed()
+            } else {
+                HttpResponse::NotFound().body("No student found with specified ID")
+            }
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/students/{id}",
+    tag = "students",
+    request_body(
+        content = Object,
+        content_type = "application/merge-patch+json",
+        description = "RFC 7396 merge patch; id, version, attachments and last_login_* are immutable, including inside embedded records. `{\"parent\": {\"_id\": ...}}` on its own re-links the guardian"
+    ),
+    params(("id" = String, Path, description = "Student id"), ("If-Match" = Option<String>, Header, description = "Version from a previous ETag; 412 if stale"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Student patched", body = Student, headers(("ETag" = String, description = "Current version of the student"))),
+        (status = 404, description = "No student found with specified ID", body = String),
+        (status = 412, description = "Record was modified by another request", body = String),
+        (status = 422, description = "Validation errors on the fields the patch sets, or that it newly breaks; a value stored before it had to be valid is left alone", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn patch_student(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    patch_body: Json<Value>,
+    ExpectedVersion(expected_version): ExpectedVersion,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    let current_student = match db.get_student(&id, false).await {
+        Ok(Some(student)) => student,
+        Ok(None) => return HttpResponse::NotFound().body("No student found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    if expected_version.is_some_and(|version| version != current_student.version) {
+        return etag::precondition_failed();
+    }
+    let mut patch_body = patch_body.into_inner();
+    let relinked = match take_parent_link(&mut patch_body) {
+        Ok(None) => None,
+        Ok(Some(parent_id)) => match linked_parent(&db, Some(parent_id)).await {
+            Ok(Some(parent)) => Some(parent),
+            Ok(None) => return unknown_parent(),
+            Err(err) => return internal_server_error(err),
+        },
+        Err(err) => return HttpResponse::UnprocessableEntity().body(err),
+    };
+    let (mut patched_student, mut fields) = match merge_patch::apply(&current_student, &patch_body)
+    {
+        Ok(patched) => patched,
+        Err(err) => return HttpResponse::UnprocessableEntity().body(err),
+    };
+    if let Some(parent) = relinked {
+        match to_bson(&parent) {
+            Ok(parent) => fields.insert("parent", parent),
+            Err(err) => return internal_server_error(err),
+        };
+        patched_student.parent = parent;
+    }
+    if let Err(errors) = patched_student.validate() {
+        let current = current_student.validate().err();
+        let fields = validation::patch_field_errors(&errors, current.as_ref(), &patch_body);
+        if !fields.is_empty() {
+            return HttpResponse::UnprocessableEntity().json(fields);
+        }
+    }
+    if fields.is_empty() {
+        return HttpResponse::Ok()
+            .insert_header(etag::entity_tag(patched_student.version))
+            .json(patched_student);
+    }
+    let patch_result = db
+        .patch_student(&id, fields, current_student.version, &actor)
+        .await;
+    match patch_result {
+        Ok(update) => {
+            if update.matched_count == 1 {
+                patched_student.version += 1;
+                HttpResponse::Ok()
+                    .insert_header(etag::entity_tag(patched_student.version))
+                    .json(patched_student)
+            } else {
+                etag::precondition_failed()
+            }
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/students/{id}",
+    tag = "students",
+    params(("id" = String, Path, description = "Student id"), ("If-Match" = Option<String>, Header, description = "Version from a previous ETag; 412 if stale"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Student archived", body = String),
+        (status = 404, description = "No student found with specified ID", body = String),
+        (status = 412, description = "Record was modified by another request", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn delete_student(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    ExpectedVersion(expected_version): ExpectedVersion,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    let result = db.delete_student(&id, expected_version, &actor).await;
+    match result {
+        Ok(res) => {
+            if res.modified_count == 1 {
+                HttpResponse::Ok().json("Student successfully deleted")
+            } else if expected_version.is_some()
+                && matches!(db.get_student(&id, false).await, Ok(Some(_)))
+            {
+                etag::precondition_failed()
             } else {
-                return HttpResponse::NotFound().body("No student found with specified ID");
+                HttpResponse::NotFound().json("Student with specified ID not found")
             }
         }
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Err(err) => internal_server_error(err),
     }
 }
 
-#[delete("/student/{id}")]
-pub async fn delete_student(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+#[utoipa::path(
+    post,
+    path = "/api/v1/students/{id}/restore",
+    tag = "students",
+    params(("id" = String, Path, description = "Student id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Student restored", body = Student, headers(("ETag" = String, description = "Current version of the student"))),
+        (status = 404, description = "No archived student found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn restore_student(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    actor: AuditContext,
+) -> HttpResponse {
     let id = path.into_inner();
-    if id.is_empty() {
+    if validators::object_id(&id).is_err() {
         return HttpResponse::BadRequest().body("invalid ID");
     };
-    let result = db.delete_student(&id).await;
+    let result = db.restore_student(&id, &actor).await;
     match result {
         Ok(res) => {
-            if res.deleted_count == 1 {
-                return HttpResponse::Ok().json("Student successfully deleted");
+            if res.modified_count == 1 {
+                let restored_student_info = db.get_student(&id, false).await;
+                match restored_student_info {
+                    Ok(Some(student)) => HttpResponse::Ok()
+                        .insert_header(etag::entity_tag(student.version))
+                        .json(student),
+                    Ok(None) => HttpResponse::NotFound().body("No student found with specified ID"),
+                    Err(err) => internal_server_error(err),
+                }
             } else {
-                return HttpResponse::NotFound().json("Student with specified ID not found");
+                HttpResponse::NotFound().body("No archived student found with specified ID")
             }
         }
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Err(err) => internal_server_error(err),
     }
 }
 
-#[get("/students")]
-pub async fn get_all_students(db: Data<MongoRepo>) -> HttpResponse {
-    let students = db.get_all_students().await;
+#[utoipa::path(
+    get,
+    path = "/api/v1/students",
+    tag = "students",
+    params(("include_deleted" = Option<bool>, Query, description = "Include archived records (admin only)")),
+    responses(
+        (status = 200, description = "All students", body = [Student]),
+        (status = 403, description = "include_deleted requires admin access", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security((), ("admin_token" = []))
+)]
+pub async fn get_all_students(
+    db: Data<MongoRepo>,
+    IncludeDeleted(include_deleted): IncludeDeleted,
+) -> HttpResponse {
+    let students = db.get_all_students(include_deleted).await;
     match students {
         Ok(student) => HttpResponse::Ok().json(student),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+/// The stored parent a student is being linked to, if it exists.
+async fn linked_parent(
+    db: &MongoRepo,
+    parent_id: Option<ObjectId>,
+) -> Result<Option<Parent>, Error> {
+    match parent_id {
+        Some(parent_id) => db.get_parent(&parent_id.to_hex(), false).await,
+        None => Ok(None),
+    }
+}
+
+fn unknown_parent() -> HttpResponse {
+    HttpResponse::UnprocessableEntity().json(BTreeMap::from([(
+        "parent",
+        vec!["must reference an existing parent"],
+    )]))
+}
+
+/// Takes a `{"parent": {"_id": ...}}` re-link out of a merge patch, since
+/// ids are otherwise immutable. Returns the parent's id when there is one.
+fn take_parent_link(patch: &mut Value) -> Result<Option<ObjectId>, String> {
+    let Some(fields) = patch.as_object_mut() else {
+        return Ok(None);
+    };
+    let id = match fields.get("parent").and_then(Value::as_object) {
+        Some(parent) if parent.contains_key("_id") => {
+            if parent.len() > 1 {
+                return Err("re-linking `parent` takes its `_id` alone".to_string());
+            }
+            parent["_id"].clone()
+        }
+        _ => return Ok(None),
+    };
+    fields.remove("parent");
+    let hex = match &id {
+        Value::String(hex) => Some(hex.as_str()),
+        Value::Object(oid) => oid.get("$oid").and_then(Value::as_str),
+        _ => None,
+    };
+    hex.and_then(|hex| ObjectId::parse_str(hex).ok())
+        .map(Some)
+        .ok_or_else(|| "field `parent._id` must be an ObjectId".to_string())
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/students")
+            .route(web::get().to(get_all_students))
+            .route(web::post().to(create_student)),
+    )
+    .service(
+        web::resource("/students/{id}")
+            .route(web::get().to(get_student))
+            .route(web::put().to(update_student))
+            .route(web::patch().to(patch_student))
+            .route(web::delete().to(delete_student)),
+    )
+    .service(web::resource("/students/{id}/restore").route(web::post().to(restore_student)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{self, person};
+    use super::*;
+    use actix_web::{
+        http::StatusCode,
+        test::{call_service, init_service, read_body_json, TestRequest},
+        App,
+    };
+    use serde_json::json;
+
+    #[actix_web::test]
+    async fn invalid_guardians_and_dates_are_rejected_by_path() {
+        let app =
+            init_service(App::new().configure(testing::app(testing::offline_repo().await))).await;
+        let mut student = person("ada@school.example");
+        student["parent"] = person("no-at-sign");
+        student["dob"] = json!({"$date": {"$numberLong": "1262304000000"}});
+        student["date_of_join"] = json!({"$date": {"$numberLong": "1230768000000"}});
+        let request = TestRequest::post()
+            .uri("/api/v1/students")
+            .set_json(&student)
+            .to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let errors: Value = read_body_json(response).await;
+        assert_eq!(
+            errors,
+            json!({
+                "__all__": ["date_of_join must be after dob"],
+                "parent.email": ["must be a valid email address"],
+            })
+        );
+    }
+
+    #[test]
+    fn take_parent_link_removes_a_bare_id() {
+        let hex = "63455a1b5f3b8e2a4c1d9e01";
+        for id in [json!(hex), json!({ "$oid": hex })] {
+            let mut patch = json!({"fname": "Ada", "parent": {"_id": id}});
+            let taken = take_parent_link(&mut patch).unwrap();
+            assert_eq!(taken, Some(ObjectId::parse_str(hex).unwrap()));
+            assert_eq!(patch, json!({"fname": "Ada"}));
+        }
+    }
+
+    #[test]
+    fn take_parent_link_leaves_other_parent_changes() {
+        let mut patch = json!({"parent": {"fname": "Grace"}});
+        assert_eq!(take_parent_link(&mut patch).unwrap(), None);
+        assert_eq!(patch, json!({"parent": {"fname": "Grace"}}));
+    }
+
+    #[test]
+    fn take_parent_link_rejects_mixed_or_malformed_ids() {
+        let mut mixed = json!({"parent": {"_id": "63455a1b5f3b8e2a4c1d9e01", "fname": "Grace"}});
+        assert!(take_parent_link(&mut mixed).is_err());
+        let mut malformed = json!({"parent": {"_id": "nope"}});
+        assert!(take_parent_link(&mut malformed).is_err());
     }
 }
@@ -1,12 +1,40 @@
-use crate::{models::teacher::Teacher, repository::mongodb_repo::MongoRepo};
+use super::{
+    archive::IncludeDeleted,
+    errors::internal_server_error,
+    etag::{self, ExpectedVersion},
+    merge_patch, validation,
+};
+use crate::{
+    models::{audit_entry::AuditContext, teacher::Teacher, validators},
+    repository::mongodb_repo::MongoRepo,
+};
 use actix_web::{
-    delete, get, post, put,
-    web::{Data, Json, Path},
+    web::{self, Data, Json, Path},
     HttpResponse,
 };
+use serde_json::Value;
+use validator::Validate;
 
-#[post("/teacher")]
-pub async fn create_teacher(db: Data<MongoRepo>, new_teacher: Json<Teacher>) -> HttpResponse {
+#[utoipa::path(
+    post,
+    path = "/api/v1/teachers",
+    tag = "teachers",
+    request_body = Teacher,
+    params(("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Teacher created; returns the inserted id", body = Object),
+        (status = 422, description = "Validation errors per field", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn create_teacher(
+    db: Data<MongoRepo>,
+    new_teacher: Json<Teacher>,
+    actor: AuditContext,
+) -> HttpResponse {
+    if let Err(errors) = new_teacher.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
     let data = Teacher {
         id: None,
         email: new_teacher.email.to_string(),
@@ -19,37 +47,77 @@ pub async fn create_teacher(db: Data<MongoRepo>, new_teacher: Json<Teacher>) ->
         status: new_teacher.status.to_owned(),
         last_login_date: new_teacher.last_login_date.to_owned(),
         last_login_ip: new_teacher.last_login_ip.to_string(),
+        attachments: Vec::new(),
+        version: 0,
+        deleted_at: None,
     };
-    let teacher_detail = db.create_teacher(data).await;
+    let teacher_detail = db.create_teacher(data, &actor).await;
     match teacher_detail {
         Ok(teacher) => HttpResponse::Ok().json(teacher),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Err(err) => internal_server_error(err),
     }
 }
 
-#[get("/teacher/{id}")]
-pub async fn get_teacher(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+#[utoipa::path(
+    get,
+    path = "/api/v1/teachers/{id}",
+    tag = "teachers",
+    params(("id" = String, Path, description = "Teacher id"), ("include_deleted" = Option<bool>, Query, description = "Include archived records (admin only)")),
+    responses(
+        (status = 200, description = "Teacher found", body = Teacher, headers(("ETag" = String, description = "Current version of the teacher"))),
+        (status = 403, description = "include_deleted requires admin access", body = String),
+        (status = 404, description = "No teacher found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security((), ("admin_token" = []))
+)]
+pub async fn get_teacher(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    IncludeDeleted(include_deleted): IncludeDeleted,
+) -> HttpResponse {
     let id = path.into_inner();
-    if id.is_empty() {
+    if validators::object_id(&id).is_err() {
         return HttpResponse::BadRequest().body("invalid ID");
     }
-    let teacher_detail = db.get_teacher(&id).await;
+    let teacher_detail = db.get_teacher(&id, include_deleted).await;
     match teacher_detail {
-        Ok(teacher) => HttpResponse::Ok().json(teacher),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Ok(Some(teacher)) => HttpResponse::Ok()
+            .insert_header(etag::entity_tag(teacher.version))
+            .json(teacher),
+        Ok(None) => HttpResponse::NotFound().body("No teacher found with specified ID"),
+        Err(err) => internal_server_error(err),
     }
 }
 
-#[put("/teacher/{id}")]
+#[utoipa::path(
+    put,
+    path = "/api/v1/teachers/{id}",
+    tag = "teachers",
+    request_body(content = Teacher, description = "The teacher; last_login_* are kept as they are"),
+    params(("id" = String, Path, description = "Teacher id"), ("If-Match" = Option<String>, Header, description = "Version from a previous ETag; 412 if stale"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Teacher replaced", body = Teacher, headers(("ETag" = String, description = "Current version of the teacher"))),
+        (status = 404, description = "No teacher found with specified ID", body = String),
+        (status = 412, description = "Record was modified by another request", body = String),
+        (status = 422, description = "Validation errors per field", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
 pub async fn update_teacher(
     db: Data<MongoRepo>,
     path: Path<String>,
     new_teacher: Json<Teacher>,
+    ExpectedVersion(expected_version): ExpectedVersion,
+    actor: AuditContext,
 ) -> HttpResponse {
     let id = path.into_inner();
-    if id.is_empty() {
+    if validators::object_id(&id).is_err() {
         return HttpResponse::BadRequest().body("invalid ID");
     };
+    if let Err(errors) = new_teacher.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
     let data = Teacher {
         id: None,
         email: new_teacher.email.to_string(),
@@ -62,48 +130,330 @@ pub async fn update_teacher(
         status: new_teacher.status.to_owned(),
         last_login_date: new_teacher.last_login_date.to_owned(),
         last_login_ip: new_teacher.last_login_ip.to_string(),
+        attachments: Vec::new(),
+        version: 0,
+        deleted_at: None,
     };
-    let update_result = db.update_teacher(&id, data).await;
+    let update_result = db.update_teacher(&id, data, expected_version, &actor).await;
     match update_result {
         Ok(update) => {
             if update.matched_count == 1 {
-                let updated_teacher_info = db.get_teacher(&id).await;
-                return match updated_teacher_info {
-                    Ok(teacher) => HttpResponse::Ok().json(teacher),
-                    Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
-                };
+                let updated_teacher_info = db.get_teacher(&id, false).await;
+                match updated_teacher_info {
+                    Ok(Some(teacher)) => HttpResponse::Ok()
+                        .insert_header(etag::entity_tag(teacher.version))
+                        .json(teacher),
+                    Ok(None) => HttpResponse::NotFound().body("No teacher found with specified ID"),
+                    Err(err) => internal_server_error(err),
+                }
+            } else if expected_version.is_some()
+                && matches!(db.get_teacher(&id, false).await, Ok(Some(_)))
+            {
+                etag::precondition_failed()
+            } else {
+                HttpResponse::NotFound().body("No teacher found with specified ID")
+            }
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    patch,
+    path = "/api/v1/teachers/{id}",
+    tag = "teachers",
+    request_body(
+        content = Object,
+        content_type = "application/merge-patch+json",
+        description = "RFC 7396 merge patch; id, version, attachments and last_login_* are immutable, including inside embedded records"
+    ),
+    params(("id" = String, Path, description = "Teacher id"), ("If-Match" = Option<String>, Header, description = "Version from a previous ETag; 412 if stale"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Teacher patched", body = Teacher, headers(("ETag" = String, description = "Current version of the teacher"))),
+        (status = 404, description = "No teacher found with specified ID", body = String),
+        (status = 412, description = "Record was modified by another request", body = String),
+        (status = 422, description = "Validation errors on the fields the patch sets, or that it newly breaks; a value stored before it had to be valid is left alone", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn patch_teacher(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    patch_body: Json<Value>,
+    ExpectedVersion(expected_version): ExpectedVersion,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    let current_teacher = match db.get_teacher(&id, false).await {
+        Ok(Some(teacher)) => teacher,
+        Ok(None) => return HttpResponse::NotFound().body("No teacher found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    if expected_version.is_some_and(|version| version != current_teacher.version) {
+        return etag::precondition_failed();
+    }
+    let (mut patched_teacher, fields) = match merge_patch::apply(&current_teacher, &patch_body) {
+        Ok(patched) => patched,
+        Err(err) => return HttpResponse::UnprocessableEntity().body(err),
+    };
+    if let Err(errors) = patched_teacher.validate() {
+        let current = current_teacher.validate().err();
+        let fields = validation::patch_field_errors(&errors, current.as_ref(), &patch_body);
+        if !fields.is_empty() {
+            return HttpResponse::UnprocessableEntity().json(fields);
+        }
+    }
+    if fields.is_empty() {
+        return HttpResponse::Ok()
+            .insert_header(etag::entity_tag(patched_teacher.version))
+            .json(patched_teacher);
+    }
+    let patch_result = db
+        .patch_teacher(&id, fields, current_teacher.version, &actor)
+        .await;
+    match patch_result {
+        Ok(update) => {
+            if update.matched_count == 1 {
+                patched_teacher.version += 1;
+                HttpResponse::Ok()
+                    .insert_header(etag::entity_tag(patched_teacher.version))
+                    .json(patched_teacher)
+            } else {
+                etag::precondition_failed()
+            }
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/teachers/{id}",
+    tag = "teachers",
+    params(("id" = String, Path, description = "Teacher id"), ("If-Match" = Option<String>, Header, description = "Version from a previous ETag; 412 if stale"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Teacher archived", body = String),
+        (status = 404, description = "No teacher found with specified ID", body = String),
+        (status = 412, description = "Record was modified by another request", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn delete_teacher(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    ExpectedVersion(expected_version): ExpectedVersion,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    };
+    let result = db.delete_teacher(&id, expected_version, &actor).await;
+    match result {
+        Ok(res) => {
+            if res.modified_count == 1 {
+                HttpResponse::Ok().json("Teacher successfully deleted")
+            } else if expected_version.is_some()
+                && matches!(db.get_teacher(&id, false).await, Ok(Some(_)))
+            {
+                etag::precondition_failed()
             } else {
-                return HttpResponse::NotFound().body("No teacher found with specified ID");
+                HttpResponse::NotFound().json("Teacher with specified ID not found")
             }
         }
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Err(err) => internal_server_error(err),
     }
 }
 
-#[delete("/teacher/{id}")]
-pub async fn delete_teacher(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+#[utoipa::path(
+    post,
+    path = "/api/v1/teachers/{id}/restore",
+    tag = "teachers",
+    params(("id" = String, Path, description = "Teacher id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Teacher restored", body = Teacher, headers(("ETag" = String, description = "Current version of the teacher"))),
+        (status = 404, description = "No archived teacher found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn restore_teacher(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    actor: AuditContext,
+) -> HttpResponse {
     let id = path.into_inner();
-    if id.is_empty() {
+    if validators::object_id(&id).is_err() {
         return HttpResponse::BadRequest().body("invalid ID");
     };
-    let result = db.delete_teacher(&id).await;
+    let result = db.restore_teacher(&id, &actor).await;
     match result {
         Ok(res) => {
-            if res.deleted_count == 1 {
-                return HttpResponse::Ok().json("Teacher successfully deleted");
+            if res.modified_count == 1 {
+                let restored_teacher_info = db.get_teacher(&id, false).await;
+                match restored_teacher_info {
+                    Ok(Some(teacher)) => HttpResponse::Ok()
+                        .insert_header(etag::entity_tag(teacher.version))
+                        .json(teacher),
+                    Ok(None) => HttpResponse::NotFound().body("No teacher found with specified ID"),
+                    Err(err) => internal_server_error(err),
+                }
             } else {
-                return HttpResponse::NotFound().json("Teacher with specified ID not found");
+                HttpResponse::NotFound().body("No archived teacher found with specified ID")
             }
         }
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Err(err) => internal_server_error(err),
     }
 }
 
-#[get("/teachers")]
-pub async fn get_all_teachers(db: Data<MongoRepo>) -> HttpResponse {
-    let teachers = db.get_all_teachers().await;
+#[utoipa::path(
+    get,
+    path = "/api/v1/teachers",
+    tag = "teachers",
+    params(("include_deleted" = Option<bool>, Query, description = "Include archived records (admin only)")),
+    responses(
+        (status = 200, description = "All teachers", body = [Teacher]),
+        (status = 403, description = "include_deleted requires admin access", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security((), ("admin_token" = []))
+)]
+pub async fn get_all_teachers(
+    db: Data<MongoRepo>,
+    IncludeDeleted(include_deleted): IncludeDeleted,
+) -> HttpResponse {
+    let teachers = db.get_all_teachers(include_deleted).await;
     match teachers {
         Ok(teacher) => HttpResponse::Ok().json(teacher),
-        Err(err) => HttpResponse::InternalServerError().body(err.to_string()),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/teachers")
+            .route(web::get().to(get_all_teachers))
+            .route(web::post().to(create_teacher)),
+    )
+    .service(
+        web::resource("/teachers/{id}")
+            .route(web::get().to(get_teacher))
+            .route(web::put().to(update_teacher))
+            .route(web::patch().to(patch_teacher))
+            .route(web::delete().to(delete_teacher)),
+    )
+    .service(web::resource("/teachers/{id}/restore").route(web::post().to(restore_teacher)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::{self, person};
+    use actix_web::{
+        http::{header, StatusCode},
+        test::{call_and_read_body_json, call_service, init_service, read_body_json, TestRequest},
+        App,
+    };
+    use serde_json::{json, Value};
+
+    #[actix_web::test]
+    async fn invalid_fields_are_rejected_before_anything_is_stored() {
+        let app =
+            init_service(App::new().configure(testing::app(testing::offline_repo().await))).await;
+        let mut teacher = person("not an email");
+        teacher["phone"] = json!("555-0100");
+        teacher["fname"] = json!("");
+        let request = TestRequest::post()
+            .uri("/api/v1/teachers")
+            .set_json(&teacher)
+            .to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let errors: Value = read_body_json(response).await;
+        assert_eq!(
+            errors,
+            json!({
+                "email": ["must be a valid email address"],
+                "fname": ["must not be empty"],
+                "phone": ["must be an E.164 phone number"],
+            })
+        );
+    }
+
+    #[actix_web::test]
+    async fn if_match_without_a_version_is_refused() {
+        let app =
+            init_service(App::new().configure(testing::app(testing::offline_repo().await))).await;
+        for if_match in ["W/\"1\"", "\"one\"", "1"] {
+            let request = TestRequest::put()
+                .uri("/api/v1/teachers/63455a1b5f3b8e2a4c1d9e01")
+                .insert_header((header::IF_MATCH, if_match))
+                .set_json(person("ada@school.example"))
+                .to_request();
+            let response = call_service(&app, request).await;
+            assert_eq!(
+                response.status(),
+                StatusCode::PRECONDITION_FAILED,
+                "{}",
+                if_match
+            );
+        }
+    }
+
+    #[actix_web::test]
+    #[ignore = "needs MongoDB at SM_TEST_MONGODB_URI"]
+    async fn writes_with_a_stale_if_match_conflict() {
+        let db = testing::mongodb_repo().await;
+        let app = init_service(App::new().configure(testing::app(db.clone()))).await;
+        let request = TestRequest::post()
+            .uri("/api/v1/teachers")
+            .set_json(person("ada@school.example"))
+            .to_request();
+        let id = testing::inserted_id(&call_and_read_body_json(&app, request).await);
+        let uri = format!("/api/v1/teachers/{}", id);
+
+        let request = TestRequest::get().uri(&uri).to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.headers().get(header::ETAG).unwrap(), "\"1\"");
+
+        let mut renamed = person("ada@school.example");
+        renamed["fname"] = json!("Augusta");
+        let request = TestRequest::put()
+            .uri(&uri)
+            .insert_header((header::IF_MATCH, "\"1\""))
+            .set_json(&renamed)
+            .to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(header::ETAG).unwrap(), "\"2\"");
+
+        // Both writes were based on version 1, which is gone.
+        let request = TestRequest::put()
+            .uri(&uri)
+            .insert_header((header::IF_MATCH, "\"1\""))
+            .set_json(person("ada@school.example"))
+            .to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let request = TestRequest::patch()
+            .uri(&uri)
+            .insert_header((header::IF_MATCH, "\"1\""))
+            .set_json(json!({"lname": "King"}))
+            .to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        let request = TestRequest::delete()
+            .uri(&uri)
+            .insert_header((header::IF_MATCH, "\"1\""))
+            .to_request();
+        let response = call_service(&app, request).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+        let request = TestRequest::get().uri(&uri).to_request();
+        let teacher: Value = call_and_read_body_json(&app, request).await;
+        assert_eq!(teacher["fname"], "Augusta");
+        assert_eq!(teacher["lname"], "Lovelace");
+        testing::drop_database(&db).await;
     }
 }
@@ -0,0 +1,102 @@
+//! What the tests share: the app as `main` wires it and a database to run
+//! it against. Those that need MongoDB are ignored by default; see the
+//! README for running them. Each gets a database of its own, dropped when
+//! it passes.
+
+use super::{auth::AdminToken, v1};
+use crate::{
+    config::{DatabaseSettings, NotificationSettings, SchoolSettings, StorageSettings},
+    notifications::Notifier,
+    repository::mongodb_repo::MongoRepo,
+    storage::{self, BlobStore},
+};
+use actix_web::web::{Data, ServiceConfig};
+use serde_json::{json, Value};
+use std::env;
+use uuid::Uuid;
+
+pub const ADMIN_TOKEN: &str = "test-admin-token";
+
+/// The `Authorization` header of a caller holding the admin token.
+pub fn admin() -> (&'static str, String) {
+    ("Authorization", format!("Bearer {}", ADMIN_TOKEN))
+}
+
+async fn repo(uri: String) -> MongoRepo {
+    let settings = DatabaseSettings {
+        uri,
+        name: format!("school_manager_test_{}", Uuid::new_v4().simple()),
+        connect_timeout_secs: 2,
+        server_selection_timeout_secs: 2,
+        ..DatabaseSettings::default()
+    };
+    MongoRepo::init(&settings)
+        .await
+        .expect("test database settings are valid")
+}
+
+/// A repository nothing listens behind, for requests that are answered
+/// before the database is asked.
+pub async fn offline_repo() -> Data<MongoRepo> {
+    Data::new(repo("mongodb://127.0.0.1:9".to_string()).await)
+}
+
+/// A fresh database on the server at `SM_TEST_MONGODB_URI`, indexed as in
+/// production.
+pub async fn mongodb_repo() -> Data<MongoRepo> {
+    let uri = env::var("SM_TEST_MONGODB_URI")
+        .expect("SM_TEST_MONGODB_URI must name the MongoDB server to test against");
+    let db = repo(uri).await;
+    let failed = db.ensure_indexes().await;
+    assert!(failed.is_empty(), "indexes not created: {:?}", failed);
+    Data::new(db)
+}
+
+pub async fn drop_database(db: &MongoRepo) {
+    db.database()
+        .drop(None)
+        .await
+        .expect("test database can be dropped");
+}
+
+/// The version 1 API with the app data `main` gives it.
+pub fn app(db: Data<MongoRepo>) -> impl FnOnce(&mut ServiceConfig) {
+    move |cfg| {
+        let storage_settings = StorageSettings::default();
+        let blob_store: Data<dyn BlobStore> =
+            Data::from(storage::from_settings(&storage_settings, db.database()));
+        let school = SchoolSettings::default();
+        let notifier = Notifier::new(&NotificationSettings::default(), &school, &[], false);
+        cfg.app_data(db)
+            .app_data(Data::new(AdminToken(ADMIN_TOKEN.to_string())))
+            .app_data(Data::new(school))
+            .app_data(blob_store)
+            .app_data(Data::new(storage_settings))
+            .app_data(Data::new(notifier))
+            .configure(v1::config);
+    }
+}
+
+/// A valid body for creating a teacher or parent with `email`.
+pub fn person(email: &str) -> Value {
+    json!({
+        "email": email,
+        "password": "secret",
+        "fname": "Ada",
+        "lname": "Lovelace",
+        "dob": {"$date": {"$numberLong": "946684800000"}},
+        "phone": "+15555550100",
+        "mobile": "+15555550101",
+        "status": true,
+        "last_login_date": {"$date": {"$numberLong": "946684800000"}},
+        "last_login_ip": ""
+    })
+}
+
+/// The id in a create response, `{"insertedId": {"$oid": ...}}`.
+pub fn inserted_id(created: &Value) -> String {
+    created["insertedId"]["$oid"]
+        .as_str()
+        .expect("create responses carry the inserted id")
+        .to_string()
+}
@@ -0,0 +1,653 @@
+use super::{auth::Admin, errors::internal_server_error, staffing_api, validation};
+use crate::repository::Error;
+use crate::{
+    jobs::generate_timetable::{self, Generation, Lesson},
+    models::{
+        audit_entry::AuditContext,
+        classroom::Classroom,
+        course::Course,
+        period::{Period, Weekday},
+        teacher::Teacher,
+        timetable_entry::TimetableEntry,
+        timetable_job::{JobStatus, TimetableJob},
+        validators,
+    },
+    repository::mongodb_repo::MongoRepo,
+    scheduler::{Booking, Problem, Requirement, Room, Slot},
+};
+use actix_web::{
+    http::header,
+    rt,
+    web::{self, Data, Json, Path},
+    HttpResponse,
+};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+/// A timetable grouped by day, each day's entries in start-time order.
+type Timetable = BTreeMap<Weekday, Vec<TimetableEntry>>;
+
+fn by_day(entries: Vec<TimetableEntry>) -> Timetable {
+    let mut timetable = Timetable::new();
+    for entry in entries {
+        timetable.entry(entry.period.day).or_default().push(entry);
+    }
+    timetable
+}
+
+#[derive(Deserialize, Validate, ToSchema)]
+pub struct TimetableEntryRequest {
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    classroom_id: String,
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    course_id: String,
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    teacher_id: String,
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    period_id: String,
+    #[validate(length(min = 1, message = "must not be empty"))]
+    room: String,
+}
+
+/// What an existing entry shares with the one being scheduled.
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Clash {
+    Teacher,
+    Room,
+    Classroom,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TimetableConflict {
+    entry: TimetableEntry,
+    clashes: Vec<Clash>,
+}
+
+fn conflict(new_entry: &TimetableEntry, existing: TimetableEntry) -> TimetableConflict {
+    let mut clashes = Vec::new();
+    if existing.teacher.id == new_entry.teacher.id {
+        clashes.push(Clash::Teacher);
+    }
+    if existing.room == new_entry.room {
+        clashes.push(Clash::Room);
+    }
+    if existing.classroom.id == new_entry.classroom.id {
+        clashes.push(Clash::Classroom);
+    }
+    TimetableConflict {
+        entry: existing,
+        clashes,
+    }
+}
+
+async fn find_conflicts(
+    db: &MongoRepo,
+    entry: &TimetableEntry,
+) -> Result<Vec<TimetableConflict>, Error> {
+    let existing = db.find_timetable_conflicts(entry).await?;
+    Ok(existing
+        .into_iter()
+        .map(|existing| conflict(entry, existing))
+        .collect())
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/periods",
+    tag = "timetable",
+    request_body = Period,
+    params(("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Period created; returns the inserted id", body = Object),
+        (status = 409, description = "The day already has a period with that number", body = Period),
+        (status = 422, description = "Validation errors per field", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn create_period(
+    db: Data<MongoRepo>,
+    new_period: Json<Period>,
+    actor: AuditContext,
+) -> HttpResponse {
+    if let Err(errors) = new_period.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
+    match db
+        .get_period_by_slot(new_period.day, new_period.number)
+        .await
+    {
+        Ok(Some(existing)) => return HttpResponse::Conflict().json(existing),
+        Ok(None) => {}
+        Err(err) => return internal_server_error(err),
+    }
+    let data = Period {
+        id: None,
+        day: new_period.day,
+        number: new_period.number,
+        start_time: new_period.start_time.to_string(),
+        end_time: new_period.end_time.to_string(),
+    };
+    let period_detail = db.create_period(data, &actor).await;
+    match period_detail {
+        Ok(period) => HttpResponse::Ok().json(period),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/periods",
+    tag = "timetable",
+    responses(
+        (status = 200, description = "Periods by day, in start-time order", body = BTreeMap<String, Vec<Period>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_all_periods(db: Data<MongoRepo>) -> HttpResponse {
+    let periods = db.get_all_periods().await;
+    match periods {
+        Ok(periods) => {
+            let mut week: BTreeMap<Weekday, Vec<Period>> = BTreeMap::new();
+            for period in periods {
+                week.entry(period.day).or_default().push(period);
+            }
+            HttpResponse::Ok().json(week)
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/timetable",
+    tag = "timetable",
+    request_body = TimetableEntryRequest,
+    params(("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Entry scheduled; returns the inserted id", body = Object),
+        (status = 404, description = "Classroom, course, teacher or period not found", body = String),
+        (status = 409, description = "Teacher, room or classroom is already booked in an overlapping period", body = Vec<TimetableConflict>),
+        (status = 422, description = "Validation errors per field, including a teacher not qualified for the course", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn create_timetable_entry(
+    db: Data<MongoRepo>,
+    request: Json<TimetableEntryRequest>,
+    actor: AuditContext,
+) -> HttpResponse {
+    let mut request = request.into_inner();
+    request.room = request.room.trim().to_string();
+    if let Err(errors) = request.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
+    let classroom = match db.get_classroom(&request.classroom_id).await {
+        Ok(Some(classroom)) => classroom,
+        Ok(None) => return HttpResponse::NotFound().body("No classroom found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    let course = match db.get_course(&request.course_id).await {
+        Ok(Some(course)) => course,
+        Ok(None) => return HttpResponse::NotFound().body("No course found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    let teacher = match db.get_teacher(&request.teacher_id, false).await {
+        Ok(Some(teacher)) => teacher,
+        Ok(None) => return HttpResponse::NotFound().body("No teacher found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    let period = match db.get_period(&request.period_id).await {
+        Ok(Some(period)) => period,
+        Ok(None) => return HttpResponse::NotFound().body("No period found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    };
+    if let Err(response) =
+        staffing_api::require_qualification(&db, &request.teacher_id, &request.course_id).await
+    {
+        return response;
+    }
+    let data = TimetableEntry {
+        id: None,
+        classroom,
+        course,
+        teacher,
+        room: request.room,
+        period,
+    };
+    match find_conflicts(&db, &data).await {
+        Ok(conflicts) if !conflicts.is_empty() => {
+            return HttpResponse::Conflict().json(conflicts);
+        }
+        Ok(_) => {}
+        Err(err) => return internal_server_error(err),
+    }
+    let entry_detail = db.create_timetable_entry(data.clone(), &actor).await;
+    match entry_detail {
+        Ok(Some(entry)) => HttpResponse::Ok().json(entry),
+        // Booked by a concurrent request since the check above.
+        Ok(None) => match find_conflicts(&db, &data).await {
+            Ok(conflicts) => HttpResponse::Conflict().json(conflicts),
+            Err(err) => internal_server_error(err),
+        },
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/api/v1/timetable/{id}",
+    tag = "timetable",
+    params(("id" = String, Path, description = "Timetable entry id"), ("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 200, description = "Entry removed from the timetable", body = String),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No timetable entry found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn delete_timetable_entry(
+    db: Data<MongoRepo>,
+    path: Path<String>,
+    actor: AuditContext,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    let result = db.delete_timetable_entry(&id, &actor).await;
+    match result {
+        Ok(res) => {
+            if res.deleted_count == 1 {
+                HttpResponse::Ok().json("Timetable entry successfully deleted")
+            } else {
+                HttpResponse::NotFound().json("Timetable entry with specified ID not found")
+            }
+        }
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/classrooms/{id}/timetable",
+    tag = "timetable",
+    params(("id" = String, Path, description = "Classroom id")),
+    responses(
+        (status = 200, description = "The classroom's week by day", body = BTreeMap<String, Vec<TimetableEntry>>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No classroom found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_classroom_timetable(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.get_classroom(&id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("No classroom found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    }
+    match db.get_timetable_for_classroom(&id).await {
+        Ok(entries) => HttpResponse::Ok().json(by_day(entries)),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/teachers/{id}/timetable",
+    tag = "timetable",
+    params(("id" = String, Path, description = "Teacher id")),
+    responses(
+        (status = 200, description = "The teacher's week by day", body = BTreeMap<String, Vec<TimetableEntry>>),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 404, description = "No teacher found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    )
+)]
+pub async fn get_teacher_timetable(db: Data<MongoRepo>, path: Path<String>) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.get_teacher(&id, false).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return HttpResponse::NotFound().body("No teacher found with specified ID"),
+        Err(err) => return internal_server_error(err),
+    }
+    match db.get_timetable_for_teacher(&id).await {
+        Ok(entries) => HttpResponse::Ok().json(by_day(entries)),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct CourseRequirement {
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    classroom_id: String,
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    course_id: String,
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    teacher_id: String,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    periods_per_week: u32,
+}
+
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct TeacherAvailability {
+    #[validate(custom(function = "validators::object_id", message = "must be a valid id"))]
+    teacher_id: String,
+    #[validate(custom(function = "validators::object_ids", message = "must be valid ids"))]
+    unavailable_period_ids: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Validate, ToSchema)]
+pub struct RoomCapacity {
+    #[validate(length(min = 1, message = "must not be empty"))]
+    name: String,
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    capacity: u32,
+}
+
+/// Teachers are available in every period unless listed otherwise. Entries
+/// of other classrooms already in the timetable are kept and worked around.
+#[derive(Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "distinct_rooms", skip_on_field_errors = false))]
+pub struct GenerateTimetableRequest {
+    #[validate(length(min = 1, message = "must not be empty"))]
+    #[validate]
+    requirements: Vec<CourseRequirement>,
+    #[serde(default)]
+    #[validate]
+    teacher_availability: Vec<TeacherAvailability>,
+    #[validate(length(min = 1, message = "must not be empty"))]
+    #[validate]
+    rooms: Vec<RoomCapacity>,
+    /// Propose a timetable in the job without replacing the stored one
+    #[serde(default)]
+    dry_run: bool,
+}
+
+fn distinct_rooms(request: &GenerateTimetableRequest) -> Result<(), ValidationError> {
+    let mut names = HashSet::new();
+    if request
+        .rooms
+        .iter()
+        .all(|room| names.insert(room.name.trim()))
+    {
+        return Ok(());
+    }
+    let mut error = ValidationError::new("duplicate_room");
+    error.message = Some("room names must be distinct".into());
+    Err(error)
+}
+
+#[utoipa::path(
+    post,
+    path = "/api/v1/timetable/generate",
+    tag = "timetable",
+    request_body = GenerateTimetableRequest,
+    params(("X-Actor" = Option<String>, Header, description = "Who the caller says they are; audited as a claim, not as the actor")),
+    responses(
+        (status = 202, description = "Generation queued; poll the job at the Location header", body = TimetableJob, headers(("Location" = String, description = "Where to poll the job"))),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "Classroom, course, teacher or period not found", body = String),
+        (status = 422, description = "Validation errors per field, including a teacher not qualified for a course", body = BTreeMap<String, Vec<String>>),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn generate_timetable(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    request: Json<GenerateTimetableRequest>,
+    actor: AuditContext,
+) -> HttpResponse {
+    if let Err(errors) = request.validate() {
+        return validation::unprocessable_entity(&errors);
+    }
+    let mut periods = match db.get_all_periods().await {
+        Ok(periods) => periods,
+        Err(err) => return internal_server_error(err),
+    };
+    if periods.is_empty() {
+        let errors = BTreeMap::from([("periods", ["no periods are defined yet"])]);
+        return HttpResponse::UnprocessableEntity().json(errors);
+    }
+    periods.sort_by_key(|period| period.day);
+    let slots: Vec<Slot> = periods
+        .iter()
+        .map(|period| Slot {
+            day: period.day,
+            start_time: period.start_time.clone(),
+            end_time: period.end_time.clone(),
+        })
+        .collect();
+    let slot_of = |id: &ObjectId| periods.iter().position(|period| period.id == Some(*id));
+
+    let mut classrooms: HashMap<String, Classroom> = HashMap::new();
+    let mut courses: HashMap<String, Course> = HashMap::new();
+    let mut teachers: HashMap<String, Teacher> = HashMap::new();
+    for requirement in &request.requirements {
+        if !classrooms.contains_key(&requirement.classroom_id) {
+            match db.get_classroom(&requirement.classroom_id).await {
+                Ok(Some(classroom)) => {
+                    classrooms.insert(requirement.classroom_id.clone(), classroom);
+                }
+                Ok(None) => {
+                    return HttpResponse::NotFound().body("No classroom found with specified ID")
+                }
+                Err(err) => return internal_server_error(err),
+            }
+        }
+        if !courses.contains_key(&requirement.course_id) {
+            match db.get_course(&requirement.course_id).await {
+                Ok(Some(course)) => {
+                    courses.insert(requirement.course_id.clone(), course);
+                }
+                Ok(None) => {
+                    return HttpResponse::NotFound().body("No course found with specified ID")
+                }
+                Err(err) => return internal_server_error(err),
+            }
+        }
+        if !teachers.contains_key(&requirement.teacher_id) {
+            match db.get_teacher(&requirement.teacher_id, false).await {
+                Ok(Some(teacher)) => {
+                    teachers.insert(requirement.teacher_id.clone(), teacher);
+                }
+                Ok(None) => {
+                    return HttpResponse::NotFound().body("No teacher found with specified ID")
+                }
+                Err(err) => return internal_server_error(err),
+            }
+        }
+    }
+
+    for (index, requirement) in request.requirements.iter().enumerate() {
+        match db
+            .get_qualification(&requirement.teacher_id, &requirement.course_id)
+            .await
+        {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                let field = format!("requirements[{}].teacher_id", index);
+                let errors = BTreeMap::from([(field, ["is not qualified to teach this course"])]);
+                return HttpResponse::UnprocessableEntity().json(errors);
+            }
+            Err(err) => return internal_server_error(err),
+        }
+    }
+
+    let classroom_ids: Vec<ObjectId> = classrooms.values().filter_map(|c| c.id).collect();
+    let mut class_sizes: HashMap<ObjectId, u32> = HashMap::new();
+    match db.get_enrollments_for_classrooms(&classroom_ids).await {
+        Ok(enrollments) => {
+            for enrollment in enrollments {
+                if let Some(id) = enrollment.classroom.id {
+                    *class_sizes.entry(id).or_default() += 1;
+                }
+            }
+        }
+        Err(err) => return internal_server_error(err),
+    }
+
+    let mut unavailable: HashMap<ObjectId, HashSet<usize>> = HashMap::new();
+    for availability in &request.teacher_availability {
+        let teacher_id = ObjectId::parse_str(&availability.teacher_id).unwrap();
+        for period_id in &availability.unavailable_period_ids {
+            match slot_of(&ObjectId::parse_str(period_id).unwrap()) {
+                Some(slot) => {
+                    unavailable.entry(teacher_id).or_default().insert(slot);
+                }
+                None => return HttpResponse::NotFound().body("No period found with specified ID"),
+            }
+        }
+    }
+
+    // Entries of the classrooms being regenerated are replaced, not kept.
+    let fixed = match db.get_all_timetable_entries().await {
+        Ok(entries) => entries
+            .into_iter()
+            .filter(|entry| {
+                entry
+                    .classroom
+                    .id
+                    .is_none_or(|id| !classroom_ids.contains(&id))
+            })
+            .filter_map(|entry| {
+                Some(Booking {
+                    slot: slot_of(&entry.period.id?)?,
+                    classroom_id: entry.classroom.id?,
+                    teacher_id: entry.teacher.id?,
+                    room: entry.room,
+                })
+            })
+            .collect(),
+        Err(err) => return internal_server_error(err),
+    };
+
+    let mut requirements = Vec::new();
+    let mut lessons = Vec::new();
+    for requirement in &request.requirements {
+        let classroom = classrooms[&requirement.classroom_id].clone();
+        let course = courses[&requirement.course_id].clone();
+        let teacher = teachers[&requirement.teacher_id].clone();
+        let classroom_id = classroom.id.unwrap_or_default();
+        requirements.push(Requirement {
+            classroom_id,
+            classroom_name: format!("{} {}", classroom.year, classroom.section),
+            course_name: course.name.clone(),
+            teacher_id: teacher.id.unwrap_or_default(),
+            teacher_name: format!("{} {}", teacher.fname, teacher.lname),
+            periods_per_week: requirement.periods_per_week,
+            class_size: class_sizes.get(&classroom_id).copied().unwrap_or(0),
+        });
+        lessons.push(Lesson {
+            classroom,
+            course,
+            teacher,
+        });
+    }
+    let problem = Problem {
+        slots,
+        rooms: request
+            .rooms
+            .iter()
+            .map(|room| Room {
+                name: room.name.trim().to_string(),
+                capacity: room.capacity,
+            })
+            .collect(),
+        requirements,
+        unavailable,
+        fixed,
+    };
+
+    let mut job = TimetableJob {
+        id: None,
+        status: JobStatus::Queued,
+        dry_run: request.dry_run,
+        requested_by: actor.actor.clone(),
+        total: problem.lessons() as i64,
+        placed: 0,
+        problems: Vec::new(),
+        entries: Vec::new(),
+        created_at: DateTime::now(),
+        finished_at: None,
+    };
+    let job_id = match db.create_timetable_job(job.clone()).await {
+        Ok(inserted) => match inserted.inserted_id.as_object_id() {
+            Some(id) => id,
+            None => return internal_server_error("timetable job stored without an id"),
+        },
+        Err(err) => return internal_server_error(err),
+    };
+    job.id = Some(job_id);
+    let generation = Generation {
+        job_id,
+        dry_run: request.dry_run,
+        problem,
+        lessons,
+        periods,
+    };
+    rt::spawn(generate_timetable::run(db, generation, actor));
+    HttpResponse::Accepted()
+        .insert_header((
+            header::LOCATION,
+            format!("/api/v1/timetable/jobs/{}", job_id.to_hex()),
+        ))
+        .json(job)
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/timetable/jobs/{id}",
+    tag = "timetable",
+    params(("id" = String, Path, description = "Timetable job id")),
+    responses(
+        (status = 200, description = "Status and progress of the generator", body = TimetableJob),
+        (status = 400, description = "Invalid id", body = String),
+        (status = 403, description = "Admin access required", body = String),
+        (status = 404, description = "No timetable job found with specified ID", body = String),
+        (status = 500, description = "Database error", body = String),
+    ),
+    security(("admin_token" = []))
+)]
+pub async fn get_timetable_job(
+    db: Data<MongoRepo>,
+    _admin: Admin,
+    path: Path<String>,
+) -> HttpResponse {
+    let id = path.into_inner();
+    if validators::object_id(&id).is_err() {
+        return HttpResponse::BadRequest().body("invalid ID");
+    }
+    match db.get_timetable_job(&id).await {
+        Ok(Some(job)) => HttpResponse::Ok().json(job),
+        Ok(None) => HttpResponse::NotFound().body("No timetable job found with specified ID"),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/periods")
+            .route(web::get().to(get_all_periods))
+            .route(web::post().to(create_period)),
+    )
+    .service(web::resource("/timetable").route(web::post().to(create_timetable_entry)))
+    .service(web::resource("/timetable/generate").route(web::post().to(generate_timetable)))
+    .service(web::resource("/timetable/jobs/{id}").route(web::get().to(get_timetable_job)))
+    .service(web::resource("/timetable/{id}").route(web::delete().to(delete_timetable_entry)))
+    .service(
+        web::resource("/classrooms/{id}/timetable").route(web::get().to(get_classroom_timetable)),
+    )
+    .service(web::resource("/teachers/{id}/timetable").route(web::get().to(get_teacher_timetable)));
+}
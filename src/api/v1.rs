@@ -0,0 +1,30 @@
+use super::{
+    attachments_api, audit_api, billing_api, calendar_api, documents_api, export_api, homework_api,
+    import_api, messaging_api, notifications_api, oneroster_api, parents_api, staffing_api,
+    students_api, teachers_api, timetable_api,
+};
+use actix_web::web;
+
+/// Mounts the version 1 API under `/api/v1`. A later version gets its own
+/// module with its own scope, reusing whichever handlers did not change.
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::scope("/api/v1")
+            .configure(teachers_api::config)
+            .configure(parents_api::config)
+            .configure(students_api::config)
+            .configure(audit_api::config)
+            .configure(import_api::config)
+            .configure(export_api::config)
+            .configure(oneroster_api::config)
+            .configure(timetable_api::config)
+            .configure(calendar_api::config)
+            .configure(staffing_api::config)
+            .configure(homework_api::config)
+            .configure(attachments_api::config)
+            .configure(billing_api::config)
+            .configure(documents_api::config)
+            .configure(notifications_api::config)
+            .configure(messaging_api::config),
+    );
+}
@@ -0,0 +1,69 @@
+use actix_web::HttpResponse;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use validator::{ValidationErrors, ValidationErrorsKind};
+
+/// Responds 422 with a map from field path (e.g. `parent.email`) to messages.
+pub fn unprocessable_entity(errors: &ValidationErrors) -> HttpResponse {
+    HttpResponse::UnprocessableEntity().json(field_errors(errors))
+}
+
+/// Responds 422 with a single message for one field.
+pub fn field_error(field: &str, message: impl Into<String>) -> HttpResponse {
+    HttpResponse::UnprocessableEntity().json(BTreeMap::from([(field, [message.into()])]))
+}
+
+pub fn field_errors(errors: &ValidationErrors) -> BTreeMap<String, Vec<String>> {
+    let mut fields = BTreeMap::new();
+    collect(errors, "", &mut fields);
+    fields
+}
+
+/// The errors a merge patch is answerable for: those on fields it sets, and
+/// any the record did not already have before it was patched. A value that
+/// was stored before it had to be valid does not block patching the rest.
+pub fn patch_field_errors(
+    patched: &ValidationErrors,
+    current: Option<&ValidationErrors>,
+    patch: &Value,
+) -> BTreeMap<String, Vec<String>> {
+    let existing = current.map(field_errors).unwrap_or_default();
+    let mut fields = field_errors(patched);
+    fields.retain(|path, messages| {
+        let top = path.split(['.', '[']).next().unwrap_or_default();
+        if patch.get(top).is_some() {
+            return true;
+        }
+        let before = existing.get(path);
+        messages.retain(|message| before.is_none_or(|before| !before.contains(message)));
+        !messages.is_empty()
+    });
+    fields
+}
+
+fn collect(errors: &ValidationErrors, prefix: &str, fields: &mut BTreeMap<String, Vec<String>>) {
+    for (field, kind) in errors.errors() {
+        let path = if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", prefix, field)
+        };
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                fields
+                    .entry(path)
+                    .or_default()
+                    .extend(errors.iter().map(|error| match &error.message {
+                        Some(message) => message.to_string(),
+                        None => error.code.to_string(),
+                    }))
+            }
+            ValidationErrorsKind::Struct(nested) => collect(nested, &path, fields),
+            ValidationErrorsKind::List(items) => {
+                for (index, nested) in items {
+                    collect(nested, &format!("{}[{}]", path, index), fields);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,666 @@
+use dotenv::dotenv;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{env, fmt, fs, time::Duration};
+use tracing_subscriber::EnvFilter;
+
+/// Env vars of the form `SM__SECTION__KEY` override the matching setting,
+/// e.g. `SM__SERVER__PORT=9000`.
+const ENV_PREFIX: &str = "SM__";
+const DEFAULT_CONFIG_FILE: &str = "config.json";
+
+/// Variables the service read before typed configuration existed.
+const LEGACY_ENV: [(&str, &str); 3] = [
+    ("MONGOURI", "database.uri"),
+    ("ADMIN_TOKEN", "auth.admin_token"),
+    ("ARCHIVE_RETENTION_DAYS", "archive.retention_days"),
+];
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(String, std::io::Error),
+    Parse(String, serde_json::Error),
+    UnknownSetting(String),
+    InvalidValue(String, String),
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, err) => write!(f, "cannot read {}: {}", path, err),
+            ConfigError::Parse(source, err) => write!(f, "cannot parse {}: {}", source, err),
+            ConfigError::UnknownSetting(key) => write!(f, "unknown setting `{}`", key),
+            ConfigError::InvalidValue(key, value) => {
+                write!(f, "invalid value {:?} for `{}`", value, key)
+            }
+            ConfigError::Invalid(problems) => write!(f, "{}", problems.join("; ")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub server: ServerSettings,
+    pub database: DatabaseSettings,
+    pub auth: AuthSettings,
+    pub school: SchoolSettings,
+    pub archive: ArchiveSettings,
+    pub features: FeatureSettings,
+    pub logging: LoggingSettings,
+    pub storage: StorageSettings,
+    pub notifications: NotificationSettings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerSettings {
+    pub host: String,
+    pub port: u16,
+    /// Defaults to one worker per physical core when unset.
+    pub workers: Option<usize>,
+    pub request_timeout_secs: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseSettings {
+    pub uri: String,
+    pub name: String,
+    pub max_pool_size: Option<u32>,
+    pub connect_timeout_secs: u64,
+    pub server_selection_timeout_secs: u64,
+    /// How long `/readyz` waits for the database to answer a ping.
+    pub ping_timeout_ms: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSettings {
+    /// Bearer token granting administrative access; admin access is disabled
+    /// while empty.
+    pub admin_token: String,
+}
+
+/// How the school identifies itself to other systems.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchoolSettings {
+    pub name: String,
+    /// Stable identifier, used as the OneRoster org `sourcedId`.
+    pub code: String,
+    /// Course assignments are made per term, numbered from 1.
+    pub terms_per_year: i32,
+    /// Weekly teaching hours above which a teacher's workload is flagged.
+    pub max_weekly_teaching_hours: u32,
+    /// Share of a term grade that comes from homework; the rest is exams.
+    pub homework_weight_percent: u32,
+    /// ISO 4217 code that fees and payments are in.
+    pub currency: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ArchiveSettings {
+    pub retention_days: i64,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureSettings {
+    pub graphql: bool,
+    pub docs: bool,
+    pub legacy_routes: bool,
+    pub purge_archived: bool,
+    /// Queue and deliver notifications and send overdue-fee reminders.
+    pub notifications: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingSettings {
+    /// An `EnvFilter` directive such as `info` or `school_manager=debug,warn`.
+    pub level: String,
+    pub format: LogFormat,
+}
+
+/// Where uploaded files are kept and what may be uploaded.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageSettings {
+    pub backend: StorageBackend,
+    /// Directory the `local` backend writes under.
+    pub local_root: String,
+    /// Bucket the `gridfs` backend writes to, in the service's database.
+    pub gridfs_bucket: String,
+    pub max_file_bytes: usize,
+    /// Media types accepted for upload; `type/*` allows a whole family.
+    pub allowed_content_types: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StorageBackend {
+    Local,
+    GridFs,
+}
+
+/// How guardians are told about absences, exam results and overdue fees.
+/// Email and SMS are only offered once configured; the in-app inbox always
+/// is.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationSettings {
+    pub smtp: SmtpSettings,
+    pub sms: SmsSettings,
+    /// Delivery attempts before a notification is given up on.
+    pub max_attempts: u32,
+    /// Wait before the first retry; doubled after every further failure.
+    pub retry_delay_secs: u64,
+    /// How often the outbox is checked for notifications that are due.
+    pub poll_interval_secs: u64,
+    /// Reminders repeat this often for as long as an invoice is overdue.
+    pub overdue_reminder_days: i64,
+    pub templates: NotificationTemplates,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmtpSettings {
+    /// Email is disabled while empty.
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    /// Sent with AUTH PLAIN when not empty.
+    pub username: String,
+    pub password: String,
+    /// Address notifications are sent from.
+    pub from: String,
+    pub timeout_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Upgrade a plain connection with STARTTLS; the submission port, 587.
+    StartTls,
+    /// TLS from the start; port 465.
+    Tls,
+    /// Unencrypted, for a relay on the local network only; never with a
+    /// username, as the password would go in the clear.
+    None,
+}
+
+/// A gateway that takes `{"from", "to", "text"}` as JSON, with the key as
+/// a bearer token.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SmsSettings {
+    /// SMS is disabled while empty.
+    pub gateway_url: String,
+    pub api_key: String,
+    /// Sender id or number the gateway sends from.
+    pub sender: String,
+    pub timeout_secs: u64,
+}
+
+/// Message text per kind of notification, with `{placeholders}` filled in
+/// when one is queued.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationTemplates {
+    /// `{guardian}`, `{student}`, `{date}`, `{remark}`, `{school}`
+    pub absence: MessageTemplate,
+    /// `{guardian}`, `{student}`, `{exam}`, `{course}`, `{marks}`, `{school}`
+    pub exam_result: MessageTemplate,
+    /// `{guardian}`, `{student}`, `{invoice}`, `{balance}`, `{currency}`,
+    /// `{due_date}`, `{school}`
+    pub overdue_fee: MessageTemplate,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MessageTemplate {
+    /// Used for email; SMS and the inbox show the body only.
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Json,
+    Pretty,
+}
+
+impl Default for ServerSettings {
+    fn default() -> Self {
+        ServerSettings {
+            host: "127.0.0.1".to_string(),
+            port: 8080,
+            workers: None,
+            request_timeout_secs: 5,
+        }
+    }
+}
+
+impl Default for DatabaseSettings {
+    fn default() -> Self {
+        DatabaseSettings {
+            uri: String::new(),
+            name: "student_manager".to_string(),
+            max_pool_size: None,
+            connect_timeout_secs: 10,
+            server_selection_timeout_secs: 30,
+            ping_timeout_ms: 2000,
+        }
+    }
+}
+
+impl Default for SchoolSettings {
+    fn default() -> Self {
+        SchoolSettings {
+            name: "School".to_string(),
+            code: "school".to_string(),
+            terms_per_year: 3,
+            max_weekly_teaching_hours: 25,
+            homework_weight_percent: 30,
+            currency: "USD".to_string(),
+        }
+    }
+}
+
+impl Default for ArchiveSettings {
+    fn default() -> Self {
+        ArchiveSettings {
+            retention_days: 365,
+        }
+    }
+}
+
+impl Default for FeatureSettings {
+    fn default() -> Self {
+        FeatureSettings {
+            graphql: true,
+            docs: true,
+            legacy_routes: true,
+            purge_archived: true,
+            notifications: true,
+        }
+    }
+}
+
+impl Default for LoggingSettings {
+    fn default() -> Self {
+        LoggingSettings {
+            level: "info".to_string(),
+            format: LogFormat::Json,
+        }
+    }
+}
+
+impl Default for NotificationSettings {
+    fn default() -> Self {
+        NotificationSettings {
+            smtp: SmtpSettings::default(),
+            sms: SmsSettings::default(),
+            max_attempts: 6,
+            retry_delay_secs: 60,
+            poll_interval_secs: 10,
+            overdue_reminder_days: 7,
+            templates: NotificationTemplates::default(),
+        }
+    }
+}
+
+impl Default for SmtpSettings {
+    fn default() -> Self {
+        SmtpSettings {
+            host: String::new(),
+            port: 587,
+            security: SmtpSecurity::StartTls,
+            username: String::new(),
+            password: String::new(),
+            from: String::new(),
+            timeout_secs: 10,
+        }
+    }
+}
+
+impl Default for SmsSettings {
+    fn default() -> Self {
+        SmsSettings {
+            gateway_url: String::new(),
+            api_key: String::new(),
+            sender: String::new(),
+            timeout_secs: 10,
+        }
+    }
+}
+
+impl Default for NotificationTemplates {
+    fn default() -> Self {
+        let template = |subject: &str, body: &str| MessageTemplate {
+            subject: subject.to_string(),
+            body: body.to_string(),
+        };
+        NotificationTemplates {
+            absence: template(
+                "{student} was absent on {date}",
+                "Dear {guardian}, {student} was marked absent on {date}. {remark}",
+            ),
+            exam_result: template(
+                "New result for {student}: {exam}",
+                "Dear {guardian}, {student} scored {marks} in {course} ({exam}).",
+            ),
+            overdue_fee: template(
+                "Invoice {invoice} is overdue",
+                "Dear {guardian}, invoice {invoice} for {student} was due on {due_date}. \
+                 {currency} {balance} is still outstanding.",
+            ),
+        }
+    }
+}
+
+impl NotificationTemplates {
+    /// Each template by its kind's name, with the placeholders it may use.
+    pub fn all(&self) -> [(&'static str, &MessageTemplate, &'static [&'static str]); 3] {
+        [
+            (
+                "absence",
+                &self.absence,
+                &["guardian", "student", "date", "remark", "school"],
+            ),
+            (
+                "exam_result",
+                &self.exam_result,
+                &["guardian", "student", "exam", "course", "marks", "school"],
+            ),
+            (
+                "overdue_fee",
+                &self.overdue_fee,
+                &[
+                    "guardian", "student", "invoice", "balance", "currency", "due_date", "school",
+                ],
+            ),
+        ]
+    }
+}
+
+impl MessageTemplate {
+    /// Names between braces in the subject and body.
+    pub fn placeholders(&self) -> Vec<&str> {
+        [self.subject.as_str(), self.body.as_str()]
+            .into_iter()
+            .flat_map(|text| text.split('{').skip(1))
+            .filter_map(|rest| rest.split_once('}').map(|(name, _)| name))
+            .collect()
+    }
+
+    /// The subject and body with each `{name}` replaced by its value.
+    pub fn render(&self, values: &[(&str, String)]) -> (String, String) {
+        let fill = |text: &str| {
+            values.iter().fold(text.to_string(), |text, (name, value)| {
+                text.replace(&format!("{{{}}}", name), value)
+            })
+        };
+        (fill(&self.subject), fill(&self.body))
+    }
+}
+
+impl Default for StorageSettings {
+    fn default() -> Self {
+        StorageSettings {
+            backend: StorageBackend::Local,
+            local_root: "attachments".to_string(),
+            gridfs_bucket: "attachments".to_string(),
+            max_file_bytes: 8 * 1024 * 1024,
+            allowed_content_types: [
+                "image/png",
+                "image/jpeg",
+                "image/gif",
+                "image/webp",
+                "application/pdf",
+                "text/plain",
+                "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+                "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+            ]
+            .map(str::to_string)
+            .to_vec(),
+        }
+    }
+}
+
+impl StorageSettings {
+    /// Whether `content_type`, without parameters, may be uploaded.
+    pub fn allows(&self, content_type: &str) -> bool {
+        self.allowed_content_types
+            .iter()
+            .any(|allowed| match allowed.strip_suffix("/*") {
+                Some(family) => content_type
+                    .split_once('/')
+                    .is_some_and(|(kind, _)| kind.eq_ignore_ascii_case(family)),
+                None => allowed.eq_ignore_ascii_case(content_type),
+            })
+    }
+}
+
+impl ServerSettings {
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+}
+
+impl DatabaseSettings {
+    pub fn connect_timeout(&self) -> Duration {
+        Duration::from_secs(self.connect_timeout_secs)
+    }
+
+    pub fn server_selection_timeout(&self) -> Duration {
+        Duration::from_secs(self.server_selection_timeout_secs)
+    }
+
+    pub fn ping_timeout(&self) -> Duration {
+        Duration::from_millis(self.ping_timeout_ms)
+    }
+}
+
+impl Settings {
+    /// Layers defaults, the JSON file named by `CONFIG_FILE` (default
+    /// `config.json`, optional unless named explicitly), then environment
+    /// overrides, and validates the result.
+    pub fn load() -> Result<Self, ConfigError> {
+        dotenv().ok();
+        let mut tree = serde_json::to_value(Settings::default()).expect("defaults serialize");
+
+        let explicit = env::var("CONFIG_FILE").ok();
+        let path = explicit.clone().unwrap_or(DEFAULT_CONFIG_FILE.to_string());
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let file: Value = serde_json::from_str(&contents)
+                    .map_err(|err| ConfigError::Parse(path.clone(), err))?;
+                merge(&mut tree, file);
+            }
+            Err(err) if explicit.is_some() || err.kind() != std::io::ErrorKind::NotFound => {
+                return Err(ConfigError::Read(path, err));
+            }
+            Err(_) => {}
+        }
+
+        for (var, key) in LEGACY_ENV {
+            if let Ok(value) = env::var(var) {
+                set(&mut tree, key, &value)?;
+            }
+        }
+        for (var, value) in env::vars() {
+            if let Some(key) = var.strip_prefix(ENV_PREFIX) {
+                let key = key.to_lowercase().replace("__", ".");
+                set(&mut tree, &key, &value)?;
+            }
+        }
+
+        let settings: Settings =
+            serde_json::from_value(tree).map_err(|err| ConfigError::Parse(path, err))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+        if self.server.host.is_empty() {
+            problems.push("server.host must not be empty".to_string());
+        }
+        if self.server.port == 0 {
+            problems.push("server.port must not be 0".to_string());
+        }
+        if self.server.workers == Some(0) {
+            problems.push("server.workers must be at least 1".to_string());
+        }
+        if self.database.uri.is_empty() {
+            problems
+                .push("database.uri is required (set MONGOURI or SM__DATABASE__URI)".to_string());
+        } else if !self.database.uri.starts_with("mongodb://")
+            && !self.database.uri.starts_with("mongodb+srv://")
+        {
+            problems.push("database.uri must be a mongodb:// or mongodb+srv:// URI".to_string());
+        }
+        if self.database.name.is_empty() {
+            problems.push("database.name must not be empty".to_string());
+        }
+        if self.database.max_pool_size == Some(0) {
+            problems.push("database.max_pool_size must be at least 1".to_string());
+        }
+        if self.database.ping_timeout_ms == 0 {
+            problems.push("database.ping_timeout_ms must be at least 1".to_string());
+        }
+        if self.school.name.is_empty() {
+            problems.push("school.name must not be empty".to_string());
+        }
+        if self.school.code.is_empty() {
+            problems.push("school.code must not be empty".to_string());
+        }
+        if self.school.terms_per_year < 1 {
+            problems.push("school.terms_per_year must be at least 1".to_string());
+        }
+        if self.school.max_weekly_teaching_hours == 0 {
+            problems.push("school.max_weekly_teaching_hours must be at least 1".to_string());
+        }
+        if self.school.homework_weight_percent > 100 {
+            problems.push("school.homework_weight_percent must be at most 100".to_string());
+        }
+        let currency = &self.school.currency;
+        if currency.len() != 3 || !currency.bytes().all(|b| b.is_ascii_uppercase()) {
+            problems.push("school.currency must be a three-letter ISO 4217 code".to_string());
+        }
+        if self.archive.retention_days < 1 {
+            problems.push("archive.retention_days must be at least 1".to_string());
+        }
+        if EnvFilter::try_new(&self.logging.level).is_err() {
+            problems.push(format!(
+                "logging.level {:?} is not a valid filter",
+                self.logging.level
+            ));
+        }
+        if matches!(self.storage.backend, StorageBackend::Local)
+            && self.storage.local_root.is_empty()
+        {
+            problems.push("storage.local_root must not be empty".to_string());
+        }
+        if matches!(self.storage.backend, StorageBackend::GridFs)
+            && self.storage.gridfs_bucket.is_empty()
+        {
+            problems.push("storage.gridfs_bucket must not be empty".to_string());
+        }
+        if self.storage.max_file_bytes == 0 {
+            problems.push("storage.max_file_bytes must be at least 1".to_string());
+        }
+        let notifications = &self.notifications;
+        if !notifications.smtp.host.is_empty() {
+            if notifications.smtp.port == 0 {
+                problems.push("notifications.smtp.port must not be 0".to_string());
+            }
+            if !notifications.smtp.from.contains('@') {
+                problems.push(
+                    "notifications.smtp.from must be an email address when smtp.host is set"
+                        .to_string(),
+                );
+            }
+        }
+        let gateway = &notifications.sms.gateway_url;
+        if !gateway.is_empty()
+            && !gateway.starts_with("http://")
+            && !gateway.starts_with("https://")
+        {
+            problems.push("notifications.sms.gateway_url must be an http(s) URL".to_string());
+        }
+        if notifications.max_attempts == 0 {
+            problems.push("notifications.max_attempts must be at least 1".to_string());
+        }
+        if notifications.poll_interval_secs == 0 {
+            problems.push("notifications.poll_interval_secs must be at least 1".to_string());
+        }
+        if notifications.overdue_reminder_days < 1 {
+            problems.push("notifications.overdue_reminder_days must be at least 1".to_string());
+        }
+        for (kind, template, allowed) in notifications.templates.all() {
+            if template.body.trim().is_empty() {
+                problems.push(format!(
+                    "notifications.templates.{}.body must not be empty",
+                    kind
+                ));
+            }
+            for name in template.placeholders() {
+                if !allowed.contains(&name) {
+                    problems.push(format!(
+                        "notifications.templates.{} has unknown placeholder {{{}}}",
+                        kind, name
+                    ));
+                }
+            }
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+}
+
+fn merge(target: &mut Value, overlay: Value) {
+    match (target, overlay) {
+        (Value::Object(target), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                merge(target.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (target, overlay) => *target = overlay,
+    }
+}
+
+/// Sets the dotted `key` from an environment string, coercing it to the type
+/// of the setting it replaces so `SM__SERVER__PORT=9000` stays a number.
+fn set(tree: &mut Value, key: &str, raw: &str) -> Result<(), ConfigError> {
+    let mut node = tree;
+    for part in key.split('.') {
+        node = match node {
+            Value::Object(map) => map
+                .get_mut(part)
+                .ok_or_else(|| ConfigError::UnknownSetting(key.to_string()))?,
+            _ => return Err(ConfigError::UnknownSetting(key.to_string())),
+        };
+    }
+    let invalid = || ConfigError::InvalidValue(key.to_string(), raw.to_string());
+    *node = match node {
+        Value::String(_) => Value::String(raw.to_string()),
+        Value::Bool(_) => Value::Bool(raw.parse().map_err(|_| invalid())?),
+        Value::Number(_) => serde_json::from_str::<serde_json::Number>(raw)
+            .map(Value::Number)
+            .map_err(|_| invalid())?,
+        Value::Null => serde_json::from_str(raw).map_err(|_| invalid())?,
+        Value::Object(_) | Value::Array(_) => {
+            return Err(ConfigError::UnknownSetting(key.to_string()))
+        }
+    };
+    Ok(())
+}
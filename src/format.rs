@@ -0,0 +1,10 @@
+use chrono::{TimeZone, Utc};
+use mongodb::bson::DateTime;
+
+/// The UTC calendar date of `instant`, e.g. `19 October 2026`.
+pub fn date(instant: DateTime) -> String {
+    Utc.timestamp_millis_opt(instant.timestamp_millis())
+        .single()
+        .map(|utc| utc.format("%-d %B %Y").to_string())
+        .unwrap_or_default()
+}
@@ -0,0 +1,234 @@
+use crate::{
+    models::{
+        attendance::Attendance, classroom::Classroom, classroom_student::ClassroomStudent,
+        exam_result::ExamResult, parent::Parent, student::Student, teacher::Teacher,
+    },
+    repository::mongodb_repo::MongoRepo,
+};
+use actix_web::web::Data;
+use async_graphql::{dataloader::Loader, Error};
+use mongodb::bson::oid::ObjectId;
+use std::collections::HashMap;
+
+/// Batches the lookups issued while resolving one GraphQL request so each
+/// relationship costs a single `$in` query instead of one query per parent.
+pub struct SchoolLoader {
+    db: Data<MongoRepo>,
+}
+
+impl SchoolLoader {
+    pub fn new(db: Data<MongoRepo>) -> Self {
+        SchoolLoader { db }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TeacherId(pub ObjectId);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ParentId(pub ObjectId);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct StudentId(pub ObjectId);
+
+/// Classrooms whose homeroom teacher is the given teacher.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HomeroomsOf(pub ObjectId);
+
+/// Students whose guardian is the given parent.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChildrenOf(pub ObjectId);
+
+/// Enrollments in the given classroom.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RosterOf(pub ObjectId);
+
+/// Enrollments of the given student.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EnrollmentsOf(pub ObjectId);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AttendanceOf(pub ObjectId);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ResultsOf(pub ObjectId);
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ResultsForExam(pub ObjectId);
+
+fn index_by_id<T>(rows: Vec<T>, id: impl Fn(&T) -> Option<ObjectId>) -> HashMap<ObjectId, T> {
+    rows.into_iter()
+        .filter_map(|row| id(&row).map(|key| (key, row)))
+        .collect()
+}
+
+fn group_by<T>(rows: Vec<T>, owner: impl Fn(&T) -> Option<ObjectId>) -> HashMap<ObjectId, Vec<T>> {
+    let mut groups: HashMap<ObjectId, Vec<T>> = HashMap::new();
+    for row in rows {
+        if let Some(key) = owner(&row) {
+            groups.entry(key).or_default().push(row);
+        }
+    }
+    groups
+}
+
+fn rekey<K, V>(map: HashMap<ObjectId, V>, key: impl Fn(ObjectId) -> K) -> HashMap<K, V>
+where
+    K: Eq + std::hash::Hash,
+{
+    map.into_iter()
+        .map(|(id, value)| (key(id), value))
+        .collect()
+}
+
+impl Loader<TeacherId> for SchoolLoader {
+    type Value = Teacher;
+    type Error = Error;
+
+    async fn load(&self, keys: &[TeacherId]) -> Result<HashMap<TeacherId, Teacher>, Error> {
+        let ids: Vec<ObjectId> = keys.iter().map(|key| key.0).collect();
+        let teachers = self.db.get_teachers_by_ids(&ids).await?;
+        Ok(rekey(
+            index_by_id(teachers, |teacher| teacher.id),
+            TeacherId,
+        ))
+    }
+}
+
+impl Loader<ParentId> for SchoolLoader {
+    type Value = Parent;
+    type Error = Error;
+
+    async fn load(&self, keys: &[ParentId]) -> Result<HashMap<ParentId, Parent>, Error> {
+        let ids: Vec<ObjectId> = keys.iter().map(|key| key.0).collect();
+        let parents = self.db.get_parents_by_ids(&ids).await?;
+        Ok(rekey(index_by_id(parents, |parent| parent.id), ParentId))
+    }
+}
+
+impl Loader<StudentId> for SchoolLoader {
+    type Value = Student;
+    type Error = Error;
+
+    async fn load(&self, keys: &[StudentId]) -> Result<HashMap<StudentId, Student>, Error> {
+        let ids: Vec<ObjectId> = keys.iter().map(|key| key.0).collect();
+        let students = self.db.get_students_by_ids(&ids).await?;
+        Ok(rekey(
+            index_by_id(students, |student| student.id),
+            StudentId,
+        ))
+    }
+}
+
+impl Loader<HomeroomsOf> for SchoolLoader {
+    type Value = Vec<Classroom>;
+    type Error = Error;
+
+    async fn load(
+        &self,
+        keys: &[HomeroomsOf],
+    ) -> Result<HashMap<HomeroomsOf, Vec<Classroom>>, Error> {
+        let ids: Vec<ObjectId> = keys.iter().map(|key| key.0).collect();
+        let classrooms = self.db.get_classrooms_for_teachers(&ids).await?;
+        Ok(rekey(
+            group_by(classrooms, |classroom| classroom.teacher.id),
+            HomeroomsOf,
+        ))
+    }
+}
+
+impl Loader<ChildrenOf> for SchoolLoader {
+    type Value = Vec<Student>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[ChildrenOf]) -> Result<HashMap<ChildrenOf, Vec<Student>>, Error> {
+        let ids: Vec<ObjectId> = keys.iter().map(|key| key.0).collect();
+        let students = self.db.get_students_for_parents(&ids).await?;
+        Ok(rekey(
+            group_by(students, |student| student.parent.id),
+            ChildrenOf,
+        ))
+    }
+}
+
+impl Loader<RosterOf> for SchoolLoader {
+    type Value = Vec<ClassroomStudent>;
+    type Error = Error;
+
+    async fn load(
+        &self,
+        keys: &[RosterOf],
+    ) -> Result<HashMap<RosterOf, Vec<ClassroomStudent>>, Error> {
+        let ids: Vec<ObjectId> = keys.iter().map(|key| key.0).collect();
+        let enrollments = self.db.get_enrollments_for_classrooms(&ids).await?;
+        Ok(rekey(
+            group_by(enrollments, |enrollment| enrollment.classroom.id),
+            RosterOf,
+        ))
+    }
+}
+
+impl Loader<EnrollmentsOf> for SchoolLoader {
+    type Value = Vec<ClassroomStudent>;
+    type Error = Error;
+
+    async fn load(
+        &self,
+        keys: &[EnrollmentsOf],
+    ) -> Result<HashMap<EnrollmentsOf, Vec<ClassroomStudent>>, Error> {
+        let ids: Vec<ObjectId> = keys.iter().map(|key| key.0).collect();
+        let enrollments = self.db.get_enrollments_for_students(&ids).await?;
+        Ok(rekey(
+            group_by(enrollments, |enrollment| enrollment.student.id),
+            EnrollmentsOf,
+        ))
+    }
+}
+
+impl Loader<AttendanceOf> for SchoolLoader {
+    type Value = Vec<Attendance>;
+    type Error = Error;
+
+    async fn load(
+        &self,
+        keys: &[AttendanceOf],
+    ) -> Result<HashMap<AttendanceOf, Vec<Attendance>>, Error> {
+        let ids: Vec<ObjectId> = keys.iter().map(|key| key.0).collect();
+        let attendance = self.db.get_attendance_for_students(&ids).await?;
+        Ok(rekey(
+            group_by(attendance, |attendance| attendance.student.id),
+            AttendanceOf,
+        ))
+    }
+}
+
+impl Loader<ResultsOf> for SchoolLoader {
+    type Value = Vec<ExamResult>;
+    type Error = Error;
+
+    async fn load(&self, keys: &[ResultsOf]) -> Result<HashMap<ResultsOf, Vec<ExamResult>>, Error> {
+        let ids: Vec<ObjectId> = keys.iter().map(|key| key.0).collect();
+        let results = self.db.get_exam_results_for_students(&ids).await?;
+        Ok(rekey(
+            group_by(results, |result| result.student.id),
+            ResultsOf,
+        ))
+    }
+}
+
+impl Loader<ResultsForExam> for SchoolLoader {
+    type Value = Vec<ExamResult>;
+    type Error = Error;
+
+    async fn load(
+        &self,
+        keys: &[ResultsForExam],
+    ) -> Result<HashMap<ResultsForExam, Vec<ExamResult>>, Error> {
+        let ids: Vec<ObjectId> = keys.iter().map(|key| key.0).collect();
+        let results = self.db.get_exam_results_for_exams(&ids).await?;
+        Ok(rekey(
+            group_by(results, |result| result.exam.id),
+            ResultsForExam,
+        ))
+    }
+}
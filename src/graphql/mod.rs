@@ -0,0 +1,66 @@
+mod loader;
+mod mutation;
+mod query;
+mod types;
+
+use crate::{
+    api::auth::Admin, models::audit_entry::AuditContext, notifications::Notifier,
+    repository::mongodb_repo::MongoRepo,
+};
+use actix_web::{
+    rt,
+    web::{self, Data, Json},
+    HttpResponse,
+};
+use async_graphql::{dataloader::DataLoader, http::GraphiQLSource, EmptySubscription, Schema};
+use loader::SchoolLoader;
+use mutation::MutationRoot;
+use query::QueryRoot;
+
+/// Nesting such as student → guardian → students is bounded, as is the
+/// number of fields a single query may resolve.
+const MAX_DEPTH: usize = 10;
+const MAX_COMPLEXITY: usize = 500;
+
+pub type SchoolSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+pub fn schema(db: Data<MongoRepo>, notifier: Data<Notifier>) -> SchoolSchema {
+    Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+        .data(db)
+        .data(notifier)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// Each request gets its own loader so batched results never leak between
+/// callers, and mutations are audited under the caller's identity. Only an
+/// admin may run mutations.
+async fn graphql(
+    schema: Data<SchoolSchema>,
+    db: Data<MongoRepo>,
+    actor: AuditContext,
+    admin: Option<Admin>,
+    request: Json<async_graphql::Request>,
+) -> HttpResponse {
+    let loader = DataLoader::new(SchoolLoader::new(db), rt::spawn);
+    let mut request = request.into_inner().data(loader).data(actor);
+    if let Some(admin) = admin {
+        request = request.data(admin);
+    }
+    HttpResponse::Ok().json(schema.execute(request).await)
+}
+
+async fn graphiql() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    cfg.service(
+        web::resource("/graphql")
+            .route(web::post().to(graphql))
+            .route(web::get().to(graphiql)),
+    );
+}
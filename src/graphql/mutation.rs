@@ -0,0 +1,411 @@
+use super::{
+    loader::{ParentId, StudentId, TeacherId},
+    types::{loader, parse_id, parse_timestamp},
+};
+use crate::{
+    api::{auth::Admin, validation::field_errors},
+    models::{
+        attendance::Attendance, audit_entry::AuditContext, classroom::Classroom,
+        classroom_student::ClassroomStudent, course::Course, exam::Exam, exam_result::ExamResult,
+        exam_type::ExamType, grade::Grade, parent::Parent, student::Student, teacher::Teacher,
+    },
+    notifications::Notifier,
+    repository::mongodb_repo::MongoRepo,
+};
+use actix_web::web::Data;
+use async_graphql::{
+    Context, Error, ErrorExtensions, Guard, InputObject, Object, Result, Value, ID,
+};
+use mongodb::bson::DateTime;
+use validator::{Validate, ValidationErrors};
+
+fn invalid(errors: &ValidationErrors) -> Error {
+    let fields = serde_json::to_value(field_errors(errors)).unwrap_or_default();
+    Error::new("validation failed").extend_with(|_, extensions| {
+        extensions.set("code", "UNPROCESSABLE_ENTITY");
+        extensions.set("fields", Value::from_json(fields).unwrap_or_default());
+    })
+}
+
+/// Lets only callers holding the admin token through.
+struct AdminGuard;
+
+impl Guard for AdminGuard {
+    async fn check(&self, ctx: &Context<'_>) -> Result<()> {
+        match ctx.data_opt::<Admin>() {
+            Some(_) => Ok(()),
+            None => Err(Error::new("admin access required")
+                .extend_with(|_, extensions| extensions.set("code", "FORBIDDEN"))),
+        }
+    }
+}
+
+fn not_found(entity: &str, id: &ID) -> Error {
+    Error::new(format!("{} {} not found", entity, id.0))
+}
+
+#[derive(InputObject)]
+pub struct PersonInput {
+    pub email: String,
+    pub password: String,
+    pub fname: String,
+    pub lname: String,
+    /// RFC 3339 timestamp.
+    pub dob: String,
+    pub phone: String,
+    pub mobile: String,
+    pub status: bool,
+}
+
+#[derive(InputObject)]
+pub struct StudentInput {
+    pub email: String,
+    pub password: String,
+    pub fname: String,
+    pub lname: String,
+    /// RFC 3339 timestamp.
+    pub dob: String,
+    pub phone: String,
+    pub mobile: String,
+    pub status: bool,
+    pub parent_id: ID,
+    /// RFC 3339 timestamp.
+    pub date_of_join: String,
+}
+
+#[derive(InputObject)]
+pub struct ClassroomInput {
+    pub year: i32,
+    pub grade_id: i64,
+    pub section: String,
+    pub status: bool,
+    pub remarks: String,
+    pub teacher_id: ID,
+}
+
+#[derive(InputObject)]
+pub struct GradeInput {
+    pub name: String,
+    pub desc: String,
+}
+
+#[derive(InputObject)]
+pub struct CourseInput {
+    pub name: String,
+    pub description: String,
+    pub grade: GradeInput,
+}
+
+#[derive(InputObject)]
+pub struct ExamTypeInput {
+    pub name: String,
+    pub desc: String,
+}
+
+#[derive(InputObject)]
+pub struct ExamInput {
+    pub name: String,
+    pub exam_type: ExamTypeInput,
+    /// RFC 3339 timestamp.
+    pub start_date: String,
+}
+
+#[derive(InputObject)]
+pub struct ExamResultInput {
+    pub exam_id: ID,
+    pub student_id: ID,
+    pub course_id: ID,
+    pub marks: String,
+}
+
+#[derive(InputObject)]
+pub struct AttendanceInput {
+    pub student_id: ID,
+    /// RFC 3339 timestamp.
+    pub date: String,
+    pub status: bool,
+    pub remark: String,
+}
+
+/// Every mutation requires the admin token.
+pub struct MutationRoot;
+
+#[Object(guard = "AdminGuard")]
+impl MutationRoot {
+    async fn create_teacher(&self, ctx: &Context<'_>, input: PersonInput) -> Result<Teacher> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        let mut teacher = Teacher {
+            id: None,
+            email: input.email,
+            password: input.password,
+            fname: input.fname,
+            lname: input.lname,
+            dob: parse_timestamp(&input.dob)?,
+            phone: input.phone,
+            mobile: input.mobile,
+            status: input.status,
+            last_login_date: DateTime::now(),
+            last_login_ip: String::new(),
+            attachments: Vec::new(),
+            version: 0,
+            deleted_at: None,
+        };
+        teacher.validate().map_err(|errors| invalid(&errors))?;
+        let created = db
+            .create_teacher(teacher.clone(), ctx.data_unchecked::<AuditContext>())
+            .await?;
+        teacher.id = created.inserted_id.as_object_id();
+        teacher.version = 1;
+        Ok(teacher)
+    }
+
+    async fn create_parent(&self, ctx: &Context<'_>, input: PersonInput) -> Result<Parent> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        let mut parent = Parent {
+            id: None,
+            email: input.email,
+            password: input.password,
+            fname: input.fname,
+            lname: input.lname,
+            dob: parse_timestamp(&input.dob)?,
+            phone: input.phone,
+            mobile: input.mobile,
+            status: input.status,
+            last_login_date: DateTime::now(),
+            last_login_ip: String::new(),
+            version: 0,
+            deleted_at: None,
+        };
+        parent.validate().map_err(|errors| invalid(&errors))?;
+        let created = db
+            .create_parent(parent.clone(), ctx.data_unchecked::<AuditContext>())
+            .await?;
+        parent.id = created.inserted_id.as_object_id();
+        parent.version = 1;
+        Ok(parent)
+    }
+
+    async fn create_student(&self, ctx: &Context<'_>, input: StudentInput) -> Result<Student> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        let parent = loader(ctx)
+            .load_one(ParentId(parse_id(&input.parent_id)?))
+            .await?
+            .ok_or_else(|| not_found("parent", &input.parent_id))?;
+        let mut student = Student {
+            id: None,
+            email: input.email,
+            password: input.password,
+            fname: input.fname,
+            lname: input.lname,
+            dob: parse_timestamp(&input.dob)?,
+            phone: input.phone,
+            mobile: input.mobile,
+            parent,
+            date_of_join: parse_timestamp(&input.date_of_join)?,
+            status: input.status,
+            last_login_date: DateTime::now(),
+            last_login_ip: String::new(),
+            attachments: Vec::new(),
+            version: 0,
+            deleted_at: None,
+        };
+        student.validate().map_err(|errors| invalid(&errors))?;
+        let created = db
+            .create_student(student.clone(), ctx.data_unchecked::<AuditContext>())
+            .await?;
+        student.id = created.inserted_id.as_object_id();
+        student.version = 1;
+        Ok(student)
+    }
+
+    /// Archives the teacher, returning false when no live teacher matched.
+    async fn archive_teacher(&self, ctx: &Context<'_>, id: ID) -> Result<bool> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        let result = db
+            .delete_teacher(
+                &parse_id(&id)?.to_hex(),
+                None,
+                ctx.data_unchecked::<AuditContext>(),
+            )
+            .await?;
+        Ok(result.modified_count == 1)
+    }
+
+    /// Archives the parent, returning false when no live parent matched.
+    async fn archive_parent(&self, ctx: &Context<'_>, id: ID) -> Result<bool> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        let result = db
+            .delete_parent(
+                &parse_id(&id)?.to_hex(),
+                None,
+                ctx.data_unchecked::<AuditContext>(),
+            )
+            .await?;
+        Ok(result.modified_count == 1)
+    }
+
+    /// Archives the student, returning false when no live student matched.
+    async fn archive_student(&self, ctx: &Context<'_>, id: ID) -> Result<bool> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        let result = db
+            .delete_student(
+                &parse_id(&id)?.to_hex(),
+                None,
+                ctx.data_unchecked::<AuditContext>(),
+            )
+            .await?;
+        Ok(result.modified_count == 1)
+    }
+
+    async fn create_classroom(
+        &self,
+        ctx: &Context<'_>,
+        input: ClassroomInput,
+    ) -> Result<Classroom> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        let teacher = loader(ctx)
+            .load_one(TeacherId(parse_id(&input.teacher_id)?))
+            .await?
+            .ok_or_else(|| not_found("teacher", &input.teacher_id))?;
+        let mut classroom = Classroom {
+            id: None,
+            year: input.year,
+            grade_id: input.grade_id,
+            section: input.section,
+            status: input.status,
+            remarks: input.remarks,
+            teacher,
+        };
+        let created = db
+            .create_classroom(classroom.clone(), ctx.data_unchecked::<AuditContext>())
+            .await?;
+        classroom.id = created.inserted_id.as_object_id();
+        Ok(classroom)
+    }
+
+    /// Adds the student to the classroom's roster and returns the classroom.
+    async fn enroll_student(
+        &self,
+        ctx: &Context<'_>,
+        classroom_id: ID,
+        student_id: ID,
+    ) -> Result<Classroom> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        let classroom = db
+            .get_classroom(&parse_id(&classroom_id)?.to_hex())
+            .await?
+            .ok_or_else(|| not_found("classroom", &classroom_id))?;
+        let student = loader(ctx)
+            .load_one(StudentId(parse_id(&student_id)?))
+            .await?
+            .ok_or_else(|| not_found("student", &student_id))?;
+        let enrollment = ClassroomStudent {
+            id: None,
+            classroom: classroom.clone(),
+            student,
+        };
+        db.enroll_student(enrollment, ctx.data_unchecked::<AuditContext>())
+            .await?;
+        Ok(classroom)
+    }
+
+    async fn create_course(&self, ctx: &Context<'_>, input: CourseInput) -> Result<Course> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        let mut course = Course {
+            id: None,
+            name: input.name,
+            description: input.description,
+            grade: Grade {
+                id: None,
+                name: input.grade.name,
+                desc: input.grade.desc,
+            },
+        };
+        let created = db
+            .create_course(course.clone(), ctx.data_unchecked::<AuditContext>())
+            .await?;
+        course.id = created.inserted_id.as_object_id();
+        Ok(course)
+    }
+
+    async fn create_exam(&self, ctx: &Context<'_>, input: ExamInput) -> Result<Exam> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        let mut exam = Exam {
+            id: None,
+            exam_type: ExamType {
+                id: None,
+                name: input.exam_type.name,
+                desc: input.exam_type.desc,
+            },
+            name: input.name,
+            start_date: parse_timestamp(&input.start_date)?,
+        };
+        let created = db
+            .create_exam(exam.clone(), ctx.data_unchecked::<AuditContext>())
+            .await?;
+        exam.id = created.inserted_id.as_object_id();
+        Ok(exam)
+    }
+
+    async fn record_exam_result(
+        &self,
+        ctx: &Context<'_>,
+        input: ExamResultInput,
+    ) -> Result<ExamResult> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        let exam = db
+            .get_exam(&parse_id(&input.exam_id)?.to_hex())
+            .await?
+            .ok_or_else(|| not_found("exam", &input.exam_id))?;
+        let course = db
+            .get_course(&parse_id(&input.course_id)?.to_hex())
+            .await?
+            .ok_or_else(|| not_found("course", &input.course_id))?;
+        let student = loader(ctx)
+            .load_one(StudentId(parse_id(&input.student_id)?))
+            .await?
+            .ok_or_else(|| not_found("student", &input.student_id))?;
+        let mut result = ExamResult {
+            id: None,
+            exam,
+            student,
+            course,
+            marks: input.marks,
+        };
+        let created = db
+            .create_exam_result(result.clone(), ctx.data_unchecked::<AuditContext>())
+            .await?;
+        result.id = created.inserted_id.as_object_id();
+        ctx.data_unchecked::<Data<Notifier>>()
+            .exam_result(db, &result)
+            .await?;
+        Ok(result)
+    }
+
+    async fn record_attendance(
+        &self,
+        ctx: &Context<'_>,
+        input: AttendanceInput,
+    ) -> Result<Attendance> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        let student = loader(ctx)
+            .load_one(StudentId(parse_id(&input.student_id)?))
+            .await?
+            .ok_or_else(|| not_found("student", &input.student_id))?;
+        let mut attendance = Attendance {
+            id: None,
+            date: parse_timestamp(&input.date)?,
+            student,
+            status: input.status,
+            remark: input.remark,
+        };
+        let created = db
+            .record_attendance(attendance.clone(), ctx.data_unchecked::<AuditContext>())
+            .await?;
+        attendance.id = created.inserted_id.as_object_id();
+        ctx.data_unchecked::<Data<Notifier>>()
+            .absence(db, &attendance)
+            .await?;
+        Ok(attendance)
+    }
+}
@@ -0,0 +1,75 @@
+use super::{
+    loader::{ParentId, StudentId, TeacherId},
+    types::{loader, parse_id},
+};
+use crate::{
+    models::{
+        classroom::Classroom, course::Course, exam::Exam, parent::Parent, student::Student,
+        teacher::Teacher,
+    },
+    repository::mongodb_repo::MongoRepo,
+};
+use actix_web::web::Data;
+use async_graphql::{Context, Object, Result, ID};
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    async fn teacher(&self, ctx: &Context<'_>, id: ID) -> Result<Option<Teacher>> {
+        loader(ctx).load_one(TeacherId(parse_id(&id)?)).await
+    }
+
+    async fn teachers(&self, ctx: &Context<'_>) -> Result<Vec<Teacher>> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        Ok(db.get_all_teachers(false).await?)
+    }
+
+    async fn parent(&self, ctx: &Context<'_>, id: ID) -> Result<Option<Parent>> {
+        loader(ctx).load_one(ParentId(parse_id(&id)?)).await
+    }
+
+    async fn parents(&self, ctx: &Context<'_>) -> Result<Vec<Parent>> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        Ok(db.get_all_parents(false).await?)
+    }
+
+    async fn student(&self, ctx: &Context<'_>, id: ID) -> Result<Option<Student>> {
+        loader(ctx).load_one(StudentId(parse_id(&id)?)).await
+    }
+
+    async fn students(&self, ctx: &Context<'_>) -> Result<Vec<Student>> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        Ok(db.get_all_students(false).await?)
+    }
+
+    async fn classroom(&self, ctx: &Context<'_>, id: ID) -> Result<Option<Classroom>> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        Ok(db.get_classroom(&parse_id(&id)?.to_hex()).await?)
+    }
+
+    async fn classrooms(&self, ctx: &Context<'_>) -> Result<Vec<Classroom>> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        Ok(db.get_all_classrooms().await?)
+    }
+
+    async fn course(&self, ctx: &Context<'_>, id: ID) -> Result<Option<Course>> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        Ok(db.get_course(&parse_id(&id)?.to_hex()).await?)
+    }
+
+    async fn courses(&self, ctx: &Context<'_>) -> Result<Vec<Course>> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        Ok(db.get_all_courses().await?)
+    }
+
+    async fn exam(&self, ctx: &Context<'_>, id: ID) -> Result<Option<Exam>> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        Ok(db.get_exam(&parse_id(&id)?.to_hex()).await?)
+    }
+
+    async fn exams(&self, ctx: &Context<'_>) -> Result<Vec<Exam>> {
+        let db = ctx.data_unchecked::<Data<MongoRepo>>();
+        Ok(db.get_all_exams().await?)
+    }
+}
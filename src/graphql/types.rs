@@ -0,0 +1,424 @@
+use super::loader::{
+    AttendanceOf, ChildrenOf, EnrollmentsOf, HomeroomsOf, ParentId, ResultsForExam, ResultsOf,
+    RosterOf, SchoolLoader, StudentId, TeacherId,
+};
+use crate::models::{
+    attendance::Attendance, classroom::Classroom, course::Course, exam::Exam,
+    exam_result::ExamResult, exam_type::ExamType, grade::Grade, parent::Parent, student::Student,
+    teacher::Teacher,
+};
+use async_graphql::{dataloader::DataLoader, Context, Error, Object, Result, ID};
+use mongodb::bson::{oid::ObjectId, DateTime};
+
+pub fn to_id(id: Option<ObjectId>) -> ID {
+    ID(id.map(|id| id.to_hex()).unwrap_or_default())
+}
+
+pub fn parse_id(id: &ID) -> Result<ObjectId> {
+    ObjectId::parse_str(id.as_str()).map_err(|_| Error::new(format!("invalid id: {}", id.0)))
+}
+
+pub fn parse_timestamp(value: &str) -> Result<DateTime> {
+    DateTime::parse_rfc3339_str(value)
+        .map_err(|_| Error::new(format!("invalid RFC 3339 timestamp: {}", value)))
+}
+
+fn timestamp(value: &DateTime) -> String {
+    value.try_to_rfc3339_string().unwrap_or_default()
+}
+
+pub fn loader<'a>(ctx: &Context<'a>) -> &'a DataLoader<SchoolLoader> {
+    ctx.data_unchecked::<DataLoader<SchoolLoader>>()
+}
+
+#[Object]
+impl Teacher {
+    async fn id(&self) -> ID {
+        to_id(self.id)
+    }
+
+    async fn email(&self) -> &str {
+        &self.email
+    }
+
+    async fn fname(&self) -> &str {
+        &self.fname
+    }
+
+    async fn lname(&self) -> &str {
+        &self.lname
+    }
+
+    async fn dob(&self) -> String {
+        timestamp(&self.dob)
+    }
+
+    async fn phone(&self) -> &str {
+        &self.phone
+    }
+
+    async fn mobile(&self) -> &str {
+        &self.mobile
+    }
+
+    async fn status(&self) -> bool {
+        self.status
+    }
+
+    async fn last_login_date(&self) -> String {
+        timestamp(&self.last_login_date)
+    }
+
+    async fn version(&self) -> i64 {
+        self.version
+    }
+
+    async fn deleted_at(&self) -> Option<String> {
+        self.deleted_at.as_ref().map(timestamp)
+    }
+
+    /// Classrooms this teacher is the homeroom teacher of.
+    async fn classrooms(&self, ctx: &Context<'_>) -> Result<Vec<Classroom>> {
+        let Some(id) = self.id else {
+            return Ok(Vec::new());
+        };
+        Ok(loader(ctx)
+            .load_one(HomeroomsOf(id))
+            .await?
+            .unwrap_or_default())
+    }
+}
+
+#[Object]
+impl Parent {
+    async fn id(&self) -> ID {
+        to_id(self.id)
+    }
+
+    async fn email(&self) -> &str {
+        &self.email
+    }
+
+    async fn fname(&self) -> &str {
+        &self.fname
+    }
+
+    async fn lname(&self) -> &str {
+        &self.lname
+    }
+
+    async fn dob(&self) -> String {
+        timestamp(&self.dob)
+    }
+
+    async fn phone(&self) -> &str {
+        &self.phone
+    }
+
+    async fn mobile(&self) -> &str {
+        &self.mobile
+    }
+
+    async fn status(&self) -> bool {
+        self.status
+    }
+
+    async fn last_login_date(&self) -> String {
+        timestamp(&self.last_login_date)
+    }
+
+    async fn version(&self) -> i64 {
+        self.version
+    }
+
+    async fn deleted_at(&self) -> Option<String> {
+        self.deleted_at.as_ref().map(timestamp)
+    }
+
+    /// Students who list this parent as their guardian.
+    async fn children(&self, ctx: &Context<'_>) -> Result<Vec<Student>> {
+        let Some(id) = self.id else {
+            return Ok(Vec::new());
+        };
+        Ok(loader(ctx)
+            .load_one(ChildrenOf(id))
+            .await?
+            .unwrap_or_default())
+    }
+}
+
+#[Object]
+impl Student {
+    async fn id(&self) -> ID {
+        to_id(self.id)
+    }
+
+    async fn email(&self) -> &str {
+        &self.email
+    }
+
+    async fn fname(&self) -> &str {
+        &self.fname
+    }
+
+    async fn lname(&self) -> &str {
+        &self.lname
+    }
+
+    async fn dob(&self) -> String {
+        timestamp(&self.dob)
+    }
+
+    async fn phone(&self) -> &str {
+        &self.phone
+    }
+
+    async fn mobile(&self) -> &str {
+        &self.mobile
+    }
+
+    async fn date_of_join(&self) -> String {
+        timestamp(&self.date_of_join)
+    }
+
+    async fn status(&self) -> bool {
+        self.status
+    }
+
+    async fn last_login_date(&self) -> String {
+        timestamp(&self.last_login_date)
+    }
+
+    async fn version(&self) -> i64 {
+        self.version
+    }
+
+    async fn deleted_at(&self) -> Option<String> {
+        self.deleted_at.as_ref().map(timestamp)
+    }
+
+    /// The current parent record, or null once the parent is archived.
+    async fn guardian(&self, ctx: &Context<'_>) -> Result<Option<Parent>> {
+        match self.parent.id {
+            Some(id) => loader(ctx).load_one(ParentId(id)).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn classrooms(&self, ctx: &Context<'_>) -> Result<Vec<Classroom>> {
+        let Some(id) = self.id else {
+            return Ok(Vec::new());
+        };
+        let enrollments = loader(ctx)
+            .load_one(EnrollmentsOf(id))
+            .await?
+            .unwrap_or_default();
+        Ok(enrollments
+            .into_iter()
+            .map(|enrollment| enrollment.classroom)
+            .collect())
+    }
+
+    async fn attendance(&self, ctx: &Context<'_>) -> Result<Vec<Attendance>> {
+        let Some(id) = self.id else {
+            return Ok(Vec::new());
+        };
+        Ok(loader(ctx)
+            .load_one(AttendanceOf(id))
+            .await?
+            .unwrap_or_default())
+    }
+
+    async fn results(&self, ctx: &Context<'_>) -> Result<Vec<ExamResult>> {
+        let Some(id) = self.id else {
+            return Ok(Vec::new());
+        };
+        Ok(loader(ctx)
+            .load_one(ResultsOf(id))
+            .await?
+            .unwrap_or_default())
+    }
+}
+
+#[Object]
+impl Classroom {
+    async fn id(&self) -> ID {
+        to_id(self.id)
+    }
+
+    async fn year(&self) -> i32 {
+        self.year
+    }
+
+    async fn grade_id(&self) -> i64 {
+        self.grade_id
+    }
+
+    async fn section(&self) -> &str {
+        &self.section
+    }
+
+    async fn status(&self) -> bool {
+        self.status
+    }
+
+    async fn remarks(&self) -> &str {
+        &self.remarks
+    }
+
+    /// The current teacher record, or null once the teacher is archived.
+    async fn teacher(&self, ctx: &Context<'_>) -> Result<Option<Teacher>> {
+        match self.teacher.id {
+            Some(id) => loader(ctx).load_one(TeacherId(id)).await,
+            None => Ok(None),
+        }
+    }
+
+    async fn roster(&self, ctx: &Context<'_>) -> Result<Vec<Student>> {
+        let Some(id) = self.id else {
+            return Ok(Vec::new());
+        };
+        let enrollments = loader(ctx)
+            .load_one(RosterOf(id))
+            .await?
+            .unwrap_or_default();
+        let students = loader(ctx)
+            .load_many(
+                enrollments
+                    .iter()
+                    .filter_map(|enrollment| enrollment.student.id.map(StudentId)),
+            )
+            .await?;
+        Ok(students.into_values().collect())
+    }
+}
+
+#[Object]
+impl Grade {
+    async fn id(&self) -> ID {
+        to_id(self.id)
+    }
+
+    async fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn desc(&self) -> &str {
+        &self.desc
+    }
+}
+
+#[Object]
+impl Course {
+    async fn id(&self) -> ID {
+        to_id(self.id)
+    }
+
+    async fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn description(&self) -> &str {
+        &self.description
+    }
+
+    async fn grade(&self) -> &Grade {
+        &self.grade
+    }
+}
+
+#[Object]
+impl ExamType {
+    async fn id(&self) -> ID {
+        to_id(self.id)
+    }
+
+    async fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn desc(&self) -> &str {
+        &self.desc
+    }
+}
+
+#[Object]
+impl Exam {
+    async fn id(&self) -> ID {
+        to_id(self.id)
+    }
+
+    async fn name(&self) -> &str {
+        &self.name
+    }
+
+    async fn exam_type(&self) -> &ExamType {
+        &self.exam_type
+    }
+
+    async fn start_date(&self) -> String {
+        timestamp(&self.start_date)
+    }
+
+    async fn results(&self, ctx: &Context<'_>) -> Result<Vec<ExamResult>> {
+        let Some(id) = self.id else {
+            return Ok(Vec::new());
+        };
+        Ok(loader(ctx)
+            .load_one(ResultsForExam(id))
+            .await?
+            .unwrap_or_default())
+    }
+}
+
+#[Object]
+impl ExamResult {
+    async fn id(&self) -> ID {
+        to_id(self.id)
+    }
+
+    async fn exam(&self) -> &Exam {
+        &self.exam
+    }
+
+    async fn course(&self) -> &Course {
+        &self.course
+    }
+
+    async fn marks(&self) -> &str {
+        &self.marks
+    }
+
+    async fn student(&self, ctx: &Context<'_>) -> Result<Option<Student>> {
+        match self.student.id {
+            Some(id) => loader(ctx).load_one(StudentId(id)).await,
+            None => Ok(None),
+        }
+    }
+}
+
+#[Object]
+impl Attendance {
+    async fn id(&self) -> ID {
+        to_id(self.id)
+    }
+
+    async fn date(&self) -> String {
+        timestamp(&self.date)
+    }
+
+    async fn status(&self) -> bool {
+        self.status
+    }
+
+    async fn remark(&self) -> &str {
+        &self.remark
+    }
+
+    async fn student(&self, ctx: &Context<'_>) -> Result<Option<Student>> {
+        match self.student.id {
+            Some(id) => loader(ctx).load_one(StudentId(id)).await,
+            None => Ok(None),
+        }
+    }
+}
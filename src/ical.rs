@@ -0,0 +1,201 @@
+//! Just enough of iCalendar (RFC 5545) to publish read-only feeds of
+//! events: text escaping, line folding and the three kinds of date we use.
+
+use chrono::{NaiveDate, NaiveDateTime, TimeZone, Utc};
+use mongodb::bson::DateTime;
+
+/// Lines longer than this many octets are folded onto continuation lines.
+const LINE_LIMIT: usize = 75;
+
+pub enum When {
+    /// A point in time, written in UTC.
+    Instant(DateTime),
+    /// A whole day.
+    Date(NaiveDate),
+    /// Local time in whatever zone the reader's calendar is in; used for
+    /// periods, which are times on the school clock.
+    Floating(NaiveDateTime),
+}
+
+impl When {
+    fn property(&self, name: &str) -> String {
+        match self {
+            When::Instant(instant) => {
+                // Out of chrono's range only for dates no school will see.
+                let utc = Utc
+                    .timestamp_millis_opt(instant.timestamp_millis())
+                    .single()
+                    .unwrap_or_else(|| Utc.timestamp_millis_opt(0).unwrap());
+                format!("{}:{}", name, utc.format("%Y%m%dT%H%M%SZ"))
+            }
+            When::Date(date) => format!("{};VALUE=DATE:{}", name, date.format("%Y%m%d")),
+            When::Floating(local) => format!("{}:{}", name, local.format("%Y%m%dT%H%M%S")),
+        }
+    }
+}
+
+pub struct Event {
+    /// Must stay the same across fetches so calendars update the event in
+    /// place instead of adding a copy.
+    pub uid: String,
+    pub summary: String,
+    pub description: String,
+    pub location: String,
+    pub start: When,
+    pub end: When,
+    /// e.g. `FREQ=WEEKLY` for an event that repeats from `start`.
+    pub recurrence: Option<String>,
+}
+
+/// Escapes commas, semicolons, backslashes and newlines in a TEXT value.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Appends `line` folded at 75 octets, never inside a UTF-8 sequence.
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > LINE_LIMIT {
+            out.push_str("\r\n ");
+            // The leading space of a continuation line counts toward it.
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+pub struct Calendar {
+    name: String,
+    product: String,
+    events: Vec<Event>,
+}
+
+impl Calendar {
+    pub fn new(name: String, product: String) -> Self {
+        Calendar {
+            name,
+            product,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, event: Event) {
+        self.events.push(event);
+    }
+
+    /// Renders the feed, stamping every event with `now`.
+    pub fn render(&self, now: DateTime) -> String {
+        let mut out = String::new();
+        let stamp = When::Instant(now).property("DTSTAMP");
+        push_line(&mut out, "BEGIN:VCALENDAR");
+        push_line(&mut out, "VERSION:2.0");
+        push_line(&mut out, &format!("PRODID:{}", self.product));
+        push_line(&mut out, "CALSCALE:GREGORIAN");
+        push_line(&mut out, "METHOD:PUBLISH");
+        push_line(&mut out, &format!("NAME:{}", escape(&self.name)));
+        push_line(&mut out, &format!("X-WR-CALNAME:{}", escape(&self.name)));
+        push_line(&mut out, "REFRESH-INTERVAL;VALUE=DURATION:PT1H");
+        push_line(&mut out, "X-PUBLISHED-TTL:PT1H");
+        for event in &self.events {
+            push_line(&mut out, "BEGIN:VEVENT");
+            push_line(&mut out, &format!("UID:{}", event.uid));
+            push_line(&mut out, &stamp);
+            push_line(&mut out, &event.start.property("DTSTART"));
+            push_line(&mut out, &event.end.property("DTEND"));
+            if let Some(rule) = &event.recurrence {
+                push_line(&mut out, &format!("RRULE:{}", rule));
+            }
+            push_line(&mut out, &format!("SUMMARY:{}", escape(&event.summary)));
+            if !event.description.is_empty() {
+                push_line(
+                    &mut out,
+                    &format!("DESCRIPTION:{}", escape(&event.description)),
+                );
+            }
+            if !event.location.is_empty() {
+                push_line(&mut out, &format!("LOCATION:{}", escape(&event.location)));
+            }
+            push_line(&mut out, "END:VEVENT");
+        }
+        push_line(&mut out, "END:VCALENDAR");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folded(line: &str) -> Vec<String> {
+        let mut out = String::new();
+        push_line(&mut out, line);
+        let out = out.strip_suffix("\r\n").expect("line ends with CRLF");
+        out.split("\r\n").map(str::to_string).collect()
+    }
+
+    #[test]
+    fn escape_handles_text_specials() {
+        assert_eq!(escape("a\\b;c,d\r\ne"), r"a\\b\;c\,d\ne");
+        assert_eq!(escape("Café"), "Café");
+    }
+
+    #[test]
+    fn short_lines_are_not_folded() {
+        let line = "x".repeat(LINE_LIMIT);
+        assert_eq!(folded(&line), vec![line]);
+    }
+
+    #[test]
+    fn long_ascii_lines_fold_at_75_octets() {
+        let lines = folded(&"x".repeat(200));
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].len(), 75);
+        assert_eq!(lines[1].len(), 75);
+        assert!(lines[1].starts_with(' '));
+        assert_eq!(lines.concat().replace(' ', ""), "x".repeat(200));
+    }
+
+    #[test]
+    fn multi_byte_characters_are_never_split() {
+        // 'é' is two octets and '日' three, so neither lines up with 75.
+        for text in ["é".repeat(100), format!("x{}", "日".repeat(60))] {
+            let lines = folded(&text);
+            assert!(lines.len() > 1);
+            for (index, line) in lines.iter().enumerate() {
+                assert!(
+                    line.len() <= LINE_LIMIT,
+                    "line {} is {} octets",
+                    index,
+                    line.len()
+                );
+                let last = index == lines.len() - 1;
+                assert!(
+                    last || line.len() > LINE_LIMIT - 3,
+                    "line {} folded early",
+                    index
+                );
+                assert_eq!(line.starts_with(' '), index > 0);
+            }
+            let unfolded: String = lines
+                .iter()
+                .enumerate()
+                .map(|(index, line)| if index == 0 { &line[..] } else { &line[1..] })
+                .collect();
+            assert_eq!(unfolded, text);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{
+    config::NotificationSettings,
+    notifications::{Channel, DeliveryError},
+    repository::mongodb_repo::MongoRepo,
+};
+use actix_web::{rt::time, web::Data};
+use mongodb::bson::DateTime;
+use std::{sync::Arc, time::Duration};
+
+/// How long a claimed notification is left to its worker before another
+/// may try it; well past any channel's timeout.
+const LEASE_MILLIS: i64 = 5 * 60 * 1000;
+
+/// Longest wait between attempts, however many have failed.
+const MAX_RETRY_DELAY_SECS: u64 = 24 * 60 * 60;
+
+/// Works through the outbox every `poll_interval_secs`, sending whatever
+/// is due. Failures are retried with exponential backoff until
+/// `max_attempts`; permanent ones are given up on at once.
+pub async fn run(
+    db: Data<MongoRepo>,
+    channels: Vec<Arc<dyn Channel>>,
+    settings: NotificationSettings,
+) {
+    let mut interval = time::interval(Duration::from_secs(settings.poll_interval_secs));
+    loop {
+        interval.tick().await;
+        loop {
+            let now = DateTime::now();
+            let lease_until = DateTime::from_millis(now.timestamp_millis() + LEASE_MILLIS);
+            let notification = match db.claim_notification(now, lease_until).await {
+                Ok(Some(notification)) => notification,
+                Ok(None) => break,
+                Err(err) => {
+                    tracing::error!(error = %err, "error reading the outbox");
+                    break;
+                }
+            };
+            let Some(id) = notification.id else {
+                continue;
+            };
+            let sent = match channels
+                .iter()
+                .find(|channel| channel.kind() == notification.channel)
+            {
+                Some(channel) => channel.send(&notification).await,
+                None => Err(DeliveryError::Permanent(
+                    "the channel is no longer configured".to_string(),
+                )),
+            };
+            let recorded = match sent {
+                Ok(()) => db.mark_notification_sent(&notification).await,
+                Err(err) => {
+                    let retry_at = match err {
+                        DeliveryError::Transient(_)
+                            if (notification.attempts as u32) < settings.max_attempts =>
+                        {
+                            let doublings = (notification.attempts - 1).clamp(0, 16) as u32;
+                            let delay = settings
+                                .retry_delay_secs
+                                .saturating_mul(1 << doublings)
+                                .min(MAX_RETRY_DELAY_SECS);
+                            Some(DateTime::from_millis(
+                                DateTime::now().timestamp_millis() + delay as i64 * 1000,
+                            ))
+                        }
+                        _ => None,
+                    };
+                    tracing::warn!(
+                        notification = %id,
+                        channel = ?notification.channel,
+                        attempts = notification.attempts,
+                        giving_up = retry_at.is_none(),
+                        error = %err,
+                        "notification not delivered"
+                    );
+                    db.mark_notification_failed(&notification, &err.to_string(), retry_at)
+                        .await
+                }
+            };
+            match recorded {
+                Ok(true) => {}
+                Ok(false) => tracing::warn!(
+                    notification = %id,
+                    "lease on the notification ran out before its outcome was recorded"
+                ),
+                Err(err) => {
+                    tracing::error!(error = %err, notification = %id, "error updating the outbox")
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use crate::repository::mongodb_repo::{is_unreachable, MongoRepo};
+use actix_web::{rt::time, web::Data};
+use std::time::Duration;
+
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+const MAX_ATTEMPTS: u32 = 20;
+
+/// Creates the database's unique indexes, trying again for a while so the
+/// service can start before the database is reachable. Gives up at once
+/// when the database is reachable but refuses an index, e.g. because
+/// existing records break it, since retrying cannot fix that.
+pub async fn run(db: Data<MongoRepo>) {
+    for attempt in 1..=MAX_ATTEMPTS {
+        let failed = db.ensure_indexes().await;
+        if failed.is_empty() {
+            tracing::info!("database indexes are in place");
+            return;
+        }
+        for (index, err) in &failed {
+            tracing::error!(index, error = %err, "error creating database index");
+        }
+        if !failed.iter().any(|(_, err)| is_unreachable(err)) {
+            tracing::error!("database refused the indexes; fix the cause and restart");
+            return;
+        }
+        if attempt < MAX_ATTEMPTS {
+            time::sleep(RETRY_INTERVAL).await;
+        }
+    }
+    tracing::error!(attempts = MAX_ATTEMPTS, "gave up creating database indexes");
+}
@@ -0,0 +1,237 @@
+use crate::{
+    models::{
+        audit_entry::AuditContext, classroom::Classroom, course::Course, period::Period,
+        teacher::Teacher, timetable_entry::TimetableEntry, timetable_job::JobStatus,
+    },
+    repository::mongodb_repo::MongoRepo,
+    scheduler::{self, Outcome, Problem},
+};
+use actix_web::{
+    rt::{task, time},
+    web::Data,
+};
+use mongodb::bson::oid::ObjectId;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Who teaches what to whom, for one requirement of the problem.
+pub struct Lesson {
+    pub classroom: Classroom,
+    pub course: Course,
+    pub teacher: Teacher,
+}
+
+/// Everything a queued job needs. `lessons` line up with the problem's
+/// requirements and `periods` with its slots.
+pub struct Generation {
+    pub job_id: ObjectId,
+    pub dry_run: bool,
+    pub problem: Problem,
+    pub lessons: Vec<Lesson>,
+    pub periods: Vec<Period>,
+}
+
+/// Solves on a blocking thread, writing progress to the job every second.
+/// Unless it is a dry run, a solution replaces the timetables of the
+/// classrooms involved; the job fails instead if that cannot be done
+/// cleanly, e.g. because entries booked since queuing now clash.
+pub async fn run(db: Data<MongoRepo>, generation: Generation, ctx: AuditContext) {
+    let Generation {
+        job_id,
+        dry_run,
+        problem,
+        lessons,
+        periods,
+    } = generation;
+    let total = problem.lessons() as i64;
+    let progress = Arc::new(AtomicUsize::new(0));
+    let solver = {
+        let progress = progress.clone();
+        task::spawn_blocking(move || {
+            let outcome = scheduler::solve(&problem, &progress);
+            (problem, outcome)
+        })
+    };
+    let mut interval = time::interval(PROGRESS_INTERVAL);
+    while !solver.is_finished() {
+        interval.tick().await;
+        let placed = progress.load(Ordering::Relaxed) as i64;
+        if let Err(err) = db
+            .update_timetable_job_progress(job_id, JobStatus::Running, placed)
+            .await
+        {
+            tracing::error!(error = %err, "error updating timetable job");
+        }
+    }
+    let (status, problems, entries) = match solver.await {
+        Ok((problem, Outcome::Solved(mut placements))) => {
+            placements.sort_by_key(|placement| placement.slot);
+            let mut entries: Vec<TimetableEntry> = placements
+                .into_iter()
+                .map(|placement| {
+                    let lesson = &lessons[placement.requirement];
+                    TimetableEntry {
+                        id: None,
+                        classroom: lesson.classroom.clone(),
+                        course: lesson.course.clone(),
+                        teacher: lesson.teacher.clone(),
+                        room: problem.rooms[placement.room].name.clone(),
+                        period: periods[placement.slot].clone(),
+                    }
+                })
+                .collect();
+            let saved = match dry_run {
+                true => Ok(()),
+                false => replace_timetables(&db, &mut entries, &ctx).await,
+            };
+            match saved {
+                Ok(()) => (JobStatus::Succeeded, Vec::new(), entries),
+                Err(problems) => (JobStatus::Failed, problems, Vec::new()),
+            }
+        }
+        Ok((_, Outcome::Unsatisfiable(problems))) => {
+            (JobStatus::Unsatisfiable, problems, Vec::new())
+        }
+        Ok((_, Outcome::GaveUp(mut problems))) => {
+            problems.insert(
+                0,
+                "search limit reached before a timetable was found; try relaxing constraints"
+                    .to_string(),
+            );
+            (JobStatus::Failed, problems, Vec::new())
+        }
+        Err(err) => (JobStatus::Failed, vec![err.to_string()], Vec::new()),
+    };
+    let placed = match status {
+        JobStatus::Succeeded => total,
+        _ => progress.load(Ordering::Relaxed) as i64,
+    };
+    let finished = async {
+        db.update_timetable_job_progress(job_id, status, placed)
+            .await?;
+        db.finish_timetable_job(job_id, status, problems, &entries)
+            .await
+    };
+    match finished.await {
+        Ok(()) => tracing::info!(job = %job_id, ?status, "timetable generation finished"),
+        Err(err) => tracing::error!(error = %err, "error finishing timetable job"),
+    }
+}
+
+/// Where and what an entry is, for the job's problems.
+fn describe(entry: &TimetableEntry) -> String {
+    let day = format!("{:?}", entry.period.day).to_lowercase();
+    format!(
+        "{} {} {} {}-{} in {}",
+        entry.course.name,
+        day,
+        entry.period.number,
+        entry.period.start_time,
+        entry.period.end_time,
+        entry.room
+    )
+}
+
+/// Swaps the classrooms' current entries for the generated ones, setting
+/// the ids they are stored under. Nothing is written if a generated entry
+/// clashes with one booked for another classroom since the job was queued.
+/// Without transactions the swap deletes then inserts, and puts the old
+/// entries back if any write fails, so a failure leaves the timetables as
+/// they were. A write that errors may still have happened, so generated
+/// entries get their ids up front and every entry touched is undone.
+/// Returns the problems when it does not go through.
+async fn replace_timetables(
+    db: &MongoRepo,
+    entries: &mut [TimetableEntry],
+    ctx: &AuditContext,
+) -> Result<(), Vec<String>> {
+    let mut classroom_ids: Vec<ObjectId> = entries
+        .iter()
+        .filter_map(|entry| entry.classroom.id)
+        .collect();
+    classroom_ids.sort();
+    classroom_ids.dedup();
+    let mut problems = Vec::new();
+    for entry in entries.iter() {
+        let conflicts = db
+            .find_timetable_conflicts(entry)
+            .await
+            .map_err(|err| vec![err.to_string()])?;
+        for existing in conflicts {
+            let replaced = existing
+                .classroom
+                .id
+                .is_some_and(|id| classroom_ids.contains(&id));
+            if !replaced {
+                problems.push(format!(
+                    "{}: clashes with {} booked since the job was queued",
+                    describe(entry),
+                    describe(&existing)
+                ));
+            }
+        }
+    }
+    if !problems.is_empty() {
+        return Err(problems);
+    }
+    let current = db
+        .get_timetable_for_classrooms(&classroom_ids)
+        .await
+        .map_err(|err| vec![err.to_string()])?;
+    let mut removed = Vec::new();
+    for entry in &current {
+        let Some(id) = entry.id else { continue };
+        let deleted = db.delete_timetable_entry(&id.to_hex(), ctx).await;
+        removed.push(entry.clone());
+        if let Err(err) = deleted {
+            problems.push(format!("error removing {}: {}", describe(entry), err));
+            break;
+        }
+    }
+    if problems.is_empty() {
+        for entry in entries.iter_mut() {
+            entry.id = Some(ObjectId::new());
+            match db.create_timetable_entry(entry.clone(), ctx).await {
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    entry.id = None;
+                    problems.push(format!(
+                        "{}: clashes with an entry booked while saving",
+                        describe(entry)
+                    ));
+                    break;
+                }
+                Err(err) => {
+                    problems.push(format!("error saving {}: {}", describe(entry), err));
+                    break;
+                }
+            }
+        }
+    }
+    if problems.is_empty() {
+        return Ok(());
+    }
+    for entry in entries.iter_mut() {
+        if let Some(id) = entry.id.take() {
+            if let Err(err) = db.delete_timetable_entry(&id.to_hex(), ctx).await {
+                tracing::error!(error = %err, "error undoing generated timetable entry");
+            }
+        }
+    }
+    for entry in removed {
+        match db.create_timetable_entry(entry, ctx).await {
+            Ok(Some(_)) => {}
+            // Also the case when its delete errored without removing it.
+            Ok(None) => tracing::warn!("timetable entry present or taken while restoring it"),
+            Err(err) => tracing::error!(error = %err, "error restoring timetable entry"),
+        }
+    }
+    Err(problems)
+}
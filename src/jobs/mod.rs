@@ -0,0 +1,5 @@
+pub mod deliver_notifications;
+pub mod ensure_indexes;
+pub mod generate_timetable;
+pub mod purge_archived;
+pub mod remind_overdue;
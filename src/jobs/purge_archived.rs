@@ -0,0 +1,22 @@
+use crate::{models::audit_entry::AuditContext, repository::mongodb_repo::MongoRepo};
+use actix_web::{rt::time, web::Data};
+use mongodb::bson::DateTime;
+use std::time::Duration;
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+/// Once a day, permanently deletes people records that have been archived
+/// for longer than `retention_days`.
+pub async fn run(db: Data<MongoRepo>, retention_days: i64) {
+    let ctx = AuditContext::system("system:purge");
+    let mut interval = time::interval(Duration::from_millis(DAY_MILLIS as u64));
+    loop {
+        interval.tick().await;
+        let cutoff =
+            DateTime::from_millis(DateTime::now().timestamp_millis() - retention_days * DAY_MILLIS);
+        match db.purge_archived(cutoff, &ctx).await {
+            Ok(purged) => tracing::info!(purged, "purged archived records"),
+            Err(err) => tracing::error!(error = %err, "error purging archived records"),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use crate::{notifications::Notifier, repository::mongodb_repo::MongoRepo};
+use actix_web::{rt::time, web::Data};
+use mongodb::bson::DateTime;
+use std::time::Duration;
+
+const HOUR_SECS: u64 = 60 * 60;
+
+/// Every hour, queues reminders for invoices past their due date. Each
+/// reminder is queued once, so checking often only makes them prompt.
+pub async fn run(db: Data<MongoRepo>, notifier: Data<Notifier>) {
+    let mut interval = time::interval(Duration::from_secs(HOUR_SECS));
+    loop {
+        interval.tick().await;
+        let now = DateTime::now();
+        let invoices = match db.get_overdue_invoices(now).await {
+            Ok(invoices) => invoices,
+            Err(err) => {
+                tracing::error!(error = %err, "error finding overdue invoices");
+                continue;
+            }
+        };
+        let mut queued = 0;
+        for invoice in &invoices {
+            match notifier.overdue_fee(&db, invoice, now).await {
+                Ok(count) => queued += count,
+                Err(err) => tracing::error!(error = %err, "error queueing overdue reminder"),
+            }
+        }
+        if queued > 0 {
+            tracing::info!(queued, "queued overdue fee reminders");
+        }
+    }
+}
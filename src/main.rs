@@ -1,32 +1,105 @@
 mod api;
+mod config;
+mod format;
+mod graphql;
+mod ical;
+mod jobs;
+mod metrics;
 mod models;
+mod notifications;
+mod oneroster;
+mod pdf;
 mod repository;
+mod scheduler;
+mod storage;
+mod telemetry;
 
-use actix_web::{web::Data, App, HttpServer};
-use api::{parents_api::*, students_api::*, teachers_api::*};
+use actix_web::{rt, web::Data, App, HttpServer};
+use api::{auth::AdminToken, health_api, legacy, metrics_api, openapi::*, v1};
+use config::Settings;
+use notifications::Notifier;
 use repository::mongodb_repo::MongoRepo;
+use std::process;
+use storage::BlobStore;
+use tracing_actix_web::TracingLogger;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let db = MongoRepo::init().await;
+    let settings = Settings::load().unwrap_or_else(|err| {
+        eprintln!("Invalid configuration: {}", err);
+        process::exit(1);
+    });
+    telemetry::init(&settings.logging);
+    let db = MongoRepo::init(&settings.database)
+        .await
+        .unwrap_or_else(|err| {
+            tracing::error!(error = %err, "cannot connect to the database");
+            process::exit(1);
+        });
+    let blob_store: Data<dyn BlobStore> =
+        Data::from(storage::from_settings(&settings.storage, db.database()));
     let db_data = Data::new(db);
-    HttpServer::new(move || {
+    let admin_token = Data::new(AdminToken(settings.auth.admin_token.clone()));
+    let school = Data::new(settings.school.clone());
+    let storage_settings = Data::new(settings.storage.clone());
+    let features = settings.features;
+    rt::spawn(jobs::ensure_indexes::run(db_data.clone()));
+    if features.purge_archived {
+        rt::spawn(jobs::purge_archived::run(
+            db_data.clone(),
+            settings.archive.retention_days,
+        ));
+    }
+    let channels = notifications::channels_from_settings(&settings.notifications);
+    let notifier = Data::new(Notifier::new(
+        &settings.notifications,
+        &settings.school,
+        &channels,
+        features.notifications,
+    ));
+    if features.notifications {
+        rt::spawn(jobs::deliver_notifications::run(
+            db_data.clone(),
+            channels,
+            settings.notifications.clone(),
+        ));
+        rt::spawn(jobs::remind_overdue::run(db_data.clone(), notifier.clone()));
+    }
+    let schema = Data::new(graphql::schema(db_data.clone(), notifier.clone()));
+    let mut server = HttpServer::new(move || {
         App::new()
             .app_data(db_data.clone())
-            .service(create_parent)
-            .service(get_parent)
-            .service(update_parent)
-            .service(delete_parent)
-            .service(create_student)
-            .service(get_student)
-            .service(update_student)
-            .service(delete_student)
-            .service(create_teacher)
-            .service(get_teacher)
-            .service(update_teacher)
-            .service(delete_teacher)
+            .app_data(admin_token.clone())
+            .app_data(schema.clone())
+            .app_data(school.clone())
+            .app_data(blob_store.clone())
+            .app_data(storage_settings.clone())
+            .app_data(notifier.clone())
+            .wrap_fn(metrics::track)
+            .wrap(TracingLogger::<telemetry::RequestSpan>::new())
+            .wrap_fn(telemetry::assign_request_id)
+            .configure(health_api::config)
+            .configure(metrics_api::config)
+            .configure(v1::config)
+            .configure(oneroster::config)
+            .configure(|cfg| {
+                if features.legacy_routes {
+                    legacy::config(cfg);
+                }
+                if features.graphql {
+                    graphql::config(cfg);
+                }
+                if features.docs {
+                    cfg.service(get_openapi).service(get_docs);
+                }
+            })
     })
-    .bind(("127.0.0.1", 8080))?
-    .run()
-    .await
+    .client_request_timeout(settings.server.request_timeout());
+    if let Some(workers) = settings.server.workers {
+        server = server.workers(workers);
+    }
+    server
+        .bind((settings.server.host.as_str(), settings.server.port))?
+        .run()
+        .await
 }
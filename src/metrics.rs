@@ -0,0 +1,152 @@
+use actix_web::{
+    body::MessageBody,
+    dev::{Service, ServiceRequest, ServiceResponse},
+    Error,
+};
+use mongodb::event::command::{
+    CommandEventHandler, CommandFailedEvent, CommandStartedEvent, CommandSucceededEvent,
+};
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_gauge, register_histogram_vec, register_int_counter_vec, register_int_gauge, Encoder,
+    Gauge, HistogramVec, IntCounterVec, IntGauge, TextEncoder,
+};
+use std::{collections::HashMap, future::Future, sync::Mutex, time::Instant};
+
+static HTTP_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "http_requests_total",
+        "HTTP requests by route and status",
+        &["method", "route", "status"]
+    )
+    .unwrap()
+});
+
+static HTTP_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "http_request_duration_seconds",
+        "HTTP request latency by route and status",
+        &["method", "route", "status"]
+    )
+    .unwrap()
+});
+
+static REPO_DURATION: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "repository_operation_duration_seconds",
+        "MongoDB command latency by collection and operation",
+        &["collection", "operation", "outcome"]
+    )
+    .unwrap()
+});
+
+pub static ACTIVE_STUDENTS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "active_students",
+        "Students that are neither archived nor inactive"
+    )
+    .unwrap()
+});
+
+pub static ATTENDANCE_RATE_TODAY: Lazy<Gauge> = Lazy::new(|| {
+    register_gauge!(
+        "attendance_rate_today",
+        "Share of today's attendance records marked present"
+    )
+    .unwrap()
+});
+
+/// Renders every registered metric in the Prometheus text format.
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buffer)
+        .expect("Error encoding metrics");
+    String::from_utf8(buffer).expect("Prometheus text is UTF-8")
+}
+
+/// `App::wrap_fn` middleware counting and timing requests. Routes are
+/// labelled by their pattern (`/api/v1/students/{id}`) so ids don't explode
+/// the label set.
+pub fn track<S, B>(
+    req: ServiceRequest,
+    srv: &S,
+) -> impl Future<Output = Result<ServiceResponse<B>, Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    B: MessageBody,
+{
+    let started = Instant::now();
+    let method = req.method().to_string();
+    let route = req
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+    let response = srv.call(req);
+    async move {
+        let response = response.await?;
+        let status = response.status().as_u16().to_string();
+        let labels = [method.as_str(), route.as_str(), status.as_str()];
+        HTTP_REQUESTS.with_label_values(&labels).inc();
+        HTTP_DURATION
+            .with_label_values(&labels)
+            .observe(started.elapsed().as_secs_f64());
+        Ok(response)
+    }
+}
+
+/// Times every MongoDB command the driver runs, keyed by the collection the
+/// command targets.
+#[derive(Default)]
+pub struct RepositoryMetrics {
+    in_flight: Mutex<HashMap<i32, String>>,
+}
+
+impl RepositoryMetrics {
+    fn observe(&self, request_id: i32, operation: &str, outcome: &str, seconds: f64) {
+        let collection = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .remove(&request_id)
+            .unwrap_or_default();
+        REPO_DURATION
+            .with_label_values(&[&collection, operation, outcome])
+            .observe(seconds);
+    }
+}
+
+impl CommandEventHandler for RepositoryMetrics {
+    fn handle_command_started_event(&self, event: CommandStartedEvent) {
+        // The first key of a command names the operation, its value the
+        // collection (or `1` for database-level commands such as `ping`).
+        let collection = event
+            .command
+            .iter()
+            .next()
+            .and_then(|(_, value)| value.as_str())
+            .unwrap_or("")
+            .to_string();
+        self.in_flight
+            .lock()
+            .unwrap()
+            .insert(event.request_id, collection);
+    }
+
+    fn handle_command_succeeded_event(&self, event: CommandSucceededEvent) {
+        self.observe(
+            event.request_id,
+            &event.command_name,
+            "success",
+            event.duration.as_secs_f64(),
+        );
+    }
+
+    fn handle_command_failed_event(&self, event: CommandFailedEvent) {
+        self.observe(
+            event.request_id,
+            &event.command_name,
+            "failure",
+            event.duration.as_secs_f64(),
+        );
+    }
+}
@@ -0,0 +1,27 @@
+use super::{attachment::AttachmentRef, classroom::Classroom, course::Course, teacher::Teacher};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Homework set for a classroom in a course. Not to be confused with a
+/// `CourseAssignment`, which says who teaches the course.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Assignment {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    pub classroom: Classroom,
+    pub course: Course,
+    pub teacher: Teacher,
+    pub title: String,
+    pub instructions: String,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub due_at: DateTime,
+    pub max_points: f64,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub created_at: DateTime,
+    /// Worksheets and other material handed out with the assignment.
+    #[serde(default)]
+    pub attachments: Vec<AttachmentRef>,
+}
@@ -0,0 +1,55 @@
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// What an attachment belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum OwnerKind {
+    Student,
+    Teacher,
+    Assignment,
+    Submission,
+    Message,
+}
+
+/// An attachment as listed on its owner.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AttachmentRef {
+    #[schema(value_type = Object, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: ObjectId,
+    pub name: String,
+    pub content_type: String,
+    pub size: i64,
+}
+
+/// An uploaded file. The bytes are in the blob store under the hex id.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Attachment {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    pub owner_kind: OwnerKind,
+    #[schema(value_type = Object, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub owner_id: ObjectId,
+    pub name: String,
+    pub content_type: String,
+    pub size: i64,
+    /// Hex SHA-256 of the bytes, checked whenever they are read back.
+    pub sha256: String,
+    pub uploaded_by: String,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub uploaded_at: DateTime,
+}
+
+impl Attachment {
+    pub fn reference(&self) -> Option<AttachmentRef> {
+        Some(AttachmentRef {
+            id: self.id?,
+            name: self.name.clone(),
+            content_type: self.content_type.clone(),
+            size: self.size,
+        })
+    }
+}
@@ -2,11 +2,14 @@ use super::student::Student;
 use mongodb::bson::oid::ObjectId;
 use mongodb::bson::DateTime;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Attendance {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
     pub id: Option<ObjectId>,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
     pub date: DateTime,
     pub student: Student,
     pub status: bool,
@@ -0,0 +1,44 @@
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::{DateTime, Document};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    /// `admin` for holders of the admin token, otherwise `anonymous`.
+    pub actor: String,
+    /// Who an `X-Actor` header said the caller was. Nothing checks it, so it
+    /// is kept apart from `actor`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claimed_actor: Option<String>,
+    pub action: String,
+    pub entity: String,
+    #[schema(value_type = Object)]
+    pub entity_id: ObjectId,
+    #[schema(value_type = Object)]
+    pub changes: Document,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub timestamp: DateTime,
+    pub client_ip: Option<String>,
+}
+
+/// Who is performing a mutation, recorded alongside every audit entry.
+#[derive(Debug, Clone)]
+pub struct AuditContext {
+    pub actor: String,
+    pub claimed_actor: Option<String>,
+    pub client_ip: Option<String>,
+}
+
+impl AuditContext {
+    pub fn system(actor: &str) -> Self {
+        AuditContext {
+            actor: actor.to_string(),
+            claimed_actor: None,
+            client_ip: None,
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Grants a guardian's printable invoices and receipts to whoever holds the
+/// token, like a calendar feed; shared only with that guardian and revoked
+/// by deleting.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BillingLink {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    #[schema(value_type = Object, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub parent_id: ObjectId,
+    pub token: String,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub created_at: DateTime,
+}
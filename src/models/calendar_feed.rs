@@ -0,0 +1,27 @@
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedKind {
+    Student,
+    Teacher,
+    Classroom,
+}
+
+/// A subscribable `.ics` feed. Anyone holding the token can read the feed,
+/// so it is shared only with the people it is for and revoked by deleting.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CalendarFeed {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    pub kind: FeedKind,
+    #[schema(value_type = Object, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub subject_id: ObjectId,
+    pub token: String,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub created_at: DateTime,
+}
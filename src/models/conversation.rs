@@ -0,0 +1,80 @@
+use super::attachment::AttachmentRef;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Which side of a conversation someone is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Participant {
+    Teacher,
+    Parent,
+}
+
+impl Participant {
+    pub fn other(self) -> Self {
+        match self {
+            Participant::Teacher => Participant::Parent,
+            Participant::Parent => Participant::Teacher,
+        }
+    }
+
+    /// The conversation field counting messages this side has not read.
+    pub fn unread_field(self) -> &'static str {
+        match self {
+            Participant::Teacher => "teacher_unread",
+            Participant::Parent => "parent_unread",
+        }
+    }
+}
+
+/// A thread between a teacher and the guardian of a student they teach,
+/// about that student. Names are copied in when it is started so lists
+/// read without looking anyone up.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Conversation {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    #[schema(value_type = Object, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub teacher_id: ObjectId,
+    #[schema(value_type = Object, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub parent_id: ObjectId,
+    #[schema(value_type = Object, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub student_id: ObjectId,
+    pub teacher_name: String,
+    pub parent_name: String,
+    pub student_name: String,
+    pub subject: String,
+    pub started_by: Participant,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub created_at: DateTime,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub last_message_at: DateTime,
+    /// Messages from the parent the teacher has not read yet.
+    pub teacher_unread: i32,
+    /// Messages from the teacher the parent has not read yet.
+    pub parent_unread: i32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Message {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    #[schema(value_type = Object, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub conversation_id: ObjectId,
+    pub sender: Participant,
+    #[schema(value_type = Object, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub sender_id: ObjectId,
+    pub body: String,
+    #[serde(default)]
+    pub attachments: Vec<AttachmentRef>,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub sent_at: DateTime,
+    /// When the other side read it; the read receipt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub read_at: Option<DateTime>,
+}
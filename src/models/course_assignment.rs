@@ -0,0 +1,19 @@
+use super::{classroom::Classroom, course::Course, teacher::Teacher};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Who teaches a course to a classroom in a given term. A classroom's
+/// homeroom teacher is kept on the classroom itself.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CourseAssignment {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    pub classroom: Classroom,
+    pub course: Course,
+    pub teacher: Teacher,
+    pub year: i32,
+    /// Term of the school year, from 1
+    pub term: i32,
+}
@@ -0,0 +1,41 @@
+use super::student::StudentSummary;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DiscountKind {
+    /// `value` is a percentage of the term's fees.
+    Percent,
+    /// `value` is an amount in cents.
+    Fixed,
+}
+
+/// A sibling discount, scholarship or similar reduction granted to one
+/// student, taken off the invoices generated for them.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Discount {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    pub student: StudentSummary,
+    pub name: String,
+    pub kind: DiscountKind,
+    pub value: i64,
+    pub year: i32,
+    /// Only this term; every term of the year when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub term: Option<i32>,
+}
+
+impl Discount {
+    /// How much comes off `fees`, never more than `fees` itself.
+    pub fn amount_off(&self, fees: i64) -> i64 {
+        let off = match self.kind {
+            DiscountKind::Percent => fees * self.value / 100,
+            DiscountKind::Fixed => self.value,
+        };
+        off.clamp(0, fees.max(0))
+    }
+}
@@ -1,12 +1,16 @@
+use super::exam::Exam;
 use super::student::Student;
 use crate::models::course::Course;
 use mongodb::bson::oid::ObjectId;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Parent {
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ExamResult {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
     pub id: Option<ObjectId>,
+    pub exam: Exam,
     pub student: Student,
     pub course: Course,
     pub marks: String,
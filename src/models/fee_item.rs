@@ -0,0 +1,20 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Something every student in a grade is billed for in a term, such as
+/// tuition or a lab fee.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FeeItem {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    /// The grade as numbered on classrooms
+    pub grade_id: i64,
+    pub year: i32,
+    /// Term of the school year, from 1
+    pub term: i32,
+    pub name: String,
+    /// In the minor unit of the school's currency, e.g. cents
+    pub amount_cents: i64,
+}
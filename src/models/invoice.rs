@@ -0,0 +1,86 @@
+use super::student::StudentSummary;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum InvoiceStatus {
+    Open,
+    Paid,
+    /// Cancelled; no longer owed.
+    Void,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct InvoiceLine {
+    pub description: String,
+    /// Negative for discounts
+    pub amount_cents: i64,
+}
+
+/// What a student owes for one term.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Invoice {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    /// Gapless within the academic year; assigned once the invoice is
+    /// stored, so briefly absent on a new invoice.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub number: Option<i64>,
+    pub student: StudentSummary,
+    pub year: i32,
+    pub term: i32,
+    pub lines: Vec<InvoiceLine>,
+    pub total_cents: i64,
+    /// Sum of the payments recorded against the invoice.
+    pub paid_cents: i64,
+    pub status: InvoiceStatus,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub issued_at: DateTime,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub due_at: DateTime,
+}
+
+pub const INVOICE_PREFIX: &str = "INV";
+pub const RECEIPT_PREFIX: &str = "RCT";
+
+/// Name of the numbering series for a kind of billing document in `year`.
+pub fn series(prefix: &str, year: i32) -> String {
+    format!("{}-{}", prefix, year)
+}
+
+/// How a document number is printed, e.g. `INV-2026-000042`.
+pub fn document_number(prefix: &str, year: i32, number: i64) -> String {
+    format!("{}-{:06}", series(prefix, year), number)
+}
+
+/// `1234567` cents as `12,345.67`.
+pub fn format_cents(cents: i64) -> String {
+    let digits = (cents.abs() / 100).to_string();
+    let mut units = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            units.push(',');
+        }
+        units.push(digit);
+    }
+    let sign = if cents < 0 { "-" } else { "" };
+    format!("{}{}.{:02}", sign, units, cents.abs() % 100)
+}
+
+impl Invoice {
+    pub fn display_number(&self) -> Option<String> {
+        self.number
+            .map(|number| document_number(INVOICE_PREFIX, self.year, number))
+    }
+
+    pub fn outstanding_cents(&self) -> i64 {
+        match self.status {
+            InvoiceStatus::Void => 0,
+            InvoiceStatus::Open | InvoiceStatus::Paid => self.total_cents - self.paid_cents,
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use super::conversation::Participant;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Lets whoever holds the token read and send messages as one teacher or
+/// parent, like a billing link; shared only with that person and revoked
+/// by deleting.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MessagingLink {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    pub participant: Participant,
+    #[schema(value_type = Object, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub participant_id: ObjectId,
+    pub token: String,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub created_at: DateTime,
+}
@@ -1,11 +1,33 @@
-mod attendance;
-mod classroom;
-mod classroom_student;
-mod course;
-mod exam;
-mod exam_result;
-mod exam_type;
-mod grade;
+pub mod assignment;
+pub mod attachment;
+pub mod attendance;
+pub mod audit_entry;
+pub mod billing_link;
+pub mod calendar_feed;
+pub mod classroom;
+pub mod classroom_student;
+pub mod conversation;
+pub mod course;
+pub mod course_assignment;
+pub mod discount;
+pub mod exam;
+pub mod exam_result;
+pub mod exam_type;
+pub mod fee_item;
+pub mod grade;
+pub mod invoice;
+pub mod messaging_link;
+pub mod notification;
+pub mod notification_preferences;
 pub mod parent;
+pub mod payment;
+pub mod period;
+pub mod qualification;
+pub mod school_event;
+pub mod sequence;
 pub mod student;
+pub mod submission;
 pub mod teacher;
+pub mod timetable_entry;
+pub mod timetable_job;
+pub mod validators;
@@ -0,0 +1,70 @@
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelKind {
+    Email,
+    Sms,
+    InApp,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    Absence,
+    ExamResult,
+    OverdueFee,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Pending,
+    Sent,
+    /// Given up on, after a permanent error or too many attempts.
+    Failed,
+}
+
+/// A message to a guardian on one channel; the outbox holds every one ever
+/// queued, and in-app ones double as the guardian's inbox.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Notification {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    #[schema(value_type = Object, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub parent_id: ObjectId,
+    pub kind: NotificationKind,
+    pub channel: ChannelKind,
+    /// Email address or phone number; empty for the inbox
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+    /// Identifies what is being notified, so nobody hears of it twice on
+    /// the same channel.
+    pub event_key: String,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    /// When delivery is next tried while pending, or when the lease on it
+    /// runs out while it is being delivered.
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub next_attempt_at: DateTime,
+    /// Identifies the claim of the worker delivering it, if any, so only
+    /// that worker records the outcome.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lease_owner: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub created_at: DateTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub sent_at: Option<DateTime>,
+    /// When the guardian opened it in their inbox.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub read_at: Option<DateTime>,
+}
@@ -0,0 +1,37 @@
+use super::notification::{ChannelKind, NotificationKind};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// The channels a guardian wants each kind of notification on. Stored
+/// under the parent's id; guardians who never chose get [`Self::defaults`].
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NotificationPreferences {
+    #[serde(rename = "_id")]
+    #[schema(value_type = Object, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub parent_id: ObjectId,
+    pub absence: Vec<ChannelKind>,
+    pub exam_result: Vec<ChannelKind>,
+    pub overdue_fee: Vec<ChannelKind>,
+}
+
+impl NotificationPreferences {
+    /// Email and the inbox for everything; SMS, which costs, is opt-in.
+    pub fn defaults(parent_id: ObjectId) -> Self {
+        let channels = vec![ChannelKind::Email, ChannelKind::InApp];
+        NotificationPreferences {
+            parent_id,
+            absence: channels.clone(),
+            exam_result: channels.clone(),
+            overdue_fee: channels,
+        }
+    }
+
+    pub fn channels(&self, kind: NotificationKind) -> &[ChannelKind] {
+        match kind {
+            NotificationKind::Absence => &self.absence,
+            NotificationKind::ExamResult => &self.exam_result,
+            NotificationKind::OverdueFee => &self.overdue_fee,
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentMethod {
+    Cash,
+    Card,
+    BankTransfer,
+    Cheque,
+    Other,
+}
+
+/// Money received against an invoice; one entry in the payment ledger.
+/// Entries are never changed or removed once recorded.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Payment {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    #[schema(value_type = Object, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub invoice_id: ObjectId,
+    #[schema(value_type = Object, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub student_id: ObjectId,
+    pub amount_cents: i64,
+    pub method: PaymentMethod,
+    /// Receipt, transaction or cheque number, if any
+    pub reference: String,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub received_at: DateTime,
+    pub recorded_by: String,
+    /// Gapless within the invoice's academic year; assigned once the
+    /// payment is stored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub receipt_number: Option<i64>,
+}
@@ -0,0 +1,64 @@
+use crate::models::validators;
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+/// Days of the school week, in calendar order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+/// A slot in the weekly timetable, e.g. Monday's third period.
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "ends_after_start", skip_on_field_errors = false))]
+pub struct Period {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    pub day: Weekday,
+    /// Position within the day, from 1
+    #[validate(range(min = 1, message = "must be at least 1"))]
+    pub number: i32,
+    #[validate(regex(path = "validators::TIME_OF_DAY", message = "must be a time as HH:MM"))]
+    #[schema(example = "08:00")]
+    pub start_time: String,
+    #[validate(regex(path = "validators::TIME_OF_DAY", message = "must be a time as HH:MM"))]
+    #[schema(example = "08:40")]
+    pub end_time: String,
+}
+
+impl Period {
+    /// Length of the period, zero if its times do not parse.
+    pub fn minutes(&self) -> i64 {
+        match (
+            minute_of_day(&self.start_time),
+            minute_of_day(&self.end_time),
+        ) {
+            (Some(start), Some(end)) if end > start => end - start,
+            _ => 0,
+        }
+    }
+}
+
+fn minute_of_day(time: &str) -> Option<i64> {
+    let (hours, minutes) = time.split_once(':')?;
+    Some(hours.parse::<i64>().ok()? * 60 + minutes.parse::<i64>().ok()?)
+}
+
+fn ends_after_start(period: &Period) -> Result<(), ValidationError> {
+    if period.end_time <= period.start_time {
+        let mut error = ValidationError::new("ends_before_start");
+        error.message = Some("end_time must be after start_time".into());
+        return Err(error);
+    }
+    Ok(())
+}
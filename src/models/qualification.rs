@@ -0,0 +1,14 @@
+use super::{course::Course, teacher::Teacher};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A course the teacher may be assigned to teach.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Qualification {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    pub teacher: Teacher,
+    pub course: Course,
+}
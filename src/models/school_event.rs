@@ -0,0 +1,37 @@
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+/// Something on the school calendar for everyone, such as a sports day or
+/// the end of term.
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "ends_after_start", skip_on_field_errors = false))]
+pub struct SchoolEvent {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    #[validate(length(min = 1, message = "must not be empty"))]
+    pub title: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub location: String,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub starts_at: DateTime,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041382800000"}}))]
+    pub ends_at: DateTime,
+    /// Whole days from the date of `starts_at` through that of `ends_at`.
+    #[serde(default)]
+    pub all_day: bool,
+}
+
+fn ends_after_start(event: &SchoolEvent) -> Result<(), ValidationError> {
+    if event.ends_at < event.starts_at {
+        let mut error = ValidationError::new("ends_before_start");
+        error.message = Some("ends_at must not be before starts_at".into());
+        return Err(error);
+    }
+    Ok(())
+}
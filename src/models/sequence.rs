@@ -0,0 +1,14 @@
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+
+/// A gapless numbering series, such as one academic year's invoices.
+/// `last` is the number most recently handed out and `owner` the document
+/// it went to, so that a claim interrupted before the number was written
+/// onto the document can be finished by whoever allocates next.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sequence {
+    #[serde(rename = "_id")]
+    pub id: String,
+    pub last: i64,
+    pub owner: ObjectId,
+}
@@ -1,22 +1,87 @@
-use crate::models::parent::Parent;
+use crate::models::{attachment::AttachmentRef, parent::Parent, validators};
 use mongodb::bson::oid::ObjectId;
 use mongodb::bson::DateTime;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
+#[validate(schema(function = "joined_after_birth", skip_on_field_errors = false))]
 pub struct Student {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
     pub id: Option<ObjectId>,
+    #[validate(email(message = "must be a valid email address"))]
     pub email: String,
     pub password: String,
+    #[validate(length(min = 1, message = "must not be empty"))]
     pub fname: String,
+    #[validate(length(min = 1, message = "must not be empty"))]
     pub lname: String,
+    #[validate(custom(
+        function = "validators::plausible_birth_date",
+        message = "must be a plausible birth date"
+    ))]
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
     pub dob: DateTime,
+    #[validate(regex(path = "validators::E164", message = "must be an E.164 phone number"))]
     pub phone: String,
+    #[validate(regex(path = "validators::E164", message = "must be an E.164 phone number"))]
     pub mobile: String,
+    #[validate]
     pub parent: Parent,
+    #[validate(custom(
+        function = "validators::plausible_join_date",
+        message = "must be a plausible join date"
+    ))]
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
     pub date_of_join: DateTime,
     pub status: bool,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
     pub last_login_date: DateTime,
     pub last_login_ip: String,
+    /// Photos, forms and other files; managed through the attachment
+    /// endpoints, not by updating the record.
+    #[serde(default)]
+    pub attachments: Vec<AttachmentRef>,
+    #[serde(default)]
+    pub version: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub deleted_at: Option<DateTime>,
+}
+
+/// What billing records keep of a student: who they are and who pays for
+/// them, without the login or contact details of either.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct StudentSummary {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    pub fname: String,
+    pub lname: String,
+    /// The guardian when the record was made; they may have changed since.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub parent_id: Option<ObjectId>,
+}
+
+impl From<&Student> for StudentSummary {
+    fn from(student: &Student) -> Self {
+        StudentSummary {
+            id: student.id,
+            fname: student.fname.clone(),
+            lname: student.lname.clone(),
+            parent_id: student.parent.id,
+        }
+    }
+}
+
+fn joined_after_birth(student: &Student) -> Result<(), ValidationError> {
+    if student.date_of_join < student.dob {
+        let mut error = ValidationError::new("joined_before_birth");
+        error.message = Some("date_of_join must be after dob".into());
+        return Err(error);
+    }
+    Ok(())
 }
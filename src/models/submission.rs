@@ -0,0 +1,32 @@
+use super::{assignment::Assignment, attachment::AttachmentRef, student::Student};
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SubmissionGrade {
+    pub points: f64,
+    pub comment: String,
+    pub graded_by: String,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub graded_at: DateTime,
+}
+
+/// A student's answer to an assignment.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Submission {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    pub assignment: Assignment,
+    pub student: Student,
+    pub text: String,
+    pub files: Vec<AttachmentRef>,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub submitted_at: DateTime,
+    /// Handed in after the assignment was due.
+    pub late: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grade: Option<SubmissionGrade>,
+}
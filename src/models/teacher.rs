@@ -1,19 +1,43 @@
+use crate::models::{attachment::AttachmentRef, validators};
 use mongodb::bson::oid::ObjectId;
 use mongodb::bson::DateTime;
 use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use validator::Validate;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, ToSchema)]
 pub struct Teacher {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
     pub id: Option<ObjectId>,
+    #[validate(email(message = "must be a valid email address"))]
     pub email: String,
     pub password: String,
+    #[validate(length(min = 1, message = "must not be empty"))]
     pub fname: String,
+    #[validate(length(min = 1, message = "must not be empty"))]
     pub lname: String,
+    #[validate(custom(
+        function = "validators::plausible_birth_date",
+        message = "must be a plausible birth date"
+    ))]
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
     pub dob: DateTime,
+    #[validate(regex(path = "validators::E164", message = "must be an E.164 phone number"))]
     pub phone: String,
+    #[validate(regex(path = "validators::E164", message = "must be an E.164 phone number"))]
     pub mobile: String,
     pub status: bool,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
     pub last_login_date: DateTime,
     pub last_login_ip: String,
+    /// Photos, forms and other files; managed through the attachment
+    /// endpoints, not by updating the record.
+    #[serde(default)]
+    pub attachments: Vec<AttachmentRef>,
+    #[serde(default)]
+    pub version: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub deleted_at: Option<DateTime>,
 }
@@ -0,0 +1,17 @@
+use super::{classroom::Classroom, course::Course, period::Period, teacher::Teacher};
+use mongodb::bson::oid::ObjectId;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// A classroom taking a course with a teacher, in a room, during a period.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TimetableEntry {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    pub classroom: Classroom,
+    pub course: Course,
+    pub teacher: Teacher,
+    pub room: String,
+    pub period: Period,
+}
@@ -0,0 +1,41 @@
+use super::timetable_entry::TimetableEntry;
+use mongodb::bson::oid::ObjectId;
+use mongodb::bson::DateTime;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    /// No timetable meets the constraints; `problems` says why.
+    Unsatisfiable,
+    Failed,
+}
+
+/// A background run of the timetable generator, polled for progress.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TimetableJob {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>, example = json!({"$oid": "63455a1b5f3b8e2a4c1d9e01"}))]
+    pub id: Option<ObjectId>,
+    pub status: JobStatus,
+    /// Only propose a timetable; leave the stored one untouched.
+    pub dry_run: bool,
+    pub requested_by: String,
+    /// Periods to place, summed over all requirements.
+    pub total: i64,
+    /// Most periods placed at once so far; the search backtracks, so this
+    /// only says how close it has come.
+    pub placed: i64,
+    pub problems: Vec<String>,
+    /// The generated timetable, once succeeded.
+    pub entries: Vec<TimetableEntry>,
+    #[schema(value_type = Object, example = json!({"$date": {"$numberLong": "1041379200000"}}))]
+    pub created_at: DateTime,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    pub finished_at: Option<DateTime>,
+}
@@ -0,0 +1,61 @@
+use mongodb::bson::{oid::ObjectId, DateTime};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use validator::ValidationError;
+
+const YEAR_MILLIS: i64 = 365 * 24 * 60 * 60 * 1000;
+const OLDEST_PLAUSIBLE_AGE_YEARS: i64 = 120;
+
+/// Phone numbers in E.164 form, e.g. `+256772123456`.
+pub static E164: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\+[1-9]\d{1,14}$").unwrap());
+
+fn years_ago(years: i64) -> i64 {
+    DateTime::now().timestamp_millis() - years * YEAR_MILLIS
+}
+
+pub fn plausible_birth_date(dob: &DateTime) -> Result<(), ValidationError> {
+    let millis = dob.timestamp_millis();
+    if millis > DateTime::now().timestamp_millis() {
+        return Err(ValidationError::new("birth_date_in_future"));
+    }
+    if millis < years_ago(OLDEST_PLAUSIBLE_AGE_YEARS) {
+        return Err(ValidationError::new("birth_date_too_old"));
+    }
+    Ok(())
+}
+
+/// Join dates may be recorded up to a year ahead for next year's intake.
+pub fn plausible_join_date(date_of_join: &DateTime) -> Result<(), ValidationError> {
+    let millis = date_of_join.timestamp_millis();
+    if millis > years_ago(-1) {
+        return Err(ValidationError::new("join_date_too_far_ahead"));
+    }
+    if millis < years_ago(OLDEST_PLAUSIBLE_AGE_YEARS) {
+        return Err(ValidationError::new("join_date_too_old"));
+    }
+    Ok(())
+}
+
+/// Times of day as 24-hour `HH:MM`, e.g. `08:30`. Zero-padded, so they
+/// order correctly as strings.
+pub static TIME_OF_DAY: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^([01]\d|2[0-3]):[0-5]\d$").unwrap());
+
+pub fn object_id(id: &str) -> Result<(), ValidationError> {
+    match ObjectId::parse_str(id) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(ValidationError::new("invalid_object_id")),
+    }
+}
+
+pub fn object_ids(ids: &[String]) -> Result<(), ValidationError> {
+    ids.iter().try_for_each(|id| object_id(id))
+}
+
+pub fn positive(value: f64) -> Result<(), ValidationError> {
+    if value > 0.0 {
+        Ok(())
+    } else {
+        Err(ValidationError::new("not_positive"))
+    }
+}
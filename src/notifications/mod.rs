@@ -0,0 +1,256 @@
+//! Telling guardians about absences, exam results and overdue fees. An
+//! event is rendered from its template into an outbox entry for each
+//! channel the guardian wants, and the delivery job sends entries as they
+//! fall due, retrying the ones that fail.
+
+mod sms;
+mod smtp;
+
+pub use sms::SmsChannel;
+pub use smtp::SmtpChannel;
+
+use crate::repository::Error;
+use crate::{
+    config::{MessageTemplate, NotificationSettings, SchoolSettings},
+    format::date,
+    models::{
+        attendance::Attendance,
+        exam_result::ExamResult,
+        invoice::{format_cents, Invoice},
+        notification::{ChannelKind, DeliveryStatus, Notification, NotificationKind},
+        notification_preferences::NotificationPreferences,
+    },
+    repository::mongodb_repo::MongoRepo,
+};
+use async_trait::async_trait;
+use mongodb::bson::{oid::ObjectId, DateTime};
+use std::{fmt, sync::Arc};
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+
+#[derive(Debug)]
+pub enum DeliveryError {
+    /// Might work later: the server was down, busy or unreachable.
+    Transient(String),
+    /// Will fail the same way every time, such as a rejected address.
+    Permanent(String),
+}
+
+impl fmt::Display for DeliveryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeliveryError::Transient(message) | DeliveryError::Permanent(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+#[async_trait]
+pub trait Channel: Send + Sync {
+    fn kind(&self) -> ChannelKind;
+    /// Delivers the notification to its recipient.
+    async fn send(&self, notification: &Notification) -> Result<(), DeliveryError>;
+}
+
+/// The inbox is the outbox entries themselves, so delivering one only
+/// marks it sent, which makes it show up.
+pub struct InAppChannel;
+
+#[async_trait]
+impl Channel for InAppChannel {
+    fn kind(&self) -> ChannelKind {
+        ChannelKind::InApp
+    }
+
+    async fn send(&self, _: &Notification) -> Result<(), DeliveryError> {
+        Ok(())
+    }
+}
+
+/// The inbox, plus email and SMS where configured.
+pub fn channels_from_settings(settings: &NotificationSettings) -> Vec<Arc<dyn Channel>> {
+    let mut channels: Vec<Arc<dyn Channel>> = vec![Arc::new(InAppChannel)];
+    if !settings.smtp.host.is_empty() {
+        channels.push(Arc::new(SmtpChannel::new(&settings.smtp)));
+    }
+    if !settings.sms.gateway_url.is_empty() {
+        channels.push(Arc::new(SmsChannel::new(&settings.sms)));
+    }
+    channels
+}
+
+/// Queues notifications for guardians; what the delivery job sends.
+pub struct Notifier {
+    settings: NotificationSettings,
+    school: SchoolSettings,
+    /// Channels that can deliver; choices of any other are skipped.
+    available: Vec<ChannelKind>,
+    /// Whether notifications are delivered at all; nothing is queued when
+    /// they are not, so no backlog builds up to be sent once enabled.
+    enabled: bool,
+}
+
+impl Notifier {
+    pub fn new(
+        settings: &NotificationSettings,
+        school: &SchoolSettings,
+        channels: &[Arc<dyn Channel>],
+        enabled: bool,
+    ) -> Self {
+        Notifier {
+            settings: settings.clone(),
+            school: school.clone(),
+            available: channels.iter().map(|channel| channel.kind()).collect(),
+            enabled,
+        }
+    }
+
+    fn template(&self, kind: NotificationKind) -> &MessageTemplate {
+        let templates = &self.settings.templates;
+        match kind {
+            NotificationKind::Absence => &templates.absence,
+            NotificationKind::ExamResult => &templates.exam_result,
+            NotificationKind::OverdueFee => &templates.overdue_fee,
+        }
+    }
+
+    /// Queues `kind` for the guardian on each channel they want that is
+    /// available and that they have an address for, addressed as they are
+    /// now rather than as recorded with the event. Returns how many were
+    /// queued; none for an archived guardian, an event already queued or
+    /// while notifications are disabled.
+    pub async fn notify(
+        &self,
+        db: &MongoRepo,
+        parent_id: ObjectId,
+        kind: NotificationKind,
+        event_key: String,
+        values: Vec<(&str, String)>,
+    ) -> Result<usize, Error> {
+        if !self.enabled {
+            return Ok(0);
+        }
+        let id = parent_id.to_hex();
+        let Some(parent) = db.get_parent(&id, false).await? else {
+            return Ok(0);
+        };
+        let preferences = db
+            .get_notification_preferences(&id)
+            .await?
+            .unwrap_or_else(|| NotificationPreferences::defaults(parent_id));
+        let mut values = values;
+        values.push(("guardian", format!("{} {}", parent.fname, parent.lname)));
+        values.push(("school", self.school.name.clone()));
+        let (subject, body) = self.template(kind).render(&values);
+
+        let mut queued = 0;
+        for &channel in preferences.channels(kind) {
+            let recipient = match channel {
+                ChannelKind::Email => parent.email.clone(),
+                ChannelKind::Sms => parent.mobile.clone(),
+                ChannelKind::InApp => String::new(),
+            };
+            if !self.available.contains(&channel)
+                || (channel != ChannelKind::InApp && recipient.is_empty())
+            {
+                continue;
+            }
+            let now = DateTime::now();
+            let notification = Notification {
+                id: None,
+                parent_id,
+                kind,
+                channel,
+                recipient,
+                subject: subject.clone(),
+                body: body.clone(),
+                event_key: event_key.clone(),
+                status: DeliveryStatus::Pending,
+                attempts: 0,
+                next_attempt_at: now,
+                lease_owner: None,
+                last_error: None,
+                created_at: now,
+                sent_at: None,
+                read_at: None,
+            };
+            if db.queue_notification(notification).await?.is_some() {
+                queued += 1;
+            }
+        }
+        Ok(queued)
+    }
+
+    /// Tells the guardian when their child is marked absent.
+    pub async fn absence(&self, db: &MongoRepo, attendance: &Attendance) -> Result<usize, Error> {
+        let (Some(id), Some(parent_id)) = (attendance.id, attendance.student.parent.id) else {
+            return Ok(0);
+        };
+        if attendance.status {
+            return Ok(0);
+        }
+        let student = &attendance.student;
+        let values = vec![
+            ("student", format!("{} {}", student.fname, student.lname)),
+            ("date", date(attendance.date)),
+            ("remark", attendance.remark.clone()),
+        ];
+        let key = format!("absence:{}", id.to_hex());
+        self.notify(db, parent_id, NotificationKind::Absence, key, values)
+            .await
+    }
+
+    pub async fn exam_result(&self, db: &MongoRepo, result: &ExamResult) -> Result<usize, Error> {
+        let (Some(id), Some(parent_id)) = (result.id, result.student.parent.id) else {
+            return Ok(0);
+        };
+        let student = &result.student;
+        let values = vec![
+            ("student", format!("{} {}", student.fname, student.lname)),
+            ("exam", result.exam.name.clone()),
+            ("course", result.course.name.clone()),
+            ("marks", result.marks.clone()),
+        ];
+        let key = format!("exam_result:{}", id.to_hex());
+        self.notify(db, parent_id, NotificationKind::ExamResult, key, values)
+            .await
+    }
+
+    /// Reminds the student's current guardian of an unpaid invoice, at most
+    /// once per `overdue_reminder_days` while it stays overdue.
+    pub async fn overdue_fee(
+        &self,
+        db: &MongoRepo,
+        invoice: &Invoice,
+        now: DateTime,
+    ) -> Result<usize, Error> {
+        let (Some(id), Some(student_id)) = (invoice.id, invoice.student.id) else {
+            return Ok(0);
+        };
+        let guardian = db
+            .get_student(&student_id.to_hex(), true)
+            .await?
+            .and_then(|student| student.parent.id);
+        let Some(parent_id) = guardian else {
+            return Ok(0);
+        };
+        let days_overdue =
+            (now.timestamp_millis() - invoice.due_at.timestamp_millis()) / DAY_MILLIS;
+        let reminder = days_overdue / self.settings.overdue_reminder_days;
+        let student = &invoice.student;
+        let values = vec![
+            ("student", format!("{} {}", student.fname, student.lname)),
+            (
+                "invoice",
+                invoice.display_number().unwrap_or_else(|| id.to_hex()),
+            ),
+            ("balance", format_cents(invoice.outstanding_cents())),
+            ("currency", self.school.currency.clone()),
+            ("due_date", date(invoice.due_at)),
+        ];
+        let key = format!("overdue_fee:{}:{}", id.to_hex(), reminder);
+        self.notify(db, parent_id, NotificationKind::OverdueFee, key, values)
+            .await
+    }
+}
@@ -0,0 +1,63 @@
+use super::{Channel, DeliveryError};
+use crate::{
+    config::SmsSettings,
+    models::notification::{ChannelKind, Notification},
+};
+use actix_web::rt::task;
+use async_trait::async_trait;
+use serde_json::json;
+use std::time::Duration;
+
+/// Sends text messages through an HTTP gateway, one request per message.
+pub struct SmsChannel {
+    settings: SmsSettings,
+    agent: ureq::Agent,
+}
+
+impl SmsChannel {
+    pub fn new(settings: &SmsSettings) -> Self {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(Duration::from_secs(settings.timeout_secs.max(1)))
+            .build();
+        SmsChannel {
+            settings: settings.clone(),
+            agent,
+        }
+    }
+}
+
+#[async_trait]
+impl Channel for SmsChannel {
+    fn kind(&self) -> ChannelKind {
+        ChannelKind::Sms
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), DeliveryError> {
+        let request = self.agent.post(&self.settings.gateway_url).set(
+            "Authorization",
+            &format!("Bearer {}", self.settings.api_key),
+        );
+        let payload = json!({
+            "from": self.settings.sender,
+            "to": notification.recipient,
+            "text": notification.body,
+        });
+        task::spawn_blocking(move || match request.send_json(payload) {
+            Ok(_) => Ok(()),
+            // Throttled or broken at the gateway's end: try again later.
+            Err(ureq::Error::Status(status, _)) if status == 429 || status >= 500 => Err(
+                DeliveryError::Transient(format!("SMS gateway answered {}", status)),
+            ),
+            Err(ureq::Error::Status(status, _)) => Err(DeliveryError::Permanent(format!(
+                "SMS gateway rejected the message with {}",
+                status
+            ))),
+            Err(err) => Err(DeliveryError::Transient(format!(
+                "SMS gateway unreachable: {}",
+                err
+            ))),
+        })
+        .await
+        .map_err(|err| DeliveryError::Transient(err.to_string()))?
+    }
+}
@@ -0,0 +1,315 @@
+//! Just enough SMTP (RFC 5321) to hand plain-text notifications to a mail
+//! server: STARTTLS or implicit TLS, AUTH PLAIN and one recipient per
+//! message. Runs on the blocking pool; a message is a handful of lines.
+//!
+//! A maintained client such as `lettre` should take this over once it can
+//! be added to the build; until then this stays deliberately small and
+//! refuses anything it cannot send safely.
+
+use super::{Channel, DeliveryError};
+use crate::{
+    config::{SmtpSecurity, SmtpSettings},
+    models::notification::{ChannelKind, Notification},
+};
+use actix_web::rt::task;
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use rustls::{ClientConfig, ClientConnection, OwnedTrustAnchor, RootCertStore, StreamOwned};
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::Arc,
+    time::Duration,
+};
+use uuid::Uuid;
+
+pub struct SmtpChannel {
+    settings: SmtpSettings,
+    tls: Arc<ClientConfig>,
+}
+
+impl SmtpChannel {
+    pub fn new(settings: &SmtpSettings) -> Self {
+        let mut roots = RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                anchor.subject,
+                anchor.spki,
+                anchor.name_constraints,
+            )
+        }));
+        let tls = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        SmtpChannel {
+            settings: settings.clone(),
+            tls: Arc::new(tls),
+        }
+    }
+}
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.read(buf),
+            Stream::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Stream::Plain(stream) => stream.write(buf),
+            Stream::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Stream::Plain(stream) => stream.flush(),
+            Stream::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+fn io_error(err: io::Error) -> DeliveryError {
+    DeliveryError::Transient(format!("SMTP connection failed: {}", err))
+}
+
+fn secure(tcp: TcpStream, host: &str, tls: &Arc<ClientConfig>) -> Result<Stream, DeliveryError> {
+    let name = host
+        .try_into()
+        .map_err(|_| DeliveryError::Permanent(format!("invalid SMTP host {:?}", host)))?;
+    let connection = ClientConnection::new(tls.clone(), name)
+        .map_err(|err| DeliveryError::Transient(format!("TLS setup failed: {}", err)))?;
+    Ok(Stream::Tls(Box::new(StreamOwned::new(connection, tcp))))
+}
+
+struct Session {
+    stream: BufReader<Stream>,
+}
+
+impl Session {
+    /// Reads a reply, which may span several `NNN-` lines, and checks its
+    /// code. Temporary (4xx) failures are worth retrying; others are not.
+    fn expect(&mut self, expected: &[u16]) -> Result<(), DeliveryError> {
+        let mut text = String::new();
+        loop {
+            let mut line = String::new();
+            if self.stream.read_line(&mut line).map_err(io_error)? == 0 {
+                return Err(DeliveryError::Transient(
+                    "SMTP server closed the connection".to_string(),
+                ));
+            }
+            text.push_str(line.trim_end());
+            if line.as_bytes().get(3) != Some(&b'-') {
+                break;
+            }
+            text.push(' ');
+        }
+        let code: u16 = text
+            .get(..3)
+            .and_then(|code| code.parse().ok())
+            .unwrap_or(0);
+        if expected.contains(&code) {
+            Ok(())
+        } else if (400..500).contains(&code) {
+            Err(DeliveryError::Transient(format!("SMTP: {}", text)))
+        } else {
+            Err(DeliveryError::Permanent(format!("SMTP: {}", text)))
+        }
+    }
+
+    fn command(&mut self, line: &str, expected: &[u16]) -> Result<(), DeliveryError> {
+        let stream = self.stream.get_mut();
+        stream
+            .write_all(format!("{}\r\n", line).as_bytes())
+            .and_then(|_| stream.flush())
+            .map_err(io_error)?;
+        self.expect(expected)
+    }
+}
+
+/// The address as it goes in `MAIL FROM` and `RCPT TO`, refusing one that
+/// could end the command early or smuggle in another.
+fn mailbox(address: &str) -> Result<&str, DeliveryError> {
+    let unsafe_char = |c: char| c.is_control() || c.is_whitespace() || c == '<' || c == '>';
+    if address.is_empty() || address.contains(unsafe_char) {
+        return Err(DeliveryError::Permanent(format!(
+            "invalid email address {:?}",
+            address
+        )));
+    }
+    Ok(address)
+}
+
+/// Removes line breaks, which would start a new header.
+fn header_value(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+/// RFC 2047 encoding for a header that is not plain ASCII.
+fn encoded_word(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?UTF-8?B?{}?=", STANDARD.encode(value))
+    }
+}
+
+fn domain(address: &str) -> &str {
+    address
+        .rsplit_once('@')
+        .map_or("localhost", |(_, domain)| domain)
+}
+
+/// The message, base64 encoded so no line can be taken for the end of the
+/// data and any text survives servers without 8BITMIME.
+fn message(from: &str, to: &str, subject: &str, body: &str) -> String {
+    let body = STANDARD.encode(body.replace("\r\n", "\n").replace('\n', "\r\n"));
+    let lines: Vec<&str> = body
+        .as_bytes()
+        .chunks(76)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap_or_default())
+        .collect();
+    format!(
+        "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMessage-ID: <{}@{}>\r\n\
+         MIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\n\
+         Content-Transfer-Encoding: base64\r\n\r\n{}\r\n.",
+        header_value(from),
+        header_value(to),
+        encoded_word(&header_value(subject)),
+        Utc::now().to_rfc2822(),
+        Uuid::new_v4().simple(),
+        domain(from),
+        lines.join("\r\n"),
+    )
+}
+
+fn deliver(
+    settings: &SmtpSettings,
+    tls: &Arc<ClientConfig>,
+    to: &str,
+    message: &str,
+) -> Result<(), DeliveryError> {
+    let timeout = Duration::from_secs(settings.timeout_secs.max(1));
+    let address = (settings.host.as_str(), settings.port)
+        .to_socket_addrs()
+        .map_err(io_error)?
+        .next()
+        .ok_or_else(|| DeliveryError::Transient(format!("cannot resolve {}", settings.host)))?;
+    let tcp = TcpStream::connect_timeout(&address, timeout).map_err(io_error)?;
+    tcp.set_read_timeout(Some(timeout)).map_err(io_error)?;
+    tcp.set_write_timeout(Some(timeout)).map_err(io_error)?;
+    let stream = match settings.security {
+        SmtpSecurity::Tls => secure(tcp, &settings.host, tls)?,
+        SmtpSecurity::StartTls | SmtpSecurity::None => Stream::Plain(tcp),
+    };
+    let mut session = Session {
+        stream: BufReader::new(stream),
+    };
+    let from = mailbox(&settings.from)?;
+    let to = mailbox(to)?;
+    let ehlo = format!("EHLO {}", domain(from));
+    session.expect(&[220])?;
+    session.command(&ehlo, &[250])?;
+    if settings.security == SmtpSecurity::StartTls {
+        session.command("STARTTLS", &[220])?;
+        // Anything sent before the handshake could be taken as replies to
+        // commands sent after it.
+        if !session.stream.buffer().is_empty() {
+            return Err(DeliveryError::Permanent(
+                "SMTP server sent data before the TLS handshake".to_string(),
+            ));
+        }
+        let Stream::Plain(tcp) = session.stream.into_inner() else {
+            unreachable!("STARTTLS is only sent on a plain connection");
+        };
+        session = Session {
+            stream: BufReader::new(secure(tcp, &settings.host, tls)?),
+        };
+        session.command(&ehlo, &[250])?;
+    }
+    if !settings.username.is_empty() {
+        if settings.security == SmtpSecurity::None {
+            return Err(DeliveryError::Permanent(
+                "refusing to send SMTP credentials over an unencrypted connection".to_string(),
+            ));
+        }
+        let credentials =
+            STANDARD.encode(format!("\0{}\0{}", settings.username, settings.password));
+        session.command(&format!("AUTH PLAIN {}", credentials), &[235])?;
+    }
+    session.command(&format!("MAIL FROM:<{}>", from), &[250])?;
+    session.command(&format!("RCPT TO:<{}>", to), &[250, 251])?;
+    session.command("DATA", &[354])?;
+    session.command(message, &[250])?;
+    // The message is accepted; a failed goodbye does not matter.
+    let _ = session.command("QUIT", &[221]);
+    Ok(())
+}
+
+#[async_trait]
+impl Channel for SmtpChannel {
+    fn kind(&self) -> ChannelKind {
+        ChannelKind::Email
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<(), DeliveryError> {
+        let settings = self.settings.clone();
+        let tls = self.tls.clone();
+        let to = notification.recipient.clone();
+        let message = message(
+            &settings.from,
+            &to,
+            &notification.subject,
+            &notification.body,
+        );
+        task::spawn_blocking(move || deliver(&settings, &tls, &to, &message))
+            .await
+            .map_err(|err| DeliveryError::Transient(err.to_string()))?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mailbox_refuses_addresses_that_break_the_envelope() {
+        assert_eq!(
+            mailbox("office@school.example").ok(),
+            Some("office@school.example")
+        );
+        for address in [
+            "",
+            "a@b.example>\r\nRCPT TO:<c@d.example",
+            "a b@c.example",
+            "<a@b.example",
+        ] {
+            assert!(matches!(mailbox(address), Err(DeliveryError::Permanent(_))));
+        }
+    }
+
+    #[test]
+    fn message_keeps_headers_on_one_line_and_ends_the_data() {
+        let message = message(
+            "office@school.example",
+            "parent@home.example",
+            "Absence\r\nBcc: someone@else.example",
+            "line one\n.\nline two",
+        );
+        assert!(message.contains("Subject: Absence  Bcc: someone@else.example\r\n"));
+        assert!(!message.contains("\r\nBcc:"));
+        assert!(message.ends_with("\r\n."));
+        assert_eq!(message.matches("\r\n.\r\n").count(), 0);
+    }
+}
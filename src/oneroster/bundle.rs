@@ -0,0 +1,175 @@
+use super::{
+    AcademicSession, Category, Class, Course, Demographics, Enrollment, ExamResult, LineItem, Org,
+    Record, Roster, User,
+};
+use crate::config::SchoolSettings;
+use std::{
+    collections::HashMap,
+    io::{Cursor, Read, Write},
+};
+use zip::{result::ZipResult, write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+/// `manifest.csv` declares which files the bundle holds; everything is
+/// sent in full, never as a delta.
+const MANIFEST: [(&str, &str); 15] = [
+    ("file.academicSessions", "bulk"),
+    ("file.categories", "bulk"),
+    ("file.classes", "bulk"),
+    ("file.classResources", "absent"),
+    ("file.courses", "bulk"),
+    ("file.courseResources", "absent"),
+    ("file.demographics", "bulk"),
+    ("file.enrollments", "bulk"),
+    ("file.lineItems", "bulk"),
+    ("file.orgs", "bulk"),
+    ("file.resources", "absent"),
+    ("file.results", "bulk"),
+    ("file.users", "bulk"),
+    ("manifest.version", "1.0"),
+    ("oneroster.version", "1.1"),
+];
+
+fn write_csv<W, I, T>(writer: &mut csv::Writer<W>, cells: I)
+where
+    W: Write,
+    I: IntoIterator<Item = T>,
+    T: AsRef<[u8]>,
+{
+    writer
+        .write_record(cells)
+        .expect("Writing CSV to memory cannot fail");
+}
+
+fn to_csv<T: Record>(records: &[T]) -> Vec<u8> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    write_csv(
+        &mut writer,
+        ["sourcedId", "status", "dateLastModified"]
+            .iter()
+            .chain(T::HEADERS),
+    );
+    for record in records {
+        let mut cells = vec![
+            record.sourced_id().to_string(),
+            String::new(),
+            String::new(),
+        ];
+        cells.extend(record.cells());
+        write_csv(&mut writer, cells);
+    }
+    writer
+        .into_inner()
+        .expect("Writing CSV to memory cannot fail")
+}
+
+fn manifest(school: &SchoolSettings) -> Vec<u8> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    write_csv(&mut writer, ["propertyName", "value"]);
+    for (property, value) in MANIFEST {
+        write_csv(&mut writer, [property, value]);
+    }
+    write_csv(&mut writer, ["source.systemName", "school-manager"]);
+    write_csv(&mut writer, ["source.systemCode", school.code.as_str()]);
+    writer
+        .into_inner()
+        .expect("Writing CSV to memory cannot fail")
+}
+
+/// Zips the roster as a OneRoster 1.1 bulk CSV bundle.
+pub fn write_bundle(roster: &Roster, school: &SchoolSettings) -> ZipResult<Vec<u8>> {
+    let files = [
+        ("manifest.csv", manifest(school)),
+        (Org::FILE, to_csv(&roster.orgs)),
+        (AcademicSession::FILE, to_csv(&roster.academic_sessions)),
+        (Course::FILE, to_csv(&roster.courses)),
+        (Class::FILE, to_csv(&roster.classes)),
+        (User::FILE, to_csv(&roster.users)),
+        (Demographics::FILE, to_csv(&roster.demographics)),
+        (Enrollment::FILE, to_csv(&roster.enrollments)),
+        (Category::FILE, to_csv(&roster.categories)),
+        (LineItem::FILE, to_csv(&roster.line_items)),
+        (ExamResult::FILE, to_csv(&roster.results)),
+    ];
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, contents) in files {
+        zip.start_file(name, options)?;
+        zip.write_all(&contents)?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+/// One CSV row keyed by column header, with its line in the file.
+pub struct BundleRow {
+    pub line: u64,
+    fields: HashMap<String, String>,
+}
+
+impl BundleRow {
+    /// The cell under `header`, empty when the column is absent.
+    pub fn get(&self, header: &str) -> &str {
+        self.fields.get(header).map_or("", String::as_str)
+    }
+
+    /// A comma-separated list cell such as `agentSourcedIds`.
+    pub fn list(&self, header: &str) -> Vec<&str> {
+        self.get(header)
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect()
+    }
+}
+
+/// The CSV files of an uploaded bundle, by file name.
+pub struct Bundle {
+    files: HashMap<String, Vec<BundleRow>>,
+}
+
+impl Bundle {
+    pub fn read(bytes: &[u8]) -> Result<Self, String> {
+        let mut archive = ZipArchive::new(Cursor::new(bytes))
+            .map_err(|err| format!("unreadable zip: {}", err))?;
+        let mut files = HashMap::new();
+        for index in 0..archive.len() {
+            let mut file = archive
+                .by_index(index)
+                .map_err(|err| format!("unreadable zip: {}", err))?;
+            // Some tools zip the files inside a folder; only the name matters.
+            let name = match file.name().rsplit('/').next() {
+                Some(name) if name.ends_with(".csv") => name.to_string(),
+                _ => continue,
+            };
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents)
+                .map_err(|err| format!("unreadable {}: {}", name, err))?;
+            let mut reader = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .flexible(true)
+                .from_reader(contents.as_slice());
+            let headers = reader
+                .headers()
+                .map_err(|err| format!("unreadable {}: {}", name, err))?
+                .clone();
+            let mut rows = Vec::new();
+            for record in reader.records() {
+                let record = record.map_err(|err| format!("unreadable {}: {}", name, err))?;
+                rows.push(BundleRow {
+                    line: record.position().map_or(0, |position| position.line()),
+                    fields: headers
+                        .iter()
+                        .zip(record.iter())
+                        .map(|(header, value)| (header.to_string(), value.to_string()))
+                        .collect(),
+                });
+            }
+            files.insert(name, rows);
+        }
+        Ok(Bundle { files })
+    }
+
+    /// Rows of `file`; none when the bundle leaves it out.
+    pub fn rows(&self, file: &str) -> &[BundleRow] {
+        self.files.get(file).map_or(&[], Vec::as_slice)
+    }
+}
@@ -0,0 +1,906 @@
+//! OneRoster 1.1 view of the school. One snapshot backs both the read-only
+//! REST binding under `/ims/oneroster/v1p1` and the zipped CSV bundle.
+//!
+//! The school keeps no academic year or term records, so every year a
+//! classroom or exam falls in becomes a `schoolYear` session. Classrooms are
+//! not tied to a course; each is exported as a homeroom class of a
+//! per-grade homeroom course.
+
+mod bundle;
+mod rest;
+
+pub use bundle::{write_bundle, Bundle, BundleRow};
+pub use rest::config;
+
+use crate::repository::Error;
+use crate::{config::SchoolSettings, repository::mongodb_repo::MongoRepo};
+use mongodb::bson::{oid::ObjectId, DateTime};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+/// Marks carry no scale of their own; line items report them out of 100.
+const RESULT_VALUE_MAX: f64 = 100.0;
+
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RefKind {
+    AcademicSession,
+    Category,
+    Class,
+    Course,
+    LineItem,
+    Org,
+    Student,
+    User,
+}
+
+impl RefKind {
+    fn collection(self) -> &'static str {
+        match self {
+            RefKind::AcademicSession => "academicSessions",
+            RefKind::Category => "categories",
+            RefKind::Class => "classes",
+            RefKind::Course => "courses",
+            RefKind::LineItem => "lineItems",
+            RefKind::Org => "orgs",
+            RefKind::Student => "students",
+            RefKind::User => "users",
+        }
+    }
+}
+
+/// A reference to another OneRoster record.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GuidRef {
+    href: String,
+    sourced_id: String,
+    #[serde(rename = "type")]
+    kind: RefKind,
+}
+
+impl GuidRef {
+    fn new(kind: RefKind, sourced_id: impl Into<String>) -> Self {
+        let sourced_id = sourced_id.into();
+        GuidRef {
+            href: format!("{}/{}/{}", rest::BASE_PATH, kind.collection(), sourced_id),
+            sourced_id,
+            kind,
+        }
+    }
+}
+
+fn join_ids(refs: &[GuidRef]) -> String {
+    refs.iter()
+        .map(|guid| guid.sourced_id.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn ref_id(guid: &Option<GuidRef>) -> String {
+    guid.as_ref()
+        .map(|guid| guid.sourced_id.clone())
+        .unwrap_or_default()
+}
+
+/// Fields every record carries. Bulk CSV files leave `status` and
+/// `dateLastModified` blank, as the specification requires.
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Meta {
+    sourced_id: String,
+    status: &'static str,
+    date_last_modified: String,
+}
+
+/// A OneRoster record type, with its CSV file layout.
+pub trait Record: Serialize {
+    const FILE: &'static str;
+    /// Column headers after `sourcedId,status,dateLastModified`.
+    const HEADERS: &'static [&'static str];
+
+    fn meta(&self) -> &Meta;
+
+    /// Cells for `HEADERS`, in order.
+    fn cells(&self) -> Vec<String>;
+
+    fn sourced_id(&self) -> &str {
+        &self.meta().sourced_id
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Org {
+    #[serde(flatten)]
+    meta: Meta,
+    name: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    identifier: String,
+}
+
+impl Record for Org {
+    const FILE: &'static str = "orgs.csv";
+    const HEADERS: &'static [&'static str] = &["name", "type", "identifier", "parentSourcedId"];
+
+    fn meta(&self) -> &Meta {
+        &self.meta
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.name.clone(),
+            self.kind.to_string(),
+            self.identifier.clone(),
+            String::new(),
+        ]
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AcademicSession {
+    #[serde(flatten)]
+    meta: Meta,
+    title: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    start_date: String,
+    end_date: String,
+    school_year: String,
+}
+
+impl Record for AcademicSession {
+    const FILE: &'static str = "academicSessions.csv";
+    const HEADERS: &'static [&'static str] = &[
+        "title",
+        "type",
+        "startDate",
+        "endDate",
+        "parentSourcedId",
+        "schoolYear",
+    ];
+
+    fn meta(&self) -> &Meta {
+        &self.meta
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.title.clone(),
+            self.kind.to_string(),
+            self.start_date.clone(),
+            self.end_date.clone(),
+            String::new(),
+            self.school_year.clone(),
+        ]
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Course {
+    #[serde(flatten)]
+    meta: Meta,
+    title: String,
+    course_code: String,
+    grades: Vec<String>,
+    subjects: Vec<String>,
+    org: GuidRef,
+}
+
+impl Record for Course {
+    const FILE: &'static str = "courses.csv";
+    const HEADERS: &'static [&'static str] = &[
+        "schoolYearSourcedId",
+        "title",
+        "courseCode",
+        "grades",
+        "orgSourcedId",
+        "subjects",
+        "subjectCodes",
+    ];
+
+    fn meta(&self) -> &Meta {
+        &self.meta
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            String::new(),
+            self.title.clone(),
+            self.course_code.clone(),
+            self.grades.join(","),
+            self.org.sourced_id.clone(),
+            self.subjects.join(","),
+            String::new(),
+        ]
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Class {
+    #[serde(flatten)]
+    meta: Meta,
+    title: String,
+    class_code: String,
+    class_type: &'static str,
+    location: String,
+    grades: Vec<String>,
+    subjects: Vec<String>,
+    course: GuidRef,
+    school: GuidRef,
+    terms: Vec<GuidRef>,
+}
+
+impl Record for Class {
+    const FILE: &'static str = "classes.csv";
+    const HEADERS: &'static [&'static str] = &[
+        "title",
+        "grades",
+        "courseSourcedId",
+        "classCode",
+        "classType",
+        "location",
+        "schoolSourcedId",
+        "termSourcedIds",
+        "subjects",
+        "subjectCodes",
+        "periods",
+    ];
+
+    fn meta(&self) -> &Meta {
+        &self.meta
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.title.clone(),
+            self.grades.join(","),
+            self.course.sourced_id.clone(),
+            self.class_code.clone(),
+            self.class_type.to_string(),
+            self.location.clone(),
+            self.school.sourced_id.clone(),
+            join_ids(&self.terms),
+            self.subjects.join(","),
+            String::new(),
+            String::new(),
+        ]
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    #[serde(flatten)]
+    meta: Meta,
+    username: String,
+    enabled_user: String,
+    given_name: String,
+    family_name: String,
+    middle_name: String,
+    role: &'static str,
+    identifier: String,
+    email: String,
+    sms: String,
+    phone: String,
+    agents: Vec<GuidRef>,
+    orgs: Vec<GuidRef>,
+    grades: Vec<String>,
+}
+
+impl User {
+    pub fn role(&self) -> &str {
+        self.role
+    }
+}
+
+impl Record for User {
+    const FILE: &'static str = "users.csv";
+    const HEADERS: &'static [&'static str] = &[
+        "enabledUser",
+        "orgSourcedIds",
+        "role",
+        "username",
+        "userIds",
+        "givenName",
+        "familyName",
+        "middleName",
+        "identifier",
+        "email",
+        "sms",
+        "phone",
+        "agentSourcedIds",
+        "grades",
+        "password",
+    ];
+
+    fn meta(&self) -> &Meta {
+        &self.meta
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.enabled_user.clone(),
+            join_ids(&self.orgs),
+            self.role.to_string(),
+            self.username.clone(),
+            String::new(),
+            self.given_name.clone(),
+            self.family_name.clone(),
+            self.middle_name.clone(),
+            self.identifier.clone(),
+            self.email.clone(),
+            self.sms.clone(),
+            self.phone.clone(),
+            join_ids(&self.agents),
+            self.grades.join(","),
+            // Passwords never leave the system.
+            String::new(),
+        ]
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Demographics {
+    #[serde(flatten)]
+    meta: Meta,
+    birth_date: String,
+}
+
+impl Record for Demographics {
+    const FILE: &'static str = "demographics.csv";
+    const HEADERS: &'static [&'static str] = &[
+        "birthDate",
+        "sex",
+        "americanIndianOrAlaskaNative",
+        "asian",
+        "blackOrAfricanAmerican",
+        "nativeHawaiianOrOtherPacificIslander",
+        "white",
+        "demographicRaceTwoOrMoreRaces",
+        "hispanicOrLatinoEthnicity",
+        "countryOfBirthCode",
+        "stateOfBirthAbbreviation",
+        "cityOfBirth",
+        "publicSchoolResidenceStatus",
+    ];
+
+    fn meta(&self) -> &Meta {
+        &self.meta
+    }
+
+    fn cells(&self) -> Vec<String> {
+        let mut cells = vec![String::new(); Self::HEADERS.len()];
+        cells[0] = self.birth_date.clone();
+        cells
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Enrollment {
+    #[serde(flatten)]
+    meta: Meta,
+    role: &'static str,
+    primary: String,
+    user: GuidRef,
+    class: GuidRef,
+    school: GuidRef,
+}
+
+impl Enrollment {
+    pub fn role(&self) -> &str {
+        self.role
+    }
+
+    pub fn class_id(&self) -> &str {
+        &self.class.sourced_id
+    }
+
+    pub fn user_id(&self) -> &str {
+        &self.user.sourced_id
+    }
+}
+
+impl Record for Enrollment {
+    const FILE: &'static str = "enrollments.csv";
+    const HEADERS: &'static [&'static str] = &[
+        "classSourcedId",
+        "schoolSourcedId",
+        "userSourcedId",
+        "role",
+        "primary",
+        "beginDate",
+        "endDate",
+    ];
+
+    fn meta(&self) -> &Meta {
+        &self.meta
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.class.sourced_id.clone(),
+            self.school.sourced_id.clone(),
+            self.user.sourced_id.clone(),
+            self.role.to_string(),
+            self.primary.clone(),
+            String::new(),
+            String::new(),
+        ]
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Category {
+    #[serde(flatten)]
+    meta: Meta,
+    title: String,
+}
+
+impl Record for Category {
+    const FILE: &'static str = "categories.csv";
+    const HEADERS: &'static [&'static str] = &["title"];
+
+    fn meta(&self) -> &Meta {
+        &self.meta
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.title.clone()]
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LineItem {
+    #[serde(flatten)]
+    meta: Meta,
+    title: String,
+    description: String,
+    assign_date: String,
+    due_date: String,
+    /// Unset when the student was in no classroom that year.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    class: Option<GuidRef>,
+    category: GuidRef,
+    grading_period: GuidRef,
+    result_value_min: f64,
+    result_value_max: f64,
+}
+
+impl Record for LineItem {
+    const FILE: &'static str = "lineItems.csv";
+    const HEADERS: &'static [&'static str] = &[
+        "title",
+        "description",
+        "assignDate",
+        "dueDate",
+        "classSourcedId",
+        "categorySourcedId",
+        "gradingPeriodSourcedId",
+        "resultValueMin",
+        "resultValueMax",
+    ];
+
+    fn meta(&self) -> &Meta {
+        &self.meta
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.title.clone(),
+            self.description.clone(),
+            self.assign_date.clone(),
+            self.due_date.clone(),
+            ref_id(&self.class),
+            self.category.sourced_id.clone(),
+            self.grading_period.sourced_id.clone(),
+            self.result_value_min.to_string(),
+            self.result_value_max.to_string(),
+        ]
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExamResult {
+    #[serde(flatten)]
+    meta: Meta,
+    line_item: GuidRef,
+    student: GuidRef,
+    score_status: &'static str,
+    /// Unset when the marks are not a number; they are kept in `comment`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    score: Option<f64>,
+    score_date: String,
+    comment: String,
+}
+
+impl Record for ExamResult {
+    const FILE: &'static str = "results.csv";
+    const HEADERS: &'static [&'static str] = &[
+        "lineItemSourcedId",
+        "studentSourcedId",
+        "scoreStatus",
+        "score",
+        "scoreDate",
+        "comment",
+    ];
+
+    fn meta(&self) -> &Meta {
+        &self.meta
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.line_item.sourced_id.clone(),
+            self.student.sourced_id.clone(),
+            self.score_status.to_string(),
+            self.score
+                .map(|score| score.to_string())
+                .unwrap_or_default(),
+            self.score_date.clone(),
+            self.comment.clone(),
+        ]
+    }
+}
+
+/// The fields teachers, parents and students share.
+struct Person<'a> {
+    email: &'a str,
+    fname: &'a str,
+    lname: &'a str,
+    phone: &'a str,
+    mobile: &'a str,
+    status: bool,
+    dob: &'a DateTime,
+}
+
+/// Every OneRoster record the school can describe, built from one read of
+/// the repository.
+pub struct Roster {
+    pub orgs: Vec<Org>,
+    pub academic_sessions: Vec<AcademicSession>,
+    pub courses: Vec<Course>,
+    pub classes: Vec<Class>,
+    pub users: Vec<User>,
+    pub demographics: Vec<Demographics>,
+    pub enrollments: Vec<Enrollment>,
+    pub categories: Vec<Category>,
+    pub line_items: Vec<LineItem>,
+    pub results: Vec<ExamResult>,
+}
+
+fn to_id(id: &Option<ObjectId>) -> String {
+    id.map(|id| id.to_hex()).unwrap_or_default()
+}
+
+fn to_day(date: &DateTime) -> String {
+    date.try_to_rfc3339_string()
+        .map(|date| date[..10].to_string())
+        .unwrap_or_default()
+}
+
+fn year_of(date: &DateTime) -> i32 {
+    to_day(date)
+        .get(..4)
+        .and_then(|year| year.parse().ok())
+        .unwrap_or_default()
+}
+
+/// Session ids are derived, so an export and a later import agree on them.
+pub fn session_id(year: i32) -> String {
+    format!("year-{}", year)
+}
+
+pub fn homeroom_course_id(grade_id: i64) -> String {
+    format!("homeroom-{}", grade_id)
+}
+
+/// CEDS grade level code, as OneRoster expects.
+pub fn grade_code(grade_id: i64) -> String {
+    format!("{:02}", grade_id)
+}
+
+impl Roster {
+    pub async fn load(db: &MongoRepo, school: &SchoolSettings) -> Result<Self, Error> {
+        let teachers = db.get_all_teachers(false).await?;
+        let parents = db.get_all_parents(false).await?;
+        let students = db.get_all_students(false).await?;
+        let classrooms = db.get_all_classrooms().await?;
+        let enrollments = db.get_all_enrollments().await?;
+        let courses = db.get_all_courses().await?;
+        let exams = db.get_all_exams().await?;
+        let exam_results = db.get_all_exam_results().await?;
+
+        let modified = DateTime::now().try_to_rfc3339_string().unwrap_or_default();
+        let meta = |sourced_id: String| Meta {
+            sourced_id,
+            status: "active",
+            date_last_modified: modified.clone(),
+        };
+        let org = GuidRef::new(RefKind::Org, school.code.clone());
+
+        let years: BTreeSet<i32> = classrooms
+            .iter()
+            .map(|classroom| classroom.year)
+            .chain(exams.iter().map(|exam| year_of(&exam.start_date)))
+            .collect();
+        let academic_sessions = years
+            .into_iter()
+            .map(|year| AcademicSession {
+                meta: meta(session_id(year)),
+                title: year.to_string(),
+                kind: "schoolYear",
+                start_date: format!("{}-01-01", year),
+                end_date: format!("{}-12-31", year),
+                school_year: year.to_string(),
+            })
+            .collect();
+
+        let grade_ids: BTreeSet<i64> = classrooms
+            .iter()
+            .map(|classroom| classroom.grade_id)
+            .collect();
+        let courses = courses
+            .iter()
+            .map(|course| Course {
+                meta: meta(to_id(&course.id)),
+                title: course.name.clone(),
+                course_code: String::new(),
+                grades: vec![course.grade.name.clone()],
+                subjects: Vec::new(),
+                org: org.clone(),
+            })
+            .chain(grade_ids.into_iter().map(|grade_id| Course {
+                meta: meta(homeroom_course_id(grade_id)),
+                title: format!("Homeroom, grade {}", grade_id),
+                course_code: String::new(),
+                grades: vec![grade_code(grade_id)],
+                subjects: Vec::new(),
+                org: org.clone(),
+            }))
+            .collect();
+
+        let classes = classrooms
+            .iter()
+            .map(|classroom| Class {
+                meta: meta(to_id(&classroom.id)),
+                title: format!("Grade {} {}", classroom.grade_id, classroom.section),
+                class_code: classroom.section.clone(),
+                class_type: "homeroom",
+                location: String::new(),
+                grades: vec![grade_code(classroom.grade_id)],
+                subjects: Vec::new(),
+                course: GuidRef::new(RefKind::Course, homeroom_course_id(classroom.grade_id)),
+                school: org.clone(),
+                terms: vec![GuidRef::new(
+                    RefKind::AcademicSession,
+                    session_id(classroom.year),
+                )],
+            })
+            .collect();
+
+        // Students' classrooms, for their grades and to place results.
+        let mut placements: HashMap<String, Vec<(String, i32, i64)>> = HashMap::new();
+        for enrollment in &enrollments {
+            placements
+                .entry(to_id(&enrollment.student.id))
+                .or_default()
+                .push((
+                    to_id(&enrollment.classroom.id),
+                    enrollment.classroom.year,
+                    enrollment.classroom.grade_id,
+                ));
+        }
+        let mut children: HashMap<String, Vec<GuidRef>> = HashMap::new();
+        for student in &students {
+            children
+                .entry(to_id(&student.parent.id))
+                .or_default()
+                .push(GuidRef::new(RefKind::User, to_id(&student.id)));
+        }
+
+        let mut users = Vec::new();
+        let mut demographics = Vec::new();
+        // Ids are unique across people; keep the first role a record is seen in.
+        let mut seen = HashSet::new();
+        let mut add_user = |id: String,
+                            role: &'static str,
+                            person: Person,
+                            agents: Vec<GuidRef>,
+                            grades: Vec<String>| {
+            if !seen.insert(id.clone()) {
+                return;
+            }
+            users.push(User {
+                meta: meta(id.clone()),
+                username: person.email.to_string(),
+                enabled_user: person.status.to_string(),
+                given_name: person.fname.to_string(),
+                family_name: person.lname.to_string(),
+                middle_name: String::new(),
+                role,
+                identifier: String::new(),
+                email: person.email.to_string(),
+                sms: person.mobile.to_string(),
+                phone: person.phone.to_string(),
+                agents,
+                orgs: vec![org.clone()],
+                grades,
+            });
+            demographics.push(Demographics {
+                meta: meta(id),
+                birth_date: to_day(person.dob),
+            });
+        };
+        for teacher in &teachers {
+            add_user(
+                to_id(&teacher.id),
+                "teacher",
+                Person {
+                    email: &teacher.email,
+                    fname: &teacher.fname,
+                    lname: &teacher.lname,
+                    phone: &teacher.phone,
+                    mobile: &teacher.mobile,
+                    status: teacher.status,
+                    dob: &teacher.dob,
+                },
+                Vec::new(),
+                Vec::new(),
+            );
+        }
+        for student in &students {
+            let id = to_id(&student.id);
+            let grades: BTreeSet<String> = placements
+                .get(&id)
+                .into_iter()
+                .flatten()
+                .map(|(_, _, grade_id)| grade_code(*grade_id))
+                .collect();
+            add_user(
+                id,
+                "student",
+                Person {
+                    email: &student.email,
+                    fname: &student.fname,
+                    lname: &student.lname,
+                    phone: &student.phone,
+                    mobile: &student.mobile,
+                    status: student.status,
+                    dob: &student.dob,
+                },
+                vec![GuidRef::new(RefKind::User, to_id(&student.parent.id))],
+                grades.into_iter().collect(),
+            );
+        }
+        for parent in &parents {
+            let id = to_id(&parent.id);
+            let agents = children.get(&id).cloned().unwrap_or_default();
+            add_user(
+                id,
+                "parent",
+                Person {
+                    email: &parent.email,
+                    fname: &parent.fname,
+                    lname: &parent.lname,
+                    phone: &parent.phone,
+                    mobile: &parent.mobile,
+                    status: parent.status,
+                    dob: &parent.dob,
+                },
+                agents,
+                Vec::new(),
+            );
+        }
+
+        let enrollments = classrooms
+            .iter()
+            .map(|classroom| Enrollment {
+                meta: meta(format!("{}-teacher", to_id(&classroom.id))),
+                role: "teacher",
+                primary: "true".to_string(),
+                user: GuidRef::new(RefKind::User, to_id(&classroom.teacher.id)),
+                class: GuidRef::new(RefKind::Class, to_id(&classroom.id)),
+                school: org.clone(),
+            })
+            .chain(enrollments.iter().map(|enrollment| Enrollment {
+                meta: meta(to_id(&enrollment.id)),
+                role: "student",
+                primary: "false".to_string(),
+                user: GuidRef::new(RefKind::User, to_id(&enrollment.student.id)),
+                class: GuidRef::new(RefKind::Class, to_id(&enrollment.classroom.id)),
+                school: org.clone(),
+            }))
+            .collect();
+
+        let mut categories = BTreeMap::new();
+        let mut line_items = BTreeMap::new();
+        let mut results = Vec::new();
+        for result in &exam_results {
+            let exam = &result.exam;
+            let category_id = exam
+                .exam_type
+                .id
+                .map(|id| id.to_hex())
+                .unwrap_or_else(|| exam.exam_type.name.clone());
+            categories
+                .entry(category_id.clone())
+                .or_insert_with(|| Category {
+                    meta: meta(category_id.clone()),
+                    title: exam.exam_type.name.clone(),
+                });
+
+            // A line item is one exam in one course for one class; the
+            // student's class is the one they were in the year of the exam.
+            let year = year_of(&exam.start_date);
+            let student_id = to_id(&result.student.id);
+            let class_id = placements.get(&student_id).and_then(|placed| {
+                placed
+                    .iter()
+                    .find(|(_, class_year, _)| *class_year == year)
+                    .or_else(|| placed.first())
+                    .map(|(class_id, _, _)| class_id.clone())
+            });
+            let mut line_item_id = format!("{}-{}", to_id(&exam.id), to_id(&result.course.id));
+            if let Some(class_id) = &class_id {
+                line_item_id = format!("{}-{}", line_item_id, class_id);
+            }
+            line_items
+                .entry(line_item_id.clone())
+                .or_insert_with(|| LineItem {
+                    meta: meta(line_item_id.clone()),
+                    title: format!("{}, {}", exam.name, result.course.name),
+                    description: exam.exam_type.name.clone(),
+                    assign_date: to_day(&exam.start_date),
+                    due_date: to_day(&exam.start_date),
+                    class: class_id.map(|class_id| GuidRef::new(RefKind::Class, class_id)),
+                    category: GuidRef::new(RefKind::Category, category_id),
+                    grading_period: GuidRef::new(RefKind::AcademicSession, session_id(year)),
+                    result_value_min: 0.0,
+                    result_value_max: RESULT_VALUE_MAX,
+                });
+
+            let score = result.marks.trim().parse::<f64>().ok();
+            results.push(ExamResult {
+                meta: meta(to_id(&result.id)),
+                line_item: GuidRef::new(RefKind::LineItem, line_item_id),
+                student: GuidRef::new(RefKind::Student, student_id),
+                score_status: "fully graded",
+                score,
+                score_date: to_day(&exam.start_date),
+                comment: if score.is_none() {
+                    result.marks.clone()
+                } else {
+                    String::new()
+                },
+            });
+        }
+
+        Ok(Roster {
+            orgs: vec![Org {
+                meta: meta(school.code.clone()),
+                name: school.name.clone(),
+                kind: "school",
+                identifier: school.code.clone(),
+            }],
+            academic_sessions,
+            courses,
+            classes,
+            users,
+            demographics,
+            enrollments,
+            categories: categories.into_values().collect(),
+            line_items: line_items.into_values().collect(),
+            results,
+        })
+    }
+}
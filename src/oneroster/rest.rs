@@ -0,0 +1,201 @@
+//! Read-only OneRoster 1.1 REST binding. Collections take `limit` (default
+//! 100) and `offset` and report the unpaged size in `X-Total-Count`;
+//! `filter`, `sort` and `fields` are not supported.
+
+use super::{Record, Roster, User};
+use crate::{
+    api::{auth::Admin, errors::internal_server_error},
+    config::SchoolSettings,
+    repository::mongodb_repo::MongoRepo,
+};
+use actix_web::{
+    web::{self, Data, Path, Query},
+    HttpResponse, Scope,
+};
+use serde::Deserialize;
+use serde_json::json;
+
+pub const BASE_PATH: &str = "/ims/oneroster/v1p1";
+
+const DEFAULT_LIMIT: usize = 100;
+
+#[derive(Deserialize)]
+struct Page {
+    limit: Option<usize>,
+    #[serde(default)]
+    offset: usize,
+}
+
+fn collection<T: Record>(key: &str, records: Vec<T>, page: &Page) -> HttpResponse {
+    let total = records.len();
+    let records: Vec<T> = records
+        .into_iter()
+        .skip(page.offset)
+        .take(page.limit.unwrap_or(DEFAULT_LIMIT))
+        .collect();
+    HttpResponse::Ok()
+        .insert_header(("X-Total-Count", total.to_string()))
+        .json(json!({ key: records }))
+}
+
+fn single<T: Record>(key: &str, records: Vec<T>, id: &str) -> HttpResponse {
+    match records.into_iter().find(|record| record.sourced_id() == id) {
+        Some(record) => HttpResponse::Ok().json(json!({ key: record })),
+        None => HttpResponse::NotFound().body(format!("No {} found with specified ID", key)),
+    }
+}
+
+/// Loads a fresh snapshot for every request, so answers are never stale.
+async fn with_roster<F>(db: &MongoRepo, school: &SchoolSettings, respond: F) -> HttpResponse
+where
+    F: FnOnce(Roster) -> HttpResponse,
+{
+    match Roster::load(db, school).await {
+        Ok(roster) => respond(roster),
+        Err(err) => internal_server_error(err),
+    }
+}
+
+/// Registers a collection route and its `/{id}` single-record route.
+fn resource<T: Record + 'static>(
+    scope: Scope,
+    path: &str,
+    plural: &'static str,
+    singular: &'static str,
+    select: fn(Roster) -> Vec<T>,
+) -> Scope {
+    scope
+        .route(
+            path,
+            web::get().to(
+                move |db: Data<MongoRepo>,
+                      school: Data<SchoolSettings>,
+                      _admin: Admin,
+                      page: Query<Page>| async move {
+                    with_roster(&db, &school, |roster| {
+                        collection(plural, select(roster), &page)
+                    })
+                    .await
+                },
+            ),
+        )
+        .route(
+            &format!("{}/{{id}}", path),
+            web::get().to(
+                move |db: Data<MongoRepo>,
+                      school: Data<SchoolSettings>,
+                      _admin: Admin,
+                      id: Path<String>| async move {
+                    with_roster(&db, &school, |roster| single(singular, select(roster), &id)).await
+                },
+            ),
+        )
+}
+
+fn users_with_role(roster: Roster, role: &str) -> Vec<User> {
+    roster
+        .users
+        .into_iter()
+        .filter(|user| user.role() == role)
+        .collect()
+}
+
+/// Users enrolled in a class with the given role.
+fn class_members(roster: Roster, class_id: &str, role: &str) -> Vec<User> {
+    let members: Vec<String> = roster
+        .enrollments
+        .iter()
+        .filter(|enrollment| enrollment.class_id() == class_id && enrollment.role() == role)
+        .map(|enrollment| enrollment.user_id().to_string())
+        .collect();
+    roster
+        .users
+        .into_iter()
+        .filter(|user| members.iter().any(|id| id == user.sourced_id()))
+        .collect()
+}
+
+async fn class_students(
+    db: Data<MongoRepo>,
+    school: Data<SchoolSettings>,
+    _admin: Admin,
+    id: Path<String>,
+    page: Query<Page>,
+) -> HttpResponse {
+    with_roster(&db, &school, |roster| {
+        collection("users", class_members(roster, &id, "student"), &page)
+    })
+    .await
+}
+
+async fn class_teachers(
+    db: Data<MongoRepo>,
+    school: Data<SchoolSettings>,
+    _admin: Admin,
+    id: Path<String>,
+    page: Query<Page>,
+) -> HttpResponse {
+    with_roster(&db, &school, |roster| {
+        collection("users", class_members(roster, &id, "teacher"), &page)
+    })
+    .await
+}
+
+pub fn config(cfg: &mut web::ServiceConfig) {
+    let scope = web::scope(BASE_PATH)
+        .route("/classes/{id}/students", web::get().to(class_students))
+        .route("/classes/{id}/teachers", web::get().to(class_teachers));
+    let scope = resource(scope, "/orgs", "orgs", "org", |roster| roster.orgs);
+    let scope = resource(scope, "/schools", "orgs", "org", |roster| roster.orgs);
+    let scope = resource(
+        scope,
+        "/academicSessions",
+        "academicSessions",
+        "academicSession",
+        |roster| roster.academic_sessions,
+    );
+    let scope = resource(
+        scope,
+        "/schoolYears",
+        "academicSessions",
+        "academicSession",
+        |roster| roster.academic_sessions,
+    );
+    let scope = resource(scope, "/courses", "courses", "course", |roster| {
+        roster.courses
+    });
+    let scope = resource(scope, "/classes", "classes", "class", |roster| {
+        roster.classes
+    });
+    let scope = resource(scope, "/users", "users", "user", |roster| roster.users);
+    let scope = resource(scope, "/students", "users", "user", |roster| {
+        users_with_role(roster, "student")
+    });
+    let scope = resource(scope, "/teachers", "users", "user", |roster| {
+        users_with_role(roster, "teacher")
+    });
+    let scope = resource(
+        scope,
+        "/demographics",
+        "demographics",
+        "demographics",
+        |roster| roster.demographics,
+    );
+    let scope = resource(
+        scope,
+        "/enrollments",
+        "enrollments",
+        "enrollment",
+        |roster| roster.enrollments,
+    );
+    let scope = resource(scope, "/categories", "categories", "category", |roster| {
+        roster.categories
+    });
+    let scope = resource(scope, "/lineItems", "lineItems", "lineItem", |roster| {
+        roster.line_items
+    });
+    let scope = resource(scope, "/results", "results", "result", |roster| {
+        roster.results
+    });
+    cfg.service(scope);
+}
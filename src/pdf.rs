@@ -0,0 +1,169 @@
+//! Just enough PDF to print invoices and receipts: A4 pages of text with a
+//! column of right-aligned amounts, set in the standard fonts every reader
+//! has built in so nothing needs embedding.
+
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+
+/// Resource names and base fonts. Amounts are set in Courier so that they
+/// line up digit by digit; its glyphs are all 0.6 em wide, which is also
+/// what makes right-aligning them possible without font metrics.
+const FONTS: [(&str, &str); 4] = [
+    ("F1", "Helvetica"),
+    ("F2", "Helvetica-Bold"),
+    ("F3", "Courier"),
+    ("F4", "Courier-Bold"),
+];
+const COURIER_ADVANCE: f32 = 0.6;
+
+const BODY_SIZE: f32 = 10.0;
+const HEADING_SIZE: f32 = 16.0;
+const LEADING: f32 = 1.5;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Weight {
+    Regular,
+    Bold,
+}
+
+/// Maps text to WinAnsiEncoding, the encoding the standard fonts use.
+/// Characters it lacks print as `?`.
+fn encode(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            ' '..='~' => c as u8,
+            '\u{a0}'..='\u{ff}' => c as u32 as u8,
+            '€' => 0x80,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// A document laid out top to bottom, starting a new page whenever the
+/// next line would run into the bottom margin. Lines are not wrapped.
+pub struct Document {
+    title: String,
+    pages: Vec<Content>,
+    page: Content,
+    /// Baseline of the line last written.
+    y: f32,
+}
+
+impl Document {
+    pub fn new(title: impl Into<String>) -> Self {
+        Document {
+            title: title.into(),
+            pages: Vec::new(),
+            page: Content::new(),
+            y: PAGE_HEIGHT - MARGIN,
+        }
+    }
+
+    /// Moves down by a line of `size` text, on a fresh page if need be.
+    fn advance(&mut self, size: f32) {
+        self.y -= size * LEADING;
+        if self.y < MARGIN {
+            let page = std::mem::replace(&mut self.page, Content::new());
+            self.pages.push(page);
+            self.y = PAGE_HEIGHT - MARGIN - size * LEADING;
+        }
+    }
+
+    fn show(&mut self, font: &str, size: f32, x: f32, text: &str) {
+        let encoded = encode(text);
+        self.page
+            .begin_text()
+            .set_font(Name(font.as_bytes()), size)
+            .next_line(x, self.y)
+            .show(Str(&encoded))
+            .end_text();
+    }
+
+    pub fn heading(&mut self, text: &str) {
+        self.advance(HEADING_SIZE);
+        self.show("F2", HEADING_SIZE, MARGIN, text);
+    }
+
+    pub fn text(&mut self, text: &str, weight: Weight) {
+        self.advance(BODY_SIZE);
+        let font = if weight == Weight::Bold { "F2" } else { "F1" };
+        self.show(font, BODY_SIZE, MARGIN, text);
+    }
+
+    /// `label` on the left and `amount` flush with the right margin.
+    pub fn row(&mut self, label: &str, amount: &str, weight: Weight) {
+        self.text(label, weight);
+        let width = amount.chars().count() as f32 * COURIER_ADVANCE * BODY_SIZE;
+        let font = if weight == Weight::Bold { "F4" } else { "F3" };
+        self.show(font, BODY_SIZE, PAGE_WIDTH - MARGIN - width, amount);
+    }
+
+    /// A thin line across the text column, between two rows.
+    pub fn rule(&mut self) {
+        self.advance(BODY_SIZE / 2.0);
+        let y = self.y + BODY_SIZE / 2.0;
+        self.page
+            .set_line_width(0.5)
+            .move_to(MARGIN, y)
+            .line_to(PAGE_WIDTH - MARGIN, y)
+            .stroke();
+    }
+
+    pub fn space(&mut self) {
+        self.advance(BODY_SIZE);
+    }
+
+    pub fn render(mut self) -> Vec<u8> {
+        self.pages.push(self.page);
+        let catalog_id = Ref::new(1);
+        let tree_id = Ref::new(2);
+        let info_id = Ref::new(3);
+        let font_ids: Vec<Ref> = (0..FONTS.len() as i32).map(|i| Ref::new(4 + i)).collect();
+        let first_page = 4 + FONTS.len() as i32;
+        // Each page is followed by its content stream.
+        let page_ids: Vec<Ref> = (0..self.pages.len() as i32)
+            .map(|i| Ref::new(first_page + 2 * i))
+            .collect();
+
+        let mut pdf = Pdf::new();
+        pdf.catalog(catalog_id).pages(tree_id);
+        pdf.pages(tree_id)
+            .kids(page_ids.iter().copied())
+            .count(page_ids.len() as i32);
+        pdf.document_info(info_id)
+            .title(TextStr(&self.title))
+            .producer(TextStr("school-manager"));
+        for (&(_, base), &id) in FONTS.iter().zip(&font_ids) {
+            pdf.type1_font(id)
+                .base_font(Name(base.as_bytes()))
+                .encoding_predefined(Name(b"WinAnsiEncoding"));
+        }
+        for (content, &page_id) in self.pages.into_iter().zip(&page_ids) {
+            let content_id = Ref::new(page_id.get() + 1);
+            let mut page = pdf.page(page_id);
+            page.parent(tree_id)
+                .media_box(Rect::new(0.0, 0.0, PAGE_WIDTH, PAGE_HEIGHT))
+                .contents(content_id);
+            let mut resources = page.resources();
+            let mut fonts = resources.fonts();
+            for (&(name, _), &id) in FONTS.iter().zip(&font_ids) {
+                fonts.pair(Name(name.as_bytes()), id);
+            }
+            fonts.finish();
+            resources.finish();
+            page.finish();
+            pdf.stream(content_id, &content.finish());
+        }
+        pdf.finish()
+    }
+}
@@ -1 +1,38 @@
 pub mod mongodb_repo;
+
+use std::fmt;
+
+/// Why a repository call failed.
+#[derive(Debug)]
+pub enum Error {
+    /// An id that is not a valid ObjectId. Handlers reject these with 400
+    /// before asking, so reaching the repository with one is a bug.
+    InvalidId(String),
+    Database(mongodb::error::Error),
+    /// A record that could not be turned into BSON for a write.
+    Encode(mongodb::bson::ser::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidId(id) => write!(f, "invalid id {:?}", id),
+            Error::Database(err) => write!(f, "database error: {}", err),
+            Error::Encode(err) => write!(f, "encoding error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<mongodb::error::Error> for Error {
+    fn from(err: mongodb::error::Error) -> Self {
+        Error::Database(err)
+    }
+}
+
+impl From<mongodb::bson::ser::Error> for Error {
+    fn from(err: mongodb::bson::ser::Error) -> Self {
+        Error::Encode(err)
+    }
+}
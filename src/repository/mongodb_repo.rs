@@ -1,40 +1,305 @@
-use dotenv::dotenv;
-use std::env;
-
-use crate::models::{parent::Parent, student::Student, teacher::Teacher};
+mod academics;
+mod attachments;
+mod billing;
+mod calendar;
+mod exports;
+mod homework;
+mod indexes;
+mod messaging;
+mod notifications;
+mod sequences;
+mod staffing;
+mod timetable;
+
+use super::Error;
+use crate::config::DatabaseSettings;
+use crate::metrics::RepositoryMetrics;
+use crate::models::{
+    assignment::Assignment,
+    attachment::Attachment,
+    attendance::Attendance,
+    audit_entry::{AuditContext, AuditEntry},
+    billing_link::BillingLink,
+    calendar_feed::CalendarFeed,
+    classroom::Classroom,
+    classroom_student::ClassroomStudent,
+    conversation::{Conversation, Message},
+    course::Course,
+    course_assignment::CourseAssignment,
+    discount::Discount,
+    exam::Exam,
+    exam_result::ExamResult,
+    fee_item::FeeItem,
+    invoice::Invoice,
+    messaging_link::MessagingLink,
+    notification::Notification,
+    notification_preferences::NotificationPreferences,
+    parent::Parent,
+    payment::Payment,
+    period::Period,
+    qualification::Qualification,
+    school_event::SchoolEvent,
+    sequence::Sequence,
+    student::Student,
+    submission::Submission,
+    teacher::Teacher,
+    timetable_entry::TimetableEntry,
+    timetable_job::TimetableJob,
+};
 use futures::TryStreamExt;
 use mongodb::{
-    bson::{doc, extjson::de::Error, oid::ObjectId},
-    results::{DeleteResult, InsertOneResult, UpdateResult},
-    Client, Collection,
+    bson::{doc, oid::ObjectId, to_bson, Bson, DateTime, Document},
+    error::{ErrorKind, WriteFailure},
+    options::{ClientOptions, FindOptions},
+    results::{InsertOneResult, UpdateResult},
+    Client, Collection, Database,
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
 };
 
+/// Matches the live (not archived) document by id and, when a version is
+/// expected, only if it is still at that version. Documents written before
+/// versioning count as 0.
+fn versioned_filter(obj_id: ObjectId, expected_version: Option<i64>) -> Document {
+    match expected_version {
+        Some(0) => doc! {"_id": obj_id, "deleted_at": null, "version": {"$in": [0_i64, null]}},
+        Some(version) => doc! {"_id": obj_id, "deleted_at": null, "version": version},
+        None => doc! {"_id": obj_id, "deleted_at": null},
+    }
+}
+
+/// Code MongoDB reports when a write collides on a unique index.
+const DUPLICATE_KEY: i32 = 11000;
+
+/// Whether a write failed because a unique index already has its key.
+fn is_duplicate_key(err: &mongodb::error::Error) -> bool {
+    matches!(
+        *err.kind,
+        ErrorKind::Write(WriteFailure::WriteError(ref write)) if write.code == DUPLICATE_KEY
+    )
+}
+
+/// Whether an error comes from reaching the database rather than from
+/// what was asked of it, so asking again later may work.
+pub fn is_unreachable(err: &mongodb::error::Error) -> bool {
+    matches!(
+        *err.kind,
+        ErrorKind::Io(_)
+            | ErrorKind::ServerSelection { .. }
+            | ErrorKind::ConnectionPoolCleared { .. }
+            | ErrorKind::DnsResolve { .. }
+    )
+}
+
+fn archive_filter(include_deleted: bool) -> Document {
+    if include_deleted {
+        doc! {}
+    } else {
+        doc! {"deleted_at": null}
+    }
+}
+
+/// Fields whose values are never written to the audit log: credentials,
+/// the tokens behind links, and what people write to each other.
+const REDACTED_FIELDS: [&str; 3] = ["password", "token", "body"];
+
+/// Field-level changes between two snapshots as `{field: {before, after}}`.
+fn diff(before: Option<&Document>, after: Option<&Document>) -> Document {
+    let empty = Document::new();
+    let (before, after) = (before.unwrap_or(&empty), after.unwrap_or(&empty));
+    let mut changes = Document::new();
+    for key in before.keys().chain(after.keys()) {
+        let (old, new) = (before.get(key), after.get(key));
+        if old == new || changes.contains_key(key) {
+            continue;
+        }
+        let redact = |value: Option<&Bson>| match value {
+            Some(_) if REDACTED_FIELDS.contains(&key.as_str()) => Bson::from("[redacted]"),
+            Some(value) => value.clone(),
+            None => Bson::Null,
+        };
+        changes.insert(key, doc! {"before": redact(old), "after": redact(new)});
+    }
+    changes
+}
+
+async fn snapshot<T>(col: &Collection<T>, obj_id: ObjectId) -> Result<Option<Document>, Error> {
+    Ok(col
+        .clone_with_type::<Document>()
+        .find_one(doc! {"_id": obj_id}, None)
+        .await?)
+}
+
+/// Parses an id the handler should already have checked.
+fn object_id(id: impl AsRef<str>) -> Result<ObjectId, Error> {
+    let id = id.as_ref();
+    ObjectId::parse_str(id).map_err(|_| Error::InvalidId(id.to_string()))
+}
+
 pub struct MongoRepo {
+    db: Database,
+    ping_timeout: Duration,
     teacher_col: Collection<Teacher>,
     parent_col: Collection<Parent>,
     student_col: Collection<Student>,
+    audit_col: Collection<AuditEntry>,
+    classroom_col: Collection<Classroom>,
+    classroom_student_col: Collection<ClassroomStudent>,
+    course_col: Collection<Course>,
+    exam_col: Collection<Exam>,
+    exam_result_col: Collection<ExamResult>,
+    attendance_col: Collection<Attendance>,
+    period_col: Collection<Period>,
+    timetable_col: Collection<TimetableEntry>,
+    timetable_job_col: Collection<TimetableJob>,
+    school_event_col: Collection<SchoolEvent>,
+    calendar_feed_col: Collection<CalendarFeed>,
+    billing_link_col: Collection<BillingLink>,
+    qualification_col: Collection<Qualification>,
+    course_assignment_col: Collection<CourseAssignment>,
+    assignment_col: Collection<Assignment>,
+    submission_col: Collection<Submission>,
+    attachment_col: Collection<Attachment>,
+    fee_item_col: Collection<FeeItem>,
+    discount_col: Collection<Discount>,
+    invoice_col: Collection<Invoice>,
+    payment_col: Collection<Payment>,
+    sequence_col: Collection<Sequence>,
+    notification_col: Collection<Notification>,
+    notification_preferences_col: Collection<NotificationPreferences>,
+    conversation_col: Collection<Conversation>,
+    message_col: Collection<Message>,
+    messaging_link_col: Collection<MessagingLink>,
 }
 
 impl MongoRepo {
-    pub async fn init() -> Self {
-        dotenv().ok();
-        let uri = match env::var("MONGOURI") {
-            Ok(v) => v.to_string(),
-            Err(_) => format!("Error loading env variable"),
-        };
-        let client = Client::with_uri_str(uri).await.unwrap();
-        let db = client.database("student_manager");
+    pub async fn init(settings: &DatabaseSettings) -> mongodb::error::Result<Self> {
+        let mut options = ClientOptions::parse(&settings.uri).await?;
+        options.max_pool_size = settings.max_pool_size.or(options.max_pool_size);
+        options.connect_timeout = Some(settings.connect_timeout());
+        options.server_selection_timeout = Some(settings.server_selection_timeout());
+        options.command_event_handler = Some(Arc::new(RepositoryMetrics::default()));
+        let client = Client::with_options(options)?;
+        let db = client.database(&settings.name);
         let teacher_col: Collection<Teacher> = db.collection("Teacher");
         let parent_col: Collection<Parent> = db.collection("Parent");
-        let student_col: Collection<Student> = db.collection("Parent");
-        MongoRepo {
+        let student_col: Collection<Student> = db.collection("Student");
+        let audit_col: Collection<AuditEntry> = db.collection("Audit");
+        let classroom_col: Collection<Classroom> = db.collection("Classroom");
+        let classroom_student_col: Collection<ClassroomStudent> = db.collection("ClassroomStudent");
+        let course_col: Collection<Course> = db.collection("Course");
+        let exam_col: Collection<Exam> = db.collection("Exam");
+        let exam_result_col: Collection<ExamResult> = db.collection("ExamResult");
+        let attendance_col: Collection<Attendance> = db.collection("Attendance");
+        let period_col: Collection<Period> = db.collection("Period");
+        let timetable_col: Collection<TimetableEntry> = db.collection("TimetableEntry");
+        let timetable_job_col: Collection<TimetableJob> = db.collection("TimetableJob");
+        let school_event_col: Collection<SchoolEvent> = db.collection("SchoolEvent");
+        let calendar_feed_col: Collection<CalendarFeed> = db.collection("CalendarFeed");
+        let billing_link_col: Collection<BillingLink> = db.collection("BillingLink");
+        let qualification_col: Collection<Qualification> = db.collection("Qualification");
+        let course_assignment_col: Collection<CourseAssignment> = db.collection("CourseAssignment");
+        let assignment_col: Collection<Assignment> = db.collection("Assignment");
+        let submission_col: Collection<Submission> = db.collection("Submission");
+        let attachment_col: Collection<Attachment> = db.collection("Attachment");
+        let fee_item_col: Collection<FeeItem> = db.collection("FeeItem");
+        let discount_col: Collection<Discount> = db.collection("Discount");
+        let invoice_col: Collection<Invoice> = db.collection("Invoice");
+        let payment_col: Collection<Payment> = db.collection("Payment");
+        let sequence_col: Collection<Sequence> = db.collection("Sequence");
+        let notification_col: Collection<Notification> = db.collection("Notification");
+        let notification_preferences_col: Collection<NotificationPreferences> =
+            db.collection("NotificationPreferences");
+        let conversation_col: Collection<Conversation> = db.collection("Conversation");
+        let message_col: Collection<Message> = db.collection("Message");
+        let messaging_link_col: Collection<MessagingLink> = db.collection("MessagingLink");
+        Ok(MongoRepo {
+            db,
+            ping_timeout: settings.ping_timeout(),
             teacher_col,
             parent_col,
             student_col,
+            audit_col,
+            classroom_col,
+            classroom_student_col,
+            course_col,
+            exam_col,
+            exam_result_col,
+            attendance_col,
+            period_col,
+            timetable_col,
+            timetable_job_col,
+            school_event_col,
+            calendar_feed_col,
+            billing_link_col,
+            qualification_col,
+            course_assignment_col,
+            assignment_col,
+            submission_col,
+            attachment_col,
+            fee_item_col,
+            discount_col,
+            invoice_col,
+            payment_col,
+            sequence_col,
+            notification_col,
+            notification_preferences_col,
+            conversation_col,
+            message_col,
+            messaging_link_col,
+        })
+    }
+
+    /// The service's database, for stores that keep their own collections.
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    /// Pings the database, returning the round-trip time or why it failed.
+    pub async fn ping(&self) -> Result<Duration, String> {
+        let started = Instant::now();
+        let ping = self.db.run_command(doc! {"ping": 1}, None);
+        match actix_web::rt::time::timeout(self.ping_timeout, ping).await {
+            Ok(Ok(_)) => Ok(started.elapsed()),
+            Ok(Err(err)) => Err(err.to_string()),
+            Err(_) => Err(format!("no reply within {:?}", self.ping_timeout)),
         }
     }
 
-    pub async fn create_teacher(&self, new_teacher: Teacher) -> Result<InsertOneResult, Error> {
+    /// Appends an audit entry for `entity_id`, diffing `before` against the
+    /// document as it is now stored.
+    async fn audit<T>(
+        &self,
+        col: &Collection<T>,
+        ctx: &AuditContext,
+        action: &str,
+        entity: &str,
+        entity_id: ObjectId,
+        before: Option<Document>,
+    ) -> Result<(), Error> {
+        let after = snapshot(col, entity_id).await?;
+        let entry = AuditEntry {
+            id: None,
+            actor: ctx.actor.clone(),
+            claimed_actor: ctx.claimed_actor.clone(),
+            action: action.to_string(),
+            entity: entity.to_string(),
+            entity_id,
+            changes: diff(before.as_ref(), after.as_ref()),
+            timestamp: DateTime::now(),
+            client_ip: ctx.client_ip.clone(),
+        };
+        self.audit_col.insert_one(entry, None).await?;
+        Ok(())
+    }
+
+    pub async fn create_teacher(
+        &self,
+        new_teacher: Teacher,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
         let new_doc = Teacher {
             id: None,
             email: new_teacher.email,
@@ -47,19 +312,25 @@ impl MongoRepo {
             status: new_teacher.status,
             last_login_date: new_teacher.last_login_date,
             last_login_ip: new_teacher.last_login_ip,
+            attachments: new_teacher.attachments,
+            version: 1,
+            deleted_at: None,
         };
 
-        let teacher = self
-            .teacher_col
-            .insert_one(new_doc, None)
-            .await
-            .ok()
-            .expect("Error creating teacher");
+        let teacher = self.teacher_col.insert_one(new_doc, None).await?;
+        if let Some(obj_id) = teacher.inserted_id.as_object_id() {
+            self.audit(&self.teacher_col, ctx, "create", "teacher", obj_id, None)
+                .await?;
+        }
 
         Ok(teacher)
     }
 
-    pub async fn create_student(&self, new_student: Student) -> Result<InsertOneResult, Error> {
+    pub async fn create_student(
+        &self,
+        new_student: Student,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
         let new_doc = Student {
             id: None,
             email: new_student.email,
@@ -74,19 +345,25 @@ impl MongoRepo {
             date_of_join: new_student.date_of_join,
             last_login_date: new_student.last_login_date,
             last_login_ip: new_student.last_login_ip,
+            attachments: new_student.attachments,
+            version: 1,
+            deleted_at: None,
         };
 
-        let student = self
-            .student_col
-            .insert_one(new_doc, None)
-            .await
-            .ok()
-            .expect("Error creating student");
+        let student = self.student_col.insert_one(new_doc, None).await?;
+        if let Some(obj_id) = student.inserted_id.as_object_id() {
+            self.audit(&self.student_col, ctx, "create", "student", obj_id, None)
+                .await?;
+        }
 
         Ok(student)
     }
 
-    pub async fn create_parent(&self, new_parent: Parent) -> Result<InsertOneResult, Error> {
+    pub async fn create_parent(
+        &self,
+        new_parent: Parent,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
         let new_doc = Parent {
             id: None,
             email: new_parent.email,
@@ -99,65 +376,92 @@ impl MongoRepo {
             status: new_parent.status,
             last_login_date: new_parent.last_login_date,
             last_login_ip: new_parent.last_login_ip,
+            version: 1,
+            deleted_at: None,
         };
 
-        let parent = self
-            .parent_col
-            .insert_one(new_doc, None)
-            .await
-            .ok()
-            .expect("Error creating parent");
+        let parent = self.parent_col.insert_one(new_doc, None).await?;
+        if let Some(obj_id) = parent.inserted_id.as_object_id() {
+            self.audit(&self.parent_col, ctx, "create", "parent", obj_id, None)
+                .await?;
+        }
 
         Ok(parent)
     }
 
-    pub async fn get_teacher(&self, id: &String) -> Result<Teacher, Error> {
-        let obj_id = ObjectId::parse_str(id).unwrap();
-        let filter = doc! {"_id": obj_id};
-        let teacher_detail = self
-            .teacher_col
-            .find_one(filter, None)
-            .await
-            .ok()
-            .expect("Error getting teacher's detail");
-        Ok(teacher_detail.unwrap())
+    pub async fn get_teacher(
+        &self,
+        id: &String,
+        include_deleted: bool,
+    ) -> Result<Option<Teacher>, Error> {
+        let obj_id = object_id(id)?;
+        let mut filter = archive_filter(include_deleted);
+        filter.insert("_id", obj_id);
+        let teacher_detail = self.teacher_col.find_one(filter, None).await?;
+        Ok(teacher_detail)
+    }
+
+    pub async fn get_parent(
+        &self,
+        id: &String,
+        include_deleted: bool,
+    ) -> Result<Option<Parent>, Error> {
+        let obj_id = object_id(id)?;
+        let mut filter = archive_filter(include_deleted);
+        filter.insert("_id", obj_id);
+        let parent_detail = self.parent_col.find_one(filter, None).await?;
+        Ok(parent_detail)
     }
 
-    pub async fn get_parent(&self, id: &String) -> Result<Parent, Error> {
-        let obj_id = ObjectId::parse_str(id).unwrap();
-        let filter = doc! {"_id": obj_id};
+    pub async fn get_student(
+        &self,
+        id: &String,
+        include_deleted: bool,
+    ) -> Result<Option<Student>, Error> {
+        let obj_id = object_id(id)?;
+        let mut filter = archive_filter(include_deleted);
+        filter.insert("_id", obj_id);
+        let student_detail = self.student_col.find_one(filter, None).await?;
+        Ok(student_detail)
+    }
+
+    pub async fn get_parent_by_email(&self, email: &str) -> Result<Option<Parent>, Error> {
         let parent_detail = self
             .parent_col
-            .find_one(filter, None)
-            .await
-            .ok()
-            .expect("Error getting parent's detail");
-        Ok(parent_detail.unwrap())
+            .find_one(doc! {"email": email, "deleted_at": null}, None)
+            .await?;
+        Ok(parent_detail)
     }
 
-    pub async fn get_student(&self, id: &String) -> Result<Student, Error> {
-        let obj_id = ObjectId::parse_str(id).unwrap();
-        let filter = doc! {"_id": obj_id};
+    pub async fn get_teacher_by_email(&self, email: &str) -> Result<Option<Teacher>, Error> {
+        let teacher_detail = self
+            .teacher_col
+            .find_one(doc! {"email": email, "deleted_at": null}, None)
+            .await?;
+        Ok(teacher_detail)
+    }
+
+    pub async fn get_student_by_email(&self, email: &str) -> Result<Option<Student>, Error> {
         let student_detail = self
             .student_col
-            .find_one(filter, None)
-            .await
-            .ok()
-            .expect("Error getting parent's detail");
-        Ok(student_detail.unwrap())
+            .find_one(doc! {"email": email, "deleted_at": null}, None)
+            .await?;
+        Ok(student_detail)
     }
 
     pub async fn update_teacher(
         &self,
         id: &String,
         new_teacher: Teacher,
+        expected_version: Option<i64>,
+        ctx: &AuditContext,
     ) -> Result<UpdateResult, Error> {
-        let obj_id = ObjectId::parse_str(id).unwrap();
-        let filter = doc! {"_id":obj_id};
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.teacher_col, obj_id).await?;
+        let filter = versioned_filter(obj_id, expected_version);
         let new_doc = doc! {
             "$set":
             {
-                "id": new_teacher.id,
                 "email": new_teacher.email,
                 "password": new_teacher.password,
                 "fname": new_teacher.fname,
@@ -166,17 +470,16 @@ impl MongoRepo {
                 "phone": new_teacher.phone,
                 "mobile": new_teacher.mobile,
                 "status": new_teacher.status,
-                "last_login_date": new_teacher.last_login_date,
-                "last_login_ip": new_teacher.last_login_ip,
             },
+            "$inc": {"version": 1},
         };
 
-        let updated_doc = self
-            .teacher_col
-            .update_one(filter, new_doc, None)
-            .await
-            .ok()
-            .expect("Error updating teacher");
+        let updated_doc = self.teacher_col.update_one(filter, new_doc, None).await?;
+        if updated_doc.matched_count == 1 {
+            self.audit(&self.teacher_col, ctx, "update", "teacher", obj_id, before)
+                .await?;
+        }
+
         Ok(updated_doc)
     }
 
@@ -184,13 +487,15 @@ impl MongoRepo {
         &self,
         id: &String,
         new_parent: Parent,
+        expected_version: Option<i64>,
+        ctx: &AuditContext,
     ) -> Result<UpdateResult, Error> {
-        let obj_id = ObjectId::parse_str(id).unwrap();
-        let filter = doc! {"_id":obj_id};
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.parent_col, obj_id).await?;
+        let filter = versioned_filter(obj_id, expected_version);
         let new_doc = doc! {
             "$set":
             {
-                "id": new_parent.id,
                 "email": new_parent.email,
                 "password": new_parent.password,
                 "fname": new_parent.fname,
@@ -199,31 +504,34 @@ impl MongoRepo {
                 "phone": new_parent.phone,
                 "mobile": new_parent.mobile,
                 "status": new_parent.status,
-                "last_login_date": new_parent.last_login_date,
-                "last_login_ip": new_parent.last_login_ip,
             },
+            "$inc": {"version": 1},
         };
 
-        let updated_doc = self
-            .parent_col
-            .update_one(filter, new_doc, None)
-            .await
-            .ok()
-            .expect("Error updating parent");
+        let updated_doc = self.parent_col.update_one(filter, new_doc, None).await?;
+        if updated_doc.matched_count == 1 {
+            self.audit(&self.parent_col, ctx, "update", "parent", obj_id, before)
+                .await?;
+        }
+
         Ok(updated_doc)
     }
 
+    /// Replaces what a PUT may change. The login fields, attachments and
+    /// version are the server's to manage and are kept.
     pub async fn update_student(
         &self,
         id: &String,
         new_student: Student,
+        expected_version: Option<i64>,
+        ctx: &AuditContext,
     ) -> Result<UpdateResult, Error> {
-        let obj_id = ObjectId::parse_str(id).unwrap();
-        let filter = doc! {"_id":obj_id};
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.student_col, obj_id).await?;
+        let filter = versioned_filter(obj_id, expected_version);
         let new_doc = doc! {
             "$set":
             {
-                "id": new_student.id,
                 "email": new_student.email,
                 "password": new_student.password,
                 "fname": new_student.fname,
@@ -231,119 +539,295 @@ impl MongoRepo {
                 "dob": new_student.dob,
                 "phone": new_student.phone,
                 "mobile": new_student.mobile,
-                "parent": new_student.parent.id,
+                "parent": to_bson(&new_student.parent)?,
                 "date_of_join": new_student.date_of_join,
                 "status": new_student.status,
-                "last_login_date": new_student.last_login_date,
-                "last_login_ip": new_student.last_login_ip,
             },
+            "$inc": {"version": 1},
         };
 
-        let updated_doc = self
-            .student_col
-            .update_one(filter, new_doc, None)
-            .await
-            .ok()
-            .expect("Error updating parent");
+        let updated_doc = self.student_col.update_one(filter, new_doc, None).await?;
+        if updated_doc.matched_count == 1 {
+            self.audit(&self.student_col, ctx, "update", "student", obj_id, before)
+                .await?;
+        }
+
         Ok(updated_doc)
     }
 
-    pub async fn delete_teacher(&self, id: &String) -> Result<DeleteResult, Error> {
-        let obj_id = ObjectId::parse_str(id).unwrap();
-        let filter = doc! {"_id": obj_id};
-        let teacher_detail = self
-            .teacher_col
-            .delete_one(filter, None)
-            .await
-            .ok()
-            .expect("Error deleting teacher");
+    pub async fn patch_teacher(
+        &self,
+        id: &String,
+        fields: Document,
+        expected_version: i64,
+        ctx: &AuditContext,
+    ) -> Result<UpdateResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.teacher_col, obj_id).await?;
+        let filter = versioned_filter(obj_id, Some(expected_version));
+        let new_doc = doc! {"$set": fields, "$inc": {"version": 1}};
+
+        let updated_doc = self.teacher_col.update_one(filter, new_doc, None).await?;
+        if updated_doc.matched_count == 1 {
+            self.audit(&self.teacher_col, ctx, "patch", "teacher", obj_id, before)
+                .await?;
+        }
+
+        Ok(updated_doc)
+    }
+
+    pub async fn patch_parent(
+        &self,
+        id: &String,
+        fields: Document,
+        expected_version: i64,
+        ctx: &AuditContext,
+    ) -> Result<UpdateResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.parent_col, obj_id).await?;
+        let filter = versioned_filter(obj_id, Some(expected_version));
+        let new_doc = doc! {"$set": fields, "$inc": {"version": 1}};
+
+        let updated_doc = self.parent_col.update_one(filter, new_doc, None).await?;
+        if updated_doc.matched_count == 1 {
+            self.audit(&self.parent_col, ctx, "patch", "parent", obj_id, before)
+                .await?;
+        }
+
+        Ok(updated_doc)
+    }
+
+    pub async fn patch_student(
+        &self,
+        id: &String,
+        fields: Document,
+        expected_version: i64,
+        ctx: &AuditContext,
+    ) -> Result<UpdateResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.student_col, obj_id).await?;
+        let filter = versioned_filter(obj_id, Some(expected_version));
+        let new_doc = doc! {"$set": fields, "$inc": {"version": 1}};
+
+        let updated_doc = self.student_col.update_one(filter, new_doc, None).await?;
+        if updated_doc.matched_count == 1 {
+            self.audit(&self.student_col, ctx, "patch", "student", obj_id, before)
+                .await?;
+        }
+
+        Ok(updated_doc)
+    }
+
+    pub async fn delete_teacher(
+        &self,
+        id: &String,
+        expected_version: Option<i64>,
+        ctx: &AuditContext,
+    ) -> Result<UpdateResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.teacher_col, obj_id).await?;
+        let filter = versioned_filter(obj_id, expected_version);
+        let new_doc = doc! {"$set": {"deleted_at": DateTime::now()}, "$inc": {"version": 1}};
+        let teacher_detail = self.teacher_col.update_one(filter, new_doc, None).await?;
+        if teacher_detail.matched_count == 1 {
+            self.audit(&self.teacher_col, ctx, "delete", "teacher", obj_id, before)
+                .await?;
+        }
 
         Ok(teacher_detail)
     }
 
-    pub async fn delete_parent(&self, id: &String) -> Result<DeleteResult, Error> {
-        let obj_id = ObjectId::parse_str(id).unwrap();
-        let filter = doc! {"_id": obj_id};
-        let parent_detail = self
-            .parent_col
-            .delete_one(filter, None)
-            .await
-            .ok()
-            .expect("Error deleting parent");
+    pub async fn restore_teacher(
+        &self,
+        id: &String,
+        ctx: &AuditContext,
+    ) -> Result<UpdateResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.teacher_col, obj_id).await?;
+        let filter = doc! {"_id": obj_id, "deleted_at": {"$ne": null}};
+        let new_doc = doc! {"$unset": {"deleted_at": ""}, "$inc": {"version": 1}};
+        let teacher_detail = self.teacher_col.update_one(filter, new_doc, None).await?;
+        if teacher_detail.matched_count == 1 {
+            self.audit(&self.teacher_col, ctx, "restore", "teacher", obj_id, before)
+                .await?;
+        }
+
+        Ok(teacher_detail)
+    }
+
+    pub async fn delete_parent(
+        &self,
+        id: &String,
+        expected_version: Option<i64>,
+        ctx: &AuditContext,
+    ) -> Result<UpdateResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.parent_col, obj_id).await?;
+        let filter = versioned_filter(obj_id, expected_version);
+        let new_doc = doc! {"$set": {"deleted_at": DateTime::now()}, "$inc": {"version": 1}};
+        let parent_detail = self.parent_col.update_one(filter, new_doc, None).await?;
+        if parent_detail.matched_count == 1 {
+            self.audit(&self.parent_col, ctx, "delete", "parent", obj_id, before)
+                .await?;
+        }
 
         Ok(parent_detail)
     }
 
-    pub async fn delete_student(&self, id: &String) -> Result<DeleteResult, Error> {
-        let obj_id = ObjectId::parse_str(id).unwrap();
-        let filter = doc! {"_id": obj_id};
-        let student_detail = self
-            .student_col
-            .delete_one(filter, None)
-            .await
-            .ok()
-            .expect("Error deleting student");
+    pub async fn restore_parent(
+        &self,
+        id: &String,
+        ctx: &AuditContext,
+    ) -> Result<UpdateResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.parent_col, obj_id).await?;
+        let filter = doc! {"_id": obj_id, "deleted_at": {"$ne": null}};
+        let new_doc = doc! {"$unset": {"deleted_at": ""}, "$inc": {"version": 1}};
+        let parent_detail = self.parent_col.update_one(filter, new_doc, None).await?;
+        if parent_detail.matched_count == 1 {
+            self.audit(&self.parent_col, ctx, "restore", "parent", obj_id, before)
+                .await?;
+        }
+
+        Ok(parent_detail)
+    }
+
+    pub async fn delete_student(
+        &self,
+        id: &String,
+        expected_version: Option<i64>,
+        ctx: &AuditContext,
+    ) -> Result<UpdateResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.student_col, obj_id).await?;
+        let filter = versioned_filter(obj_id, expected_version);
+        let new_doc = doc! {"$set": {"deleted_at": DateTime::now()}, "$inc": {"version": 1}};
+        let student_detail = self.student_col.update_one(filter, new_doc, None).await?;
+        if student_detail.matched_count == 1 {
+            self.audit(&self.student_col, ctx, "delete", "student", obj_id, before)
+                .await?;
+        }
 
         Ok(student_detail)
     }
 
-    pub async fn get_all_teachers(&self) -> Result<Vec<Teacher>, Error> {
+    pub async fn restore_student(
+        &self,
+        id: &String,
+        ctx: &AuditContext,
+    ) -> Result<UpdateResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.student_col, obj_id).await?;
+        let filter = doc! {"_id": obj_id, "deleted_at": {"$ne": null}};
+        let new_doc = doc! {"$unset": {"deleted_at": ""}, "$inc": {"version": 1}};
+        let student_detail = self.student_col.update_one(filter, new_doc, None).await?;
+        if student_detail.matched_count == 1 {
+            self.audit(&self.student_col, ctx, "restore", "student", obj_id, before)
+                .await?;
+        }
+
+        Ok(student_detail)
+    }
+
+    pub async fn get_all_teachers(&self, include_deleted: bool) -> Result<Vec<Teacher>, Error> {
         let mut cursors = self
             .teacher_col
-            .find(None, None)
-            .await
-            .ok()
-            .expect("Error getting list of teacher");
+            .find(archive_filter(include_deleted), None)
+            .await?;
 
         let mut teachers: Vec<Teacher> = Vec::new();
-        while let Some(teacher) = cursors
-            .try_next()
-            .await
-            .ok()
-            .expect("Error mapping through cursor")
-        {
+        while let Some(teacher) = cursors.try_next().await? {
             teachers.push(teacher)
         }
         Ok(teachers)
     }
 
-    pub async fn get_all_parents(&self) -> Result<Vec<Parent>, Error> {
+    pub async fn get_all_parents(&self, include_deleted: bool) -> Result<Vec<Parent>, Error> {
         let mut cursors = self
             .parent_col
-            .find(None, None)
-            .await
-            .ok()
-            .expect("Error getting list of parent");
+            .find(archive_filter(include_deleted), None)
+            .await?;
 
         let mut parents: Vec<Parent> = Vec::new();
-        while let Some(parent) = cursors
-            .try_next()
-            .await
-            .ok()
-            .expect("Error mapping through cursor")
-        {
+        while let Some(parent) = cursors.try_next().await? {
             parents.push(parent)
         }
         Ok(parents)
     }
 
-    pub async fn get_all_students(&self) -> Result<Vec<Student>, Error> {
+    pub async fn get_all_students(&self, include_deleted: bool) -> Result<Vec<Student>, Error> {
         let mut cursors = self
             .student_col
-            .find(None, None)
-            .await
-            .ok()
-            .expect("Error getting list of student");
+            .find(archive_filter(include_deleted), None)
+            .await?;
 
         let mut students: Vec<Student> = Vec::new();
-        while let Some(student) = cursors
-            .try_next()
-            .await
-            .ok()
-            .expect("Error mapping through cursor")
-        {
+        while let Some(student) = cursors.try_next().await? {
             students.push(student)
         }
         Ok(students)
     }
+
+    /// Permanently removes people records archived before `cutoff` and
+    /// returns how many documents were purged.
+    /// Unlike the CRUD methods this surfaces driver errors, so a metrics
+    /// scrape still succeeds while the database is down.
+    pub async fn count_active_students(&self) -> mongodb::error::Result<u64> {
+        self.student_col
+            .count_documents(doc! {"deleted_at": null, "status": true}, None)
+            .await
+    }
+
+    pub async fn purge_archived(&self, cutoff: DateTime, ctx: &AuditContext) -> Result<u64, Error> {
+        let teachers = self
+            .purge_collection(&self.teacher_col, "teacher", cutoff, ctx)
+            .await?;
+        let parents = self
+            .purge_collection(&self.parent_col, "parent", cutoff, ctx)
+            .await?;
+        let students = self
+            .purge_collection(&self.student_col, "student", cutoff, ctx)
+            .await?;
+
+        Ok(teachers + parents + students)
+    }
+
+    async fn purge_collection<T>(
+        &self,
+        col: &Collection<T>,
+        entity: &str,
+        cutoff: DateTime,
+        ctx: &AuditContext,
+    ) -> Result<u64, Error> {
+        let raw_col = col.clone_with_type::<Document>();
+        let archived: Vec<Document> = raw_col
+            .find(doc! {"deleted_at": {"$lt": cutoff}}, None)
+            .await?
+            .try_collect()
+            .await?;
+        let ids: Vec<ObjectId> = archived
+            .iter()
+            .filter_map(|record| record.get_object_id("_id").ok())
+            .collect();
+        let purged = raw_col
+            .delete_many(doc! {"_id": {"$in": &ids}}, None)
+            .await?;
+        for (obj_id, before) in ids.into_iter().zip(archived) {
+            self.audit(col, ctx, "purge", entity, obj_id, Some(before))
+                .await?;
+        }
+
+        Ok(purged.deleted_count)
+    }
+
+    pub async fn get_audit_entries(&self, filter: Document) -> Result<Vec<AuditEntry>, Error> {
+        let options = FindOptions::builder().sort(doc! {"timestamp": -1}).build();
+        let mut cursors = self.audit_col.find(filter, options).await?;
+
+        let mut entries: Vec<AuditEntry> = Vec::new();
+        while let Some(entry) = cursors.try_next().await? {
+            entries.push(entry)
+        }
+        Ok(entries)
+    }
 }
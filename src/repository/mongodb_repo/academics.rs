@@ -0,0 +1,284 @@
+use super::{object_id, MongoRepo};
+use crate::models::{
+    attendance::Attendance, audit_entry::AuditContext, classroom::Classroom,
+    classroom_student::ClassroomStudent, course::Course, exam::Exam, exam_result::ExamResult,
+    parent::Parent, student::Student, teacher::Teacher,
+};
+use crate::repository::Error;
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, oid::ObjectId, DateTime, Document},
+    results::InsertOneResult,
+    Collection,
+};
+use serde::de::DeserializeOwned;
+
+async fn find_all<T>(col: &Collection<T>, filter: Document) -> Result<Vec<T>, Error>
+where
+    T: DeserializeOwned + Unpin + Send + Sync,
+{
+    Ok(col.find(filter, None).await?.try_collect().await?)
+}
+
+impl MongoRepo {
+    pub async fn create_classroom(
+        &self,
+        new_classroom: Classroom,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let classroom = self.classroom_col.insert_one(new_classroom, None).await?;
+        if let Some(obj_id) = classroom.inserted_id.as_object_id() {
+            self.audit(
+                &self.classroom_col,
+                ctx,
+                "create",
+                "classroom",
+                obj_id,
+                None,
+            )
+            .await?;
+        }
+
+        Ok(classroom)
+    }
+
+    pub async fn enroll_student(
+        &self,
+        new_enrollment: ClassroomStudent,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let enrollment = self
+            .classroom_student_col
+            .insert_one(new_enrollment, None)
+            .await?;
+        if let Some(obj_id) = enrollment.inserted_id.as_object_id() {
+            self.audit(
+                &self.classroom_student_col,
+                ctx,
+                "create",
+                "classroom_student",
+                obj_id,
+                None,
+            )
+            .await?;
+        }
+
+        Ok(enrollment)
+    }
+
+    pub async fn create_course(
+        &self,
+        new_course: Course,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let course = self.course_col.insert_one(new_course, None).await?;
+        if let Some(obj_id) = course.inserted_id.as_object_id() {
+            self.audit(&self.course_col, ctx, "create", "course", obj_id, None)
+                .await?;
+        }
+
+        Ok(course)
+    }
+
+    pub async fn create_exam(
+        &self,
+        new_exam: Exam,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let exam = self.exam_col.insert_one(new_exam, None).await?;
+        if let Some(obj_id) = exam.inserted_id.as_object_id() {
+            self.audit(&self.exam_col, ctx, "create", "exam", obj_id, None)
+                .await?;
+        }
+
+        Ok(exam)
+    }
+
+    pub async fn create_exam_result(
+        &self,
+        new_result: ExamResult,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let result = self.exam_result_col.insert_one(new_result, None).await?;
+        if let Some(obj_id) = result.inserted_id.as_object_id() {
+            self.audit(
+                &self.exam_result_col,
+                ctx,
+                "create",
+                "exam_result",
+                obj_id,
+                None,
+            )
+            .await?;
+        }
+
+        Ok(result)
+    }
+
+    pub async fn record_attendance(
+        &self,
+        new_attendance: Attendance,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let attendance = self.attendance_col.insert_one(new_attendance, None).await?;
+        if let Some(obj_id) = attendance.inserted_id.as_object_id() {
+            self.audit(
+                &self.attendance_col,
+                ctx,
+                "create",
+                "attendance",
+                obj_id,
+                None,
+            )
+            .await?;
+        }
+
+        Ok(attendance)
+    }
+
+    pub async fn get_classroom(&self, id: &String) -> Result<Option<Classroom>, Error> {
+        let obj_id = object_id(id)?;
+        let classroom_detail = self
+            .classroom_col
+            .find_one(doc! {"_id": obj_id}, None)
+            .await?;
+        Ok(classroom_detail)
+    }
+
+    pub async fn get_course(&self, id: &String) -> Result<Option<Course>, Error> {
+        let obj_id = object_id(id)?;
+        let course_detail = self.course_col.find_one(doc! {"_id": obj_id}, None).await?;
+        Ok(course_detail)
+    }
+
+    pub async fn get_exam(&self, id: &String) -> Result<Option<Exam>, Error> {
+        let obj_id = object_id(id)?;
+        let exam_detail = self.exam_col.find_one(doc! {"_id": obj_id}, None).await?;
+        Ok(exam_detail)
+    }
+
+    pub async fn get_enrollment(&self, id: &String) -> Result<Option<ClassroomStudent>, Error> {
+        let obj_id = object_id(id)?;
+        let enrollment_detail = self
+            .classroom_student_col
+            .find_one(doc! {"_id": obj_id}, None)
+            .await?;
+        Ok(enrollment_detail)
+    }
+
+    pub async fn get_all_classrooms(&self) -> Result<Vec<Classroom>, Error> {
+        find_all(&self.classroom_col, doc! {}).await
+    }
+
+    pub async fn get_all_courses(&self) -> Result<Vec<Course>, Error> {
+        find_all(&self.course_col, doc! {}).await
+    }
+
+    pub async fn get_all_exams(&self) -> Result<Vec<Exam>, Error> {
+        find_all(&self.exam_col, doc! {}).await
+    }
+
+    pub async fn get_all_enrollments(&self) -> Result<Vec<ClassroomStudent>, Error> {
+        find_all(&self.classroom_student_col, doc! {}).await
+    }
+
+    pub async fn get_all_exam_results(&self) -> Result<Vec<ExamResult>, Error> {
+        find_all(&self.exam_result_col, doc! {}).await
+    }
+
+    /// Counts the attendance records dated in `[from, to)` and how many of
+    /// them are marked present.
+    pub async fn count_attendance(
+        &self,
+        from: DateTime,
+        to: DateTime,
+    ) -> mongodb::error::Result<(u64, u64)> {
+        let filter = doc! {"date": {"$gte": from, "$lt": to}};
+        let total = self
+            .attendance_col
+            .count_documents(filter.clone(), None)
+            .await?;
+        let mut present_filter = filter;
+        present_filter.insert("status", true);
+        let present = self
+            .attendance_col
+            .count_documents(present_filter, None)
+            .await?;
+        Ok((present, total))
+    }
+
+    pub async fn get_teachers_by_ids(&self, ids: &[ObjectId]) -> Result<Vec<Teacher>, Error> {
+        let filter = doc! {"_id": {"$in": ids.to_vec()}, "deleted_at": null};
+        find_all(&self.teacher_col, filter).await
+    }
+
+    pub async fn get_parents_by_ids(&self, ids: &[ObjectId]) -> Result<Vec<Parent>, Error> {
+        let filter = doc! {"_id": {"$in": ids.to_vec()}, "deleted_at": null};
+        find_all(&self.parent_col, filter).await
+    }
+
+    pub async fn get_students_by_ids(&self, ids: &[ObjectId]) -> Result<Vec<Student>, Error> {
+        let filter = doc! {"_id": {"$in": ids.to_vec()}, "deleted_at": null};
+        find_all(&self.student_col, filter).await
+    }
+
+    pub async fn get_classrooms_for_year(&self, year: i32) -> Result<Vec<Classroom>, Error> {
+        find_all(&self.classroom_col, doc! {"year": year}).await
+    }
+
+    pub async fn get_classrooms_for_teachers(
+        &self,
+        teacher_ids: &[ObjectId],
+    ) -> Result<Vec<Classroom>, Error> {
+        let filter = doc! {"teacher._id": {"$in": teacher_ids.to_vec()}};
+        find_all(&self.classroom_col, filter).await
+    }
+
+    pub async fn get_students_for_parents(
+        &self,
+        parent_ids: &[ObjectId],
+    ) -> Result<Vec<Student>, Error> {
+        let filter = doc! {"parent._id": {"$in": parent_ids.to_vec()}, "deleted_at": null};
+        find_all(&self.student_col, filter).await
+    }
+
+    pub async fn get_enrollments_for_classrooms(
+        &self,
+        classroom_ids: &[ObjectId],
+    ) -> Result<Vec<ClassroomStudent>, Error> {
+        let filter = doc! {"classroom._id": {"$in": classroom_ids.to_vec()}};
+        find_all(&self.classroom_student_col, filter).await
+    }
+
+    pub async fn get_enrollments_for_students(
+        &self,
+        student_ids: &[ObjectId],
+    ) -> Result<Vec<ClassroomStudent>, Error> {
+        let filter = doc! {"student._id": {"$in": student_ids.to_vec()}};
+        find_all(&self.classroom_student_col, filter).await
+    }
+
+    pub async fn get_attendance_for_students(
+        &self,
+        student_ids: &[ObjectId],
+    ) -> Result<Vec<Attendance>, Error> {
+        let filter = doc! {"student._id": {"$in": student_ids.to_vec()}};
+        find_all(&self.attendance_col, filter).await
+    }
+
+    pub async fn get_exam_results_for_students(
+        &self,
+        student_ids: &[ObjectId],
+    ) -> Result<Vec<ExamResult>, Error> {
+        let filter = doc! {"student._id": {"$in": student_ids.to_vec()}};
+        find_all(&self.exam_result_col, filter).await
+    }
+
+    pub async fn get_exam_results_for_exams(
+        &self,
+        exam_ids: &[ObjectId],
+    ) -> Result<Vec<ExamResult>, Error> {
+        let filter = doc! {"exam._id": {"$in": exam_ids.to_vec()}};
+        find_all(&self.exam_result_col, filter).await
+    }
+}
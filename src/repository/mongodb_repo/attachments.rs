@@ -0,0 +1,168 @@
+use super::{object_id, snapshot, MongoRepo};
+use crate::models::{
+    attachment::{Attachment, AttachmentRef, OwnerKind},
+    audit_entry::AuditContext,
+};
+use crate::repository::Error;
+use mongodb::{
+    bson::{doc, oid::ObjectId, to_bson, Document},
+    results::{DeleteResult, InsertOneResult, UpdateResult},
+    Collection,
+};
+
+impl MongoRepo {
+    pub async fn create_attachment(
+        &self,
+        new_attachment: Attachment,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let attachment = self.attachment_col.insert_one(new_attachment, None).await?;
+        if let Some(obj_id) = attachment.inserted_id.as_object_id() {
+            self.audit(
+                &self.attachment_col,
+                ctx,
+                "create",
+                "attachment",
+                obj_id,
+                None,
+            )
+            .await?;
+        }
+
+        Ok(attachment)
+    }
+
+    pub async fn get_attachment(&self, id: &String) -> Result<Option<Attachment>, Error> {
+        let obj_id = object_id(id)?;
+        let attachment_detail = self
+            .attachment_col
+            .find_one(doc! {"_id": obj_id}, None)
+            .await?;
+        Ok(attachment_detail)
+    }
+
+    pub async fn delete_attachment(
+        &self,
+        id: &String,
+        ctx: &AuditContext,
+    ) -> Result<DeleteResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.attachment_col, obj_id).await?;
+        let attachment_detail = self
+            .attachment_col
+            .delete_one(doc! {"_id": obj_id}, None)
+            .await?;
+        if attachment_detail.deleted_count == 1 {
+            self.audit(
+                &self.attachment_col,
+                ctx,
+                "delete",
+                "attachment",
+                obj_id,
+                before,
+            )
+            .await?;
+        }
+
+        Ok(attachment_detail)
+    }
+
+    /// Lists `reference` on its owner.
+    pub async fn add_attachment_ref(
+        &self,
+        kind: OwnerKind,
+        owner_id: ObjectId,
+        reference: &AttachmentRef,
+        ctx: &AuditContext,
+    ) -> Result<UpdateResult, Error> {
+        let update = doc! {"$push": {list_field(kind): to_bson(reference).unwrap()}};
+        self.update_owner(kind, owner_id, update, ctx).await
+    }
+
+    pub async fn remove_attachment_ref(
+        &self,
+        kind: OwnerKind,
+        owner_id: ObjectId,
+        attachment_id: ObjectId,
+        ctx: &AuditContext,
+    ) -> Result<UpdateResult, Error> {
+        let update = doc! {"$pull": {list_field(kind): {"id": attachment_id}}};
+        self.update_owner(kind, owner_id, update, ctx).await
+    }
+
+    async fn update_owner(
+        &self,
+        kind: OwnerKind,
+        owner_id: ObjectId,
+        mut update: Document,
+        ctx: &AuditContext,
+    ) -> Result<UpdateResult, Error> {
+        match kind {
+            // Versioned records change their ETag with their attachments.
+            OwnerKind::Student | OwnerKind::Teacher => {
+                update.insert("$inc", doc! {"version": 1});
+            }
+            OwnerKind::Assignment | OwnerKind::Submission | OwnerKind::Message => {}
+        }
+        match kind {
+            OwnerKind::Student => {
+                update_audited(self, &self.student_col, "student", owner_id, update, ctx).await
+            }
+            OwnerKind::Teacher => {
+                update_audited(self, &self.teacher_col, "teacher", owner_id, update, ctx).await
+            }
+            OwnerKind::Assignment => {
+                update_audited(
+                    self,
+                    &self.assignment_col,
+                    "assignment",
+                    owner_id,
+                    update,
+                    ctx,
+                )
+                .await
+            }
+            OwnerKind::Submission => {
+                update_audited(
+                    self,
+                    &self.submission_col,
+                    "submission",
+                    owner_id,
+                    update,
+                    ctx,
+                )
+                .await
+            }
+            OwnerKind::Message => {
+                update_audited(self, &self.message_col, "message", owner_id, update, ctx).await
+            }
+        }
+    }
+}
+
+/// Where an owner lists its attachments.
+fn list_field(kind: OwnerKind) -> &'static str {
+    match kind {
+        OwnerKind::Submission => "files",
+        OwnerKind::Student | OwnerKind::Teacher | OwnerKind::Assignment | OwnerKind::Message => {
+            "attachments"
+        }
+    }
+}
+
+async fn update_audited<T>(
+    repo: &MongoRepo,
+    col: &Collection<T>,
+    entity: &str,
+    obj_id: ObjectId,
+    update: Document,
+    ctx: &AuditContext,
+) -> Result<UpdateResult, Error> {
+    let before = snapshot(col, obj_id).await?;
+    let result = col.update_one(doc! {"_id": obj_id}, update, None).await?;
+    if result.modified_count == 1 {
+        repo.audit(col, ctx, "update", entity, obj_id, before)
+            .await?;
+    }
+    Ok(result)
+}
@@ -0,0 +1,393 @@
+use super::{is_duplicate_key, object_id, snapshot, MongoRepo};
+use crate::models::{
+    audit_entry::AuditContext,
+    billing_link::BillingLink,
+    discount::Discount,
+    fee_item::FeeItem,
+    invoice::{series, Invoice, InvoiceStatus, INVOICE_PREFIX, RECEIPT_PREFIX},
+    payment::Payment,
+};
+use crate::repository::Error;
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, oid::ObjectId, to_bson, Document},
+    options::FindOptions,
+    results::{DeleteResult, InsertOneResult},
+    Collection,
+};
+use serde::de::DeserializeOwned;
+
+async fn find_sorted<T>(
+    col: &Collection<T>,
+    filter: Document,
+    sort: Document,
+) -> Result<Vec<T>, Error>
+where
+    T: DeserializeOwned + Unpin + Send + Sync,
+{
+    let options = FindOptions::builder().sort(sort).build();
+    Ok(col.find(filter, options).await?.try_collect().await?)
+}
+
+impl MongoRepo {
+    pub async fn create_fee_item(
+        &self,
+        new_fee: FeeItem,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let fee = self.fee_item_col.insert_one(new_fee, None).await?;
+        if let Some(obj_id) = fee.inserted_id.as_object_id() {
+            self.audit(&self.fee_item_col, ctx, "create", "fee_item", obj_id, None)
+                .await?;
+        }
+
+        Ok(fee)
+    }
+
+    /// Fee items matching whichever of the filters are given.
+    pub async fn get_fee_items(
+        &self,
+        grade_id: Option<i64>,
+        year: Option<i32>,
+        term: Option<i32>,
+    ) -> Result<Vec<FeeItem>, Error> {
+        let mut filter = Document::new();
+        if let Some(grade_id) = grade_id {
+            filter.insert("grade_id", grade_id);
+        }
+        if let Some(year) = year {
+            filter.insert("year", year);
+        }
+        if let Some(term) = term {
+            filter.insert("term", term);
+        }
+        let sort = doc! {"year": -1, "term": -1, "grade_id": 1, "name": 1};
+        find_sorted(&self.fee_item_col, filter, sort).await
+    }
+
+    pub async fn delete_fee_item(
+        &self,
+        id: &String,
+        ctx: &AuditContext,
+    ) -> Result<DeleteResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.fee_item_col, obj_id).await?;
+        let fee_detail = self
+            .fee_item_col
+            .delete_one(doc! {"_id": obj_id}, None)
+            .await?;
+        if fee_detail.deleted_count == 1 {
+            self.audit(
+                &self.fee_item_col,
+                ctx,
+                "delete",
+                "fee_item",
+                obj_id,
+                before,
+            )
+            .await?;
+        }
+
+        Ok(fee_detail)
+    }
+
+    pub async fn create_discount(
+        &self,
+        new_discount: Discount,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let discount = self.discount_col.insert_one(new_discount, None).await?;
+        if let Some(obj_id) = discount.inserted_id.as_object_id() {
+            self.audit(&self.discount_col, ctx, "create", "discount", obj_id, None)
+                .await?;
+        }
+
+        Ok(discount)
+    }
+
+    pub async fn get_discounts_for_student(
+        &self,
+        student_id: &String,
+    ) -> Result<Vec<Discount>, Error> {
+        let filter = doc! {"student._id": object_id(student_id)?};
+        find_sorted(&self.discount_col, filter, doc! {"year": -1, "term": -1}).await
+    }
+
+    /// Discounts that apply to the term, including year-long ones.
+    pub async fn get_discounts_for_term(
+        &self,
+        year: i32,
+        term: i32,
+    ) -> Result<Vec<Discount>, Error> {
+        let filter = doc! {"year": year, "$or": [{"term": term}, {"term": null}]};
+        find_sorted(&self.discount_col, filter, doc! {"name": 1}).await
+    }
+
+    pub async fn delete_discount(
+        &self,
+        id: &String,
+        ctx: &AuditContext,
+    ) -> Result<DeleteResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.discount_col, obj_id).await?;
+        let discount_detail = self
+            .discount_col
+            .delete_one(doc! {"_id": obj_id}, None)
+            .await?;
+        if discount_detail.deleted_count == 1 {
+            self.audit(
+                &self.discount_col,
+                ctx,
+                "delete",
+                "discount",
+                obj_id,
+                before,
+            )
+            .await?;
+        }
+
+        Ok(discount_detail)
+    }
+
+    /// Inserts the invoice, or returns `None` without inserting when the
+    /// student already has one for the term that is not void.
+    pub async fn create_invoice(
+        &self,
+        new_invoice: Invoice,
+        ctx: &AuditContext,
+    ) -> Result<Option<InsertOneResult>, Error> {
+        let invoice = match self.invoice_col.insert_one(new_invoice, None).await {
+            Ok(invoice) => invoice,
+            Err(err) if is_duplicate_key(&err) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        if let Some(obj_id) = invoice.inserted_id.as_object_id() {
+            self.audit(&self.invoice_col, ctx, "create", "invoice", obj_id, None)
+                .await?;
+        }
+
+        Ok(Some(invoice))
+    }
+
+    pub async fn get_invoice(&self, id: &String) -> Result<Option<Invoice>, Error> {
+        let obj_id = object_id(id)?;
+        let invoice_detail = self
+            .invoice_col
+            .find_one(doc! {"_id": obj_id}, None)
+            .await?;
+        Ok(invoice_detail)
+    }
+
+    /// Gives the invoice the next number in its academic year's series,
+    /// unless it has one already, and returns its number.
+    pub async fn number_invoice(&self, invoice_id: ObjectId, year: i32) -> Result<i64, Error> {
+        let series = series(INVOICE_PREFIX, year);
+        self.assign_number(&self.invoice_col, "number", &series, invoice_id)
+            .await
+    }
+
+    /// Students already billed for the term; void invoices do not count.
+    pub async fn get_invoiced_students(
+        &self,
+        year: i32,
+        term: i32,
+    ) -> Result<Vec<ObjectId>, Error> {
+        let filter = doc! {"year": year, "term": term, "status": {"$ne": to_bson(&InvoiceStatus::Void).unwrap()}};
+        let invoices = find_sorted(&self.invoice_col, filter, doc! {}).await?;
+        Ok(invoices
+            .into_iter()
+            .filter_map(|invoice| invoice.student.id)
+            .collect())
+    }
+
+    pub async fn get_invoices_for_student(
+        &self,
+        student_id: &String,
+    ) -> Result<Vec<Invoice>, Error> {
+        let filter = doc! {"student._id": object_id(student_id)?};
+        find_sorted(&self.invoice_col, filter, doc! {"issued_at": 1}).await
+    }
+
+    /// Invoices of the children the parent is now guardian of, archived
+    /// ones included. Guardianship is read from the students, not from the
+    /// invoices, so a child who changed guardian moves with it.
+    pub async fn get_invoices_for_parent(&self, parent_id: &String) -> Result<Vec<Invoice>, Error> {
+        let children = doc! {"parent._id": object_id(parent_id)?};
+        let student_ids: Vec<ObjectId> = find_sorted(&self.student_col, children, doc! {})
+            .await?
+            .into_iter()
+            .filter_map(|student| student.id)
+            .collect();
+        let filter = doc! {"student._id": {"$in": student_ids}};
+        find_sorted(&self.invoice_col, filter, doc! {"issued_at": 1}).await
+    }
+
+    /// Records `payment` against `invoice` as it was read. Returns `None`
+    /// without recording anything if the invoice has changed since, so
+    /// two payments can never both take the last of what is owed.
+    pub async fn record_payment(
+        &self,
+        invoice: &Invoice,
+        mut payment: Payment,
+        ctx: &AuditContext,
+    ) -> Result<Option<InsertOneResult>, Error> {
+        let obj_id = payment.invoice_id;
+        let paid_cents = invoice.paid_cents + payment.amount_cents;
+        let status = if paid_cents >= invoice.total_cents {
+            InvoiceStatus::Paid
+        } else {
+            InvoiceStatus::Open
+        };
+        // The payment goes in first so that a failure part way never leaves
+        // an invoice marked paid without the payment that paid it; it is
+        // taken out again if the invoice cannot be updated.
+        let payment_id = ObjectId::new();
+        payment.id = Some(payment_id);
+        let payment_detail = self.payment_col.insert_one(payment, None).await?;
+        let before = snapshot(&self.invoice_col, obj_id).await?;
+        let filter = doc! {
+            "_id": obj_id,
+            "paid_cents": invoice.paid_cents,
+            "status": to_bson(&InvoiceStatus::Open).unwrap(),
+        };
+        let update = doc! {"$set": {"paid_cents": paid_cents, "status": to_bson(&status).unwrap()}};
+        let updated = match self.invoice_col.update_one(filter, update, None).await {
+            Ok(updated) => updated.matched_count == 1,
+            Err(err) => {
+                self.withdraw_payment(payment_id).await;
+                return Err(err.into());
+            }
+        };
+        if !updated {
+            self.withdraw_payment(payment_id).await;
+            return Ok(None);
+        }
+        self.audit(&self.invoice_col, ctx, "update", "invoice", obj_id, before)
+            .await?;
+        self.audit(
+            &self.payment_col,
+            ctx,
+            "create",
+            "payment",
+            payment_id,
+            None,
+        )
+        .await?;
+
+        Ok(Some(payment_detail))
+    }
+
+    /// Removes a payment whose invoice could not be updated.
+    async fn withdraw_payment(&self, payment_id: ObjectId) {
+        if let Err(err) = self
+            .payment_col
+            .delete_one(doc! {"_id": payment_id}, None)
+            .await
+        {
+            tracing::error!(payment = %payment_id, error = %err, "error withdrawing payment");
+        }
+    }
+
+    /// Voids an open invoice that nothing has been paid against. Returns
+    /// whether it was voided.
+    pub async fn void_invoice(&self, id: &String, ctx: &AuditContext) -> Result<bool, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.invoice_col, obj_id).await?;
+        let filter = doc! {
+            "_id": obj_id,
+            "paid_cents": 0_i64,
+            "status": to_bson(&InvoiceStatus::Open).unwrap(),
+        };
+        let update = doc! {"$set": {"status": to_bson(&InvoiceStatus::Void).unwrap()}};
+        let invoice_detail = self.invoice_col.update_one(filter, update, None).await?;
+        if invoice_detail.matched_count == 1 {
+            self.audit(&self.invoice_col, ctx, "void", "invoice", obj_id, before)
+                .await?;
+        }
+
+        Ok(invoice_detail.matched_count == 1)
+    }
+
+    pub async fn get_payment(&self, id: &String) -> Result<Option<Payment>, Error> {
+        let obj_id = object_id(id)?;
+        let payment_detail = self
+            .payment_col
+            .find_one(doc! {"_id": obj_id}, None)
+            .await?;
+        Ok(payment_detail)
+    }
+
+    /// Gives the payment a receipt number in the series for `year`, the
+    /// academic year of the invoice it paid, unless it has one already.
+    pub async fn number_receipt(&self, payment_id: ObjectId, year: i32) -> Result<i64, Error> {
+        let series = series(RECEIPT_PREFIX, year);
+        self.assign_number(&self.payment_col, "receipt_number", &series, payment_id)
+            .await
+    }
+
+    pub async fn get_payments_for_invoices(
+        &self,
+        invoice_ids: &[ObjectId],
+    ) -> Result<Vec<Payment>, Error> {
+        let filter = doc! {"invoice_id": {"$in": invoice_ids.to_vec()}};
+        find_sorted(&self.payment_col, filter, doc! {"received_at": 1}).await
+    }
+
+    pub async fn create_billing_link(
+        &self,
+        new_link: BillingLink,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let link = self.billing_link_col.insert_one(new_link, None).await?;
+        if let Some(obj_id) = link.inserted_id.as_object_id() {
+            self.audit(
+                &self.billing_link_col,
+                ctx,
+                "create",
+                "billing_link",
+                obj_id,
+                None,
+            )
+            .await?;
+        }
+
+        Ok(link)
+    }
+
+    pub async fn get_billing_link_by_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<BillingLink>, Error> {
+        let link_detail = self
+            .billing_link_col
+            .find_one(doc! {"token": token}, None)
+            .await?;
+        Ok(link_detail)
+    }
+
+    pub async fn delete_billing_link(
+        &self,
+        id: &String,
+        ctx: &AuditContext,
+    ) -> Result<DeleteResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.billing_link_col, obj_id).await?;
+        let link_detail = self
+            .billing_link_col
+            .delete_one(doc! {"_id": obj_id}, None)
+            .await?;
+        if link_detail.deleted_count == 1 {
+            self.audit(
+                &self.billing_link_col,
+                ctx,
+                "delete",
+                "billing_link",
+                obj_id,
+                before,
+            )
+            .await?;
+        }
+
+        Ok(link_detail)
+    }
+}
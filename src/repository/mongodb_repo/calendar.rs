@@ -0,0 +1,130 @@
+use super::{object_id, snapshot, MongoRepo};
+use crate::models::{
+    audit_entry::AuditContext, calendar_feed::CalendarFeed, school_event::SchoolEvent,
+};
+use crate::repository::Error;
+use futures::TryStreamExt;
+use mongodb::{
+    bson::doc,
+    options::FindOptions,
+    results::{DeleteResult, InsertOneResult},
+};
+
+impl MongoRepo {
+    pub async fn create_school_event(
+        &self,
+        new_event: SchoolEvent,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let event = self.school_event_col.insert_one(new_event, None).await?;
+        if let Some(obj_id) = event.inserted_id.as_object_id() {
+            self.audit(
+                &self.school_event_col,
+                ctx,
+                "create",
+                "school_event",
+                obj_id,
+                None,
+            )
+            .await?;
+        }
+
+        Ok(event)
+    }
+
+    /// Every school event, soonest first.
+    pub async fn get_all_school_events(&self) -> Result<Vec<SchoolEvent>, Error> {
+        let options = FindOptions::builder().sort(doc! {"starts_at": 1}).build();
+        let events = self
+            .school_event_col
+            .find(None, options)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(events)
+    }
+
+    pub async fn delete_school_event(
+        &self,
+        id: &String,
+        ctx: &AuditContext,
+    ) -> Result<DeleteResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.school_event_col, obj_id).await?;
+        let event_detail = self
+            .school_event_col
+            .delete_one(doc! {"_id": obj_id}, None)
+            .await?;
+        if event_detail.deleted_count == 1 {
+            self.audit(
+                &self.school_event_col,
+                ctx,
+                "delete",
+                "school_event",
+                obj_id,
+                before,
+            )
+            .await?;
+        }
+
+        Ok(event_detail)
+    }
+
+    pub async fn create_calendar_feed(
+        &self,
+        new_feed: CalendarFeed,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let feed = self.calendar_feed_col.insert_one(new_feed, None).await?;
+        if let Some(obj_id) = feed.inserted_id.as_object_id() {
+            self.audit(
+                &self.calendar_feed_col,
+                ctx,
+                "create",
+                "calendar_feed",
+                obj_id,
+                None,
+            )
+            .await?;
+        }
+
+        Ok(feed)
+    }
+
+    pub async fn get_calendar_feed_by_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<CalendarFeed>, Error> {
+        let feed_detail = self
+            .calendar_feed_col
+            .find_one(doc! {"token": token}, None)
+            .await?;
+        Ok(feed_detail)
+    }
+
+    pub async fn delete_calendar_feed(
+        &self,
+        id: &String,
+        ctx: &AuditContext,
+    ) -> Result<DeleteResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.calendar_feed_col, obj_id).await?;
+        let feed_detail = self
+            .calendar_feed_col
+            .delete_one(doc! {"_id": obj_id}, None)
+            .await?;
+        if feed_detail.deleted_count == 1 {
+            self.audit(
+                &self.calendar_feed_col,
+                ctx,
+                "delete",
+                "calendar_feed",
+                obj_id,
+                before,
+            )
+            .await?;
+        }
+
+        Ok(feed_detail)
+    }
+}
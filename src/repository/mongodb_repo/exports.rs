@@ -0,0 +1,74 @@
+use super::{archive_filter, object_id, MongoRepo};
+use crate::models::{
+    attendance::Attendance, classroom_student::ClassroomStudent, exam_result::ExamResult,
+    student::Student,
+};
+use crate::repository::Error;
+use mongodb::{
+    bson::{doc, DateTime, Document},
+    options::FindOptions,
+    Cursor,
+};
+
+fn sorted_by(sort: Document) -> FindOptions {
+    FindOptions::builder().sort(sort).build()
+}
+
+/// Cursors behind the spreadsheet exports. Unlike the `get_all_*` methods
+/// these hand back the open cursor so rows can be written as documents
+/// arrive instead of being collected first.
+impl MongoRepo {
+    pub async fn stream_students(&self, include_deleted: bool) -> Result<Cursor<Student>, Error> {
+        let cursor = self
+            .student_col
+            .find(
+                archive_filter(include_deleted),
+                sorted_by(doc! {"lname": 1, "fname": 1}),
+            )
+            .await?;
+        Ok(cursor)
+    }
+
+    pub async fn stream_roster(
+        &self,
+        classroom_id: &String,
+    ) -> Result<Cursor<ClassroomStudent>, Error> {
+        let obj_id = object_id(classroom_id)?;
+        let cursor = self
+            .classroom_student_col
+            .find(
+                doc! {"classroom._id": obj_id},
+                sorted_by(doc! {"student.lname": 1, "student.fname": 1}),
+            )
+            .await?;
+        Ok(cursor)
+    }
+
+    /// Attendance dated in `[from, to)`, by day and then by student.
+    pub async fn stream_attendance(
+        &self,
+        from: DateTime,
+        to: DateTime,
+    ) -> Result<Cursor<Attendance>, Error> {
+        let cursor = self
+            .attendance_col
+            .find(
+                doc! {"date": {"$gte": from, "$lt": to}},
+                sorted_by(doc! {"date": 1, "student.lname": 1, "student.fname": 1}),
+            )
+            .await?;
+        Ok(cursor)
+    }
+
+    pub async fn stream_exam_results(&self, exam_id: &String) -> Result<Cursor<ExamResult>, Error> {
+        let obj_id = object_id(exam_id)?;
+        let cursor = self
+            .exam_result_col
+            .find(
+                doc! {"exam._id": obj_id},
+                sorted_by(doc! {"student.lname": 1, "student.fname": 1, "course.name": 1}),
+            )
+            .await?;
+        Ok(cursor)
+    }
+}
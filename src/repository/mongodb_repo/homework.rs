@@ -0,0 +1,155 @@
+use super::{object_id, snapshot, MongoRepo};
+use crate::models::{
+    assignment::Assignment,
+    audit_entry::AuditContext,
+    submission::{Submission, SubmissionGrade},
+};
+use crate::repository::Error;
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, oid::ObjectId, to_bson, Document},
+    options::FindOptions,
+    results::{InsertOneResult, UpdateResult},
+    Collection,
+};
+use serde::de::DeserializeOwned;
+
+async fn find_sorted<T>(
+    col: &Collection<T>,
+    filter: Document,
+    sort: Document,
+) -> Result<Vec<T>, Error>
+where
+    T: DeserializeOwned + Unpin + Send + Sync,
+{
+    let options = FindOptions::builder().sort(sort).build();
+    Ok(col.find(filter, options).await?.try_collect().await?)
+}
+
+impl MongoRepo {
+    pub async fn create_homework_assignment(
+        &self,
+        new_assignment: Assignment,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let assignment = self.assignment_col.insert_one(new_assignment, None).await?;
+        if let Some(obj_id) = assignment.inserted_id.as_object_id() {
+            self.audit(
+                &self.assignment_col,
+                ctx,
+                "create",
+                "assignment",
+                obj_id,
+                None,
+            )
+            .await?;
+        }
+
+        Ok(assignment)
+    }
+
+    pub async fn get_homework_assignment(&self, id: &String) -> Result<Option<Assignment>, Error> {
+        let obj_id = object_id(id)?;
+        let assignment_detail = self
+            .assignment_col
+            .find_one(doc! {"_id": obj_id}, None)
+            .await?;
+        Ok(assignment_detail)
+    }
+
+    /// Homework set for any of the classrooms, soonest due first.
+    pub async fn get_homework_for_classrooms(
+        &self,
+        classroom_ids: &[ObjectId],
+    ) -> Result<Vec<Assignment>, Error> {
+        let filter = doc! {"classroom._id": {"$in": classroom_ids.to_vec()}};
+        find_sorted(&self.assignment_col, filter, doc! {"due_at": 1}).await
+    }
+
+    pub async fn create_submission(
+        &self,
+        new_submission: Submission,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let submission = self.submission_col.insert_one(new_submission, None).await?;
+        if let Some(obj_id) = submission.inserted_id.as_object_id() {
+            self.audit(
+                &self.submission_col,
+                ctx,
+                "create",
+                "submission",
+                obj_id,
+                None,
+            )
+            .await?;
+        }
+
+        Ok(submission)
+    }
+
+    pub async fn get_submission(&self, id: &String) -> Result<Option<Submission>, Error> {
+        let obj_id = object_id(id)?;
+        let submission_detail = self
+            .submission_col
+            .find_one(doc! {"_id": obj_id}, None)
+            .await?;
+        Ok(submission_detail)
+    }
+
+    pub async fn get_student_submission(
+        &self,
+        assignment_id: &String,
+        student_id: &String,
+    ) -> Result<Option<Submission>, Error> {
+        let filter = doc! {
+            "assignment._id": object_id(assignment_id)?,
+            "student._id": object_id(student_id)?,
+        };
+        let submission_detail = self.submission_col.find_one(filter, None).await?;
+        Ok(submission_detail)
+    }
+
+    pub async fn get_submissions_for_assignment(
+        &self,
+        assignment_id: &String,
+    ) -> Result<Vec<Submission>, Error> {
+        let filter = doc! {"assignment._id": object_id(assignment_id)?};
+        find_sorted(&self.submission_col, filter, doc! {"submitted_at": 1}).await
+    }
+
+    pub async fn get_submissions_for_student(
+        &self,
+        student_id: &String,
+    ) -> Result<Vec<Submission>, Error> {
+        let filter = doc! {"student._id": object_id(student_id)?};
+        find_sorted(&self.submission_col, filter, doc! {"assignment.due_at": 1}).await
+    }
+
+    pub async fn grade_submission(
+        &self,
+        id: &String,
+        grade: SubmissionGrade,
+        ctx: &AuditContext,
+    ) -> Result<UpdateResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.submission_col, obj_id).await?;
+        let update = doc! {"$set": {"grade": to_bson(&grade).unwrap()}};
+        let submission_detail = self
+            .submission_col
+            .update_one(doc! {"_id": obj_id}, update, None)
+            .await?;
+        if submission_detail.matched_count == 1 {
+            self.audit(
+                &self.submission_col,
+                ctx,
+                "grade",
+                "submission",
+                obj_id,
+                before,
+            )
+            .await?;
+        }
+
+        Ok(submission_detail)
+    }
+}
@@ -0,0 +1,76 @@
+use super::MongoRepo;
+use crate::models::invoice::InvoiceStatus;
+use mongodb::{
+    bson::{doc, to_bson, Document},
+    options::IndexOptions,
+    IndexModel,
+};
+
+fn unique(name: &str, keys: Document) -> IndexModel {
+    unique_where(name, keys, None)
+}
+
+/// Unique only among the documents matching `filter`, when given.
+fn unique_where(name: &str, keys: Document, filter: Option<Document>) -> IndexModel {
+    let options = IndexOptions::builder()
+        .name(name.to_string())
+        .unique(true)
+        .partial_filter_expression(filter)
+        .build();
+    IndexModel::builder().keys(keys).options(options).build()
+}
+
+impl MongoRepo {
+    /// Creates the unique indexes that enforce rules a check-then-write in
+    /// the handlers cannot, since two requests can both pass the check.
+    /// Each is created on its own so one that fails does not hold back the
+    /// rest; creating an index that already exists does nothing. Returns
+    /// the ones that failed.
+    pub async fn ensure_indexes(&self) -> Vec<(&'static str, mongodb::error::Error)> {
+        let mut failed = Vec::new();
+        // No teacher, room or classroom is booked twice in one period.
+        let timetable = [
+            ("period_teacher", doc! {"period._id": 1, "teacher._id": 1}),
+            ("period_room", doc! {"period._id": 1, "room": 1}),
+            (
+                "period_classroom",
+                doc! {"period._id": 1, "classroom._id": 1},
+            ),
+        ];
+        for (name, keys) in timetable {
+            if let Err(err) = self
+                .timetable_col
+                .create_index(unique(name, keys), None)
+                .await
+            {
+                failed.push((name, err));
+            }
+        }
+        // One invoice per student and term, not counting voided ones.
+        // Partial indexes cannot filter on `$ne`, hence listing the rest;
+        // `$in` in a partial filter needs MongoDB 6.0.
+        let billable = [InvoiceStatus::Open, InvoiceStatus::Paid]
+            .map(|status| to_bson(&status).expect("InvoiceStatus serializes as a string"));
+        let student_term = unique_where(
+            "student_term",
+            doc! {"student._id": 1, "year": 1, "term": 1},
+            Some(doc! {"status": {"$in": billable.to_vec()}}),
+        );
+        if let Err(err) = self.invoice_col.create_index(student_term, None).await {
+            failed.push(("student_term", err));
+        }
+        // Each event is queued at most once per parent and channel.
+        let parent_channel_event = unique(
+            "parent_channel_event",
+            doc! {"parent_id": 1, "channel": 1, "event_key": 1},
+        );
+        if let Err(err) = self
+            .notification_col
+            .create_index(parent_channel_event, None)
+            .await
+        {
+            failed.push(("parent_channel_event", err));
+        }
+        failed
+    }
+}
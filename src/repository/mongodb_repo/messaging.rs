@@ -0,0 +1,258 @@
+use super::{object_id, snapshot, MongoRepo};
+use crate::models::{
+    audit_entry::AuditContext,
+    conversation::{Conversation, Message, Participant},
+    messaging_link::MessagingLink,
+};
+use crate::repository::Error;
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, oid::ObjectId, to_bson, DateTime, Document},
+    options::FindOptions,
+    results::{DeleteResult, InsertOneResult},
+};
+
+impl MongoRepo {
+    /// Whether the teacher teaches the student this school year, as their
+    /// homeroom teacher or through a course, going by the latest year the
+    /// student is enrolled in.
+    pub async fn teaches_student(
+        &self,
+        teacher_id: ObjectId,
+        student_id: ObjectId,
+    ) -> Result<bool, Error> {
+        let enrollments = self.get_enrollments_for_students(&[student_id]).await?;
+        let Some(year) = enrollments
+            .iter()
+            .map(|enrollment| enrollment.classroom.year)
+            .max()
+        else {
+            return Ok(false);
+        };
+        let classroom_ids: Vec<ObjectId> = enrollments
+            .iter()
+            .filter(|enrollment| enrollment.classroom.year == year)
+            .filter_map(|enrollment| enrollment.classroom.id)
+            .collect();
+        let homeroom = self
+            .classroom_col
+            .count_documents(
+                doc! {"_id": {"$in": &classroom_ids}, "teacher._id": teacher_id},
+                None,
+            )
+            .await?;
+        if homeroom > 0 {
+            return Ok(true);
+        }
+        let courses = self
+            .course_assignment_col
+            .count_documents(
+                doc! {
+                    "classroom._id": {"$in": &classroom_ids},
+                    "teacher._id": teacher_id,
+                    "year": year,
+                },
+                None,
+            )
+            .await?;
+        Ok(courses > 0)
+    }
+
+    pub async fn create_conversation(
+        &self,
+        new_conversation: Conversation,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let conversation = self
+            .conversation_col
+            .insert_one(new_conversation, None)
+            .await?;
+        if let Some(obj_id) = conversation.inserted_id.as_object_id() {
+            self.audit(
+                &self.conversation_col,
+                ctx,
+                "create",
+                "conversation",
+                obj_id,
+                None,
+            )
+            .await?;
+        }
+
+        Ok(conversation)
+    }
+
+    pub async fn get_conversation(&self, id: &String) -> Result<Option<Conversation>, Error> {
+        let obj_id = object_id(id)?;
+        let conversation_detail = self
+            .conversation_col
+            .find_one(doc! {"_id": obj_id}, None)
+            .await?;
+        Ok(conversation_detail)
+    }
+
+    /// Conversations with the latest message first, narrowed by whichever
+    /// of teacher, parent and student are given.
+    pub async fn get_conversations(
+        &self,
+        teacher_id: Option<&String>,
+        parent_id: Option<&String>,
+        student_id: Option<&String>,
+    ) -> Result<Vec<Conversation>, Error> {
+        let mut filter = Document::new();
+        for (field, id) in [
+            ("teacher_id", teacher_id),
+            ("parent_id", parent_id),
+            ("student_id", student_id),
+        ] {
+            if let Some(id) = id {
+                filter.insert(field, object_id(id)?);
+            }
+        }
+        let options = FindOptions::builder()
+            .sort(doc! {"last_message_at": -1})
+            .build();
+        let conversations = self
+            .conversation_col
+            .find(filter, options)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(conversations)
+    }
+
+    /// Adds the message to its conversation and counts it as unread for
+    /// the other side.
+    pub async fn create_message(
+        &self,
+        new_message: Message,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let conversation_id = new_message.conversation_id;
+        let unread = new_message.sender.other().unread_field();
+        let sent_at = new_message.sent_at;
+        let message = self.message_col.insert_one(new_message, None).await?;
+        if let Some(obj_id) = message.inserted_id.as_object_id() {
+            self.audit(&self.message_col, ctx, "create", "message", obj_id, None)
+                .await?;
+        }
+        self.conversation_col
+            .update_one(
+                doc! {"_id": conversation_id},
+                doc! {"$set": {"last_message_at": sent_at}, "$inc": {unread: 1}},
+                None,
+            )
+            .await?;
+
+        Ok(message)
+    }
+
+    pub async fn get_message(&self, id: &String) -> Result<Option<Message>, Error> {
+        let obj_id = object_id(id)?;
+        let message_detail = self
+            .message_col
+            .find_one(doc! {"_id": obj_id}, None)
+            .await?;
+        Ok(message_detail)
+    }
+
+    /// The conversation's messages, oldest first.
+    pub async fn get_messages(&self, conversation_id: ObjectId) -> Result<Vec<Message>, Error> {
+        let options = FindOptions::builder().sort(doc! {"sent_at": 1}).build();
+        let messages = self
+            .message_col
+            .find(doc! {"conversation_id": conversation_id}, options)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(messages)
+    }
+
+    /// Marks what the other side sent as read by `reader`, keeping the time
+    /// each message was first read. Returns how many were newly read.
+    pub async fn mark_conversation_read(
+        &self,
+        conversation_id: ObjectId,
+        reader: Participant,
+    ) -> Result<u64, Error> {
+        let now = DateTime::now();
+        let read = self
+            .message_col
+            .update_many(
+                doc! {
+                    "conversation_id": conversation_id,
+                    "sender": to_bson(&reader.other()).unwrap(),
+                    "read_at": null,
+                },
+                doc! {"$set": {"read_at": now}},
+                None,
+            )
+            .await?;
+        self.conversation_col
+            .update_one(
+                doc! {"_id": conversation_id},
+                doc! {"$set": {reader.unread_field(): 0}},
+                None,
+            )
+            .await?;
+        Ok(read.modified_count)
+    }
+
+    pub async fn create_messaging_link(
+        &self,
+        new_link: MessagingLink,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let link = self.messaging_link_col.insert_one(new_link, None).await?;
+        if let Some(obj_id) = link.inserted_id.as_object_id() {
+            self.audit(
+                &self.messaging_link_col,
+                ctx,
+                "create",
+                "messaging_link",
+                obj_id,
+                None,
+            )
+            .await?;
+        }
+
+        Ok(link)
+    }
+
+    pub async fn get_messaging_link_by_token(
+        &self,
+        token: &str,
+    ) -> Result<Option<MessagingLink>, Error> {
+        let link_detail = self
+            .messaging_link_col
+            .find_one(doc! {"token": token}, None)
+            .await?;
+        Ok(link_detail)
+    }
+
+    pub async fn delete_messaging_link(
+        &self,
+        id: &String,
+        ctx: &AuditContext,
+    ) -> Result<DeleteResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.messaging_link_col, obj_id).await?;
+        let link_detail = self
+            .messaging_link_col
+            .delete_one(doc! {"_id": obj_id}, None)
+            .await?;
+        if link_detail.deleted_count == 1 {
+            self.audit(
+                &self.messaging_link_col,
+                ctx,
+                "delete",
+                "messaging_link",
+                obj_id,
+                before,
+            )
+            .await?;
+        }
+
+        Ok(link_detail)
+    }
+}
@@ -0,0 +1,276 @@
+use super::{is_duplicate_key, object_id, snapshot, MongoRepo};
+use crate::models::{
+    audit_entry::AuditContext,
+    invoice::{Invoice, InvoiceStatus},
+    notification::{ChannelKind, DeliveryStatus, Notification},
+    notification_preferences::NotificationPreferences,
+};
+use crate::repository::Error;
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, to_bson, Bson, DateTime, Document},
+    options::{FindOneAndUpdateOptions, FindOptions, ReplaceOptions, ReturnDocument},
+    results::InsertOneResult,
+};
+use uuid::Uuid;
+
+fn status(status: DeliveryStatus) -> Bson {
+    to_bson(&status).unwrap()
+}
+
+/// Matches the notification only while it is still held under the claim
+/// it was returned with, so a worker whose lease ran out and was taken
+/// over cannot overwrite what the new holder records.
+fn leased(claimed: &Notification) -> Document {
+    doc! {
+        "_id": claimed.id,
+        "status": status(DeliveryStatus::Pending),
+        "lease_owner": claimed.lease_owner.as_deref(),
+        "next_attempt_at": claimed.next_attempt_at,
+    }
+}
+
+impl MongoRepo {
+    /// Adds the notification to the outbox unless its event was already
+    /// queued on that channel, which a unique index rules out; returns
+    /// `None` if so.
+    pub async fn queue_notification(
+        &self,
+        notification: Notification,
+    ) -> Result<Option<InsertOneResult>, Error> {
+        match self.notification_col.insert_one(notification, None).await {
+            Ok(queued) => Ok(Some(queued)),
+            Err(err) if is_duplicate_key(&err) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Takes the pending notification longest due, if any is due at `now`,
+    /// counting the attempt and holding it under a fresh lease owner until
+    /// `lease_until` so no other worker takes it meanwhile. One whose worker
+    /// died becomes due again when the lease runs out.
+    pub async fn claim_notification(
+        &self,
+        now: DateTime,
+        lease_until: DateTime,
+    ) -> Result<Option<Notification>, Error> {
+        let options = FindOneAndUpdateOptions::builder()
+            .sort(doc! {"next_attempt_at": 1})
+            .return_document(ReturnDocument::After)
+            .build();
+        let claimed = self
+            .notification_col
+            .find_one_and_update(
+                doc! {
+                    "status": status(DeliveryStatus::Pending),
+                    "next_attempt_at": {"$lte": now},
+                },
+                doc! {
+                    "$set": {
+                        "next_attempt_at": lease_until,
+                        "lease_owner": Uuid::new_v4().simple().to_string(),
+                    },
+                    "$inc": {"attempts": 1},
+                },
+                options,
+            )
+            .await?;
+        Ok(claimed)
+    }
+
+    /// Records the delivery of a notification taken with
+    /// `claim_notification`. Returns false when the claim had lapsed and
+    /// another worker holds it now.
+    pub async fn mark_notification_sent(&self, claimed: &Notification) -> Result<bool, Error> {
+        let marked = self
+            .notification_col
+            .update_one(
+                leased(claimed),
+                doc! {
+                    "$set": {
+                        "status": status(DeliveryStatus::Sent),
+                        "sent_at": DateTime::now(),
+                        "last_error": null,
+                    },
+                    "$unset": {"lease_owner": ""},
+                },
+                None,
+            )
+            .await?;
+        Ok(marked.matched_count == 1)
+    }
+
+    /// Records a failed attempt at a notification taken with
+    /// `claim_notification`: tried again at `retry_at`, or given up on when
+    /// that is `None`. Returns false when the claim had lapsed.
+    pub async fn mark_notification_failed(
+        &self,
+        claimed: &Notification,
+        error: &str,
+        retry_at: Option<DateTime>,
+    ) -> Result<bool, Error> {
+        let set = match retry_at {
+            Some(at) => doc! {"next_attempt_at": at, "last_error": error},
+            None => doc! {
+                "status": status(DeliveryStatus::Failed),
+                "last_error": error,
+            },
+        };
+        let marked = self
+            .notification_col
+            .update_one(
+                leased(claimed),
+                doc! {"$set": set, "$unset": {"lease_owner": ""}},
+                None,
+            )
+            .await?;
+        Ok(marked.matched_count == 1)
+    }
+
+    /// Queues a failed notification again with a fresh set of attempts.
+    /// Returns whether it had failed.
+    pub async fn retry_notification(&self, id: &String) -> Result<bool, Error> {
+        let obj_id = object_id(id)?;
+        let retried = self
+            .notification_col
+            .update_one(
+                doc! {"_id": obj_id, "status": status(DeliveryStatus::Failed)},
+                doc! {"$set": {
+                    "status": status(DeliveryStatus::Pending),
+                    "attempts": 0,
+                    "next_attempt_at": DateTime::now(),
+                }},
+                None,
+            )
+            .await?;
+        Ok(retried.matched_count == 1)
+    }
+
+    /// The outbox, newest first, narrowed by whichever filters are given.
+    pub async fn get_notifications(
+        &self,
+        delivery: Option<DeliveryStatus>,
+        parent_id: Option<&String>,
+        limit: i64,
+    ) -> Result<Vec<Notification>, Error> {
+        let mut filter = Document::new();
+        if let Some(delivery) = delivery {
+            filter.insert("status", status(delivery));
+        }
+        if let Some(parent_id) = parent_id {
+            filter.insert("parent_id", object_id(parent_id)?);
+        }
+        let options = FindOptions::builder()
+            .sort(doc! {"created_at": -1})
+            .limit(limit)
+            .build();
+        let notifications = self
+            .notification_col
+            .find(filter, options)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(notifications)
+    }
+
+    /// The parent's in-app notifications, newest first.
+    pub async fn get_inbox(
+        &self,
+        parent_id: &String,
+        unread_only: bool,
+    ) -> Result<Vec<Notification>, Error> {
+        let mut filter = doc! {
+            "parent_id": object_id(parent_id)?,
+            "channel": to_bson(&ChannelKind::InApp).unwrap(),
+            "status": status(DeliveryStatus::Sent),
+        };
+        if unread_only {
+            filter.insert("read_at", Bson::Null);
+        }
+        let options = FindOptions::builder().sort(doc! {"created_at": -1}).build();
+        let notifications = self
+            .notification_col
+            .find(filter, options)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(notifications)
+    }
+
+    /// Marks one of the parent's in-app notifications read, keeping the
+    /// time it was first read. Returns whether the parent has it.
+    pub async fn mark_notification_read(
+        &self,
+        parent_id: &String,
+        id: &String,
+    ) -> Result<bool, Error> {
+        let filter = doc! {
+            "_id": object_id(id)?,
+            "parent_id": object_id(parent_id)?,
+            "channel": to_bson(&ChannelKind::InApp).unwrap(),
+            "status": status(DeliveryStatus::Sent),
+        };
+        let found = self.notification_col.find_one(filter.clone(), None).await?;
+        let Some(notification) = found else {
+            return Ok(false);
+        };
+        if notification.read_at.is_none() {
+            self.notification_col
+                .update_one(filter, doc! {"$set": {"read_at": DateTime::now()}}, None)
+                .await?;
+        }
+        Ok(true)
+    }
+
+    pub async fn get_notification_preferences(
+        &self,
+        parent_id: &String,
+    ) -> Result<Option<NotificationPreferences>, Error> {
+        let obj_id = object_id(parent_id)?;
+        let preferences = self
+            .notification_preferences_col
+            .find_one(doc! {"_id": obj_id}, None)
+            .await?;
+        Ok(preferences)
+    }
+
+    pub async fn set_notification_preferences(
+        &self,
+        preferences: NotificationPreferences,
+        ctx: &AuditContext,
+    ) -> Result<(), Error> {
+        let obj_id = preferences.parent_id;
+        let before = snapshot(&self.notification_preferences_col, obj_id).await?;
+        let options = ReplaceOptions::builder().upsert(true).build();
+        self.notification_preferences_col
+            .replace_one(doc! {"_id": obj_id}, preferences, options)
+            .await?;
+        let action = if before.is_some() { "update" } else { "create" };
+        self.audit(
+            &self.notification_preferences_col,
+            ctx,
+            action,
+            "notification_preferences",
+            obj_id,
+            before,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Open invoices whose due date has passed.
+    pub async fn get_overdue_invoices(&self, now: DateTime) -> Result<Vec<Invoice>, Error> {
+        let filter = doc! {
+            "status": to_bson(&InvoiceStatus::Open).unwrap(),
+            "due_at": {"$lt": now},
+        };
+        let options = FindOptions::builder().sort(doc! {"due_at": 1}).build();
+        let invoices = self
+            .invoice_col
+            .find(filter, options)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(invoices)
+    }
+}
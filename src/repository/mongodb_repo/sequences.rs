@@ -0,0 +1,144 @@
+use super::{is_duplicate_key, MongoRepo};
+use crate::models::sequence::Sequence;
+use crate::repository::Error;
+use mongodb::{
+    bson::{doc, oid::ObjectId, Document},
+    Collection,
+};
+
+impl MongoRepo {
+    /// Gives the document `owner` in `col` the next number of `series`,
+    /// written to `field`, and returns it; a document that already has a
+    /// number keeps it. Safe to call concurrently, for the same document
+    /// too, and the series never skips a number: each claim on the series
+    /// names its owner, and is finished by the next caller should the
+    /// claimant stop before writing the number.
+    pub(super) async fn assign_number<T>(
+        &self,
+        col: &Collection<T>,
+        field: &str,
+        series: &str,
+        owner: ObjectId,
+    ) -> Result<i64, Error> {
+        let col = col.clone_with_type::<Document>();
+        loop {
+            let current = self
+                .sequence_col
+                .find_one(doc! {"_id": series}, None)
+                .await?;
+            let last = match &current {
+                Some(sequence) => {
+                    col.update_one(
+                        doc! {"_id": sequence.owner, field: null},
+                        doc! {"$set": {field: sequence.last}},
+                        None,
+                    )
+                    .await?;
+                    sequence.last
+                }
+                None => 0,
+            };
+            let numbered = col
+                .find_one(doc! {"_id": owner}, None)
+                .await?
+                .and_then(|document| document.get_i64(field).ok());
+            if let Some(number) = numbered {
+                return Ok(number);
+            }
+
+            let next = last + 1;
+            let claimed = match current {
+                Some(_) => {
+                    self.sequence_col
+                        .update_one(
+                            doc! {"_id": series, "last": last},
+                            doc! {"$set": {"last": next, "owner": owner}},
+                            None,
+                        )
+                        .await?
+                        .matched_count
+                        == 1
+                }
+                None => {
+                    let first = Sequence {
+                        id: series.to_string(),
+                        last: next,
+                        owner,
+                    };
+                    match self.sequence_col.insert_one(first, None).await {
+                        Ok(_) => true,
+                        Err(err) if is_duplicate_key(&err) => false,
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+            };
+            // Someone else claimed `next` first; start over from their claim.
+            if !claimed {
+                continue;
+            }
+            col.update_one(
+                doc! {"_id": owner, field: null},
+                doc! {"$set": {field: next}},
+                None,
+            )
+            .await?;
+            return Ok(next);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::testing;
+    use futures::future::join_all;
+
+    #[actix_web::test]
+    #[ignore = "needs MongoDB at SM_TEST_MONGODB_URI"]
+    async fn numbers_are_gapless_unique_and_assigned_once() {
+        let db = testing::mongodb_repo().await;
+        let col = db.database().collection::<Document>("Numbered");
+        let owners: Vec<ObjectId> = (0..20).map(|_| ObjectId::new()).collect();
+        let documents = owners.iter().map(|owner| doc! {"_id": owner});
+        col.insert_many(documents, None).await.unwrap();
+
+        let assigned = join_all(
+            owners
+                .iter()
+                .chain(&owners[..5])
+                .map(|&owner| db.assign_number(&col, "number", "INV-2026", owner)),
+        )
+        .await;
+        let assigned: Vec<i64> = assigned.into_iter().map(Result::unwrap).collect();
+        let mut numbers = assigned[..20].to_vec();
+        numbers.sort_unstable();
+        assert_eq!(numbers, (1..=20).collect::<Vec<_>>());
+        // Asking again for a numbered document gives the number it has.
+        assert_eq!(assigned[20..], assigned[..5]);
+
+        // A claim whose owner never got its number is finished first.
+        let (interrupted, next) = (ObjectId::new(), ObjectId::new());
+        col.insert_many([doc! {"_id": interrupted}, doc! {"_id": next}], None)
+            .await
+            .unwrap();
+        db.sequence_col
+            .update_one(
+                doc! {"_id": "INV-2026"},
+                doc! {"$set": {"last": 21, "owner": interrupted}},
+                None,
+            )
+            .await
+            .unwrap();
+        let number = db.assign_number(&col, "number", "INV-2026", next).await;
+        assert_eq!(number.unwrap(), 22);
+        let finished = col.find_one(doc! {"_id": interrupted}, None).await.unwrap();
+        assert_eq!(finished.unwrap().get_i64("number").unwrap(), 21);
+
+        // Each series counts on its own.
+        let receipt = db
+            .assign_number(&col, "receipt_number", "RCT-2026", next)
+            .await;
+        assert_eq!(receipt.unwrap(), 1);
+        testing::drop_database(&db).await;
+    }
+}
@@ -0,0 +1,202 @@
+use super::{object_id, snapshot, MongoRepo};
+use crate::models::{
+    audit_entry::AuditContext, course_assignment::CourseAssignment, qualification::Qualification,
+};
+use crate::repository::Error;
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, Document},
+    options::FindOptions,
+    results::{DeleteResult, InsertOneResult},
+};
+
+/// Narrows `filter` to a year and term when given.
+fn in_term(mut filter: Document, year: Option<i32>, term: Option<i32>) -> Document {
+    if let Some(year) = year {
+        filter.insert("year", year);
+    }
+    if let Some(term) = term {
+        filter.insert("term", term);
+    }
+    filter
+}
+
+impl MongoRepo {
+    pub async fn create_qualification(
+        &self,
+        new_qualification: Qualification,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let qualification = self
+            .qualification_col
+            .insert_one(new_qualification, None)
+            .await?;
+        if let Some(obj_id) = qualification.inserted_id.as_object_id() {
+            self.audit(
+                &self.qualification_col,
+                ctx,
+                "create",
+                "qualification",
+                obj_id,
+                None,
+            )
+            .await?;
+        }
+
+        Ok(qualification)
+    }
+
+    pub async fn get_qualification(
+        &self,
+        teacher_id: &String,
+        course_id: &String,
+    ) -> Result<Option<Qualification>, Error> {
+        let filter = doc! {
+            "teacher._id": object_id(teacher_id)?,
+            "course._id": object_id(course_id)?,
+        };
+        let qualification_detail = self.qualification_col.find_one(filter, None).await?;
+        Ok(qualification_detail)
+    }
+
+    pub async fn get_qualifications_for_teacher(
+        &self,
+        teacher_id: &String,
+    ) -> Result<Vec<Qualification>, Error> {
+        let filter = doc! {"teacher._id": object_id(teacher_id)?};
+        let options = FindOptions::builder().sort(doc! {"course.name": 1}).build();
+        let qualifications = self
+            .qualification_col
+            .find(filter, options)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(qualifications)
+    }
+
+    pub async fn delete_qualification(
+        &self,
+        id: &String,
+        ctx: &AuditContext,
+    ) -> Result<DeleteResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.qualification_col, obj_id).await?;
+        let qualification_detail = self
+            .qualification_col
+            .delete_one(doc! {"_id": obj_id}, None)
+            .await?;
+        if qualification_detail.deleted_count == 1 {
+            self.audit(
+                &self.qualification_col,
+                ctx,
+                "delete",
+                "qualification",
+                obj_id,
+                before,
+            )
+            .await?;
+        }
+
+        Ok(qualification_detail)
+    }
+
+    pub async fn create_course_assignment(
+        &self,
+        new_assignment: CourseAssignment,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let assignment = self
+            .course_assignment_col
+            .insert_one(new_assignment, None)
+            .await?;
+        if let Some(obj_id) = assignment.inserted_id.as_object_id() {
+            self.audit(
+                &self.course_assignment_col,
+                ctx,
+                "create",
+                "course_assignment",
+                obj_id,
+                None,
+            )
+            .await?;
+        }
+
+        Ok(assignment)
+    }
+
+    /// The assignment already covering the course for the classroom that term.
+    pub async fn get_course_assignment_for_term(
+        &self,
+        classroom_id: &String,
+        course_id: &String,
+        year: i32,
+        term: i32,
+    ) -> Result<Option<CourseAssignment>, Error> {
+        let filter = doc! {
+            "classroom._id": object_id(classroom_id)?,
+            "course._id": object_id(course_id)?,
+            "year": year,
+            "term": term,
+        };
+        let assignment_detail = self.course_assignment_col.find_one(filter, None).await?;
+        Ok(assignment_detail)
+    }
+
+    pub async fn get_assignments_for_classroom(
+        &self,
+        classroom_id: &String,
+        year: Option<i32>,
+        term: Option<i32>,
+    ) -> Result<Vec<CourseAssignment>, Error> {
+        let filter = doc! {"classroom._id": object_id(classroom_id)?};
+        self.find_assignments(in_term(filter, year, term)).await
+    }
+
+    pub async fn get_assignments_for_teacher(
+        &self,
+        teacher_id: &String,
+        year: Option<i32>,
+        term: Option<i32>,
+    ) -> Result<Vec<CourseAssignment>, Error> {
+        let filter = doc! {"teacher._id": object_id(teacher_id)?};
+        self.find_assignments(in_term(filter, year, term)).await
+    }
+
+    pub async fn delete_course_assignment(
+        &self,
+        id: &String,
+        ctx: &AuditContext,
+    ) -> Result<DeleteResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.course_assignment_col, obj_id).await?;
+        let assignment_detail = self
+            .course_assignment_col
+            .delete_one(doc! {"_id": obj_id}, None)
+            .await?;
+        if assignment_detail.deleted_count == 1 {
+            self.audit(
+                &self.course_assignment_col,
+                ctx,
+                "delete",
+                "course_assignment",
+                obj_id,
+                before,
+            )
+            .await?;
+        }
+
+        Ok(assignment_detail)
+    }
+
+    async fn find_assignments(&self, filter: Document) -> Result<Vec<CourseAssignment>, Error> {
+        let options = FindOptions::builder()
+            .sort(doc! {"year": -1, "term": -1, "course.name": 1})
+            .build();
+        Ok(self
+            .course_assignment_col
+            .find(filter, options)
+            .await?
+            .try_collect()
+            .await?)
+    }
+}
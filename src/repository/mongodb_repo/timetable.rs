@@ -0,0 +1,230 @@
+use super::{is_duplicate_key, object_id, snapshot, MongoRepo};
+use crate::models::{
+    audit_entry::AuditContext,
+    period::{Period, Weekday},
+    timetable_entry::TimetableEntry,
+    timetable_job::{JobStatus, TimetableJob},
+};
+use crate::repository::Error;
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, oid::ObjectId, to_bson, Bson, DateTime, Document},
+    options::FindOptions,
+    results::{DeleteResult, InsertOneResult},
+};
+
+fn day(day: Weekday) -> Bson {
+    to_bson(&day).expect("Weekday serializes as a string")
+}
+
+impl MongoRepo {
+    pub async fn create_period(
+        &self,
+        new_period: Period,
+        ctx: &AuditContext,
+    ) -> Result<InsertOneResult, Error> {
+        let period = self.period_col.insert_one(new_period, None).await?;
+        if let Some(obj_id) = period.inserted_id.as_object_id() {
+            self.audit(&self.period_col, ctx, "create", "period", obj_id, None)
+                .await?;
+        }
+
+        Ok(period)
+    }
+
+    pub async fn get_period(&self, id: &String) -> Result<Option<Period>, Error> {
+        let obj_id = object_id(id)?;
+        let period_detail = self.period_col.find_one(doc! {"_id": obj_id}, None).await?;
+        Ok(period_detail)
+    }
+
+    /// The period already numbered `number` on `day`, if any.
+    pub async fn get_period_by_slot(
+        &self,
+        weekday: Weekday,
+        number: i32,
+    ) -> Result<Option<Period>, Error> {
+        let period_detail = self
+            .period_col
+            .find_one(doc! {"day": day(weekday), "number": number}, None)
+            .await?;
+        Ok(period_detail)
+    }
+
+    /// Every period, in start-time order. Callers group them by day.
+    pub async fn get_all_periods(&self) -> Result<Vec<Period>, Error> {
+        let options = FindOptions::builder()
+            .sort(doc! {"start_time": 1, "number": 1})
+            .build();
+        let periods = self
+            .period_col
+            .find(None, options)
+            .await?
+            .try_collect()
+            .await?;
+        Ok(periods)
+    }
+
+    /// Inserts the entry, or returns `None` without inserting when its
+    /// teacher, room or classroom is already booked in the same period.
+    pub async fn create_timetable_entry(
+        &self,
+        new_entry: TimetableEntry,
+        ctx: &AuditContext,
+    ) -> Result<Option<InsertOneResult>, Error> {
+        let entry = match self.timetable_col.insert_one(new_entry, None).await {
+            Ok(entry) => entry,
+            Err(err) if is_duplicate_key(&err) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        if let Some(obj_id) = entry.inserted_id.as_object_id() {
+            self.audit(
+                &self.timetable_col,
+                ctx,
+                "create",
+                "timetable_entry",
+                obj_id,
+                None,
+            )
+            .await?;
+        }
+
+        Ok(Some(entry))
+    }
+
+    pub async fn delete_timetable_entry(
+        &self,
+        id: &String,
+        ctx: &AuditContext,
+    ) -> Result<DeleteResult, Error> {
+        let obj_id = object_id(id)?;
+        let before = snapshot(&self.timetable_col, obj_id).await?;
+        let entry_detail = self
+            .timetable_col
+            .delete_one(doc! {"_id": obj_id}, None)
+            .await?;
+        if entry_detail.deleted_count == 1 {
+            self.audit(
+                &self.timetable_col,
+                ctx,
+                "delete",
+                "timetable_entry",
+                obj_id,
+                before,
+            )
+            .await?;
+        }
+
+        Ok(entry_detail)
+    }
+
+    /// Entries whose period overlaps `entry`'s and that share its teacher,
+    /// room or classroom. Bookings of the very same period are also refused
+    /// by unique indexes when inserting, so concurrent requests cannot both
+    /// take a slot; overlapping but different periods rely on this check.
+    pub async fn find_timetable_conflicts(
+        &self,
+        entry: &TimetableEntry,
+    ) -> Result<Vec<TimetableEntry>, Error> {
+        let filter = doc! {
+            "period.day": day(entry.period.day),
+            "period.start_time": {"$lt": &entry.period.end_time},
+            "period.end_time": {"$gt": &entry.period.start_time},
+            "$or": [
+                {"teacher._id": entry.teacher.id},
+                {"room": &entry.room},
+                {"classroom._id": entry.classroom.id},
+            ],
+        };
+        self.find_timetable(filter).await
+    }
+
+    pub async fn get_timetable_for_classroom(
+        &self,
+        classroom_id: &String,
+    ) -> Result<Vec<TimetableEntry>, Error> {
+        let obj_id = object_id(classroom_id)?;
+        self.find_timetable(doc! {"classroom._id": obj_id}).await
+    }
+
+    pub async fn get_timetable_for_teacher(
+        &self,
+        teacher_id: &String,
+    ) -> Result<Vec<TimetableEntry>, Error> {
+        let obj_id = object_id(teacher_id)?;
+        self.find_timetable(doc! {"teacher._id": obj_id}).await
+    }
+
+    async fn find_timetable(&self, filter: Document) -> Result<Vec<TimetableEntry>, Error> {
+        let options = FindOptions::builder()
+            .sort(doc! {"period.start_time": 1})
+            .build();
+        Ok(self
+            .timetable_col
+            .find(filter, options)
+            .await?
+            .try_collect()
+            .await?)
+    }
+
+    pub async fn get_all_timetable_entries(&self) -> Result<Vec<TimetableEntry>, Error> {
+        self.find_timetable(doc! {}).await
+    }
+
+    pub async fn get_timetable_for_classrooms(
+        &self,
+        classroom_ids: &[ObjectId],
+    ) -> Result<Vec<TimetableEntry>, Error> {
+        let filter = doc! {"classroom._id": {"$in": classroom_ids.to_vec()}};
+        self.find_timetable(filter).await
+    }
+
+    pub async fn create_timetable_job(
+        &self,
+        new_job: TimetableJob,
+    ) -> Result<InsertOneResult, Error> {
+        let job = self.timetable_job_col.insert_one(new_job, None).await?;
+        Ok(job)
+    }
+
+    pub async fn get_timetable_job(&self, id: &String) -> Result<Option<TimetableJob>, Error> {
+        let obj_id = object_id(id)?;
+        let job_detail = self
+            .timetable_job_col
+            .find_one(doc! {"_id": obj_id}, None)
+            .await?;
+        Ok(job_detail)
+    }
+
+    pub async fn update_timetable_job_progress(
+        &self,
+        id: ObjectId,
+        status: JobStatus,
+        placed: i64,
+    ) -> Result<(), Error> {
+        let update = doc! {"$set": {"status": to_bson(&status).unwrap(), "placed": placed}};
+        self.timetable_job_col
+            .update_one(doc! {"_id": id}, update, None)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn finish_timetable_job(
+        &self,
+        id: ObjectId,
+        status: JobStatus,
+        problems: Vec<String>,
+        entries: &[TimetableEntry],
+    ) -> Result<(), Error> {
+        let update = doc! {"$set": {
+            "status": to_bson(&status).unwrap(),
+            "problems": problems,
+            "entries": to_bson(entries).unwrap(),
+            "finished_at": DateTime::now(),
+        }};
+        self.timetable_job_col
+            .update_one(doc! {"_id": id}, update, None)
+            .await?;
+        Ok(())
+    }
+}
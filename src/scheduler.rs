@@ -0,0 +1,655 @@
+//! Timetable generation as a constraint search. Given how many periods a
+//! week each classroom takes each course, it finds a period and a room for
+//! every one of them such that no teacher, classroom or room is in two
+//! places at once, teachers are only booked when available and every room
+//! holds the class. Knows nothing about the database; the generator job
+//! builds a [`Problem`] and turns [`Outcome::Solved`] into entries.
+//!
+//! The search is depth-first with backtracking. It always extends the
+//! requirement with the fewest remaining choices, places a requirement's
+//! periods in slot order so interchangeable periods are not retried in
+//! every permutation, and prefers days on which the course is not yet
+//! taught so lessons spread over the week.
+
+use crate::models::period::Weekday;
+use mongodb::bson::oid::ObjectId;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Placements tried before the search gives up on a timetable that may
+/// still exist.
+const SEARCH_LIMIT: u64 = 2_000_000;
+
+/// A bookable period of the week.
+pub struct Slot {
+    pub day: Weekday,
+    pub start_time: String,
+    pub end_time: String,
+}
+
+impl Slot {
+    fn overlaps(&self, other: &Slot) -> bool {
+        self.day == other.day
+            && self.start_time < other.end_time
+            && other.start_time < self.end_time
+    }
+}
+
+pub struct Room {
+    pub name: String,
+    pub capacity: u32,
+}
+
+/// A classroom taking a course with a teacher for so many periods a week.
+pub struct Requirement {
+    pub classroom_id: ObjectId,
+    pub classroom_name: String,
+    pub course_name: String,
+    pub teacher_id: ObjectId,
+    pub teacher_name: String,
+    pub periods_per_week: u32,
+    /// Students in the classroom, to be seated in the room.
+    pub class_size: u32,
+}
+
+impl Requirement {
+    fn label(&self) -> String {
+        format!(
+            "{} {} with {}",
+            self.classroom_name, self.course_name, self.teacher_name
+        )
+    }
+}
+
+/// An existing booking the generated timetable must work around.
+pub struct Booking {
+    pub slot: usize,
+    pub classroom_id: ObjectId,
+    pub teacher_id: ObjectId,
+    pub room: String,
+}
+
+pub struct Problem {
+    pub slots: Vec<Slot>,
+    pub rooms: Vec<Room>,
+    pub requirements: Vec<Requirement>,
+    /// Slots, by index, in which a teacher cannot be booked.
+    pub unavailable: HashMap<ObjectId, HashSet<usize>>,
+    pub fixed: Vec<Booking>,
+}
+
+impl Problem {
+    /// Periods to place in total; the job reports progress against this.
+    pub fn lessons(&self) -> usize {
+        self.requirements
+            .iter()
+            .map(|requirement| requirement.periods_per_week as usize)
+            .sum()
+    }
+}
+
+/// One period of a requirement, by index into the problem's lists.
+#[derive(Debug, Clone, Copy)]
+pub struct Placement {
+    pub requirement: usize,
+    pub slot: usize,
+    pub room: usize,
+}
+
+pub enum Outcome {
+    Solved(Vec<Placement>),
+    /// No timetable satisfies the constraints; says why, as best it can.
+    Unsatisfiable(Vec<String>),
+    /// The search limit ran out first; says which requirement it got stuck on.
+    GaveUp(Vec<String>),
+}
+
+/// Who is booked in each slot while searching. A slot is taken for a
+/// teacher, classroom or room if anything overlapping it is booked for them.
+struct Bookings {
+    overlapping: Vec<Vec<usize>>,
+    teachers: HashMap<ObjectId, Vec<u32>>,
+    classrooms: HashMap<ObjectId, Vec<u32>>,
+    rooms: Vec<Vec<u32>>,
+}
+
+impl Bookings {
+    fn new(slots: &[Slot], room_count: usize) -> Self {
+        let overlapping = slots
+            .iter()
+            .map(|slot| {
+                (0..slots.len())
+                    .filter(|other| slot.overlaps(&slots[*other]))
+                    .collect()
+            })
+            .collect();
+        Bookings {
+            overlapping,
+            teachers: HashMap::new(),
+            classrooms: HashMap::new(),
+            rooms: vec![vec![0; slots.len()]; room_count],
+        }
+    }
+
+    fn taken(&self, counts: Option<&Vec<u32>>, slot: usize) -> bool {
+        match counts {
+            Some(counts) => self.overlapping[slot]
+                .iter()
+                .any(|other| counts[*other] > 0),
+            None => false,
+        }
+    }
+
+    fn teacher_free(&self, teacher: &ObjectId, slot: usize) -> bool {
+        !self.taken(self.teachers.get(teacher), slot)
+    }
+
+    fn classroom_free(&self, classroom: &ObjectId, slot: usize) -> bool {
+        !self.taken(self.classrooms.get(classroom), slot)
+    }
+
+    fn room_free(&self, room: usize, slot: usize) -> bool {
+        !self.taken(Some(&self.rooms[room]), slot)
+    }
+
+    fn adjust(
+        &mut self,
+        teacher: ObjectId,
+        classroom: ObjectId,
+        room: Option<usize>,
+        slot: usize,
+        booked: bool,
+    ) {
+        let slots = self.overlapping.len();
+        for counts in [
+            self.teachers
+                .entry(teacher)
+                .or_insert_with(|| vec![0; slots]),
+            self.classrooms
+                .entry(classroom)
+                .or_insert_with(|| vec![0; slots]),
+        ]
+        .into_iter()
+        .chain(room.map(|room| &mut self.rooms[room]))
+        {
+            if booked {
+                counts[slot] += 1;
+            } else {
+                counts[slot] -= 1;
+            }
+        }
+    }
+}
+
+/// Slots for a requirement's next period, each with the rooms free in it.
+type Choices = Vec<(usize, Vec<usize>)>;
+
+struct Search<'a> {
+    problem: &'a Problem,
+    bookings: Bookings,
+    /// Rooms able to seat each requirement, smallest first, one per
+    /// capacity since equally sized rooms are interchangeable.
+    rooms_by_size: Vec<Vec<Vec<usize>>>,
+    remaining: Vec<u32>,
+    last_slot: Vec<Option<usize>>,
+    per_day: Vec<BTreeMap<Weekday, u32>>,
+    placements: Vec<Placement>,
+    tried: u64,
+    deepest: usize,
+    stuck_on: Option<usize>,
+    progress: &'a AtomicUsize,
+}
+
+impl<'a> Search<'a> {
+    /// Slots, in the order worth trying, where the requirement's next
+    /// period could go along with the rooms free in each.
+    fn choices(&self, requirement: usize) -> Choices {
+        let wanted = &self.problem.requirements[requirement];
+        let unavailable = self.problem.unavailable.get(&wanted.teacher_id);
+        let first = self.last_slot[requirement].map_or(0, |slot| slot + 1);
+        let mut choices: Choices = (first..self.problem.slots.len())
+            .filter(|slot| unavailable.is_none_or(|slots| !slots.contains(slot)))
+            .filter(|slot| self.bookings.teacher_free(&wanted.teacher_id, *slot))
+            .filter(|slot| self.bookings.classroom_free(&wanted.classroom_id, *slot))
+            .filter_map(|slot| {
+                let rooms: Vec<usize> = self.rooms_by_size[requirement]
+                    .iter()
+                    .filter_map(|same_size| {
+                        same_size
+                            .iter()
+                            .copied()
+                            .find(|room| self.bookings.room_free(*room, slot))
+                    })
+                    .collect();
+                (!rooms.is_empty()).then_some((slot, rooms))
+            })
+            .collect();
+        let per_day = &self.per_day[requirement];
+        choices.sort_by_key(|(slot, _)| {
+            per_day
+                .get(&self.problem.slots[*slot].day)
+                .copied()
+                .unwrap_or(0)
+        });
+        choices
+    }
+
+    /// The unfinished requirement with the fewest choices, with them.
+    fn most_constrained(&self) -> Option<(usize, Choices)> {
+        let mut best: Option<(usize, Choices)> = None;
+        for requirement in 0..self.remaining.len() {
+            if self.remaining[requirement] == 0 {
+                continue;
+            }
+            let choices = self.choices(requirement);
+            let fewer = best
+                .as_ref()
+                .is_none_or(|(_, best)| choices.len() < best.len());
+            if fewer {
+                let stuck = choices.is_empty();
+                best = Some((requirement, choices));
+                if stuck {
+                    break;
+                }
+            }
+        }
+        best
+    }
+
+    fn book(&mut self, placement: Placement, booked: bool) {
+        let wanted = &self.problem.requirements[placement.requirement];
+        self.bookings.adjust(
+            wanted.teacher_id,
+            wanted.classroom_id,
+            Some(placement.room),
+            placement.slot,
+            booked,
+        );
+        let day = self.problem.slots[placement.slot].day;
+        let on_day = self.per_day[placement.requirement].entry(day).or_insert(0);
+        if booked {
+            *on_day += 1;
+            self.remaining[placement.requirement] -= 1;
+        } else {
+            *on_day -= 1;
+            self.remaining[placement.requirement] += 1;
+        }
+    }
+
+    /// Whether every period got placed; `None` once the search limit is hit.
+    fn extend(&mut self) -> Option<bool> {
+        let (requirement, choices) = match self.most_constrained() {
+            Some(next) => next,
+            None => return Some(true),
+        };
+        if choices.is_empty() && self.placements.len() >= self.deepest {
+            self.stuck_on = Some(requirement);
+        }
+        let previous = self.last_slot[requirement];
+        for (slot, rooms) in choices {
+            for room in rooms {
+                self.tried += 1;
+                if self.tried > SEARCH_LIMIT {
+                    return None;
+                }
+                let placement = Placement {
+                    requirement,
+                    slot,
+                    room,
+                };
+                self.book(placement, true);
+                self.last_slot[requirement] = Some(slot);
+                self.placements.push(placement);
+                if self.placements.len() > self.deepest {
+                    self.deepest = self.placements.len();
+                    self.progress.store(self.deepest, Ordering::Relaxed);
+                }
+                if self.extend()? {
+                    return Some(true);
+                }
+                self.placements.pop();
+                self.last_slot[requirement] = previous;
+                self.book(placement, false);
+            }
+        }
+        Some(false)
+    }
+
+    fn stuck_report(&self) -> Vec<String> {
+        match self.stuck_on {
+            Some(requirement) => vec![format!(
+                "{}: no period left for all {} lessons a week",
+                self.problem.requirements[requirement].label(),
+                self.problem.requirements[requirement].periods_per_week
+            )],
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Conditions that rule out any timetable before searching: a class with no
+/// room big enough, or a teacher or classroom with more lessons than free
+/// periods.
+fn precheck(problem: &Problem, bookings: &Bookings) -> Vec<String> {
+    let mut problems = Vec::new();
+    let slots = 0..problem.slots.len();
+    let mut teacher_load: BTreeMap<ObjectId, (String, u32)> = BTreeMap::new();
+    let mut classroom_load: BTreeMap<ObjectId, (String, u32)> = BTreeMap::new();
+    for requirement in &problem.requirements {
+        if !problem
+            .rooms
+            .iter()
+            .any(|room| room.capacity >= requirement.class_size)
+        {
+            problems.push(format!(
+                "{}: no room seats {} students",
+                requirement.label(),
+                requirement.class_size
+            ));
+        }
+        teacher_load
+            .entry(requirement.teacher_id)
+            .or_insert_with(|| (requirement.teacher_name.clone(), 0))
+            .1 += requirement.periods_per_week;
+        classroom_load
+            .entry(requirement.classroom_id)
+            .or_insert_with(|| (requirement.classroom_name.clone(), 0))
+            .1 += requirement.periods_per_week;
+    }
+    for (teacher, (name, load)) in teacher_load {
+        let unavailable = problem.unavailable.get(&teacher);
+        let free = slots
+            .clone()
+            .filter(|slot| unavailable.is_none_or(|slots| !slots.contains(slot)))
+            .filter(|slot| bookings.teacher_free(&teacher, *slot))
+            .count() as u32;
+        if load > free {
+            problems.push(format!(
+                "{}: {} lessons a week but only {} available periods",
+                name, load, free
+            ));
+        }
+    }
+    for (classroom, (name, load)) in classroom_load {
+        let free = slots
+            .clone()
+            .filter(|slot| bookings.classroom_free(&classroom, *slot))
+            .count() as u32;
+        if load > free {
+            problems.push(format!(
+                "{}: {} lessons a week but only {} free periods",
+                name, load, free
+            ));
+        }
+    }
+    problems
+}
+
+/// Searches for a timetable, storing the most periods placed so far in
+/// `progress` as it goes.
+pub fn solve(problem: &Problem, progress: &AtomicUsize) -> Outcome {
+    let mut bookings = Bookings::new(&problem.slots, problem.rooms.len());
+    for booking in &problem.fixed {
+        let room = problem
+            .rooms
+            .iter()
+            .position(|room| room.name == booking.room);
+        bookings.adjust(
+            booking.teacher_id,
+            booking.classroom_id,
+            room,
+            booking.slot,
+            true,
+        );
+    }
+    let problems = precheck(problem, &bookings);
+    if !problems.is_empty() {
+        return Outcome::Unsatisfiable(problems);
+    }
+    let rooms_by_size = problem
+        .requirements
+        .iter()
+        .map(|requirement| {
+            let mut by_capacity: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+            for (index, room) in problem.rooms.iter().enumerate() {
+                if room.capacity >= requirement.class_size {
+                    by_capacity.entry(room.capacity).or_default().push(index);
+                }
+            }
+            by_capacity.into_values().collect()
+        })
+        .collect();
+    let mut search = Search {
+        problem,
+        bookings,
+        rooms_by_size,
+        remaining: problem
+            .requirements
+            .iter()
+            .map(|requirement| requirement.periods_per_week)
+            .collect(),
+        last_slot: vec![None; problem.requirements.len()],
+        per_day: vec![BTreeMap::new(); problem.requirements.len()],
+        placements: Vec::new(),
+        tried: 0,
+        deepest: 0,
+        stuck_on: None,
+        progress,
+    };
+    match search.extend() {
+        Some(true) => Outcome::Solved(search.placements),
+        Some(false) => Outcome::Unsatisfiable(search.stuck_report()),
+        None => Outcome::GaveUp(search.stuck_report()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slot(day: Weekday, start_time: &str, end_time: &str) -> Slot {
+        Slot {
+            day,
+            start_time: start_time.to_string(),
+            end_time: end_time.to_string(),
+        }
+    }
+
+    fn room(name: &str, capacity: u32) -> Room {
+        Room {
+            name: name.to_string(),
+            capacity,
+        }
+    }
+
+    fn requirement(
+        classroom_id: ObjectId,
+        teacher_id: ObjectId,
+        periods_per_week: u32,
+        class_size: u32,
+    ) -> Requirement {
+        Requirement {
+            classroom_id,
+            classroom_name: "2024 A".to_string(),
+            course_name: "Maths".to_string(),
+            teacher_id,
+            teacher_name: "Ada".to_string(),
+            periods_per_week,
+            class_size,
+        }
+    }
+
+    fn problem(slots: Vec<Slot>, rooms: Vec<Room>, requirements: Vec<Requirement>) -> Problem {
+        Problem {
+            slots,
+            rooms,
+            requirements,
+            unavailable: HashMap::new(),
+            fixed: Vec::new(),
+        }
+    }
+
+    fn solved(problem: &Problem) -> Vec<Placement> {
+        match solve(problem, &AtomicUsize::new(0)) {
+            Outcome::Solved(placements) => placements,
+            Outcome::Unsatisfiable(problems) | Outcome::GaveUp(problems) => {
+                panic!("not solved: {:?}", problems)
+            }
+        }
+    }
+
+    fn unsatisfiable(problem: &Problem) -> Vec<String> {
+        match solve(problem, &AtomicUsize::new(0)) {
+            Outcome::Unsatisfiable(problems) => problems,
+            Outcome::Solved(placements) => panic!("solved: {:?}", placements),
+            Outcome::GaveUp(problems) => panic!("gave up: {:?}", problems),
+        }
+    }
+
+    #[test]
+    fn bookings_take_overlapping_slots() {
+        let slots = [
+            slot(Weekday::Monday, "08:00", "09:00"),
+            slot(Weekday::Monday, "08:30", "09:30"),
+            slot(Weekday::Monday, "09:30", "10:30"),
+            slot(Weekday::Tuesday, "08:00", "09:00"),
+        ];
+        let (teacher, classroom) = (ObjectId::new(), ObjectId::new());
+        let mut bookings = Bookings::new(&slots, 1);
+        bookings.adjust(teacher, classroom, Some(0), 0, true);
+        for (slot, free) in [(0, false), (1, false), (2, true), (3, true)] {
+            assert_eq!(bookings.teacher_free(&teacher, slot), free, "slot {}", slot);
+            assert_eq!(bookings.classroom_free(&classroom, slot), free);
+            assert_eq!(bookings.room_free(0, slot), free);
+        }
+        assert!(bookings.teacher_free(&ObjectId::new(), 0));
+
+        bookings.adjust(teacher, classroom, Some(0), 0, false);
+        assert!(bookings.teacher_free(&teacher, 1));
+        assert!(bookings.room_free(0, 1));
+    }
+
+    #[test]
+    fn solve_keeps_a_teacher_out_of_overlapping_slots() {
+        let teacher = ObjectId::new();
+        let problem = problem(
+            vec![
+                slot(Weekday::Monday, "08:00", "09:00"),
+                slot(Weekday::Monday, "08:30", "09:30"),
+                slot(Weekday::Monday, "10:00", "11:00"),
+            ],
+            vec![room("R1", 30), room("R2", 30)],
+            vec![
+                requirement(ObjectId::new(), teacher, 1, 20),
+                requirement(ObjectId::new(), teacher, 1, 20),
+            ],
+        );
+        let placements = solved(&problem);
+        assert_eq!(placements.len(), 2);
+        let (first, second) = (placements[0].slot, placements[1].slot);
+        assert!(!problem.slots[first].overlaps(&problem.slots[second]));
+    }
+
+    #[test]
+    fn solve_respects_teacher_unavailability() {
+        let teacher = ObjectId::new();
+        let mut problem = problem(
+            vec![
+                slot(Weekday::Monday, "08:00", "09:00"),
+                slot(Weekday::Monday, "09:00", "10:00"),
+                slot(Weekday::Tuesday, "08:00", "09:00"),
+            ],
+            vec![room("R1", 30)],
+            vec![requirement(ObjectId::new(), teacher, 2, 20)],
+        );
+        problem.unavailable.insert(teacher, HashSet::from([0]));
+        let mut slots: Vec<usize> = solved(&problem)
+            .iter()
+            .map(|placement| placement.slot)
+            .collect();
+        slots.sort();
+        assert_eq!(slots, vec![1, 2]);
+    }
+
+    #[test]
+    fn solve_works_around_fixed_bookings() {
+        let teacher = ObjectId::new();
+        let mut problem = problem(
+            vec![
+                slot(Weekday::Monday, "08:00", "09:00"),
+                slot(Weekday::Monday, "09:00", "10:00"),
+            ],
+            vec![room("R1", 30)],
+            vec![requirement(ObjectId::new(), teacher, 1, 20)],
+        );
+        problem.fixed.push(Booking {
+            slot: 0,
+            classroom_id: ObjectId::new(),
+            teacher_id: ObjectId::new(),
+            room: "R1".to_string(),
+        });
+        assert_eq!(solved(&problem)[0].slot, 1);
+    }
+
+    #[test]
+    fn solve_seats_classes_in_rooms_big_enough() {
+        let problem = problem(
+            vec![slot(Weekday::Monday, "08:00", "09:00")],
+            vec![room("small", 10), room("large", 30)],
+            vec![requirement(ObjectId::new(), ObjectId::new(), 1, 25)],
+        );
+        assert_eq!(solved(&problem)[0].room, 1);
+    }
+
+    #[test]
+    fn precheck_reports_a_class_no_room_seats() {
+        let problem = problem(
+            vec![slot(Weekday::Monday, "08:00", "09:00")],
+            vec![room("R1", 30)],
+            vec![requirement(ObjectId::new(), ObjectId::new(), 1, 40)],
+        );
+        assert_eq!(
+            unsatisfiable(&problem),
+            vec!["2024 A Maths with Ada: no room seats 40 students"]
+        );
+    }
+
+    #[test]
+    fn precheck_reports_teachers_with_too_few_available_periods() {
+        let teacher = ObjectId::new();
+        let mut problem = problem(
+            vec![
+                slot(Weekday::Monday, "08:00", "09:00"),
+                slot(Weekday::Monday, "09:00", "10:00"),
+            ],
+            vec![room("R1", 30)],
+            vec![requirement(ObjectId::new(), teacher, 2, 20)],
+        );
+        problem.unavailable.insert(teacher, HashSet::from([1]));
+        assert_eq!(
+            unsatisfiable(&problem),
+            vec!["Ada: 2 lessons a week but only 1 available periods"]
+        );
+    }
+
+    #[test]
+    fn solve_reports_where_the_search_got_stuck() {
+        // Each teacher and classroom has a free period, but the one room
+        // cannot hold both classes at once.
+        let problem = problem(
+            vec![slot(Weekday::Monday, "08:00", "09:00")],
+            vec![room("R1", 30)],
+            vec![
+                requirement(ObjectId::new(), ObjectId::new(), 1, 20),
+                requirement(ObjectId::new(), ObjectId::new(), 1, 20),
+            ],
+        );
+        assert!(precheck(&problem, &Bookings::new(&problem.slots, 1)).is_empty());
+        assert_eq!(
+            unsatisfiable(&problem),
+            vec!["2024 A Maths with Ada: no period left for all 1 lessons a week"]
+        );
+    }
+}
@@ -0,0 +1,96 @@
+use super::{check_key, BlobStore, StorageError};
+use async_trait::async_trait;
+use futures::TryStreamExt;
+use mongodb::{
+    bson::{doc, spec::BinarySubtype, Binary, DateTime, Document},
+    options::FindOptions,
+    Collection, Database,
+};
+
+/// The GridFS default, which keeps every chunk document well under the
+/// 16 MiB BSON limit.
+const CHUNK_SIZE: usize = 255 * 1024;
+
+/// Stores blobs in a GridFS bucket (`<bucket>.files` and `<bucket>.chunks`)
+/// using the key as the file id, so `mongofiles` and other drivers can
+/// read them too.
+pub struct GridFsStore {
+    files: Collection<Document>,
+    chunks: Collection<Document>,
+}
+
+impl GridFsStore {
+    pub fn new(db: &Database, bucket: &str) -> Self {
+        GridFsStore {
+            files: db.collection(&format!("{}.files", bucket)),
+            chunks: db.collection(&format!("{}.chunks", bucket)),
+        }
+    }
+}
+
+#[async_trait]
+impl BlobStore for GridFsStore {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), StorageError> {
+        check_key(key)?;
+        self.delete(key).await?;
+        let chunks: Vec<Document> = bytes
+            .chunks(CHUNK_SIZE)
+            .enumerate()
+            .map(|(n, chunk)| {
+                doc! {
+                    "files_id": key,
+                    "n": n as i32,
+                    "data": Binary { subtype: BinarySubtype::Generic, bytes: chunk.to_vec() },
+                }
+            })
+            .collect();
+        if !chunks.is_empty() {
+            self.chunks.insert_many(chunks, None).await?;
+        }
+        // The files document goes last: until it exists the blob is absent.
+        let file = doc! {
+            "_id": key,
+            "length": bytes.len() as i64,
+            "chunkSize": CHUNK_SIZE as i32,
+            "uploadDate": DateTime::now(),
+            "filename": key,
+        };
+        self.files.insert_one(file, None).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        check_key(key)?;
+        let Some(file) = self.files.find_one(doc! {"_id": key}, None).await? else {
+            return Ok(None);
+        };
+        let length = file.get_i64("length").unwrap_or_default() as usize;
+        let options = FindOptions::builder().sort(doc! {"n": 1}).build();
+        let chunks: Vec<Document> = self
+            .chunks
+            .find(doc! {"files_id": key}, options)
+            .await?
+            .try_collect()
+            .await?;
+        let mut bytes = Vec::with_capacity(length);
+        for (n, chunk) in chunks.iter().enumerate() {
+            match (chunk.get_i32("n"), chunk.get_binary_generic("data")) {
+                (Ok(number), Ok(data)) if number as usize == n => bytes.extend_from_slice(data),
+                _ => return Err(StorageError::Corrupt(key.to_string())),
+            }
+        }
+        if bytes.len() != length {
+            return Err(StorageError::Corrupt(key.to_string()));
+        }
+        Ok(Some(bytes))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        check_key(key)?;
+        self.files.delete_one(doc! {"_id": key}, None).await?;
+        self.chunks
+            .delete_many(doc! {"files_id": key}, None)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,78 @@
+use super::{check_key, BlobStore, StorageError};
+use actix_web::rt::task;
+use async_trait::async_trait;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+use uuid::Uuid;
+
+/// Keeps each blob in its own file under `root`, fanned out into
+/// subdirectories by the first two characters of the key.
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(root: impl AsRef<Path>) -> Self {
+        LocalStore {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, StorageError> {
+        check_key(key)?;
+        let shard = key.get(..2).unwrap_or(key);
+        Ok(self.root.join(shard).join(key))
+    }
+}
+
+/// Runs blocking file IO off the async workers.
+async fn blocking<T, F>(f: F) -> Result<T, StorageError>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    task::spawn_blocking(f)
+        .await
+        .map_err(io::Error::other)?
+        .map_err(StorageError::Io)
+}
+
+#[async_trait]
+impl BlobStore for LocalStore {
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), StorageError> {
+        let path = self.path(key)?;
+        blocking(move || {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            // Written aside and renamed so readers never see half a file.
+            let partial = path.with_extension(format!("{}.partial", Uuid::new_v4().simple()));
+            fs::write(&partial, bytes)?;
+            fs::rename(&partial, &path).inspect_err(|_| {
+                let _ = fs::remove_file(&partial);
+            })
+        })
+        .await
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        let path = self.path(key)?;
+        blocking(move || match fs::read(path) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err),
+        })
+        .await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let path = self.path(key)?;
+        blocking(move || match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        })
+        .await
+    }
+}
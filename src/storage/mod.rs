@@ -0,0 +1,75 @@
+//! Where the bytes of uploaded files live. Metadata and checksums are kept
+//! in the `Attachment` collection; a store only maps keys to bytes.
+
+mod gridfs;
+mod local;
+
+pub use gridfs::GridFsStore;
+pub use local::LocalStore;
+
+use crate::config::{StorageBackend, StorageSettings};
+use async_trait::async_trait;
+use mongodb::Database;
+use std::{fmt, io, sync::Arc};
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// Keys may only hold ASCII letters, digits, `-` and `_`.
+    InvalidKey(String),
+    Io(io::Error),
+    Database(mongodb::error::Error),
+    /// Chunks are missing or do not add up to the recorded length.
+    Corrupt(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::InvalidKey(key) => write!(f, "invalid blob key {:?}", key),
+            StorageError::Io(err) => write!(f, "blob storage error: {}", err),
+            StorageError::Database(err) => write!(f, "blob storage error: {}", err),
+            StorageError::Corrupt(key) => write!(f, "stored blob {} is corrupt", key),
+        }
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(err: io::Error) -> Self {
+        StorageError::Io(err)
+    }
+}
+
+impl From<mongodb::error::Error> for StorageError {
+    fn from(err: mongodb::error::Error) -> Self {
+        StorageError::Database(err)
+    }
+}
+
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Stores `bytes` under `key`, replacing whatever was there.
+    async fn put(&self, key: &str, bytes: Vec<u8>) -> Result<(), StorageError>;
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError>;
+    /// Succeeds whether or not anything was stored under `key`.
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+}
+
+fn check_key(key: &str) -> Result<(), StorageError> {
+    let valid = !key.is_empty()
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if valid {
+        Ok(())
+    } else {
+        Err(StorageError::InvalidKey(key.to_string()))
+    }
+}
+
+/// The store picked by `storage.backend`.
+pub fn from_settings(settings: &StorageSettings, db: &Database) -> Arc<dyn BlobStore> {
+    match settings.backend {
+        StorageBackend::Local => Arc::new(LocalStore::new(&settings.local_root)),
+        StorageBackend::GridFs => Arc::new(GridFsStore::new(db, &settings.gridfs_bucket)),
+    }
+}